@@ -1,4 +1,5 @@
 use crate::typ::{self, ModuleValueConstructor, PatternConstructor, ValueConstructor};
+use serde::{Deserialize, Serialize};
 
 pub type TypedModule = Module<
     ValueConstructor,
@@ -32,6 +33,26 @@ impl<A, B, C, D, E> Module<A, B, C, D, E> {
             })
             .collect()
     }
+
+    /// The module as it is compiled to the given target. Definitions for other targets are
+    /// removed, and those for this target take the place of their annotations.
+    ///
+    pub fn for_target(mut self, target: crate::project::CompileTarget) -> Self {
+        self.statements = self
+            .statements
+            .into_iter()
+            .filter_map(|statement| match statement {
+                Statement::Target {
+                    target: t,
+                    statement,
+                    ..
+                } if t == target => Some(*statement),
+                Statement::Target { .. } => None,
+                statement => Some(statement),
+            })
+            .collect();
+        self
+    }
 }
 
 #[test]
@@ -49,18 +70,30 @@ fn module_dependencies_test() {
     );
 }
 
+pub type TypedArg = Arg<PatternConstructor>;
+
+pub type UntypedArg = Arg<()>;
+
 #[derive(Debug, Clone, PartialEq)]
-pub struct Arg {
-    pub names: ArgNames,
+pub struct Arg<PatternConstructor> {
+    pub names: ArgNames<PatternConstructor>,
     pub meta: Meta,
     pub annotation: Option<TypeAst>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum ArgNames {
+pub enum ArgNames<PatternConstructor> {
     Discard,
-    Named { name: String },
-    NamedLabelled { name: String, label: String },
+    Named {
+        name: String,
+    },
+    NamedLabelled {
+        name: String,
+        label: String,
+    },
+    Pattern {
+        pattern: Pattern<PatternConstructor>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -96,6 +129,17 @@ pub enum TypeAst {
     },
 }
 
+impl TypeAst {
+    pub fn meta(&self) -> &Meta {
+        match self {
+            TypeAst::Constructor { meta, .. }
+            | TypeAst::Fn { meta, .. }
+            | TypeAst::Var { meta, .. }
+            | TypeAst::AnonStruct { meta, .. } => meta,
+        }
+    }
+}
+
 pub type TypedStatement =
     Statement<ValueConstructor, ModuleValueConstructor, PatternConstructor, typ::Type>;
 
@@ -106,7 +150,7 @@ pub enum Statement<ValueConstructor, ModuleValueConstructor, PatternConstructor,
     Fn {
         meta: Meta,
         name: String,
-        args: Vec<Arg>,
+        args: Vec<Arg<PatternConstructor>>,
         body: Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type>,
         public: bool,
         return_annotation: Option<TypeAst>,
@@ -117,6 +161,7 @@ pub enum Statement<ValueConstructor, ModuleValueConstructor, PatternConstructor,
         name: String,
         args: Vec<String>,
         public: bool,
+        opaque: bool,
         constructors: Vec<EnumConstructor>,
     },
 
@@ -130,6 +175,15 @@ pub enum Statement<ValueConstructor, ModuleValueConstructor, PatternConstructor,
         fun: String,
     },
 
+    InlineErlangFn {
+        meta: Meta,
+        public: bool,
+        args: Vec<ExternalFnArg>,
+        name: String,
+        retrn: TypeAst,
+        erlang: String,
+    },
+
     ExternalType {
         meta: Meta,
         public: bool,
@@ -137,8 +191,17 @@ pub enum Statement<ValueConstructor, ModuleValueConstructor, PatternConstructor,
         args: Vec<String>,
     },
 
+    TypeAlias {
+        meta: Meta,
+        public: bool,
+        name: String,
+        args: Vec<String>,
+        typ: TypeAst,
+    },
+
     Import {
         meta: Meta,
+        public: bool,
         module: Vec<String>,
         as_name: Option<String>,
         unqualified: Vec<UnqualifiedImport>,
@@ -149,8 +212,42 @@ pub enum Statement<ValueConstructor, ModuleValueConstructor, PatternConstructor,
         name: String,
         type_args: Vec<String>,
         public: bool,
+        opaque: bool,
         fields: Vec<StructField>,
     },
+
+    Behaviour {
+        meta: Meta,
+        name: String,
+    },
+
+    Export {
+        meta: Meta,
+        names: Vec<String>,
+    },
+
+    /// A definition that is only compiled when compiling to the given target.
+    Target {
+        meta: Meta,
+        target: crate::project::CompileTarget,
+        statement: Box<Self>,
+    },
+
+    Const {
+        meta: Meta,
+        name: String,
+        public: bool,
+        value: Box<Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type>>,
+    },
+}
+
+/// The value of a module constant, which is inlined wherever the constant is used.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Literal {
+    Int { value: String },
+    Float { value: f64 },
+    String { value: String },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -196,6 +293,7 @@ pub enum BinOp {
     DivInt,
     DivFloat,
     ModuloInt,
+    Concatenate,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -205,6 +303,13 @@ pub struct CallArg<A> {
     pub value: A,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpdateArg<A> {
+    pub label: String,
+    pub meta: Meta,
+    pub value: A,
+}
+
 pub type TypedExpr = Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, typ::Type>;
 
 pub type UntypedExpr = Expr<(), (), (), ()>;
@@ -214,7 +319,7 @@ pub enum Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type
     Int {
         meta: Meta,
         typ: Type,
-        value: i64,
+        value: String,
     },
 
     Float {
@@ -245,7 +350,7 @@ pub enum Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type
         meta: Meta,
         typ: Type,
         is_capture: bool,
-        args: Vec<Arg>,
+        args: Vec<Arg<PatternConstructor>>,
         body: Box<Self>,
     },
 
@@ -277,6 +382,23 @@ pub enum Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type
     },
 
     Let {
+        meta: Meta,
+        typ: Type,
+        value: Box<Self>,
+        pattern: Pattern<PatternConstructor>,
+        annotation: Option<TypeAst>,
+        then: Box<Self>,
+    },
+
+    LetRec {
+        meta: Meta,
+        typ: Type,
+        name: String,
+        value: Box<Self>,
+        then: Box<Self>,
+    },
+
+    Try {
         meta: Meta,
         typ: Type,
         value: Box<Self>,
@@ -284,6 +406,24 @@ pub enum Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type
         then: Box<Self>,
     },
 
+    Assert {
+        meta: Meta,
+        typ: Type,
+        value: Box<Self>,
+        pattern: Pattern<PatternConstructor>,
+        message: Option<Box<Self>>,
+        then: Box<Self>,
+    },
+
+    /// An assertion that a boolean expression is true, evaluating to `Nil`. The source of the
+    /// expression is kept for the error raised when it is false.
+    AssertTrue {
+        meta: Meta,
+        typ: Type,
+        value: Box<Self>,
+        source: String,
+    },
+
     Case {
         meta: Meta,
         typ: Type,
@@ -298,6 +438,13 @@ pub enum Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type
         container: Box<Self>,
     },
 
+    TupleIndex {
+        meta: Meta,
+        typ: Type,
+        index: u64,
+        tuple: Box<Self>,
+    },
+
     ModuleSelect {
         meta: Meta,
         typ: Type,
@@ -312,6 +459,38 @@ pub enum Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type
         typ: Type,
         elems: Vec<Self>,
     },
+
+    StructUpdate {
+        meta: Meta,
+        typ: Type,
+        constructor: Box<Self>,
+        spread: Box<Self>,
+        args: Vec<UpdateArg<Self>>,
+    },
+
+    Block {
+        meta: Meta,
+        typ: Type,
+        body: Vec<Self>,
+    },
+
+    Negate {
+        meta: Meta,
+        typ: Type,
+        value: Box<Self>,
+    },
+
+    Todo {
+        meta: Meta,
+        typ: Type,
+        message: Option<String>,
+    },
+
+    Panic {
+        meta: Meta,
+        typ: Type,
+        message: Option<String>,
+    },
 }
 
 impl<A, B, C, D> Expr<A, B, C, D> {
@@ -323,6 +502,9 @@ impl<A, B, C, D> Expr<A, B, C, D> {
             Expr::Var { meta, .. } => meta,
             Expr::Nil { meta, .. } => meta,
             Expr::Let { then, .. } => then.meta(),
+            Expr::LetRec { then, .. } => then.meta(),
+            Expr::Try { then, .. } => then.meta(),
+            Expr::Assert { then, .. } => then.meta(),
             Expr::Case { meta, .. } => meta,
             Expr::Cons { meta, .. } => meta,
             Expr::Call { meta, .. } => meta,
@@ -331,7 +513,14 @@ impl<A, B, C, D> Expr<A, B, C, D> {
             Expr::String { meta, .. } => meta,
             Expr::AnonStruct { meta, .. } => meta,
             Expr::FieldSelect { meta, .. } => meta,
+            Expr::TupleIndex { meta, .. } => meta,
             Expr::ModuleSelect { meta, .. } => meta,
+            Expr::StructUpdate { meta, .. } => meta,
+            Expr::Block { meta, .. } => meta,
+            Expr::Negate { meta, .. } => meta,
+            Expr::AssertTrue { meta, .. } => meta,
+            Expr::Todo { meta, .. } => meta,
+            Expr::Panic { meta, .. } => meta,
         }
     }
 }
@@ -350,10 +539,20 @@ impl TypedExpr {
             Expr::Call { typ, .. } => typ,
             Expr::BinOp { typ, .. } => typ,
             Expr::Let { typ, .. } => typ,
+            Expr::LetRec { typ, .. } => typ,
+            Expr::Try { typ, .. } => typ,
+            Expr::Assert { typ, .. } => typ,
             Expr::Case { typ, .. } => typ,
             Expr::AnonStruct { typ, .. } => typ,
             Expr::FieldSelect { typ, .. } => typ,
+            Expr::TupleIndex { typ, .. } => typ,
             Expr::ModuleSelect { typ, .. } => typ,
+            Expr::StructUpdate { typ, .. } => typ,
+            Expr::Block { typ, .. } => typ,
+            Expr::Negate { typ, .. } => typ,
+            Expr::AssertTrue { typ, .. } => typ,
+            Expr::Todo { typ, .. } => typ,
+            Expr::Panic { typ, .. } => typ,
         }
     }
 }
@@ -367,6 +566,7 @@ pub type UntypedClause = Clause<(), (), (), ()>;
 pub struct Clause<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type> {
     pub meta: Meta,
     pub patterns: Vec<Pattern<PatternConstructor>>,
+    pub guard: Option<Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type>>,
     pub then: Expr<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type>,
 }
 
@@ -383,7 +583,7 @@ pub type TypedPattern = Pattern<PatternConstructor>;
 pub enum Pattern<Constructor> {
     Int {
         meta: Meta,
-        value: i64,
+        value: String,
     },
 
     Float {
@@ -427,6 +627,45 @@ pub enum Pattern<Constructor> {
         meta: Meta,
         elems: Vec<Self>,
     },
+
+    BitString {
+        meta: Meta,
+        segments: Vec<BitSegment<Self>>,
+    },
+}
+
+/// A segment of a bit string pattern, such as `version:8` or `rest:binary`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitSegment<Value> {
+    pub meta: Meta,
+    pub value: Value,
+    pub kind: BitSegmentKind,
+    pub size: Option<BitSegmentSize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitSegmentKind {
+    Int,
+    Binary,
+}
+
+/// The size of a bit string segment, in bits for an int segment and in bytes for a binary
+/// segment. It may be given by a variable bound earlier in the pattern or in an enclosing scope.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum BitSegmentSize {
+    Int { meta: Meta, value: String },
+    Var { meta: Meta, name: String },
+}
+
+impl BitSegmentSize {
+    fn to_gleam_string(&self) -> String {
+        match self {
+            BitSegmentSize::Int { value, .. } => value.clone(),
+            BitSegmentSize::Var { name, .. } => name.clone(),
+        }
+    }
 }
 
 impl<A> Pattern<A> {
@@ -441,6 +680,827 @@ impl<A> Pattern<A> {
             Pattern::String { meta, .. } => meta,
             Pattern::AnonStruct { meta, .. } => meta,
             Pattern::Constructor { meta, .. } => meta,
+            Pattern::BitString { meta, .. } => meta,
+        }
+    }
+    /// Render the pattern as Gleam source code.
+    ///
+    pub fn to_gleam_string(&self) -> String {
+        match self {
+            Pattern::Int { value, .. } => value.clone(),
+            Pattern::Float { value, .. } => format!("{:?}", value),
+            Pattern::String { value, .. } => format!("{:?}", value),
+            Pattern::Var { name, .. } => name.clone(),
+            Pattern::Discard { .. } => "_".to_string(),
+            Pattern::Nil { .. } => "[]".to_string(),
+
+            Pattern::Cons { head, tail, .. } => {
+                let mut elems = vec![head.to_gleam_string()];
+                let mut tail = tail.as_ref();
+                while let Pattern::Cons {
+                    head, tail: rest, ..
+                } = tail
+                {
+                    elems.push(head.to_gleam_string());
+                    tail = rest.as_ref();
+                }
+                match tail {
+                    Pattern::Nil { .. } => format!("[{}]", elems.join(", ")),
+                    _ => elems
+                        .into_iter()
+                        .rev()
+                        .fold(tail.to_gleam_string(), |tail, head| {
+                            format!("[{} | {}]", head, tail)
+                        }),
+                }
+            }
+
+            Pattern::Constructor {
+                name, args, module, ..
+            } => {
+                let name = match module {
+                    Some(module) => format!("{}.{}", module, name),
+                    None => name.clone(),
+                };
+                if args.is_empty() {
+                    return name;
+                }
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| match &arg.label {
+                        Some(label) => format!("{}: {}", label, arg.value.to_gleam_string()),
+                        None => arg.value.to_gleam_string(),
+                    })
+                    .collect();
+                format!("{}({})", name, args.join(", "))
+            }
+
+            Pattern::AnonStruct { elems, .. } => {
+                let elems: Vec<_> = elems.iter().map(|e| e.to_gleam_string()).collect();
+                format!("struct({})", elems.join(", "))
+            }
+
+            Pattern::BitString { segments, .. } => {
+                let segments: Vec<_> = segments
+                    .iter()
+                    .map(|segment| {
+                        let value = segment.value.to_gleam_string();
+                        match (segment.kind, &segment.size) {
+                            (BitSegmentKind::Int, None) => value,
+                            (BitSegmentKind::Int, Some(size @ BitSegmentSize::Int { .. })) => {
+                                format!("{}:{}", value, size.to_gleam_string())
+                            }
+                            (BitSegmentKind::Int, Some(size)) => {
+                                format!("{}:size({})", value, size.to_gleam_string())
+                            }
+                            (BitSegmentKind::Binary, None) => format!("{}:binary", value),
+                            (BitSegmentKind::Binary, Some(size)) => {
+                                format!("{}:binary-size({})", value, size.to_gleam_string())
+                            }
+                        }
+                    })
+                    .collect();
+                format!("<<{}>>", segments.join(", "))
+            }
         }
     }
 }
+
+#[test]
+fn pattern_to_gleam_string_test() {
+    let parse = |src: &str| match crate::grammar::ExprParser::new()
+        .parse(&format!("let {} = x x", src))
+        .expect("syntax error")
+    {
+        Expr::Let { pattern, .. } => pattern,
+        other => panic!("expected a let, got {:?}", other),
+    };
+
+    for src in &[
+        "1",
+        "1.5",
+        "\"a\\\"b\"",
+        "x",
+        "_",
+        "[]",
+        "[1, 2]",
+        "[x | xs]",
+        "[1 | [_ | xs]]",
+        "Ok(x)",
+        "Nil",
+        "one.Box(x)",
+        "Point(x: 1, y: y)",
+        "struct(a, [b])",
+        "<<version:8, rest:binary>>",
+        "<<len, body:binary-size(len), _:binary>>",
+        "<<1:4, x:size(n)>>",
+    ] {
+        assert_eq!(*src, parse(src).to_gleam_string());
+    }
+}
+
+/// Read only traversal of a module. Each method is called once for every node of its kind, with
+/// parents visited before their children. Use `walk` to drive a visitor over a module.
+///
+pub trait Visitor<A, B, C, D> {
+    fn visit_statement(&mut self, _statement: &Statement<A, B, C, D>) {}
+
+    fn visit_expr(&mut self, _expr: &Expr<A, B, C, D>) {}
+
+    fn visit_pattern(&mut self, _pattern: &Pattern<C>) {}
+}
+
+pub fn walk<A, B, C, D, E>(visitor: &mut impl Visitor<A, B, C, D>, module: &Module<A, B, C, D, E>) {
+    for statement in module.statements.iter() {
+        walk_statement(visitor, statement);
+    }
+}
+
+pub fn walk_statement<A, B, C, D>(
+    visitor: &mut impl Visitor<A, B, C, D>,
+    statement: &Statement<A, B, C, D>,
+) {
+    visitor.visit_statement(statement);
+    match statement {
+        Statement::Fn { args, body, .. } => {
+            walk_args(visitor, args);
+            walk_expr(visitor, body);
+        }
+        Statement::Const { value, .. } => walk_expr(visitor, value),
+        Statement::Target { statement, .. } => walk_statement(visitor, statement),
+        _ => (),
+    }
+}
+
+pub fn walk_expr<A, B, C, D>(visitor: &mut impl Visitor<A, B, C, D>, expr: &Expr<A, B, C, D>) {
+    visitor.visit_expr(expr);
+    match expr {
+        Expr::Int { .. }
+        | Expr::Float { .. }
+        | Expr::String { .. }
+        | Expr::Var { .. }
+        | Expr::Nil { .. }
+        | Expr::ModuleSelect { .. }
+        | Expr::Todo { .. }
+        | Expr::Panic { .. } => (),
+
+        Expr::Seq { first, then, .. } => {
+            walk_expr(visitor, first);
+            walk_expr(visitor, then);
+        }
+
+        Expr::Fn { args, body, .. } => {
+            walk_args(visitor, args);
+            walk_expr(visitor, body);
+        }
+
+        Expr::Cons { head, tail, .. } => {
+            walk_expr(visitor, head);
+            walk_expr(visitor, tail);
+        }
+
+        Expr::Call { fun, args, .. } => {
+            walk_expr(visitor, fun);
+            for arg in args {
+                walk_expr(visitor, &arg.value);
+            }
+        }
+
+        Expr::BinOp { left, right, .. } => {
+            walk_expr(visitor, left);
+            walk_expr(visitor, right);
+        }
+
+        Expr::Let {
+            value,
+            pattern,
+            then,
+            ..
+        }
+        | Expr::Try {
+            value,
+            pattern,
+            then,
+            ..
+        } => {
+            walk_expr(visitor, value);
+            walk_pattern(visitor, pattern);
+            walk_expr(visitor, then);
+        }
+
+        Expr::LetRec { value, then, .. } => {
+            walk_expr(visitor, value);
+            walk_expr(visitor, then);
+        }
+
+        Expr::Assert {
+            value,
+            pattern,
+            message,
+            then,
+            ..
+        } => {
+            walk_expr(visitor, value);
+            walk_pattern(visitor, pattern);
+            if let Some(message) = message {
+                walk_expr(visitor, message);
+            }
+            walk_expr(visitor, then);
+        }
+
+        Expr::Case {
+            subjects, clauses, ..
+        } => {
+            for subject in subjects {
+                walk_expr(visitor, subject);
+            }
+            for clause in clauses {
+                for pattern in clause.patterns.iter() {
+                    walk_pattern(visitor, pattern);
+                }
+                if let Some(guard) = &clause.guard {
+                    walk_expr(visitor, guard);
+                }
+                walk_expr(visitor, &clause.then);
+            }
+        }
+
+        Expr::FieldSelect { container, .. } => walk_expr(visitor, container),
+
+        Expr::TupleIndex { tuple, .. } => walk_expr(visitor, tuple),
+
+        Expr::AnonStruct { elems, .. } | Expr::Block { body: elems, .. } => {
+            for elem in elems {
+                walk_expr(visitor, elem);
+            }
+        }
+
+        Expr::StructUpdate {
+            constructor,
+            spread,
+            args,
+            ..
+        } => {
+            walk_expr(visitor, constructor);
+            walk_expr(visitor, spread);
+            for arg in args {
+                walk_expr(visitor, &arg.value);
+            }
+        }
+
+        Expr::Negate { value, .. } => walk_expr(visitor, value),
+        Expr::AssertTrue { value, .. } => walk_expr(visitor, value),
+    }
+}
+
+fn walk_args<A, B, C, D>(visitor: &mut impl Visitor<A, B, C, D>, args: &[Arg<C>]) {
+    for arg in args {
+        if let ArgNames::Pattern { pattern } = &arg.names {
+            walk_pattern(visitor, pattern);
+        }
+    }
+}
+
+pub fn walk_pattern<A, B, C, D>(visitor: &mut impl Visitor<A, B, C, D>, pattern: &Pattern<C>) {
+    visitor.visit_pattern(pattern);
+    match pattern {
+        Pattern::Int { .. }
+        | Pattern::Float { .. }
+        | Pattern::String { .. }
+        | Pattern::Var { .. }
+        | Pattern::Discard { .. }
+        | Pattern::Nil { .. } => (),
+
+        Pattern::Cons { head, tail, .. } => {
+            walk_pattern(visitor, head);
+            walk_pattern(visitor, tail);
+        }
+
+        Pattern::Constructor { args, .. } => {
+            for arg in args {
+                walk_pattern(visitor, &arg.value);
+            }
+        }
+
+        Pattern::AnonStruct { elems, .. } => {
+            for elem in elems {
+                walk_pattern(visitor, elem);
+            }
+        }
+
+        Pattern::BitString { segments, .. } => {
+            for segment in segments {
+                walk_pattern(visitor, &segment.value);
+            }
+        }
+    }
+}
+
+/// Rewriting traversal of a module. Children are folded before their parent, so each method
+/// receives a node whose children have already been rewritten. Use `fold` to drive a folder over
+/// a module.
+///
+pub trait Folder<A, B, C, D> {
+    fn fold_statement(&mut self, statement: Statement<A, B, C, D>) -> Statement<A, B, C, D> {
+        statement
+    }
+
+    fn fold_expr(&mut self, expr: Expr<A, B, C, D>) -> Expr<A, B, C, D> {
+        expr
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern<C>) -> Pattern<C> {
+        pattern
+    }
+}
+
+pub fn fold<A, B, C, D, E>(
+    folder: &mut impl Folder<A, B, C, D>,
+    module: Module<A, B, C, D, E>,
+) -> Module<A, B, C, D, E> {
+    Module {
+        name: module.name,
+        type_info: module.type_info,
+        statements: module
+            .statements
+            .into_iter()
+            .map(|statement| fold_statement(folder, statement))
+            .collect(),
+    }
+}
+
+pub fn fold_statement<A, B, C, D>(
+    folder: &mut impl Folder<A, B, C, D>,
+    statement: Statement<A, B, C, D>,
+) -> Statement<A, B, C, D> {
+    let statement = match statement {
+        Statement::Fn {
+            meta,
+            name,
+            args,
+            body,
+            public,
+            return_annotation,
+        } => Statement::Fn {
+            meta,
+            name,
+            args: fold_args(folder, args),
+            body: fold_expr(folder, body),
+            public,
+            return_annotation,
+        },
+
+        Statement::Const {
+            meta,
+            name,
+            public,
+            value,
+        } => Statement::Const {
+            meta,
+            name,
+            public,
+            value: Box::new(fold_expr(folder, *value)),
+        },
+
+        Statement::Target {
+            meta,
+            target,
+            statement,
+        } => Statement::Target {
+            meta,
+            target,
+            statement: Box::new(fold_statement(folder, *statement)),
+        },
+
+        statement => statement,
+    };
+    folder.fold_statement(statement)
+}
+
+pub fn fold_expr<A, B, C, D>(
+    folder: &mut impl Folder<A, B, C, D>,
+    expr: Expr<A, B, C, D>,
+) -> Expr<A, B, C, D> {
+    let expr = match expr {
+        Expr::Int { .. }
+        | Expr::Float { .. }
+        | Expr::String { .. }
+        | Expr::Var { .. }
+        | Expr::Nil { .. }
+        | Expr::ModuleSelect { .. }
+        | Expr::Todo { .. }
+        | Expr::Panic { .. } => expr,
+
+        Expr::Seq { typ, first, then } => Expr::Seq {
+            typ,
+            first: Box::new(fold_expr(folder, *first)),
+            then: Box::new(fold_expr(folder, *then)),
+        },
+
+        Expr::Fn {
+            meta,
+            typ,
+            is_capture,
+            args,
+            body,
+        } => Expr::Fn {
+            meta,
+            typ,
+            is_capture,
+            args: fold_args(folder, args),
+            body: Box::new(fold_expr(folder, *body)),
+        },
+
+        Expr::Cons {
+            meta,
+            typ,
+            head,
+            tail,
+        } => Expr::Cons {
+            meta,
+            typ,
+            head: Box::new(fold_expr(folder, *head)),
+            tail: Box::new(fold_expr(folder, *tail)),
+        },
+
+        Expr::Call {
+            meta,
+            typ,
+            fun,
+            args,
+        } => Expr::Call {
+            meta,
+            typ,
+            fun: Box::new(fold_expr(folder, *fun)),
+            args: args
+                .into_iter()
+                .map(|arg| CallArg {
+                    label: arg.label,
+                    meta: arg.meta,
+                    value: fold_expr(folder, arg.value),
+                })
+                .collect(),
+        },
+
+        Expr::BinOp {
+            meta,
+            typ,
+            name,
+            left,
+            right,
+        } => Expr::BinOp {
+            meta,
+            typ,
+            name,
+            left: Box::new(fold_expr(folder, *left)),
+            right: Box::new(fold_expr(folder, *right)),
+        },
+
+        Expr::Let {
+            meta,
+            typ,
+            value,
+            pattern,
+            annotation,
+            then,
+        } => Expr::Let {
+            meta,
+            typ,
+            value: Box::new(fold_expr(folder, *value)),
+            pattern: fold_pattern(folder, pattern),
+            annotation,
+            then: Box::new(fold_expr(folder, *then)),
+        },
+
+        Expr::LetRec {
+            meta,
+            typ,
+            name,
+            value,
+            then,
+        } => Expr::LetRec {
+            meta,
+            typ,
+            name,
+            value: Box::new(fold_expr(folder, *value)),
+            then: Box::new(fold_expr(folder, *then)),
+        },
+
+        Expr::Try {
+            meta,
+            typ,
+            value,
+            pattern,
+            then,
+        } => Expr::Try {
+            meta,
+            typ,
+            value: Box::new(fold_expr(folder, *value)),
+            pattern: fold_pattern(folder, pattern),
+            then: Box::new(fold_expr(folder, *then)),
+        },
+
+        Expr::Assert {
+            meta,
+            typ,
+            value,
+            pattern,
+            message,
+            then,
+        } => Expr::Assert {
+            meta,
+            typ,
+            value: Box::new(fold_expr(folder, *value)),
+            pattern: fold_pattern(folder, pattern),
+            message: message.map(|message| Box::new(fold_expr(folder, *message))),
+            then: Box::new(fold_expr(folder, *then)),
+        },
+
+        Expr::Case {
+            meta,
+            typ,
+            subjects,
+            clauses,
+        } => Expr::Case {
+            meta,
+            typ,
+            subjects: subjects
+                .into_iter()
+                .map(|subject| fold_expr(folder, subject))
+                .collect(),
+            clauses: clauses
+                .into_iter()
+                .map(|clause| Clause {
+                    meta: clause.meta,
+                    patterns: clause
+                        .patterns
+                        .into_iter()
+                        .map(|pattern| fold_pattern(folder, pattern))
+                        .collect(),
+                    guard: clause.guard.map(|guard| fold_expr(folder, guard)),
+                    then: fold_expr(folder, clause.then),
+                })
+                .collect(),
+        },
+
+        Expr::FieldSelect {
+            meta,
+            typ,
+            label,
+            container,
+        } => Expr::FieldSelect {
+            meta,
+            typ,
+            label,
+            container: Box::new(fold_expr(folder, *container)),
+        },
+
+        Expr::TupleIndex {
+            meta,
+            typ,
+            index,
+            tuple,
+        } => Expr::TupleIndex {
+            meta,
+            typ,
+            index,
+            tuple: Box::new(fold_expr(folder, *tuple)),
+        },
+
+        Expr::AnonStruct { meta, typ, elems } => Expr::AnonStruct {
+            meta,
+            typ,
+            elems: elems
+                .into_iter()
+                .map(|elem| fold_expr(folder, elem))
+                .collect(),
+        },
+
+        Expr::StructUpdate {
+            meta,
+            typ,
+            constructor,
+            spread,
+            args,
+        } => Expr::StructUpdate {
+            meta,
+            typ,
+            constructor: Box::new(fold_expr(folder, *constructor)),
+            spread: Box::new(fold_expr(folder, *spread)),
+            args: args
+                .into_iter()
+                .map(|arg| UpdateArg {
+                    label: arg.label,
+                    meta: arg.meta,
+                    value: fold_expr(folder, arg.value),
+                })
+                .collect(),
+        },
+
+        Expr::Block { meta, typ, body } => Expr::Block {
+            meta,
+            typ,
+            body: body.into_iter().map(|e| fold_expr(folder, e)).collect(),
+        },
+
+        Expr::AssertTrue {
+            meta,
+            typ,
+            value,
+            source,
+        } => Expr::AssertTrue {
+            meta,
+            typ,
+            value: Box::new(fold_expr(folder, *value)),
+            source,
+        },
+
+        Expr::Negate { meta, typ, value } => Expr::Negate {
+            meta,
+            typ,
+            value: Box::new(fold_expr(folder, *value)),
+        },
+    };
+    folder.fold_expr(expr)
+}
+
+fn fold_args<A, B, C, D>(folder: &mut impl Folder<A, B, C, D>, args: Vec<Arg<C>>) -> Vec<Arg<C>> {
+    args.into_iter()
+        .map(|arg| match arg.names {
+            ArgNames::Pattern { pattern } => Arg {
+                names: ArgNames::Pattern {
+                    pattern: fold_pattern(folder, pattern),
+                },
+                ..arg
+            },
+            _ => arg,
+        })
+        .collect()
+}
+
+pub fn fold_pattern<A, B, C, D>(
+    folder: &mut impl Folder<A, B, C, D>,
+    pattern: Pattern<C>,
+) -> Pattern<C> {
+    let pattern = match pattern {
+        Pattern::Int { .. }
+        | Pattern::Float { .. }
+        | Pattern::String { .. }
+        | Pattern::Var { .. }
+        | Pattern::Discard { .. }
+        | Pattern::Nil { .. } => pattern,
+
+        Pattern::Cons { meta, head, tail } => Pattern::Cons {
+            meta,
+            head: Box::new(fold_pattern(folder, *head)),
+            tail: Box::new(fold_pattern(folder, *tail)),
+        },
+
+        Pattern::Constructor {
+            meta,
+            name,
+            args,
+            module,
+            constructor,
+        } => Pattern::Constructor {
+            meta,
+            name,
+            args: args
+                .into_iter()
+                .map(|arg| CallArg {
+                    label: arg.label,
+                    meta: arg.meta,
+                    value: fold_pattern(folder, arg.value),
+                })
+                .collect(),
+            module,
+            constructor,
+        },
+
+        Pattern::AnonStruct { meta, elems } => Pattern::AnonStruct {
+            meta,
+            elems: elems
+                .into_iter()
+                .map(|elem| fold_pattern(folder, elem))
+                .collect(),
+        },
+
+        Pattern::BitString { meta, segments } => Pattern::BitString {
+            meta,
+            segments: segments
+                .into_iter()
+                .map(|segment| BitSegment {
+                    meta: segment.meta,
+                    value: fold_pattern(folder, segment.value),
+                    kind: segment.kind,
+                    size: segment.size,
+                })
+                .collect(),
+        },
+    };
+    folder.fold_pattern(pattern)
+}
+
+#[test]
+fn visitor_test() {
+    struct FnCounter(usize);
+
+    impl Visitor<(), (), (), ()> for FnCounter {
+        fn visit_statement(&mut self, statement: &UntypedStatement) {
+            if let Statement::Fn { .. } = statement {
+                self.0 += 1;
+            }
+        }
+    }
+
+    let module = crate::grammar::ModuleParser::new()
+        .parse(
+            "import other
+pub fn one() { 1 }
+external fn two() -> Int = \"m\" \"two\"
+fn three(x) { fn(y) { x + y } }
+pub fn four() { three(1)(2) }",
+        )
+        .expect("syntax error");
+
+    let mut counter = FnCounter(0);
+    walk(&mut counter, &module);
+    assert_eq!(3, counter.0);
+}
+
+#[test]
+fn folder_test() {
+    // Replace every Int literal with its successor
+    struct Increment;
+
+    impl Folder<(), (), (), ()> for Increment {
+        fn fold_expr(&mut self, expr: UntypedExpr) -> UntypedExpr {
+            match expr {
+                Expr::Int { meta, typ, value } => Expr::Int {
+                    meta,
+                    typ,
+                    value: (value.parse::<i64>().unwrap() + 1).to_string(),
+                },
+                expr => expr,
+            }
+        }
+    }
+
+    let module = crate::grammar::ModuleParser::new()
+        .parse("fn go(x) { case [x, 1] { [y] -> y + 2 _ -> 3 } }")
+        .expect("syntax error");
+
+    let module = fold(&mut Increment, module);
+    let mut ints = vec![];
+    struct IntCollector<'a>(&'a mut Vec<String>);
+    impl<'a> Visitor<(), (), (), ()> for IntCollector<'a> {
+        fn visit_expr(&mut self, expr: &UntypedExpr) {
+            if let Expr::Int { value, .. } = expr {
+                self.0.push(value.clone());
+            }
+        }
+    }
+    walk(&mut IntCollector(&mut ints), &module);
+    assert_eq!(vec!["2", "3", "4"], ints);
+}
+
+#[test]
+fn fn_arg_pattern_test() {
+    // Prefix the name of every variable bound by a pattern
+    struct Rename;
+
+    impl Folder<(), (), (), ()> for Rename {
+        fn fold_pattern(&mut self, pattern: UntypedPattern) -> UntypedPattern {
+            match pattern {
+                Pattern::Var { meta, name } => Pattern::Var {
+                    meta,
+                    name: format!("x_{}", name),
+                },
+                pattern => pattern,
+            }
+        }
+    }
+
+    struct VarCollector(Vec<String>);
+
+    impl Visitor<(), (), (), ()> for VarCollector {
+        fn visit_pattern(&mut self, pattern: &UntypedPattern) {
+            if let Pattern::Var { name, .. } = pattern {
+                self.0.push(name.clone());
+            }
+        }
+    }
+
+    // The patterns of the arguments of both functions and anonymous functions are traversed
+    let module = crate::grammar::ModuleParser::new()
+        .parse("fn go(#(a, b)) { fn(Box(c)) { a + b + c } }")
+        .expect("syntax error");
+    let mut vars = VarCollector(vec![]);
+    walk(&mut vars, &module);
+    assert_eq!(vec!["a", "b", "c"], vars.0);
+
+    let module = fold(&mut Rename, module);
+    let mut vars = VarCollector(vec![]);
+    walk(&mut vars, &module);
+    assert_eq!(vec!["x_a", "x_b", "x_c"], vars.0);
+}
@@ -0,0 +1,1204 @@
+use crate::ast::*;
+use crate::erl::{constructor_atom, float};
+use crate::pretty::*;
+use crate::typ::{
+    ModuleValueConstructor, PatternConstructor, ValueConstructor, ValueConstructorVariant,
+};
+use itertools::Itertools;
+
+const INDENT: isize = 4;
+
+#[derive(Debug, Clone)]
+struct Env<'a> {
+    module: &'a [String],
+    // Local variables that are not bound to a Core Erlang variable of the same name, such as the
+    // functions defined with `let rec`.
+    vars: im::HashMap<String, Document>,
+    uid: usize,
+}
+
+impl<'a> Env<'a> {
+    fn new(module: &'a [String]) -> Self {
+        Self {
+            module,
+            vars: Default::default(),
+            uid: 0,
+        }
+    }
+
+    /// Core Erlang variables are lexically scoped and may shadow one another, so each Gleam
+    /// variable is given the same name wherever it is bound. They are prefixed with an underscore
+    /// so that they cannot clash with the variables generated by the compiler.
+    ///
+    fn bind(&mut self, name: String) -> Document {
+        self.vars.remove(&name);
+        format!("_{}", name).to_doc()
+    }
+
+    fn local_var(&self, name: &str) -> Document {
+        match self.vars.get(name) {
+            Some(doc) => doc.clone(),
+            None => format!("_{}", name).to_doc(),
+        }
+    }
+
+    /// A variable that is not bound by the Gleam code. The name contains an `@` so that it cannot
+    /// clash with the name of a variable from the Gleam code.
+    ///
+    fn fresh(&mut self) -> Document {
+        self.uid += 1;
+        format!("Cor@{}", self.uid).to_doc()
+    }
+}
+
+/// Generate a Core Erlang module, which can be compiled with `erlc +from_core`.
+///
+pub fn module(module: TypedModule) -> String {
+    let module = module.for_target(crate::project::CompileTarget::CoreErlang);
+    let module_name = module.name;
+    let type_info = module.type_info;
+    let reexports = |unqualified: &[UnqualifiedImport]| -> Vec<(String, Vec<String>, usize)> {
+        unqualified
+            .iter()
+            .flat_map(|UnqualifiedImport { name, .. }| {
+                match type_info.value_constructors.get(name).map(|v| &v.variant) {
+                    Some(ValueConstructorVariant::ModuleFn { module, arity, .. })
+                        if module != &module_name =>
+                    {
+                        Some((name.clone(), module.clone(), *arity))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    };
+
+    let mut exports: Vec<_> = module
+        .statements
+        .iter()
+        .flat_map(|s| match s {
+            Statement::Import {
+                public: true,
+                unqualified,
+                ..
+            } => reexports(unqualified)
+                .into_iter()
+                .map(|(name, _, arity)| (name, arity))
+                .collect(),
+
+            Statement::Fn {
+                public: true,
+                name,
+                args,
+                ..
+            } => vec![(name.clone(), args.len())],
+
+            Statement::ExternalFn {
+                public: true,
+                name,
+                args,
+                ..
+            } => vec![(name.clone(), args.len())],
+
+            _ => vec![],
+        })
+        .collect();
+    exports.sort();
+    let exports = comma_list(
+        exports
+            .into_iter()
+            .map(|(name, arity)| fname(&name, arity))
+            .collect(),
+    );
+
+    let attributes: Vec<_> = module
+        .statements
+        .iter()
+        .filter_map(|s| match s {
+            Statement::Behaviour { name, .. } => Some(
+                atom("behaviour")
+                    .append(" = [")
+                    .append(atom(name))
+                    .append("]"),
+            ),
+            _ => None,
+        })
+        .collect();
+    let attributes = comma_list(attributes);
+
+    let definitions: Vec<_> = module
+        .statements
+        .into_iter()
+        .flat_map(|s| match s {
+            Statement::Import {
+                public: true,
+                unqualified,
+                ..
+            } => reexports(&unqualified)
+                .into_iter()
+                .map(|(name, module, arity)| external_fun(&name, &module.join("@"), &name, arity))
+                .collect(),
+
+            Statement::Fn {
+                name, args, body, ..
+            } => vec![mod_fun(&name, args, body, &module_name)],
+
+            Statement::ExternalFn {
+                name,
+                module,
+                fun,
+                args,
+                ..
+            } => vec![external_fun(&name, &module, &fun, args.len())],
+
+            _ => vec![],
+        })
+        .map(|definition| lines(2).append(definition))
+        .collect();
+
+    "module "
+        .to_doc()
+        .append(atom(&module_name.join("@")))
+        .append(" [")
+        .append(exports)
+        .append("]")
+        .append(
+            line()
+                .append("attributes [")
+                .append(attributes)
+                .append("]")
+                .nest(INDENT),
+        )
+        .append(definitions)
+        .append(lines(2))
+        .append("end")
+        .append(line())
+        .format(80)
+}
+
+fn mod_fun(name: &str, args: Vec<TypedArg>, body: TypedExpr, module: &[String]) -> Document {
+    let mut env = Env::new(module);
+    let arity = args.len();
+    fname(name, arity)
+        .append(" =")
+        .append(line().append(fun(args, body, &mut env)).nest(INDENT))
+}
+
+fn external_fun(name: &str, module: &str, fun: &str, arity: usize) -> Document {
+    let args = comma_list((1..=arity).map(|i| format!("Cor@{}", i).to_doc()).collect());
+    fname(name, arity).append(" =").append(
+        line()
+            .append("fun (")
+            .append(args.clone())
+            .append(") ->")
+            .append(line().append(remote_call(module, fun, args)).nest(INDENT))
+            .nest(INDENT),
+    )
+}
+
+/// Core Erlang funs take only variables, so arguments given as patterns are bound to fresh
+/// variables and matched with a `case` around the body.
+///
+fn fun(args: Vec<TypedArg>, body: TypedExpr, env: &mut Env) -> Document {
+    let vars = env.vars.clone();
+    let mut subjects = vec![];
+    let mut patterns = vec![];
+    let mut params = Vec::with_capacity(args.len());
+    for arg in args {
+        let param = match arg.names {
+            ArgNames::Discard => env.fresh(),
+            ArgNames::Named { name } | ArgNames::NamedLabelled { name, .. } => env.bind(name),
+            ArgNames::Pattern { pattern: p } => {
+                let var = env.fresh();
+                subjects.push(var.clone());
+                patterns.push(pattern(p, env));
+                var
+            }
+        };
+        params.push(param);
+    }
+    let body = if patterns.is_empty() {
+        expr(body, env)
+    } else {
+        let others: Vec<_> = subjects.iter().map(|_| env.fresh()).collect();
+        let value = match others.as_slice() {
+            [other] => other.clone(),
+            _ => tuple(others.clone()),
+        };
+        let fail = match_fail(tuple(vec![atom("badmatch"), value]));
+        let clause = clause_doc(patterns, None, expr(body, env));
+        case_doc(
+            values(subjects),
+            vec![clause, clause_doc(others, None, fail)],
+        )
+    };
+    env.vars = vars;
+    "fun ("
+        .to_doc()
+        .append(comma_list(params))
+        .append(") ->")
+        .append(line().append(body).nest(INDENT))
+}
+
+fn atom(value: &str) -> Document {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")).to_doc()
+}
+
+fn fname(name: &str, arity: usize) -> Document {
+    atom(name).append("/").append(arity)
+}
+
+fn remote_call(module: &str, fun: &str, args: impl Documentable) -> Document {
+    "call "
+        .to_doc()
+        .append(atom(module))
+        .append(":")
+        .append(atom(fun))
+        .append("(")
+        .append(args)
+        .append(")")
+}
+
+fn erlang_call(fun: &str, args: Vec<Document>) -> Document {
+    remote_call("erlang", fun, comma_list(args))
+}
+
+fn comma_list(docs: Vec<Document>) -> Document {
+    Itertools::intersperse(docs.into_iter(), ", ".to_doc())
+        .collect::<Vec<_>>()
+        .to_doc()
+}
+
+fn tuple(elems: Vec<Document>) -> Document {
+    comma_list(elems).surround("{", "}")
+}
+
+fn values(elems: Vec<Document>) -> Document {
+    comma_list(elems).surround("<", ">")
+}
+
+fn cons(head: Document, tail: Document) -> Document {
+    "[".to_doc()
+        .append(head)
+        .append("|")
+        .append(tail)
+        .append("]")
+}
+
+/// Core Erlang integers are written in decimal, so any other base is converted.
+///
+fn int(value: &str) -> Document {
+    let value = value.replace("_", "");
+    let (sign, digits) = match value.as_bytes().first() {
+        Some(b'-') => ("-", &value[1..]),
+        _ => ("", &value[..]),
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    match u128::from_str_radix(digits, radix) {
+        Ok(n) => format!("{}{}", sign, n).to_doc(),
+        Err(_) => value.to_doc(),
+    }
+}
+
+/// A string is a binary with a segment for each of its UTF-8 encoded bytes.
+///
+fn string(value: &str) -> Document {
+    comma_list(value.bytes().map(byte_segment).collect()).surround("#{", "}#")
+}
+
+fn byte_segment(byte: u8) -> Document {
+    format!("#<{}>(8,1,'integer',['unsigned'|['big']])", byte).to_doc()
+}
+
+fn binary_segment(value: Document) -> Document {
+    "#<".to_doc()
+        .append(value)
+        .append(">('all',8,'binary',['unsigned'|['big']])")
+}
+
+fn literal(literal: Literal) -> Document {
+    match literal {
+        Literal::Int { value } => int(&value),
+        Literal::Float { value } => float(value),
+        Literal::String { value } => string(&value),
+    }
+}
+
+fn expr(expression: TypedExpr, env: &mut Env) -> Document {
+    match expression {
+        Expr::Nil { .. } => "[]".to_doc(),
+        Expr::Int { value, .. } => int(&value),
+        Expr::Float { value, .. } => float(value),
+        Expr::String { value, .. } => string(&value),
+
+        Expr::Seq { first, then, .. } => "do"
+            .to_doc()
+            .append(line().append(expr(*first, env)).nest(INDENT))
+            .append(line())
+            .append(expr(*then, env)),
+
+        Expr::Var {
+            name, constructor, ..
+        } => var(&name, constructor, env),
+
+        Expr::Fn { args, body, .. } => fun(args, *body, env),
+
+        Expr::Cons { head, tail, .. } => {
+            let head = expr(*head, env);
+            cons(head, expr(*tail, env))
+        }
+
+        Expr::Call { fun, args, .. } => {
+            let args = args.into_iter().map(|arg| arg.value).collect();
+            call(*fun, args, env)
+        }
+
+        Expr::FieldSelect {
+            label, container, ..
+        } => remote_call(
+            "maps",
+            "get",
+            comma_list(vec![atom(&label), expr(*container, env)]),
+        ),
+
+        Expr::TupleIndex { index, tuple, .. } => erlang_call(
+            "element",
+            vec![(index + 1).to_string().to_doc(), expr(*tuple, env)],
+        ),
+
+        Expr::ModuleSelect {
+            typ,
+            label,
+            constructor: ModuleValueConstructor::Enum,
+            ..
+        } => match typ.fn_arity() {
+            Some(arity) => constructor_fun(&label, arity, env),
+            None => atom(&constructor_atom(&label)),
+        },
+
+        Expr::ModuleSelect {
+            typ,
+            constructor: ModuleValueConstructor::Struct { name },
+            ..
+        } => match typ.fn_arity() {
+            Some(arity) => constructor_fun(&name, arity, env),
+            None => atom(&constructor_atom(&name)),
+        },
+
+        Expr::ModuleSelect {
+            constructor: ModuleValueConstructor::Const { literal: value },
+            ..
+        } => literal(value),
+
+        Expr::ModuleSelect {
+            typ,
+            label,
+            module_name,
+            constructor: ModuleValueConstructor::Fn,
+            ..
+        } => match typ.collapse_links() {
+            crate::typ::Type::Fn { args, .. } => erlang_call(
+                "make_fun",
+                vec![
+                    atom(&module_name.join("@")),
+                    atom(&label),
+                    args.len().to_doc(),
+                ],
+            ),
+            _ => remote_call(&module_name.join("@"), &label, nil()),
+        },
+
+        Expr::Let {
+            value,
+            pattern,
+            then,
+            ..
+        } => let_(*value, pattern, *then, env),
+
+        Expr::LetRec {
+            name, value, then, ..
+        } => let_rec(name, *value, *then, env),
+
+        Expr::Try {
+            value,
+            pattern,
+            then,
+            ..
+        } => try_(*value, pattern, *then, env),
+
+        Expr::Assert {
+            meta,
+            value,
+            pattern,
+            message,
+            then,
+            ..
+        } => assert(meta, *value, pattern, message.map(|m| *m), *then, env),
+
+        Expr::AssertTrue {
+            meta,
+            value,
+            source,
+            ..
+        } => assert_true(meta, *value, &source, env),
+
+        Expr::Case {
+            subjects, clauses, ..
+        } => case(subjects, clauses, env),
+
+        Expr::BinOp {
+            name, left, right, ..
+        } => bin_op(name, *left, *right, env),
+
+        Expr::AnonStruct { elems, .. } => tuple(elems.into_iter().map(|e| expr(e, env)).collect()),
+
+        Expr::StructUpdate {
+            constructor,
+            spread,
+            args,
+            ..
+        } => struct_update(*constructor, *spread, args, env),
+
+        Expr::Block { body, .. } => block(body, env),
+
+        Expr::Negate { value, .. } => erlang_call("-", vec![expr(*value, env)]),
+
+        Expr::Todo { message, .. } => crash("todo", message),
+
+        Expr::Panic { message, .. } => crash("panic", message),
+    }
+}
+
+fn crash(kind: &str, message: Option<String>) -> Document {
+    let message = match message {
+        Some(message) => format!("{}: {}", kind, message),
+        None => kind.to_string(),
+    };
+    erlang_call("error", vec![string(&message)])
+}
+
+fn var(name: &str, constructor: ValueConstructor, env: &mut Env) -> Document {
+    match constructor.variant {
+        ValueConstructorVariant::Enum { arity: 0, .. } => atom(&constructor_atom(name)),
+
+        ValueConstructorVariant::Enum { arity, .. } => constructor_fun(name, arity, env),
+
+        ValueConstructorVariant::Struct { name, arity: 0, .. } => atom(&constructor_atom(&name)),
+
+        ValueConstructorVariant::Struct { name, arity, .. } => constructor_fun(&name, arity, env),
+
+        ValueConstructorVariant::LocalVariable => env.local_var(name),
+
+        ValueConstructorVariant::ModuleConst { literal: value } => literal(value),
+
+        ValueConstructorVariant::ModuleFn { module, arity, .. } => {
+            if module.as_slice() == env.module {
+                fname(name, arity)
+            } else {
+                erlang_call(
+                    "make_fun",
+                    vec![atom(&module.join("@")), atom(name), arity.to_doc()],
+                )
+            }
+        }
+    }
+}
+
+/// A constructor used as a value is a function that builds the tagged tuple for the constructor.
+///
+fn constructor_fun(name: &str, arity: usize, env: &mut Env) -> Document {
+    let args: Vec<_> = (0..arity).map(|_| env.fresh()).collect();
+    "fun (".to_doc().append(comma_list(args.clone())).append(
+        ") ->"
+            .to_doc()
+            .append(line().append(tag_tuple(name, args)).nest(INDENT)),
+    )
+}
+
+fn tag_tuple(name: &str, mut args: Vec<Document>) -> Document {
+    args.insert(0, atom(&constructor_atom(name)));
+    tuple(args)
+}
+
+fn call(fun: TypedExpr, args: Vec<TypedExpr>, env: &mut Env) -> Document {
+    let args_doc = |env: &mut Env| args.into_iter().map(|arg| expr(arg, env)).collect();
+    match fun {
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::Enum { .. },
+                    ..
+                },
+            name,
+            ..
+        }
+        | Expr::ModuleSelect {
+            label: name,
+            constructor: ModuleValueConstructor::Enum,
+            ..
+        }
+        | Expr::ModuleSelect {
+            constructor: ModuleValueConstructor::Struct { name },
+            ..
+        }
+        | Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::Struct { name, .. },
+                    ..
+                },
+            ..
+        } => tag_tuple(&name, args_doc(env)),
+
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::ModuleFn { module, .. },
+                    ..
+                },
+            name,
+            ..
+        } => {
+            let args = args_doc(env);
+            if module.as_slice() == env.module {
+                let arity = args.len();
+                "apply "
+                    .to_doc()
+                    .append(fname(&name, arity))
+                    .append(comma_list(args).surround("(", ")"))
+            } else {
+                remote_call(&module.join("@"), &name, comma_list(args))
+            }
+        }
+
+        Expr::ModuleSelect {
+            module_name,
+            label,
+            constructor: ModuleValueConstructor::Fn,
+            ..
+        } => remote_call(&module_name.join("@"), &label, comma_list(args_doc(env))),
+
+        fun => {
+            let fun = expr(fun, env);
+            "apply "
+                .to_doc()
+                .append(fun)
+                .append(comma_list(args_doc(env)).surround("(", ")"))
+        }
+    }
+}
+
+fn let_(value: TypedExpr, pat: TypedPattern, then: TypedExpr, env: &mut Env) -> Document {
+    let value = expr(value, env);
+    match pat {
+        Pattern::Var { name, .. } => {
+            let name = env.bind(name);
+            let_doc(name, value, expr(then, env))
+        }
+
+        pat => {
+            let vars = env.vars.clone();
+            let clause = clause_doc(vec![pattern(pat, env)], None, expr(then, env));
+            env.vars = vars;
+            let other = env.fresh();
+            let fail = match_fail(tuple(vec![atom("badmatch"), other.clone()]));
+            case_doc(value, vec![clause, clause_doc(vec![other], None, fail)])
+        }
+    }
+}
+
+fn let_doc(name: Document, value: Document, then: Document) -> Document {
+    "let "
+        .to_doc()
+        .append(values(vec![name]))
+        .append(" =")
+        .append(line().append(value).nest(INDENT))
+        .append(line())
+        .append("in ")
+        .append(then)
+}
+
+/// A recursive function is defined with `letrec`. Functions defined this way are referred to by
+/// name and arity rather than by a variable, so references to the Gleam variable are replaced.
+///
+fn let_rec(name: String, value: TypedExpr, then: TypedExpr, env: &mut Env) -> Document {
+    let (args, body) = match value {
+        Expr::Fn { args, body, .. } => (args, body),
+        _ => panic!("core_erlang: let rec with a value that is not a fn"),
+    };
+    let fname = fname(&format!("{}@rec", name), args.len());
+    env.vars.insert(name, fname.clone());
+    let value = fun(args, *body, env);
+    "letrec "
+        .to_doc()
+        .append(fname)
+        .append(" =")
+        .append(line().append(value).nest(INDENT))
+        .append(line())
+        .append("in ")
+        .append(expr(then, env))
+}
+
+fn try_(value: TypedExpr, pat: TypedPattern, then: TypedExpr, env: &mut Env) -> Document {
+    let value = expr(value, env);
+    let error = env.fresh();
+    let error_clause = clause_doc(
+        vec![tuple(vec![atom("error"), error.clone()])],
+        None,
+        tuple(vec![atom("error"), error]),
+    );
+    let vars = env.vars.clone();
+    let ok_clause = clause_doc(
+        vec![tuple(vec![atom("ok"), pattern(pat, env)])],
+        None,
+        expr(then, env),
+    );
+    env.vars = vars;
+    let other = env.fresh();
+    let fail = match_fail(tuple(vec![atom("badmatch"), other.clone()]));
+    case_doc(
+        value,
+        vec![error_clause, ok_clause, clause_doc(vec![other], None, fail)],
+    )
+}
+
+/// Crashes with the same error as the Erlang backend when the pattern does not match.
+///
+fn assert(
+    meta: Meta,
+    value: TypedExpr,
+    pat: TypedPattern,
+    message: Option<TypedExpr>,
+    then: TypedExpr,
+    env: &mut Env,
+) -> Document {
+    let value = expr(value, env);
+    let message = match message {
+        Some(message) => expr(message, env),
+        None => string("Assertion pattern match failed"),
+    };
+    let pattern_string = string(&pat.to_gleam_string());
+    let vars = env.vars.clone();
+    let match_clause = clause_doc(vec![pattern(pat, env)], None, expr(then, env));
+    env.vars = vars;
+    let other = env.fresh();
+    let error = comma_list(vec![
+        atom("gleam_error").append("=>").append(atom("assert")),
+        atom("message").append("=>").append(message),
+        atom("pattern").append("=>").append(pattern_string),
+        atom("start").append("=>").append(meta.start),
+        atom("end").append("=>").append(meta.end),
+        atom("value").append("=>").append(other.clone()),
+    ])
+    .surround("~{", "}~");
+    let fail = erlang_call("error", vec![error]);
+    case_doc(
+        value,
+        vec![match_clause, clause_doc(vec![other], None, fail)],
+    )
+}
+
+fn assert_true(meta: Meta, value: TypedExpr, source: &str, env: &mut Env) -> Document {
+    let value = expr(value, env);
+    let error = comma_list(vec![
+        atom("gleam_error").append("=>").append(atom("assert")),
+        atom("message")
+            .append("=>")
+            .append(string("Assertion failed")),
+        atom("expression").append("=>").append(string(source)),
+        atom("start").append("=>").append(meta.start),
+        atom("end").append("=>").append(meta.end),
+    ])
+    .surround("~{", "}~");
+    let fail = erlang_call("error", vec![error]);
+    case_doc(
+        value,
+        vec![
+            clause_doc(vec![atom("true")], None, atom("nil")),
+            clause_doc(vec![atom("false")], None, fail),
+        ],
+    )
+}
+
+fn match_fail(reason: Document) -> Document {
+    "primop 'match_fail'(".to_doc().append(reason).append(")")
+}
+
+fn case_doc(subject: Document, clauses: Vec<Document>) -> Document {
+    "case "
+        .to_doc()
+        .append(subject)
+        .append(" of")
+        .append(
+            clauses
+                .into_iter()
+                .map(|clause| line().append(clause))
+                .collect::<Vec<_>>()
+                .to_doc()
+                .nest(INDENT),
+        )
+        .append(line())
+        .append("end")
+}
+
+fn clause_doc(patterns: Vec<Document>, guard: Option<Document>, then: Document) -> Document {
+    values(patterns)
+        .append(" when ")
+        .append(guard.unwrap_or_else(|| atom("true")))
+        .append(" ->")
+        .append(line().append(then).nest(INDENT))
+}
+
+fn case(subjects: Vec<TypedExpr>, clauses: Vec<TypedClause>, env: &mut Env) -> Document {
+    let arity = subjects.len();
+    let subjects = subjects.into_iter().map(|s| expr(s, env)).collect();
+    let mut clauses: Vec<_> = clauses
+        .into_iter()
+        .map(|clause| {
+            let vars = env.vars.clone();
+            let patterns = clause
+                .patterns
+                .into_iter()
+                .map(|p| pattern(p, env))
+                .collect();
+            let guard = clause.guard.map(|g| guard(g, env));
+            let doc = clause_doc(patterns, guard, expr(clause.then, env));
+            env.vars = vars;
+            doc
+        })
+        .collect();
+    let others: Vec<_> = (0..arity).map(|_| env.fresh()).collect();
+    let value = if arity == 1 {
+        others[0].clone()
+    } else {
+        tuple(others.clone())
+    };
+    let fail = match_fail(tuple(vec![atom("case_clause"), value]));
+    clauses.push(clause_doc(others, None, fail));
+    case_doc(values(subjects), clauses)
+}
+
+/// Guards cannot contain case expressions, so the boolean operators are not short circuiting
+/// within them.
+///
+fn guard(guard_expr: TypedExpr, env: &mut Env) -> Document {
+    match guard_expr {
+        Expr::BinOp {
+            name: BinOp::And,
+            left,
+            right,
+            ..
+        } => erlang_call("and", vec![guard(*left, env), guard(*right, env)]),
+
+        Expr::BinOp {
+            name: BinOp::Or,
+            left,
+            right,
+            ..
+        } => erlang_call("or", vec![guard(*left, env), guard(*right, env)]),
+
+        other => expr(other, env),
+    }
+}
+
+fn bin_op(name: BinOp, left: TypedExpr, right: TypedExpr, env: &mut Env) -> Document {
+    let op = match name {
+        BinOp::Pipe => return call(right, vec![left], env),
+        BinOp::Concatenate => return concatenate(left, right, env),
+        BinOp::And => {
+            let left = expr(left, env);
+            let right = expr(right, env);
+            return case_doc(
+                left,
+                vec![
+                    clause_doc(vec![atom("true")], None, right),
+                    clause_doc(vec![atom("false")], None, atom("false")),
+                ],
+            );
+        }
+        BinOp::Or => {
+            let left = expr(left, env);
+            let right = expr(right, env);
+            return case_doc(
+                left,
+                vec![
+                    clause_doc(vec![atom("true")], None, atom("true")),
+                    clause_doc(vec![atom("false")], None, right),
+                ],
+            );
+        }
+        BinOp::LtInt | BinOp::LtFloat => "<",
+        BinOp::LtEqInt | BinOp::LtEqFloat => "=<",
+        BinOp::Eq => "=:=",
+        BinOp::NotEq => "=/=",
+        BinOp::GtInt | BinOp::GtFloat => ">",
+        BinOp::GtEqInt | BinOp::GtEqFloat => ">=",
+        BinOp::AddInt | BinOp::AddFloat => "+",
+        BinOp::SubInt | BinOp::SubFloat => "-",
+        BinOp::MultInt | BinOp::MultFloat => "*",
+        BinOp::DivInt => "div",
+        BinOp::DivFloat => "/",
+        BinOp::ModuloInt => "rem",
+    };
+    let left = expr(left, env);
+    erlang_call(op, vec![left, expr(right, env)])
+}
+
+fn concatenate(left: TypedExpr, right: TypedExpr, env: &mut Env) -> Document {
+    let segments: Vec<_> = vec![left, right]
+        .into_iter()
+        .flat_map(|side| match side {
+            Expr::String { value, .. } => value.bytes().map(byte_segment).collect::<Vec<_>>(),
+            Expr::BinOp {
+                name: BinOp::Concatenate,
+                left,
+                right,
+                ..
+            } => vec![concatenate(*left, *right, env)]
+                .into_iter()
+                .map(binary_segment)
+                .collect(),
+            other => vec![binary_segment(expr(other, env))],
+        })
+        .collect();
+    comma_list(segments).surround("#{", "}#")
+}
+
+fn block(body: Vec<TypedExpr>, env: &mut Env) -> Document {
+    let vars = env.vars.clone();
+    let mut body: Vec<_> = body.into_iter().map(|e| expr(e, env)).collect();
+    env.vars = vars;
+    let last = body.pop().unwrap_or_else(|| "[]".to_doc());
+    body.into_iter().rev().fold(last, |then, first| {
+        "do".to_doc()
+            .append(line().append(first).nest(INDENT))
+            .append(line())
+            .append(then)
+    })
+}
+
+fn struct_update(
+    constructor: TypedExpr,
+    spread: TypedExpr,
+    args: Vec<UpdateArg<TypedExpr>>,
+    env: &mut Env,
+) -> Document {
+    let field_map = match constructor {
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::Struct { field_map, .. },
+                    ..
+                },
+            ..
+        } => field_map,
+        _ => panic!("core_erlang: struct update with a constructor that is not a struct"),
+    };
+    let spread = expr(spread, env);
+    args.into_iter().fold(spread, |doc, arg| {
+        let index = field_map
+            .index(&arg.label)
+            .expect("core_erlang: struct update with unknown label");
+        erlang_call(
+            "setelement",
+            vec![(index + 2).to_doc(), doc, expr(arg.value, env)],
+        )
+    })
+}
+
+fn pattern(p: TypedPattern, env: &mut Env) -> Document {
+    match p {
+        Pattern::Nil { .. } => "[]".to_doc(),
+
+        Pattern::Cons { head, tail, .. } => {
+            let head = pattern(*head, env);
+            cons(head, pattern(*tail, env))
+        }
+
+        Pattern::Discard { .. } => env.fresh(),
+
+        Pattern::Var { name, .. } => env.bind(name),
+
+        Pattern::Int { value, .. } => int(&value),
+
+        Pattern::Float { value, .. } => float(value),
+
+        Pattern::String { value, .. } => string(&value),
+
+        Pattern::Constructor {
+            name,
+            args,
+            constructor: PatternConstructor::Enum,
+            ..
+        }
+        | Pattern::Constructor {
+            args,
+            constructor: PatternConstructor::Struct { name },
+            ..
+        } => {
+            if args.is_empty() {
+                atom(&constructor_atom(&name))
+            } else {
+                let args = args.into_iter().map(|a| pattern(a.value, env)).collect();
+                tag_tuple(&name, args)
+            }
+        }
+
+        Pattern::AnonStruct { elems, .. } => {
+            tuple(elems.into_iter().map(|p| pattern(p, env)).collect())
+        }
+
+        Pattern::BitString { segments, .. } => comma_list(
+            segments
+                .into_iter()
+                .map(|segment| bit_segment_pattern(segment, env))
+                .collect(),
+        )
+        .surround("#{", "}#"),
+    }
+}
+
+/// A segment of a Core Erlang binary pattern, which gives the size, unit, type and flags of the
+/// segment explicitly, as `#<Value>(Size, Unit, Type, Flags)`. Integer segments default to 8 bits
+/// and binary segments without a size match the rest of the binary.
+///
+fn bit_segment_pattern(segment: BitSegment<TypedPattern>, env: &mut Env) -> Document {
+    let value = pattern(segment.value, env);
+    let size = match segment.size {
+        Some(BitSegmentSize::Int { value, .. }) => int(&value),
+        Some(BitSegmentSize::Var { name, .. }) => env.local_var(&name),
+        None => match segment.kind {
+            BitSegmentKind::Int => "8".to_doc(),
+            BitSegmentKind::Binary => "'all'".to_doc(),
+        },
+    };
+    let (unit, typ) = match segment.kind {
+        BitSegmentKind::Int => ("1", "'integer'"),
+        BitSegmentKind::Binary => ("8", "'binary'"),
+    };
+    "#<".to_doc()
+        .append(value)
+        .append(">(")
+        .append(size)
+        .append(format!(",{},{},['unsigned'|['big']])", unit, typ))
+}
+
+#[test]
+fn module_test() {
+    let compile = |src: &str| {
+        let mut ast = crate::grammar::ModuleParser::new()
+            .parse(src)
+            .expect("syntax error");
+        ast.name = vec!["the_app".to_string()];
+        let typed = crate::typ::infer_module(ast, &std::collections::HashMap::new(), &mut vec![])
+            .expect("should successfully infer");
+        module(typed)
+    };
+
+    assert_eq!(
+        compile("pub fn go(x) { x + 1 } fn zero() { 0 }"),
+        "module 'the_app' ['go'/1]
+    attributes []
+
+'go'/1 =
+    fun (_x) ->
+        call 'erlang':'+'(_x, 1)
+
+'zero'/0 =
+    fun () ->
+        0
+
+end
+"
+    );
+
+    assert_eq!(
+        compile(
+            r#"pub external fn print(String) -> Nil = "io" "format"
+               pub fn hello() { print("Hi") }"#
+        ),
+        "module 'the_app' ['hello'/0, 'print'/1]
+    attributes []
+
+'print'/1 =
+    fun (Cor@1) ->
+        call 'io':'format'(Cor@1)
+
+'hello'/0 =
+    fun () ->
+        apply 'print'/1(#{#<72>(8,1,'integer',['unsigned'|['big']]), #<105>(8,1,'integer',['unsigned'|['big']])}#)
+
+end
+"
+    );
+
+    assert_eq!(
+        compile(
+            r#"@behaviour("gen_server")
+             pub enum Shape { Square Circle(Float) }
+             pub struct Point { x: Int y: Int }
+             pub fn area(shape) {
+               case shape {
+                 Square -> 1.0
+                 Circle(r) -> r *. r
+               }
+             }
+             pub fn origin() { Point(x: 0, y: 0) }
+             pub fn move(p) { let Point(x: x, y: _) = p Point(..p, x: x + 1) }"#
+        ),
+        "module 'the_app' ['area'/1, 'move'/1, 'origin'/0]
+    attributes ['behaviour' = ['gen_server']]
+
+'area'/1 =
+    fun (_shape) ->
+        case <_shape> of
+            <'square'> when 'true' ->
+                1.0
+            <{'circle', _r}> when 'true' ->
+                call 'erlang':'*'(_r, _r)
+            <Cor@1> when 'true' ->
+                primop 'match_fail'({'case_clause', Cor@1})
+        end
+
+'origin'/0 =
+    fun () ->
+        {'point', 0, 0}
+
+'move'/1 =
+    fun (_p) ->
+        case _p of
+            <{'point', _x, Cor@1}> when 'true' ->
+                call 'erlang':'setelement'(2, _p, call 'erlang':'+'(_x, 1))
+            <Cor@2> when 'true' ->
+                primop 'match_fail'({'badmatch', Cor@2})
+        end
+
+end
+"
+    );
+
+    assert_eq!(
+        compile(
+            "fn go(x) {
+               let rec count = fn(n) { case n == 0 { True -> [] False -> [n | count(n - 1)] } }
+               count(x)
+             }"
+        ),
+        "module 'the_app' []
+    attributes []
+
+'go'/1 =
+    fun (_x) ->
+        letrec 'count@rec'/1 =
+            fun (_n) ->
+                case <call 'erlang':'=:='(_n, 0)> of
+                    <'true'> when 'true' ->
+                        []
+                    <'false'> when 'true' ->
+                        [_n|apply 'count@rec'/1(call 'erlang':'-'(_n, 1))]
+                    <Cor@1> when 'true' ->
+                        primop 'match_fail'({'case_clause', Cor@1})
+                end
+        in apply 'count@rec'/1(_x)
+
+end
+"
+    );
+
+    assert_eq!(
+        compile(
+            r#"pub fn version(packet) {
+                 case packet {
+                   <<version:8, rest:binary>> -> version
+                   _ -> 0
+                 }
+               }"#
+        ),
+        "module 'the_app' ['version'/1]
+    attributes []
+
+'version'/1 =
+    fun (_packet) ->
+        case <_packet> of
+            <#{#<_version>(8,1,'integer',['unsigned'|['big']]), #<_rest>('all',8,'binary',['unsigned'|['big']])}#> when 'true' ->
+                _version
+            <Cor@1> when 'true' ->
+                0
+            <Cor@2> when 'true' ->
+                primop 'match_fail'({'case_clause', Cor@2})
+        end
+
+end
+"
+    );
+
+    assert_eq!(
+        compile("pub fn first(#(a, _), x) { a + x }"),
+        "module 'the_app' ['first'/2]
+    attributes []
+
+'first'/2 =
+    fun (Cor@1, _x) ->
+        case <Cor@1> of
+            <{_a, Cor@2}> when 'true' ->
+                call 'erlang':'+'(_a, _x)
+            <Cor@3> when 'true' ->
+                primop 'match_fail'({'badmatch', Cor@3})
+        end
+
+end
+"
+    );
+}
+
+#[test]
+fn erlc_from_core_test() {
+    // The emitted Core Erlang can only be validated by the Erlang compiler, so
+    // this test is skipped when erlc is not installed.
+    if std::process::Command::new("erlc").output().is_err() {
+        return;
+    }
+
+    let src = r#"
+pub enum Box(a) { Box(a) Empty }
+external fn add(Int, Int) -> Int = "erlang" "+"
+pub fn go(x) {
+  case Box(add(x, 1)) {
+    Box(n) -> n
+    Empty -> 0
+  }
+}
+pub fn wrap() { Box }
+pub fn first(#(a, _)) { a }
+"#;
+    let mut ast = crate::grammar::ModuleParser::new()
+        .parse(src)
+        .expect("syntax error");
+    ast.name = vec!["gleam_erlc_from_core".to_string()];
+    let typed = crate::typ::infer_module(ast, &std::collections::HashMap::new(), &mut vec![])
+        .expect("should successfully infer");
+
+    let dir = std::env::temp_dir().join("gleam_erlc_from_core_test");
+    std::fs::create_dir_all(&dir).expect("create temp dir");
+    let path = dir.join("gleam_erlc_from_core.core");
+    std::fs::write(&path, module(typed)).expect("write core file");
+
+    let output = std::process::Command::new("erlc")
+        .arg("+from_core")
+        .arg("-o")
+        .arg(&dir)
+        .arg(&path)
+        .output()
+        .expect("run erlc");
+    assert!(
+        output.status.success(),
+        "erlc rejected generated Core Erlang:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,178 @@
+use crate::ast::{Statement, TypedModule};
+use itertools::Itertools;
+use std::collections::HashSet;
+
+/// Render the HTML documentation page for a module, with a section for each public definition.
+/// Imports of modules named in `linkable` link to the pages for those modules, which are written
+/// to the same directory.
+///
+pub fn module_html(module: &TypedModule, linkable: &HashSet<String>) -> String {
+    let name = module.name_string();
+    let mut imports = vec![];
+    let mut types = vec![];
+    let mut values = vec![];
+
+    for statement in module.statements.iter() {
+        match statement {
+            Statement::Import { module, .. } => {
+                let imported = module.join("/");
+                if linkable.contains(&imported) {
+                    imports.push(format!(
+                        r#"<li><a href="{}">{}</a></li>"#,
+                        page_name(module),
+                        escape(&imported)
+                    ));
+                } else {
+                    imports.push(format!("<li>{}</li>", escape(&imported)));
+                }
+            }
+
+            Statement::Enum {
+                public: true,
+                name,
+                args,
+                opaque,
+                constructors,
+                ..
+            } => {
+                let constructors = if *opaque {
+                    vec![]
+                } else {
+                    constructors.iter().map(|c| c.name.as_str()).collect()
+                };
+                types.push(type_section(module, name, args, &constructors));
+            }
+
+            Statement::Struct {
+                public: true,
+                name,
+                type_args,
+                opaque,
+                ..
+            } => {
+                let constructors = if *opaque { vec![] } else { vec![name.as_str()] };
+                types.push(type_section(module, name, type_args, &constructors));
+            }
+
+            Statement::ExternalType {
+                public: true,
+                name,
+                args,
+                ..
+            }
+            | Statement::TypeAlias {
+                public: true,
+                name,
+                args,
+                ..
+            } => types.push(type_section(module, name, args, &[])),
+
+            Statement::Fn {
+                public: true, name, ..
+            }
+            | Statement::ExternalFn {
+                public: true, name, ..
+            }
+            | Statement::InlineErlangFn {
+                public: true, name, ..
+            }
+            | Statement::Const {
+                public: true, name, ..
+            } => values.push(value_section(module, name)),
+
+            _ => (),
+        }
+    }
+
+    let mut sections = vec![];
+    if !imports.is_empty() {
+        sections.push(format!(
+            "<section id=\"imports\">\n<h2>Imports</h2>\n<ul>\n{}\n</ul>\n</section>",
+            imports.join("\n")
+        ));
+    }
+    if !types.is_empty() {
+        sections.push(format!("<h2>Types</h2>\n{}", types.join("\n")));
+    }
+    if !values.is_empty() {
+        sections.push(format!("<h2>Values</h2>\n{}", values.join("\n")));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{name}</title>
+<style>{style}</style>
+</head>
+<body>
+<h1>{name}</h1>
+{sections}
+</body>
+</html>
+"#,
+        name = escape(&name),
+        style = STYLE,
+        sections = sections.join("\n"),
+    )
+}
+
+pub fn page_name(module: &[String]) -> String {
+    format!("{}.html", module.join("@"))
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: auto; } \
+section { border-top: 1px solid #ddd; } \
+pre { background: #f5f5f5; padding: 0.5em; }";
+
+fn type_section(
+    module: &TypedModule,
+    name: &str,
+    args: &[String],
+    constructors: &[&str],
+) -> String {
+    let head = if args.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}({})", name, args.join(", "))
+    };
+    let constructors = constructors
+        .iter()
+        .filter_map(|c| module.type_info.value_constructors.get(*c).map(|v| (c, v)))
+        .map(|(c, v)| format!("<pre>{}: {}</pre>", c, escape(&v.typ.to_gleam_string())))
+        .join("\n");
+    format!(
+        "<section id=\"type-{name}\">\n<h3><a href=\"#type-{name}\">{name}</a></h3>\n<pre>{head}</pre>\n{constructors}\n</section>",
+        name = name,
+        head = escape(&head),
+        constructors = constructors,
+    )
+}
+
+fn value_section(module: &TypedModule, name: &str) -> String {
+    let typ = module
+        .type_info
+        .value_constructors
+        .get(name)
+        .map(|v| format!("\n<pre>{}</pre>", escape(&v.typ.to_gleam_string())))
+        .unwrap_or_default();
+    format!(
+        "<section id=\"{name}\">\n<h3><a href=\"#{name}\">{name}</a></h3>{typ}\n</section>",
+        name = name,
+        typ = typ,
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn escape_test() {
+    assert_eq!("fn(a) -&gt; List(a)", escape("fn(a) -> List(a)"));
+    assert_eq!("&lt;&amp;&quot;", escape("<&\""));
+}
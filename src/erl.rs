@@ -8,19 +8,27 @@ use itertools::Itertools;
 use std::char;
 use std::default::Default;
 
-const INDENT: isize = 4;
+pub const DEFAULT_INDENT: usize = 4;
 
 #[derive(Debug, Clone)]
 struct Env<'a> {
     module: &'a Vec<String>,
     vars: im::HashMap<String, usize>,
+    // The highest number given to each variable within a block that has ended. Erlang does not
+    // scope variables to blocks so these names cannot be bound again.
+    block_vars: im::HashMap<String, usize>,
+    indent: isize,
+    uid: usize,
 }
 
 impl<'a> Env<'a> {
-    pub fn new(module: &'a Vec<String>) -> Self {
+    pub fn new(module: &'a Vec<String>, indent: isize) -> Self {
         Self {
             vars: Default::default(),
+            block_vars: Default::default(),
             module,
+            indent,
+            uid: 0,
         }
     }
 
@@ -36,18 +44,30 @@ impl<'a> Env<'a> {
     }
 
     pub fn next_local_var_name(&mut self, name: String) -> Document {
+        let previous = self.vars.get(&name).max(self.block_vars.get(&name));
         self.vars
-            .insert(name.clone(), self.vars.get(&name).map_or(0, |i| i + 1));
+            .insert(name.clone(), previous.map_or(0, |i| i + 1));
         self.local_var_name(name)
     }
+
+    /// A variable that is not bound by the Gleam code. The name contains an `@` so that it cannot
+    /// clash with the name of a variable from the Gleam code.
+    ///
+    pub fn fresh(&mut self, name: &str) -> Document {
+        self.uid += 1;
+        format!("{}@{}", name, self.uid).to_doc()
+    }
 }
 
-pub fn records(module: &TypedModule) -> Vec<(&str, String)> {
+/// Opaque structs have no record as their fields are not part of the module's interface.
+///
+pub fn records<A, B, C, D, E>(module: &Module<A, B, C, D, E>) -> Vec<(&str, String)> {
     let mut records = vec![];
     for statement in &module.statements {
         match statement {
             Statement::Struct {
                 public: true,
+                opaque: false,
                 fields,
                 name,
                 ..
@@ -59,9 +79,12 @@ pub fn records(module: &TypedModule) -> Vec<(&str, String)> {
     records
 }
 
+/// A struct is a tuple tagged with its snake case name, which is the layout of an Erlang record, so
+/// `Point(x: 1, y: 2)` is `{point, 1, 2}` and also `#point{x = 1, y = 2}`.
+///
 pub fn struct_record(name: &str, fields: &[StructField]) -> String {
     use std::fmt::Write;
-    let mut buffer = format!("-record({}, {{", name.to_snake_case());
+    let mut buffer = format!("-record({}, {{", constructor_atom(name));
     for field in fields.iter().map(|f| f.label.as_ref()).intersperse(", ") {
         write!(buffer, "{}", field).unwrap();
     }
@@ -97,37 +120,235 @@ fn struct_record_test() {
     );
 }
 
+/// Generate the `gleam_test_runner` module, whose `main/0` calls each test function and returns the
+/// `{Module, Function}` of those that crashed.
+///
+pub fn test_runner(tests: &[(String, String)]) -> String {
+    format!(
+        r#"-module(gleam_test_runner).
+-compile(no_auto_import).
+
+-export([main/0]).
+
+-dialyzer({{nowarn_function, [main/0, passes/2]}}).
+
+main() ->
+    Tests = [{}],
+    [Test || {{Module, Function}} = Test <- Tests, not passes(Module, Function)].
+
+passes(Module, Function) ->
+    try Module:Function() of
+        _ ->
+            true
+    catch
+        _:_ ->
+            false
+    end.
+"#,
+        tests
+            .iter()
+            .map(|(module, function)| format!(
+                "{{{}, {}}}",
+                atom(module.clone()).format(80),
+                atom(function.clone()).format(80)
+            ))
+            .join(", "),
+    )
+}
+
+/// Generate an escript that calls the public `main/0` of the module and prints the result using the
+/// given `io:format` control string.
+///
+pub fn escript(module: TypedModule, result_format: &str) -> String {
+    let erl = self::module(module).replacen(
+        "-compile(no_auto_import).\n",
+        "-compile(no_auto_import).\n-export([main/1]).\n",
+        1,
+    );
+    format!(
+        r#"#!/usr/bin/env escript
+{}
+main(_) ->
+    io:format("{}", [main()]).
+"#,
+        erl, result_format,
+    )
+}
+
+pub fn app_resource(name: &str, version: &str, modules: &[String]) -> String {
+    format!(
+        r#"{{application, {},
+ [{{description, ""}},
+  {{vsn, "{}"}},
+  {{registered, []}},
+  {{applications, [kernel, stdlib]}},
+  {{env, []}},
+  {{modules, [{}]}}
+]}}.
+"#,
+        atom(name.to_string()).format(80),
+        version,
+        modules
+            .iter()
+            .map(|m| atom(m.clone()).format(80))
+            .join(", "),
+    )
+}
+
+#[test]
+fn app_resource_test() {
+    assert_eq!(
+        app_resource(
+            "my_app",
+            "1.2.0",
+            &["one".to_string(), "nested@two".to_string()]
+        ),
+        r#"{application, my_app,
+ [{description, ""},
+  {vsn, "1.2.0"},
+  {registered, []},
+  {applications, [kernel, stdlib]},
+  {env, []},
+  {modules, [one, nested@two]}
+]}.
+"#
+        .to_string()
+    );
+}
+
 pub fn module(module: TypedModule) -> String {
+    module_document(module, None, DEFAULT_INDENT)
+}
+
+/// When the path and code of the Gleam source are given each function is preceded by a `-file`
+/// attribute, so that errors from the Erlang compiler point to the Gleam code.
+///
+pub fn module_with_options(
+    module: TypedModule,
+    source: Option<(&str, &str)>,
+    indent: usize,
+) -> String {
+    module_document(module, source, indent)
+}
+
+pub fn header(version: &str, source_path: &str) -> String {
+    format!("%% Generated by Gleam {} from {}\n", version, source_path)
+}
+
+fn module_document(module: TypedModule, source: Option<(&str, &str)>, indent: usize) -> String {
+    let indent = indent as isize;
+    let module = module.for_target(crate::project::CompileTarget::Erlang);
     let module_name = module.name;
-    let exports: Vec<_> = module
+    let type_info = module.type_info;
+    let reexports = |unqualified: &[UnqualifiedImport]| -> Vec<(String, Vec<String>, usize)> {
+        unqualified
+            .iter()
+            .flat_map(|UnqualifiedImport { name, .. }| {
+                match type_info.value_constructors.get(name).map(|v| &v.variant) {
+                    Some(ValueConstructorVariant::ModuleFn { module, arity, .. })
+                        if module != &module_name =>
+                    {
+                        Some((name.clone(), module.clone(), *arity))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    };
+
+    let mut exports: Vec<_> = module
         .statements
         .iter()
         .flat_map(|s| match s {
+            Statement::Import {
+                public: true,
+                unqualified,
+                ..
+            } => reexports(unqualified)
+                .into_iter()
+                .map(|(name, _, arity)| (name, arity))
+                .collect(),
+
             Statement::Fn {
                 public: true,
                 name,
                 args,
                 ..
-            } => Some((name.clone(), args.len())),
+            } => vec![(name.clone(), args.len())],
 
             Statement::ExternalFn {
                 public: true,
                 name,
                 args,
                 ..
-            } => Some((name.clone(), args.len())),
+            } => vec![(name.clone(), args.len())],
 
-            _ => None,
+            Statement::InlineErlangFn {
+                public: true,
+                name,
+                args,
+                ..
+            } => vec![(name.clone(), args.len())],
+
+            _ => vec![],
         })
+        .collect();
+    exports.sort();
+    let exports: Vec<_> = exports
+        .into_iter()
         .map(|(n, a)| atom(n).append("/").append(a))
         .intersperse(", ".to_doc())
         .collect();
 
-    format!("-module({}).", module_name.join("@"))
+    let mut external_types: Vec<_> = module
+        .statements
+        .iter()
+        .flat_map(|s| match s {
+            Statement::ExternalType {
+                public: true,
+                name,
+                args,
+                ..
+            } if !is_erlang_builtin_type(&name.to_snake_case()) => {
+                Some((name.to_snake_case(), args))
+            }
+
+            _ => None,
+        })
+        .collect();
+    external_types.sort();
+
+    let type_exports: Vec<_> = external_types
+        .iter()
+        .map(|(n, a)| atom(n.clone()).append("/").append(a.len()))
+        .intersperse(", ".to_doc())
+        .collect();
+
+    let behaviours: Vec<_> = module
+        .statements
+        .iter()
+        .filter_map(|s| match s {
+            Statement::Behaviour { name, .. } => Some(
+                "-behaviour("
+                    .to_doc()
+                    .append(atom(name.clone()))
+                    .append(").")
+                    .append(line()),
+            ),
+            _ => None,
+        })
+        .collect();
+
+    let doc = format!("-module({}).", module_name.join("@"))
         .to_doc()
         .append(line())
         .append("-compile(no_auto_import).")
         .append(lines(2))
+        .append(if behaviours.is_empty() {
+            nil()
+        } else {
+            behaviours.to_doc().append(line())
+        })
         .append(if exports.is_empty() {
             nil()
         } else {
@@ -137,72 +358,273 @@ pub fn module(module: TypedModule) -> String {
                 .append("]).")
                 .append(lines(2))
         })
+        .append(if external_types.is_empty() {
+            nil()
+        } else {
+            "-export_type(["
+                .to_doc()
+                .append(type_exports)
+                .append("]).")
+                .append(lines(2))
+                .append(
+                    external_types
+                        .into_iter()
+                        .map(|(name, args)| {
+                            let args: Vec<_> = args
+                                .iter()
+                                .map(|a| format!("_{}", a.to_camel_case()).to_doc())
+                                .intersperse(", ".to_doc())
+                                .collect();
+                            "-type "
+                                .to_doc()
+                                .append(atom(name))
+                                .append("(")
+                                .append(args)
+                                .append(") :: any().")
+                                .append(line())
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .append(line())
+        })
         .append(
             module
                 .statements
                 .into_iter()
-                .flat_map(|s| statement(s, &module_name))
+                .flat_map(|s| {
+                    let attribute = match (&s, source) {
+                        (Statement::Fn { meta, .. }, Some((path, src)))
+                        | (Statement::ExternalFn { meta, .. }, Some((path, src))) => {
+                            Some(file_attribute(path, src, meta))
+                        }
+                        _ => None,
+                    };
+                    if let Statement::Import {
+                        public: true,
+                        unqualified,
+                        ..
+                    } = &s
+                    {
+                        let forwarders = reexports(unqualified);
+                        if forwarders.is_empty() {
+                            return None;
+                        }
+                        return Some(
+                            forwarders
+                                .into_iter()
+                                .map(|(name, module, arity)| {
+                                    let module = module.join("@");
+                                    nowarn_function(&name, arity)
+                                        .append(line())
+                                        .append(external_fun(
+                                            name.clone(),
+                                            module,
+                                            name,
+                                            arity,
+                                            indent,
+                                        ))
+                                })
+                                .intersperse(lines(2))
+                                .collect::<Vec<_>>()
+                                .to_doc(),
+                        );
+                    }
+                    statement(s, &module_name, indent).map(|doc| match attribute {
+                        Some(attribute) => attribute.append(line()).append(doc),
+                        None => doc,
+                    })
+                })
                 .intersperse(lines(2))
                 .collect::<Vec<_>>(),
         )
-        .append(line())
-        .format(80)
+        .format(80);
+
+    // Whatever forms were generated, end the module with exactly one newline
+    format!("{}\n", doc.trim_end())
+}
+
+/// Dialyzer warnings are suppressed for generated functions as the user could not fix them.
+///
+fn nowarn_function(name: &str, arity: usize) -> Document {
+    "-dialyzer({nowarn_function, ["
+        .to_doc()
+        .append(atom(name.to_string()))
+        .append(format!("/{}]}}).", arity))
 }
 
-fn statement(statement: TypedStatement, module: &Vec<String>) -> Option<Document> {
+fn file_attribute(path: &str, src: &str, meta: &Meta) -> Document {
+    let line = src[..meta.start].matches('\n').count() + 1;
+    let path = path.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("-file(\"{}\", {}).", path, line).to_doc()
+}
+
+fn statement(statement: TypedStatement, module: &Vec<String>, indent: isize) -> Option<Document> {
     match statement {
         Statement::Enum { .. } => None,
         Statement::Struct { .. } => None,
         Statement::Import { .. } => None,
         Statement::ExternalType { .. } => None,
+        Statement::TypeAlias { .. } => None,
+        Statement::Const { .. } => None,
+        Statement::Behaviour { .. } => None,
+        Statement::Export { .. } => None,
+        // Removed from the module by `for_target` before code is generated
+        Statement::Target { .. } => None,
         Statement::Fn {
             args, name, body, ..
-        } => Some(mod_fun(name, args, body, module)),
+        } => Some(mod_fun(name, args, body, module, indent)),
         Statement::ExternalFn {
             fun,
             module,
             args,
             name,
             ..
-        } => Some(external_fun(name, module, fun, args.len())),
+        } => Some(external_fun(name, module, fun, args.len(), indent)),
+        Statement::InlineErlangFn { erlang, .. } => Some(erlang.trim().to_string().to_doc()),
     }
 }
 
-fn mod_fun(name: String, args: Vec<Arg>, body: TypedExpr, module: &Vec<String>) -> Document {
-    let mut env = Env::new(module);
+fn mod_fun(
+    name: String,
+    args: Vec<TypedArg>,
+    body: TypedExpr,
+    module: &Vec<String>,
+    indent: isize,
+) -> Document {
+    let mut env = Env::new(module, indent);
 
     atom(name)
         .append(fun_args(args, &mut env))
         .append(" ->")
-        .append(line().append(expr(body, &mut env)).nest(INDENT).group())
+        .append(line().append(expr(body, &mut env)).nest(indent).group())
         .append(".")
 }
 
-fn fun_args(args: Vec<Arg>, env: &mut Env) -> Document {
-    wrap_args(args.into_iter().map(|a| match a.names {
+fn fun_args(args: Vec<TypedArg>, env: &mut Env) -> Document {
+    let indent = env.indent;
+    let args = args.into_iter().map(|a| match a.names {
         ArgNames::Discard => "_".to_doc(),
         ArgNames::Named { name } | ArgNames::NamedLabelled { name, .. } => {
             env.next_local_var_name(name)
         }
-    }))
+        ArgNames::Pattern { pattern: p } => pattern(p, env),
+    });
+    wrap_args(args, indent)
 }
 
 fn call_args(args: Vec<CallArg<TypedExpr>>, env: &mut Env) -> Document {
-    wrap_args(args.into_iter().map(|arg| wrap_expr(arg.value, env)))
+    let indent = env.indent;
+    wrap_args(args.into_iter().map(|arg| wrap_expr(arg.value, env)), indent)
 }
 
-fn wrap_args<I>(args: I) -> Document
+fn wrap_args<I>(args: I, indent: isize) -> Document
 where
     I: Iterator<Item = Document>,
 {
     break_("", "")
         .append(args.intersperse(delim(",")).collect::<Vec<_>>())
-        .nest(INDENT)
+        .nest(indent)
         .append(break_("", ""))
         .surround("(", ")")
         .group()
 }
 
+fn int(value: String) -> Document {
+    let value = value.replace("_", "");
+    let (sign, value) = match value.as_bytes().first() {
+        Some(b'-') => ("-", &value[1..]),
+        _ => ("", &value[..]),
+    };
+    match value.get(..2) {
+        Some("0x") => format!("{}16#{}", sign, &value[2..]).to_doc(),
+        Some("0o") => format!("{}8#{}", sign, &value[2..]).to_doc(),
+        Some("0b") => format!("{}2#{}", sign, &value[2..]).to_doc(),
+        _ => format!("{}{}", sign, value).to_doc(),
+    }
+}
+
+/// Erlang does not permit these types to be redefined, so external types that share their names
+/// cannot be exported.
+fn is_erlang_builtin_type(name: &str) -> bool {
+    matches!(
+        name,
+        "any"
+            | "arity"
+            | "atom"
+            | "binary"
+            | "bitstring"
+            | "boolean"
+            | "byte"
+            | "char"
+            | "float"
+            | "fun"
+            | "function"
+            | "identifier"
+            | "integer"
+            | "iodata"
+            | "iolist"
+            | "list"
+            | "map"
+            | "maybe_improper_list"
+            | "mfa"
+            | "module"
+            | "neg_integer"
+            | "nil"
+            | "no_return"
+            | "node"
+            | "non_neg_integer"
+            | "none"
+            | "nonempty_list"
+            | "number"
+            | "pid"
+            | "port"
+            | "pos_integer"
+            | "reference"
+            | "string"
+            | "term"
+            | "timeout"
+            | "tuple"
+    )
+}
+
+/// The atom tagging the values built by a constructor, such as `box` for `Box` and `http_response`
+/// for `HTTPResponse`. It depends only on the name of the constructor, so it is the same whatever
+/// order the constructors are defined in.
+///
+pub fn constructor_atom(name: &str) -> String {
+    name.to_snake_case()
+}
+
+#[test]
+fn constructor_atom_test() {
+    assert_eq!("box", constructor_atom("Box"));
+    assert_eq!("http_response", constructor_atom("HTTPResponse"));
+    assert_eq!("not_found", constructor_atom("NotFound"));
+    assert_eq!("ok", constructor_atom("Ok"));
+
+    // Names differing only in case share an atom, which the type checker rejects within an enum
+    assert_eq!("http_response", constructor_atom("HttpResponse"));
+
+    // Constructors sharing a prefix are given distinct atoms
+    assert_eq!("b", constructor_atom("B"));
+    assert_eq!("box_set", constructor_atom("BoxSet"));
+    assert_eq!("boxes", constructor_atom("Boxes"));
+    assert_eq!("boxed_box", constructor_atom("BoxedBox"));
+}
+
+/// Erlang floats must have a digit after the decimal point, so exponents such as `1e300` are
+/// written as `1.0e300`.
+///
+pub fn float(value: f64) -> Document {
+    let value = format!("{:?}", value);
+    match value.find('e') {
+        Some(i) if !value[..i].contains('.') => {
+            format!("{}.0{}", &value[..i], &value[i..]).to_doc()
+        }
+        _ => value.to_doc(),
+    }
+}
+
 fn atom(value: String) -> Document {
     use regex::Regex;
     lazy_static! {
@@ -224,7 +646,31 @@ fn atom(value: String) -> Document {
 }
 
 fn string(value: String) -> Document {
-    value.to_doc().surround("<<\"", "\">>")
+    binary_segment(&value).surround("<<", ">>")
+}
+
+/// Strings containing non-ASCII characters are UTF-8 encoded.
+///
+fn binary_segment(value: &str) -> Document {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\x{{{:X}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    if value.is_ascii() {
+        escaped.to_doc()
+    } else {
+        escaped.to_doc().append("/utf8")
+    }
 }
 
 fn tuple(elems: Vec<Document>) -> Document {
@@ -250,12 +696,13 @@ fn seq(first: TypedExpr, then: TypedExpr, env: &mut Env) -> Document {
 fn bin_op(name: BinOp, left: TypedExpr, right: TypedExpr, env: &mut Env) -> Document {
     let op = match name {
         BinOp::Pipe => return pipe(left, right, env),
+        BinOp::Concatenate => return concatenate(left, right, env),
         BinOp::And => "andalso",
         BinOp::Or => "orelse",
         BinOp::LtInt | BinOp::LtFloat => "<",
         BinOp::LtEqInt | BinOp::LtEqFloat => "=<",
         BinOp::Eq => "=:=",
-        BinOp::NotEq => "/=",
+        BinOp::NotEq => "=/=",
         BinOp::GtInt | BinOp::GtFloat => ">",
         BinOp::GtEqInt | BinOp::GtEqFloat => ">=",
         BinOp::AddInt => "+",
@@ -288,6 +735,55 @@ fn pipe(value: TypedExpr, fun: TypedExpr, env: &mut Env) -> Document {
     )
 }
 
+fn concatenate(left: TypedExpr, right: TypedExpr, env: &mut Env) -> Document {
+    let mut segments = vec![];
+    collect_concatenate_segments(left, &mut segments);
+    collect_concatenate_segments(right, &mut segments);
+    segments
+        .into_iter()
+        .map(|segment| match segment {
+            Expr::String { value, .. } => binary_segment(&value),
+            segment @ Expr::Var { .. } => expr(segment, env).append("/binary"),
+            segment => expr(segment, env).surround("(", ")/binary"),
+        })
+        .intersperse(delim(","))
+        .collect::<Vec<_>>()
+        .to_doc()
+        .nest_current()
+        .surround("<<", ">>")
+        .group()
+}
+
+fn collect_concatenate_segments(expression: TypedExpr, segments: &mut Vec<TypedExpr>) {
+    match expression {
+        Expr::BinOp {
+            name: BinOp::Concatenate,
+            left,
+            right,
+            ..
+        } => {
+            collect_concatenate_segments(*left, segments);
+            collect_concatenate_segments(*right, segments);
+        }
+        other => segments.push(other),
+    }
+}
+
+/// A recursive function is bound to an Erlang named fun, whose name is in scope in its body.
+///
+fn let_rec(name: String, value: TypedExpr, then: TypedExpr, env: &mut Env) -> Document {
+    let name = env.next_local_var_name(name);
+    let value = match value {
+        Expr::Fn { args, body, .. } => fun(Some(name.clone()), args, *body, env),
+        _ => panic!("erl: let rec with a value that is not a fn"),
+    };
+    name.append(" = ")
+        .append(value)
+        .append(",")
+        .append(line())
+        .append(expr(then, env))
+}
+
 fn let_(value: TypedExpr, pat: TypedPattern, then: TypedExpr, env: &mut Env) -> Document {
     let body = expr(value, env);
     pattern(pat, env)
@@ -298,6 +794,117 @@ fn let_(value: TypedExpr, pat: TypedPattern, then: TypedExpr, env: &mut Env) ->
         .append(expr(then, env))
 }
 
+fn try_(value: TypedExpr, pat: TypedPattern, then: TypedExpr, env: &mut Env) -> Document {
+    let value = wrap_expr(value, env);
+    let error = tuple(vec!["error".to_doc(), env.fresh("TryError")]);
+    let vars = env.vars.clone();
+    let ok_clause = tuple(vec!["ok".to_doc(), pattern(pat, env)])
+        .append(" ->")
+        .append(line().append(expr(then, env)).nest(env.indent).group());
+    env.vars = vars;
+    "case "
+        .to_doc()
+        .append(value.group())
+        .append(" of")
+        .append(
+            line()
+                .append(error.clone())
+                .append(" ->")
+                .append(line().append(error).nest(env.indent).group())
+                .append(";")
+                .append(lines(2))
+                .append(ok_clause)
+                .nest(env.indent),
+        )
+        .append(line())
+        .append("end")
+        .group()
+}
+
+fn assert(
+    meta: Meta,
+    value: TypedExpr,
+    pat: TypedPattern,
+    message: Option<TypedExpr>,
+    then: TypedExpr,
+    env: &mut Env,
+) -> Document {
+    let value = wrap_expr(value, env);
+    let var = env.fresh("AssertValue");
+    let message = match message {
+        Some(message) => expr(message, env),
+        None => string("Assertion pattern match failed".to_string()),
+    };
+    let error = format!(
+        "#{{gleam_error => assert, message => {}, pattern => {}, start => {}, 'end' => {}, value => {}}}",
+        message.format(80),
+        string(pat.to_gleam_string()).format(80),
+        meta.start,
+        meta.end,
+        var.clone().format(80),
+    );
+    let vars = env.vars.clone();
+    let match_clause = pattern(pat, env)
+        .append(" ->")
+        .append(line().append(expr(then, env)).nest(env.indent).group());
+    env.vars = vars;
+    "case "
+        .to_doc()
+        .append(value.group())
+        .append(" of")
+        .append(
+            line()
+                .append(match_clause)
+                .append(";")
+                .append(lines(2))
+                .append(var)
+                .append(" ->")
+                .append(
+                    line()
+                        .append(format!("erlang:error({})", error))
+                        .nest(env.indent)
+                        .group(),
+                )
+                .nest(env.indent),
+        )
+        .append(line())
+        .append("end")
+        .group()
+}
+
+fn assert_true(meta: Meta, value: TypedExpr, source: String, env: &mut Env) -> Document {
+    let value = wrap_expr(value, env);
+    let error = format!(
+        "#{{gleam_error => assert, message => {}, expression => {}, start => {}, 'end' => {}}}",
+        string("Assertion failed".to_string()).format(80),
+        string(source).format(80),
+        meta.start,
+        meta.end,
+    );
+    "case "
+        .to_doc()
+        .append(value.group())
+        .append(" of")
+        .append(
+            line()
+                .append("true ->")
+                .append(line().append("nil").nest(env.indent))
+                .append(";")
+                .append(lines(2))
+                .append("false ->")
+                .append(
+                    line()
+                        .append(format!("erlang:error({})", error))
+                        .nest(env.indent)
+                        .group(),
+                )
+                .nest(env.indent),
+        )
+        .append(line())
+        .append("end")
+        .group()
+}
+
 fn pattern(p: TypedPattern, env: &mut Env) -> Document {
     match p {
         Pattern::Nil { .. } => "[]".to_doc(),
@@ -308,9 +915,9 @@ fn pattern(p: TypedPattern, env: &mut Env) -> Document {
 
         Pattern::Var { name, .. } => env.next_local_var_name(name),
 
-        Pattern::Int { value, .. } => value.to_doc(),
+        Pattern::Int { value, .. } => int(value),
 
-        Pattern::Float { value, .. } => value.to_doc(),
+        Pattern::Float { value, .. } => float(value),
 
         Pattern::String { value, .. } => string(value),
 
@@ -330,6 +937,33 @@ fn pattern(p: TypedPattern, env: &mut Env) -> Document {
         Pattern::AnonStruct { elems, .. } => {
             tuple(elems.into_iter().map(|p| pattern(p, env)).collect())
         }
+
+        Pattern::BitString { segments, .. } => {
+            let segments = segments
+                .into_iter()
+                .map(|segment| bit_segment_pattern(segment, env));
+            Itertools::intersperse(segments, ", ".to_doc())
+                .collect::<Vec<_>>()
+                .to_doc()
+                .surround("<<", ">>")
+        }
+    }
+}
+
+/// The size of a binary segment is in bytes, as the default unit of Erlang binary segments is 8 bits.
+///
+fn bit_segment_pattern(segment: BitSegment<TypedPattern>, env: &mut Env) -> Document {
+    let value = pattern(segment.value, env);
+    let value = match segment.size {
+        None => value,
+        Some(BitSegmentSize::Int { value: size, .. }) => value.append(":").append(int(size)),
+        Some(BitSegmentSize::Var { name, .. }) => {
+            value.append(":").append(env.local_var_name(name))
+        }
+    };
+    match segment.kind {
+        BitSegmentKind::Int => value,
+        BitSegmentKind::Binary => value.append("/binary"),
     }
 }
 
@@ -411,24 +1045,14 @@ enum ListType<E, T> {
 
 fn var(name: String, constructor: ValueConstructor, env: &mut Env) -> Document {
     match constructor.variant {
-        ValueConstructorVariant::Enum { .. } => atom(name.to_snake_case()),
+        ValueConstructorVariant::Enum { arity, .. } => constructor_fun(&name, arity),
 
-        ValueConstructorVariant::Struct { name, arity: 0, .. } => name.to_snake_case().to_doc(),
-
-        ValueConstructorVariant::Struct { arity, .. } => {
-            let chars = incrementing_args_list(arity);
-            "fun("
-                .to_doc()
-                .append(chars.clone())
-                .append(") -> {")
-                .append(name.to_snake_case())
-                .append(", ")
-                .append(chars)
-                .append("} end")
-        }
+        ValueConstructorVariant::Struct { name, arity, .. } => constructor_fun(&name, arity),
 
         ValueConstructorVariant::LocalVariable => env.local_var_name(name),
 
+        ValueConstructorVariant::ModuleConst { literal } => literal_(literal),
+
         ValueConstructorVariant::ModuleFn { arity, module, .. } => "fun "
             .to_doc()
             .append(module.join("@"))
@@ -439,13 +1063,28 @@ fn var(name: String, constructor: ValueConstructor, env: &mut Env) -> Document {
     }
 }
 
+fn constructor_fun(name: &str, arity: usize) -> Document {
+    if arity == 0 {
+        return atom(constructor_atom(name));
+    }
+    let chars = incrementing_args_list(arity);
+    "fun("
+        .to_doc()
+        .append(chars.clone())
+        .append(") -> {")
+        .append(atom(constructor_atom(name)))
+        .append(", ")
+        .append(chars)
+        .append("} end")
+}
+
 fn tag_tuple_pattern(name: String, args: Vec<CallArg<TypedPattern>>, env: &mut Env) -> Document {
     if args.is_empty() {
-        atom(name.to_snake_case())
+        atom(constructor_atom(&name))
     } else {
         let mut args: Vec<_> = args.into_iter().map(|p| pattern(p.value, env)).collect();
         // FIXME: O(n), insert at start shuffles the elemes forward by one place
-        args.insert(0, atom(name.to_snake_case()));
+        args.insert(0, atom(constructor_atom(&name)));
         tuple(args)
     }
 }
@@ -461,9 +1100,14 @@ fn clause(mut clause: TypedClause, env: &mut Env) -> Document {
             .collect();
         tuple(docs)
     };
+    let guard_doc = match clause.guard {
+        Some(guard) => " when ".to_doc().append(expr(guard, env)),
+        None => nil(),
+    };
     patterns_doc
+        .append(guard_doc)
         .append(" ->")
-        .append(line().append(expr(clause.then, env)).nest(INDENT).group())
+        .append(line().append(expr(clause.then, env)).nest(env.indent).group())
 }
 
 fn clauses(cs: Vec<TypedClause>, env: &mut Env) -> Document {
@@ -489,7 +1133,7 @@ fn case(mut subjects: Vec<TypedExpr>, cs: Vec<TypedClause>, env: &mut Env) -> Do
         .to_doc()
         .append(subjects_doc)
         .append(" of")
-        .append(line().append(clauses(cs, env)).nest(INDENT))
+        .append(line().append(clauses(cs, env)).nest(env.indent))
         .append(line())
         .append("end")
         .group()
@@ -498,7 +1142,7 @@ fn case(mut subjects: Vec<TypedExpr>, cs: Vec<TypedClause>, env: &mut Env) -> Do
 fn tag_tuple(name: String, args: Vec<CallArg<TypedExpr>>, env: &mut Env) -> Document {
     let mut args: Vec<_> = args.into_iter().map(|arg| expr(arg.value, env)).collect();
     // FIXME: O(n), insert at start shuffles the elemes forward by one place
-    args.insert(0, atom(name.to_snake_case()));
+    args.insert(0, atom(constructor_atom(&name)));
     tuple(args)
 }
 
@@ -525,7 +1169,7 @@ fn call(fun: TypedExpr, args: Vec<CallArg<TypedExpr>>, env: &mut Env) -> Documen
                     ..
                 },
             ..
-        } => tag_tuple(name.to_snake_case(), args, env),
+        } => tag_tuple(name, args, env),
 
         Expr::Var {
             constructor:
@@ -605,10 +1249,10 @@ fn call(fun: TypedExpr, args: Vec<CallArg<TypedExpr>>, env: &mut Env) -> Documen
 
 /// Wrap a document in begin end
 ///
-fn begin_end(document: Document) -> Document {
+fn begin_end(document: Document, indent: isize) -> Document {
     force_break()
         .append("begin")
-        .append(line().append(document).nest(INDENT))
+        .append(line().append(document).nest(indent))
         .append(line())
         .append("end")
 }
@@ -617,8 +1261,14 @@ fn begin_end(document: Document) -> Document {
 ///
 fn wrap_expr(expression: TypedExpr, env: &mut Env) -> Document {
     match &expression {
-        Expr::Seq { .. } => begin_end(expr(expression, env)),
-        Expr::Let { .. } => begin_end(expr(expression, env)),
+        Expr::Seq { .. }
+        | Expr::Let { .. }
+        | Expr::LetRec { .. }
+        | Expr::Try { .. }
+        | Expr::Assert { .. } => {
+            let indent = env.indent;
+            begin_end(expr(expression, env), indent)
+        }
         _ => expr(expression, env),
     }
 }
@@ -626,8 +1276,8 @@ fn wrap_expr(expression: TypedExpr, env: &mut Env) -> Document {
 fn expr(expression: TypedExpr, env: &mut Env) -> Document {
     match expression {
         Expr::Nil { .. } => "[]".to_doc(),
-        Expr::Int { value, .. } => value.to_doc(),
-        Expr::Float { value, .. } => value.to_doc(),
+        Expr::Int { value, .. } => int(value),
+        Expr::Float { value, .. } => float(value),
         Expr::String { value, .. } => string(value),
         Expr::Seq { first, then, .. } => seq(*first, *then, env),
 
@@ -635,7 +1285,7 @@ fn expr(expression: TypedExpr, env: &mut Env) -> Document {
             name, constructor, ..
         } => var(name, constructor, env),
 
-        Expr::Fn { args, body, .. } => fun(args, *body, env),
+        Expr::Fn { args, body, .. } => fun(None, args, *body, env),
 
         Expr::Cons { head, tail, .. } => expr_list_cons(*head, *tail, env),
 
@@ -645,16 +1295,25 @@ fn expr(expression: TypedExpr, env: &mut Env) -> Document {
             label, container, ..
         } => map_select(*container, label, env),
 
+        Expr::TupleIndex { index, tuple, .. } => tuple_index(*tuple, index, env),
+
         Expr::ModuleSelect {
+            typ,
             label,
             constructor: ModuleValueConstructor::Enum,
             ..
-        } => atom(label.to_snake_case()),
+        } => constructor_fun(&label, typ.fn_arity().unwrap_or(0)),
 
         Expr::ModuleSelect {
+            typ,
             constructor: ModuleValueConstructor::Struct { name },
             ..
-        } => name.to_snake_case().to_doc(),
+        } => constructor_fun(&name, typ.fn_arity().unwrap_or(0)),
+
+        Expr::ModuleSelect {
+            constructor: ModuleValueConstructor::Const { literal },
+            ..
+        } => literal_(literal),
 
         Expr::ModuleSelect {
             typ,
@@ -671,6 +1330,26 @@ fn expr(expression: TypedExpr, env: &mut Env) -> Document {
             ..
         } => let_(*value, pattern, *then, env),
 
+        Expr::LetRec {
+            name, value, then, ..
+        } => let_rec(name, *value, *then, env),
+
+        Expr::Try {
+            value,
+            pattern,
+            then,
+            ..
+        } => try_(*value, pattern, *then, env),
+
+        Expr::Assert {
+            meta,
+            value,
+            pattern,
+            message,
+            then,
+            ..
+        } => assert(meta, *value, pattern, message.map(|m| *m), *then, env),
+
         Expr::Case {
             subjects, clauses, ..
         } => case(subjects, clauses, env),
@@ -682,30 +1361,120 @@ fn expr(expression: TypedExpr, env: &mut Env) -> Document {
         Expr::AnonStruct { elems, .. } => {
             tuple(elems.into_iter().map(|e| wrap_expr(e, env)).collect())
         }
+
+        Expr::StructUpdate {
+            constructor,
+            spread,
+            args,
+            ..
+        } => struct_update(*constructor, *spread, args, env),
+
+        Expr::Block { body, .. } => block(body, env),
+
+        Expr::Negate { value, .. } => negate(*value, env),
+
+        Expr::AssertTrue {
+            meta,
+            value,
+            source,
+            ..
+        } => assert_true(meta, *value, source, env),
+
+        Expr::Todo { message, .. } => crash("todo", message),
+
+        Expr::Panic { message, .. } => crash("panic", message),
     }
 }
 
-fn module_select_fn(typ: crate::typ::Type, module_name: Vec<String>, label: String) -> Document {
-    match typ.collapse_links() {
-        crate::typ::Type::Fn { args, .. } => "fun "
-            .to_doc()
-            .append(module_name.join("@"))
-            .append(":")
-            .append(atom(label))
-            .append("/")
-            .append(args.len()),
+fn crash(kind: &str, message: Option<String>) -> Document {
+    let message = match message {
+        Some(message) => format!("{}: {}", kind, message),
+        None => kind.to_string(),
+    };
+    "erlang:error(".to_doc().append(string(message)).append(")")
+}
 
-        _ => module_name
-            .join("@")
-            .to_doc()
-            .append(":")
-            .append(label)
-            .append("()"),
-    }
+fn negate(value: TypedExpr, env: &mut Env) -> Document {
+    // Two minus signs must be separated by a space as `--` is the Erlang list subtraction operator
+    let operator = match &value {
+        Expr::Negate { .. } => "- ",
+        Expr::Int { value, .. } if value.starts_with('-') => "- ",
+        Expr::Float { value, .. } if *value < 0.0 => "- ",
+        _ => "-",
+    };
+    operator.to_doc().append(expr(value, env))
 }
 
-// TODO: Nest, break, etc
-fn map_select(map: TypedExpr, label: String, env: &mut Env) -> Document {
+/// Variables defined within a block are not in scope after it, so the names they shadowed are
+/// restored once the block has ended.
+///
+fn block(body: Vec<TypedExpr>, env: &mut Env) -> Document {
+    let vars = env.vars.clone();
+    let body = body
+        .into_iter()
+        .map(|e| expr(e, env))
+        .intersperse(",".to_doc().append(line()))
+        .collect::<Vec<_>>();
+    for (name, i) in std::mem::replace(&mut env.vars, vars) {
+        let highest = env.block_vars.get(&name).map_or(i, |j| i.max(*j));
+        env.block_vars.insert(name, highest);
+    }
+    begin_end(body.to_doc(), env.indent)
+}
+
+fn struct_update(
+    constructor: TypedExpr,
+    spread: TypedExpr,
+    args: Vec<UpdateArg<TypedExpr>>,
+    env: &mut Env,
+) -> Document {
+    let field_map = match constructor {
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::Struct { field_map, .. },
+                    ..
+                },
+            ..
+        } => field_map,
+        _ => panic!("erl: struct update with a constructor that is not a struct"),
+    };
+    args.into_iter().fold(wrap_expr(spread, env), |doc, arg| {
+        let index = field_map
+            .index(&arg.label)
+            .expect("erl: struct update with unknown label");
+        "erlang:setelement("
+            .to_doc()
+            .append(index + 2)
+            .append(", ")
+            .append(doc)
+            .append(", ")
+            .append(wrap_expr(arg.value, env))
+            .append(")")
+    })
+}
+
+fn module_select_fn(typ: crate::typ::Type, module_name: Vec<String>, label: String) -> Document {
+    match typ.collapse_links() {
+        crate::typ::Type::Fn { args, .. } => "fun "
+            .to_doc()
+            .append(module_name.join("@"))
+            .append(":")
+            .append(atom(label))
+            .append("/")
+            .append(args.len()),
+
+        _ => module_name
+            .join("@")
+            .to_doc()
+            .append(":")
+            .append(label)
+            .append("()"),
+    }
+}
+
+// TODO: Nest, break, etc
+fn map_select(map: TypedExpr, label: String, env: &mut Env) -> Document {
     "maps:get("
         .to_doc()
         .append(atom(label))
@@ -714,14 +1483,40 @@ fn map_select(map: TypedExpr, label: String, env: &mut Env) -> Document {
         .append(")")
 }
 
-fn fun(args: Vec<Arg>, body: TypedExpr, env: &mut Env) -> Document {
-    "fun"
+fn tuple_index(tuple: TypedExpr, index: u64, env: &mut Env) -> Document {
+    "erlang:element("
         .to_doc()
+        .append((index + 1).to_string())
+        .append(", ")
+        .append(wrap_expr(tuple, env))
+        .append(")")
+}
+
+fn literal_(literal: Literal) -> Document {
+    match literal {
+        Literal::Int { value } => int(value),
+        Literal::Float { value } => float(value),
+        Literal::String { value } => string(value),
+    }
+}
+
+/// The arguments and variables of a fun are not in scope after it, so the names they shadowed are
+/// restored once the fun has ended.
+///
+fn fun(name: Option<Document>, args: Vec<TypedArg>, body: TypedExpr, env: &mut Env) -> Document {
+    let vars = env.vars.clone();
+    let head = match name {
+        Some(name) => "fun ".to_doc().append(name),
+        None => "fun".to_doc(),
+    };
+    let doc = head
         .append(fun_args(args, env).append(" ->"))
-        .append(break_("", " ").append(expr(body, env)).nest(INDENT))
+        .append(break_("", " ").append(expr(body, env)).nest(env.indent))
         .append(break_("", " "))
         .append("end")
-        .group()
+        .group();
+    env.vars = vars;
+    doc
 }
 
 fn incrementing_args_list(arity: usize) -> String {
@@ -732,7 +1527,13 @@ fn incrementing_args_list(arity: usize) -> String {
         .collect()
 }
 
-fn external_fun(name: String, module: String, fun: String, arity: usize) -> Document {
+fn external_fun(
+    name: String,
+    module: String,
+    fun: String,
+    arity: usize,
+    indent: isize,
+) -> Document {
     let chars: String = incrementing_args_list(arity);
 
     atom(name)
@@ -742,7 +1543,7 @@ fn external_fun(name: String, module: String, fun: String, arity: usize) -> Docu
         .append(":")
         .append(atom(fun))
         .append(format!("({}).", chars))
-        .nest(INDENT)
+        .nest(indent)
 }
 
 #[test]
@@ -765,6 +1566,7 @@ fn module_test() {
             Statement::Enum {
                 meta: default(),
                 public: true,
+                opaque: false,
                 name: "Any".to_string(),
                 args: vec![],
                 constructors: vec![EnumConstructor {
@@ -775,6 +1577,7 @@ fn module_test() {
             },
             Statement::Import {
                 meta: default(),
+                public: false,
                 module: vec!["result".to_string()],
                 as_name: None,
                 unqualified: vec![],
@@ -859,7 +1662,7 @@ map() ->
                 body: Expr::Int {
                     typ: crate::typ::int(),
                     meta: default(),
-                    value: 176,
+                    value: "176".to_string(),
                 },
             },
             Statement::Fn {
@@ -908,12 +1711,12 @@ map() ->
                     first: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 1,
+                        value: "1".to_string(),
                     }),
                     then: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 2,
+                        value: "2".to_string(),
                     }),
                 },
             },
@@ -930,12 +1733,12 @@ map() ->
                     left: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 1,
+                        value: "1".to_string(),
                     }),
                     right: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 2,
+                        value: "2".to_string(),
                     }),
                 },
             },
@@ -964,12 +1767,13 @@ map() ->
                 args: vec![],
                 name: "let".to_string(),
                 body: Expr::Let {
+                    annotation: None,
                     meta: default(),
                     typ: crate::typ::int(),
                     value: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 1,
+                        value: "1".to_string(),
                     }),
                     pattern: Pattern::Var {
                         meta: default(),
@@ -997,7 +1801,7 @@ map() ->
                     head: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 12,
+                        value: "12".to_string(),
                     }),
                     tail: Box::new(Expr::Cons {
                         meta: default(),
@@ -1005,7 +1809,7 @@ map() ->
                         head: Box::new(Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 34,
+                            value: "34".to_string(),
                         }),
                         tail: Box::new(Expr::Nil {
                             meta: default(),
@@ -1043,7 +1847,7 @@ map() ->
                     body: Box::new(Expr::Int {
                         typ: crate::typ::int(),
                         meta: default(),
-                        value: 100000000000,
+                        value: "100000000000".to_string(),
                     }),
                 },
             },
@@ -1060,7 +1864,7 @@ map() ->
                         Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                         Expr::Float {
                             meta: default(),
@@ -1188,7 +1992,7 @@ tup() ->
             body: Expr::Int {
                 typ: crate::typ::int(),
                 meta: default(),
-                value: 1,
+                value: "1".to_string(),
             },
         }],
     };
@@ -1284,7 +2088,7 @@ some_function(
                         value: Expr::Int {
                             meta: default(),
                             typ: crate::typ::int(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     }],
                     fun: Box::new(Expr::ModuleSelect {
@@ -1336,19 +2140,20 @@ moddy4() ->
                 subjects: vec![Expr::Int {
                     typ: crate::typ::int(),
                     meta: default(),
-                    value: 1,
+                    value: "1".to_string(),
                 }],
                 clauses: vec![
                     Clause {
                         meta: default(),
                         patterns: vec![Pattern::Int {
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         }],
+                        guard: None,
                         then: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     },
                     Clause {
@@ -1357,10 +2162,11 @@ moddy4() ->
                             meta: default(),
                             value: 1.0,
                         }],
+                        guard: None,
                         then: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     },
                     Clause {
@@ -1369,19 +2175,21 @@ moddy4() ->
                             meta: default(),
                             value: "hello".to_string(),
                         }],
+                        guard: None,
                         then: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     },
                     Clause {
                         meta: default(),
                         patterns: vec![Pattern::Nil { meta: default() }],
+                        guard: None,
                         then: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     },
                     Clause {
@@ -1395,15 +2203,16 @@ moddy4() ->
                                 meta: default(),
                                 value: Pattern::Int {
                                     meta: default(),
-                                    value: 2,
+                                    value: "2".to_string(),
                                 },
                             }],
                             constructor: PatternConstructor::Enum,
                         }],
+                        guard: None,
                         then: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     },
                     Clause {
@@ -1413,18 +2222,19 @@ moddy4() ->
                             elems: vec![
                                 Pattern::Int {
                                     meta: default(),
-                                    value: 1,
+                                    value: "1".to_string(),
                                 },
                                 Pattern::Int {
                                     meta: default(),
-                                    value: 2,
+                                    value: "2".to_string(),
                                 },
                             ],
                         }],
+                        guard: None,
                         then: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     },
                 ],
@@ -1481,7 +2291,7 @@ go() ->
                         value: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     }],
                     fun: Box::new(Expr::Var {
@@ -1513,7 +2323,7 @@ go() ->
                         value: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 1,
+                            value: "1".to_string(),
                         },
                     }],
                     fun: Box::new(Expr::Var {
@@ -1541,7 +2351,7 @@ go() ->
                         value: Expr::Int {
                             typ: crate::typ::int(),
                             meta: default(),
-                            value: 2,
+                            value: "2".to_string(),
                         },
                     }],
                     fun: Box::new(Expr::Call {
@@ -1553,7 +2363,7 @@ go() ->
                             value: Expr::Int {
                                 typ: crate::typ::int(),
                                 meta: default(),
-                                value: 1,
+                                value: "1".to_string(),
                             },
                         }],
                         fun: Box::new(Expr::Var {
@@ -1695,12 +2505,49 @@ add(X, Y) ->
 
 go() ->
     add(add(2, add(1, 1)), 3).
+"#,
+        },
+        Case {
+            src: r#"fn inc(x) { x + 1 }
+                    fn add(x, y) { x + y }
+                    pub fn go(x) { x |> inc |> add(2) |> add(_, 3) }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/1]).
+
+inc(X) ->
+    X + 1.
+
+add(X, Y) ->
+    X + Y.
+
+go(X) ->
+    add(add(inc(X), 2), 3).
+"#,
+        },
+        Case {
+            src: r#"fn go(a, b, x, y) {
+                      let bools = struct(a && b, a || b)
+                      let equality = struct(x == y, x != y)
+                      let ints = struct(1 < 2, 1 <= 2, 1 > 2, 1 >= 2)
+                      struct(bools, equality, ints, 1.0 <. 2.0, 1.0 <=. 2.0, 1.0 >. 2.0, 1.0 >=. 2.0)
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(A, B, X, Y) ->
+    Bools = {A andalso B, A orelse B},
+    Equality = {X =:= Y, X =/= Y},
+    Ints = {1 < 2, 1 =< 2, 1 > 2, 1 >= 2},
+    {Bools, Equality, Ints, 1.0 < 2.0, 1.0 =< 2.0, 1.0 > 2.0, 1.0 >= 2.0}.
 "#,
         },
         Case {
             src: r#"fn and(x, y) { x && y }
                     fn or(x, y) { x || y }
                     fn modulo(x, y) { x % y }
+                    fn divide(x, y) { x / y }
             "#,
             erl: r#"-module(the_app).
 -compile(no_auto_import).
@@ -1713,6 +2560,9 @@ go() ->
 
 modulo(X, Y) ->
     X rem Y.
+
+divide(X, Y) ->
+    X div Y.
 "#,
         },
         Case {
@@ -1758,7 +2608,7 @@ x() ->
             erl: r#"-module(the_app).
 -compile(no_auto_import).
 
--export(['receive'/0, 'catch'/1]).
+-export(['catch'/1, 'receive'/0]).
 
 'receive'() ->
     'try':'and'().
@@ -1826,6 +2676,24 @@ x() ->
 x(Y) ->
     {point, A, B} = Y,
     A.
+"#
+        },
+        Case {
+            src: r#"pub external type Dict(key, value)
+                    pub external type Any
+                    external type Private
+                    pub external fn new() -> Dict(k, v) = "maps" "new""#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([new/0]).
+
+-export_type([dict/2]).
+
+-type dict(_Key, _Value) :: any().
+
+new() ->
+    maps:new().
 "#
         },
         Case {
@@ -1912,12 +2780,189 @@ fn go(a) {
 go(A) ->
     case A of
         99 ->
-            A1 = A,
-            1;
+            begin
+                A1 = A,
+                1
+            end;
 
         _ ->
             A
     end.
+"#
+        },
+        Case {
+            src: r#"pub fn go(x) {
+  let rec fact = fn(n) {
+    case n {
+      0 -> 1
+      n -> n * fact(n - 1)
+    }
+  }
+  let fact = fact(x)
+  fact
+}"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/1]).
+
+go(X) ->
+    Fact = fun Fact(N) -> case N of
+            0 ->
+                1;
+
+            N1 ->
+                N1 * Fact(N1 - 1)
+        end end,
+    Fact1 = Fact(X),
+    Fact1.
+"#
+        },
+        Case {
+            src: r#"pub fn go() {
+  let x: Int = 1
+  let y: List(Int) = [x]
+  y
+}"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/0]).
+
+go() ->
+    X = 1,
+    Y = [X],
+    Y.
+"#
+        },
+        Case {
+            src: r#"fn go(x, y) {
+  let a = -1
+  let b = -x
+  let c = - -x
+  let d = - -1
+  let e = -y(x)
+  let f = -1.5
+  x -a * b
+}"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X, Y) ->
+    A = -1,
+    B = -X,
+    C = - -X,
+    D = - -1,
+    E = -Y(X),
+    F = -1.5,
+    X - A * B.
+"#
+        },
+        Case {
+            src: r#"pub fn zip(x) { x }
+                    pub external fn add(Int, Int) -> Int = "erlang" "plus"
+                    pub fn add_one(x) { x }
+                    fn private() { 1 }
+                    pub fn beta(x, y) { x }
+                    pub fn alpha(x, y, z) { x }
+                    pub external type Zoo
+                    pub external type Bar(a)"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([add/2, add_one/1, alpha/3, beta/2, zip/1]).
+
+-export_type([bar/1, zoo/0]).
+
+-type bar(_A) :: any().
+-type zoo() :: any().
+
+zip(X) ->
+    X.
+
+add(A, B) ->
+    erlang:plus(A, B).
+
+add_one(X) ->
+    X.
+
+private() ->
+    1.
+
+beta(X, Y) ->
+    X.
+
+alpha(X, Y, Z) ->
+    X.
+"#
+        },
+        Case {
+            src: r#"fn go(x) {
+  if x > 1 {
+    let y = x + 1
+    y
+  } else if x < 0 {
+    0
+  } else {
+    1
+  }
+}"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X) ->
+    case X > 1 of
+        true ->
+            Y = X + 1,
+            Y;
+
+        false ->
+            case X < 0 of
+                true ->
+                    0;
+
+                false ->
+                    1
+            end
+    end.
+"#
+        },
+        Case {
+            src: r#"fn go(x) {
+  let y = case x {
+    True -> {
+      let x = 1
+      let z = x + 1
+      z
+    }
+    False -> 0
+  }
+  {
+    let x = y
+    x
+  }
+  x
+}"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X) ->
+    Y = case X of
+        true ->
+            begin
+                X1 = 1,
+                Z = X1 + 1,
+                Z
+            end;
+
+        false ->
+            0
+    end,
+    begin
+        X2 = Y,
+        X2
+    end,
+    X.
 "#
         },
         Case {
@@ -1950,6 +2995,711 @@ fn go(a) {
 go(A) ->
     A1 = 1,
     A1.
+"#
+        },
+        Case {
+            src: r#"pub fn add(x) { fn(y) { x + y } }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([add/1]).
+
+add(X) ->
+    fun(Y) -> X + Y end.
+"#
+        },
+        Case {
+            src: r#"pub fn twice(f, x) { f(f(x)) }
+                    pub fn go() { twice(fn(x) { x + 1 }, 1) }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/0, twice/2]).
+
+twice(F, X) ->
+    F(F(X)).
+
+go() ->
+    twice(fun(X) -> X + 1 end, 1).
+"#
+        },
+        Case {
+            src: r#"pub fn sign(x) { case x { n if n > 0 -> 1 0 -> 0 _ -> -1 } }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([sign/1]).
+
+sign(X) ->
+    case X of
+        N when N > 0 ->
+            1;
+
+        0 ->
+            0;
+
+        _ ->
+            -1
+    end.
+"#
+        },
+        Case {
+            src: r#"pub fn greet(name) { "Hello, " <> name <> "!" }
+                    pub fn join(a, b) { greet(a) <> ", " <> b }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([greet/1, join/2]).
+
+greet(Name) ->
+    <<"Hello, ", Name/binary, "!">>.
+
+join(A, B) ->
+    <<(greet(A))/binary, ", ", B/binary>>.
+"#
+        },
+        Case {
+            src: r#"fn parse(x) { case x { 0 -> Error("zero") n -> Ok(n) } }
+                    fn add(a, b) {
+                      try x = parse(a)
+                      try y = parse(b)
+                      Ok(x + y)
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+parse(X) ->
+    case X of
+        0 ->
+            {error, <<"zero">>};
+
+        N ->
+            {ok, N}
+    end.
+
+add(A, B) ->
+    case parse(A) of
+        {error, TryError@1} ->
+            {error, TryError@1};
+
+        {ok, X} ->
+            case parse(B) of
+                {error, TryError@2} ->
+                    {error, TryError@2};
+
+                {ok, Y} ->
+                    {ok, X + Y}
+            end
+    end.
+"#
+        },
+        Case {
+            src: r#"fn parse(x) { case x { 0 -> Error("zero") n -> Ok(n) } }
+                    fn go(a, b) {
+                      case a {
+                        0 -> { try x = parse(b) Ok(x) }
+                        _ -> { try y = parse(a) Ok(y) }
+                      }
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+parse(X) ->
+    case X of
+        0 ->
+            {error, <<"zero">>};
+
+        N ->
+            {ok, N}
+    end.
+
+go(A, B) ->
+    case A of
+        0 ->
+            begin
+                case parse(B) of
+                    {error, TryError@1} ->
+                        {error, TryError@1};
+
+                    {ok, X} ->
+                        {ok, X}
+                end
+            end;
+
+        _ ->
+            begin
+                case parse(A) of
+                    {error, TryError@2} ->
+                        {error, TryError@2};
+
+                    {ok, Y} ->
+                        {ok, Y}
+                end
+            end
+    end.
+"#
+        },
+        Case {
+            src: r#"fn go(x) {
+                      assert Ok(y) = x
+                      y + 1
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X) ->
+    case X of
+        {ok, Y} ->
+            Y + 1;
+
+        AssertValue@1 ->
+            erlang:error(#{gleam_error => assert, message => <<"Assertion pattern match failed">>, pattern => <<"Ok(y)">>, start => 33, 'end' => 49, value => AssertValue@1})
+    end.
+"#
+        },
+        Case {
+            src: r#"fn go(x, y) {
+                      case x {
+                        Ok(a) -> { assert Ok(b) = y a + b }
+                        _ -> { assert Error(c) = y c }
+                      }
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X, Y) ->
+    case X of
+        {ok, A} ->
+            begin
+                case Y of
+                    {ok, B} ->
+                        A + B;
+
+                    AssertValue@1 ->
+                        erlang:error(#{gleam_error => assert, message => <<"Assertion pattern match failed">>, pattern => <<"Ok(b)">>, start => 80, 'end' => 96, value => AssertValue@1})
+                end
+            end;
+
+        _ ->
+            begin
+                case Y of
+                    {error, C} ->
+                        C;
+
+                    AssertValue@2 ->
+                        erlang:error(#{gleam_error => assert, message => <<"Assertion pattern match failed">>, pattern => <<"Error(c)">>, start => 136, 'end' => 155, value => AssertValue@2})
+                end
+            end
+    end.
+"#
+        },
+        Case {
+            src: r#"fn go(x) {
+                      let assert [y] = x as "x must have one element"
+                      y
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X) ->
+    case X of
+        [Y] ->
+            Y;
+
+        AssertValue@1 ->
+            erlang:error(#{gleam_error => assert, message => <<"x must have one element">>, pattern => <<"[y]">>, start => 33, 'end' => 51, value => AssertValue@1})
+    end.
+"#
+        },
+        Case {
+            src: r#"fn go(x, y) {
+                      let assert [a] = x as "x must have one element"
+                      let assert [b] = y as "y must have one element"
+                      a + b
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X, Y) ->
+    case X of
+        [A] ->
+            case Y of
+                [B] ->
+                    A + B;
+
+                AssertValue@2 ->
+                    erlang:error(#{gleam_error => assert, message => <<"y must have one element">>, pattern => <<"[b]">>, start => 106, 'end' => 124, value => AssertValue@2})
+            end;
+
+        AssertValue@1 ->
+            erlang:error(#{gleam_error => assert, message => <<"x must have one element">>, pattern => <<"[a]">>, start => 36, 'end' => 54, value => AssertValue@1})
+    end.
+"#
+        },
+        Case {
+            src: r#"fn mask(x) { case x { 0xFF -> [0o77] _ -> [0b1010, -0x1F, 1_000_000] } }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+mask(X) ->
+    case X of
+        16#FF ->
+            [8#77];
+
+        _ ->
+            [2#1010, -16#1F, 1000000]
+    end.
+"#
+        },
+        Case {
+            src: r#"const max_retries = 3
+                    pub const greeting = "Hello"
+                    const pi = 3.14
+                    pub fn go(x) { case x { n if n > max_retries -> greeting _ -> "Bye" } }
+                    pub fn area(r) { r *. r *. pi }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([area/1, go/1]).
+
+go(X) ->
+    case X of
+        N when N > 3 ->
+            <<"Hello">>;
+
+        _ ->
+            <<"Bye">>
+    end.
+
+area(R) ->
+    R * R * 3.14.
+"#
+        },
+        Case {
+            src: r#"pub struct Point { x: Int y: Int }
+                    fn go(p) { Point(..p, y: 2) }
+                    fn both(p) { Point(..go(p), x: 1, y: 3) }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(P) ->
+    erlang:setelement(3, P, 2).
+
+both(P) ->
+    erlang:setelement(3, erlang:setelement(2, go(P), 1), 3).
+"#
+        },
+        Case {
+            src: r#"fn first(pair: #(Int, Float)) { pair.0 }
+                    fn second() { #(1, 2.0).1 }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+first(Pair) ->
+    erlang:element(1, Pair).
+
+second() ->
+    erlang:element(2, {1, 2.0}).
+"#
+        },
+        Case {
+            src: r#"@behaviour("gen_server")
+                    @behaviour("my_behaviour")
+                    pub fn init(x) { x }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-behaviour(gen_server).
+-behaviour(my_behaviour).
+
+-export([init/1]).
+
+init(X) ->
+    X.
+"#
+        },
+        Case {
+            src: r#"fn go(x) {
+                      case x {
+                        "a\"b" -> "line\nbreak\t\\"
+                        _ -> "caf\u{E9} \u{1F600}" <> x
+                      }
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go(X) ->
+    case X of
+        <<"a\"b">> ->
+            <<"line\nbreak\t\\">>;
+
+        _ ->
+            <<"café 😀"/utf8, X/binary>>
+    end.
+"#
+        },
+        Case {
+            src: r#"pub enum Shape { Circle(radius: Float) Rect(width: Float, height: Float) }
+                    fn go() { Rect(height: 2.0, width: 1.0) }
+                    fn area(shape) {
+                      case shape {
+                        Circle(radius: r) -> r *. r *. 3.14
+                        Rect(height: h, width: w) -> w *. h
+                      }
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+go() ->
+    {rect, 1.0, 2.0}.
+
+area(Shape) ->
+    case Shape of
+        {circle, R} ->
+            R * R * 3.14;
+
+        {rect, W, H} ->
+            W * H
+    end.
+"#
+        },
+        Case {
+            src: r#"pub external fn print(String) -> Nil = "io" "format"
+                    pub fn go() { print("a") Nil }
+                    pub fn is_nil(x) { case x { Nil -> True } }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/0, is_nil/1, print/1]).
+
+print(A) ->
+    io:format(A).
+
+go() ->
+    print(<<"a">>),
+    nil.
+
+is_nil(X) ->
+    case X of
+        nil ->
+            true
+    end.
+"#
+        },
+        Case {
+            src: r#"pub enum Response { HTTPResponse(Int) NotFound }
+                    pub fn go(code) {
+                      case code { 404 -> NotFound _ -> HTTPResponse(code) }
+                    }
+                    pub fn code(r) { case r { HTTPResponse(c) -> c NotFound -> 404 } }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([code/1, go/1]).
+
+go(Code) ->
+    case Code of
+        404 ->
+            not_found;
+
+        _ ->
+            {http_response, Code}
+    end.
+
+code(R) ->
+    case R of
+        {http_response, C} ->
+            C;
+
+        not_found ->
+            404
+    end.
+"#
+        },
+        Case {
+            src: r#"pub fn go(x) {
+                      case x { 1 -> todo 2 -> todo as "two" _ -> panic as "not \"1\" or 2" }
+                    }
+                    pub fn stop() { panic }
+                    pub fn check(x) { assert Ok(y) = panic as "impossible" y }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([check/1, go/1, stop/0]).
+
+go(X) ->
+    case X of
+        1 ->
+            erlang:error(<<"todo">>);
+
+        2 ->
+            erlang:error(<<"todo: two">>);
+
+        _ ->
+            erlang:error(<<"panic: not \"1\" or 2">>)
+    end.
+
+stop() ->
+    erlang:error(<<"panic">>).
+
+check(X) ->
+    case erlang:error(<<"panic: impossible">>) of
+        {ok, Y} ->
+            Y;
+
+        AssertValue@1 ->
+            erlang:error(#{gleam_error => assert, message => <<"Assertion pattern match failed">>, pattern => <<"Ok(y)">>, start => 212, 'end' => 248, value => AssertValue@1})
+    end.
+"#
+        },
+        Case {
+            src: r#"pub struct Point { x: Int y: Int }
+                    pub fn sum(p: Point) { p.x + p.y }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([sum/1]).
+
+sum(P) ->
+    erlang:element(2, P) + erlang:element(3, P).
+"#
+        },
+        Case {
+            src: r#"@external_erlang("
+size(Bin) when is_binary(Bin) ->
+  erlang:byte_size(Bin);
+size(_) ->
+  erlang:error({not_binary, \"size\"}).
+")
+pub external fn size(String) -> Int
+
+pub fn go() { size("abc") }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/0, size/1]).
+
+size(Bin) when is_binary(Bin) ->
+  erlang:byte_size(Bin);
+size(_) ->
+  erlang:error({not_binary, "size"}).
+
+go() ->
+    size(<<"abc">>).
+"#
+        },
+        Case {
+            src: r#"pub fn push(x, xs) { [x, ..xs] }
+                    pub fn push_two(xs) { [1, 2, ..xs] }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([push/2, push_two/1]).
+
+push(X, Xs) ->
+    [X | Xs].
+
+push_two(Xs) ->
+    [1, 2 | Xs].
+"#
+        },
+        Case {
+            src: r#"pub fn with_file(name, f) { f(name) }
+                    pub fn go() { use file <- with_file("a") let size = 1 struct(file, size) }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/0, with_file/2]).
+
+with_file(Name, F) ->
+    F(Name).
+
+go() ->
+    with_file(<<"a">>, fun(File) -> Size = 1,
+            {File, Size} end).
+"#
+        },
+        Case {
+            src: r#"pub fn parse(packet) {
+                      case packet {
+                        <<1:8, len:16, body:binary-size(len), rest:binary>> -> Ok(struct(body, rest))
+                        <<version:8, _:binary>> -> Error(version)
+                      }
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([parse/1]).
+
+parse(Packet) ->
+    case Packet of
+        <<1:8, Len:16, Body:Len/binary, Rest/binary>> ->
+            {ok, {Body, Rest}};
+
+        <<Version:8, _/binary>> ->
+            {error, Version}
+    end.
+"#
+        },
+        Case {
+            src: r#"pub fn scale(x) {
+                      case x {
+                        1.0e3 -> 1.5e10
+                        1e2 -> 2e-1
+                        _ -> 2.0e-3 *. 1.0E-300 *. -1.5e300
+                      }
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([scale/1]).
+
+scale(X) ->
+    case X of
+        1000.0 ->
+            15000000000.0;
+
+        100.0 ->
+            0.2;
+
+        _ ->
+            0.002 * 1.0e-300 * -1.5e300
+    end.
+"#
+        },
+        Case {
+            src: r#"pub struct Box { inner: Int }
+                    pub fn first(#(a, _)) { a }
+                    pub fn unbox(Box(x), #(_, y)) { x + y }
+                    pub fn add() { let f = fn(#(a, b)) { a + b } f(#(1, 2)) }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([add/0, first/1, unbox/2]).
+
+first({A, _}) ->
+    A.
+
+unbox({box, X}, {_, Y}) ->
+    X + Y.
+
+add() ->
+    F = fun({A, B}) -> A + B end,
+    F({1, 2}).
+"#
+        },
+        Case {
+            src: r#"pub enum Box { Box(Int) Empty }
+                    external fn map(List(a), fn(a) -> b) -> List(b) = "lists" "map"
+                    pub fn wrap(xs) { map(xs, Box) }
+                    pub fn call() { let f = Box f(1) }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([call/0, wrap/1]).
+
+map(A, B) ->
+    lists:map(A, B).
+
+wrap(Xs) ->
+    map(Xs, fun(A) -> {box, A} end).
+
+call() ->
+    F = fun(A) -> {box, A} end,
+    F(1).
+"#
+        },
+        Case {
+            src: r#"@export([go, run])
+                    pub fn helper() { 1 }
+                    pub fn go() { helper() }
+                    pub fn run() { 2 }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/0, run/0]).
+
+helper() ->
+    1.
+
+go() ->
+    helper().
+
+run() ->
+    2.
+"#
+        },
+        Case {
+            src: r#"pub fn go(xs, step) {
+  fn sum(xs) {
+    case xs {
+      [] -> 0
+      [x | rest] -> x * step + sum(rest)
+    }
+  }
+  sum(xs)
+}"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([go/2]).
+
+go(Xs, Step) ->
+    Sum = fun Sum(Xs1) -> case Xs1 of
+            [] ->
+                0;
+
+            [X | Rest] ->
+                X * Step + Sum(Rest)
+        end end,
+    Sum(Xs).
+"#
+        },
+        Case {
+            src: r#"@target(erlang)
+                    pub external fn now() -> Int = "erlang" "monotonic_time"
+                    @target(core_erlang)
+                    pub external fn now() -> Int = "os" "system_time"
+                    pub fn later() { now() + 1 }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([later/0, now/0]).
+
+now() ->
+    erlang:monotonic_time().
+
+later() ->
+    now() + 1.
+"#
+        },
+        Case {
+            src: r#"pub fn add_test() {
+                      let x = 2 + 3
+                      assert x == 5
+                      assert x != 4
+                    }"#,
+            erl: r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([add_test/0]).
+
+add_test() ->
+    X = 2 + 3,
+    case X =:= 5 of
+        true ->
+            nil;
+
+        false ->
+            erlang:error(#{gleam_error => assert, message => <<"Assertion failed">>, expression => <<"x == 5">>, start => 78, 'end' => 91})
+    end,
+    case X =/= 4 of
+        true ->
+            nil;
+
+        false ->
+            erlang:error(#{gleam_error => assert, message => <<"Assertion failed">>, expression => <<"x != 4">>, start => 114, 'end' => 127})
+    end.
 "#
         },
     ];
@@ -1959,13 +3709,79 @@ go(A) ->
             .parse(src)
             .expect("syntax error");
         ast.name = vec!["the_app".to_string()];
-        let ast = crate::typ::infer_module(ast, &std::collections::HashMap::new())
+        let ast = crate::typ::infer_module(ast, &std::collections::HashMap::new(), &mut vec![])
             .expect("should successfully infer");
         let output = module(ast);
         assert_eq!((src, output), (src, erl.to_string()));
     }
 }
 
+#[test]
+fn module_whitespace_test() {
+    for src in &[
+        "",
+        "enum Box { Box(Int) }",
+        "pub external type Thing",
+        "@behaviour(\"gen_server\")",
+        "@behaviour(\"gen_server\") pub fn go() { 1 }",
+        "pub external type Thing(a) pub fn go() { 1 }",
+        "fn go() { 1 } enum Box { Box(Int) }",
+    ] {
+        let mut ast = crate::grammar::ModuleParser::new()
+            .parse(src)
+            .expect("syntax error");
+        ast.name = vec!["the_app".to_string()];
+        let ast = crate::typ::infer_module(ast, &std::collections::HashMap::new(), &mut vec![])
+            .expect("should successfully infer");
+        let output = module(ast);
+        assert!(!output.contains("\n\n\n"), "{:?}\n{}", src, output);
+        assert!(output.ends_with(".\n"), "{:?}\n{}", src, output);
+        assert!(!output.contains("-export([])"), "{:?}\n{}", src, output);
+    }
+}
+
+#[test]
+fn module_with_file_attributes_test() {
+    let src = r#"// The first function
+pub fn one() { 1 }
+
+external fn two() -> Int = "mod" "two"
+
+enum Box { Box(Int) }
+
+fn three(x) {
+  x
+}
+"#;
+    let mut ast = crate::grammar::ModuleParser::new()
+        .parse(&crate::parser::strip_extra(src).expect("unterminated comment"))
+        .expect("syntax error");
+    ast.name = vec!["the_app".to_string()];
+    let ast = crate::typ::infer_module(ast, &std::collections::HashMap::new(), &mut vec![])
+        .expect("should successfully infer");
+    assert_eq!(
+        module_with_options(ast, Some(("src/the_app.gleam", src)), DEFAULT_INDENT),
+        r#"-module(the_app).
+-compile(no_auto_import).
+
+-export([one/0]).
+
+-file("src/the_app.gleam", 2).
+one() ->
+    1.
+
+-file("src/the_app.gleam", 4).
+two() ->
+    mod:two().
+
+-file("src/the_app.gleam", 8).
+three(X) ->
+    X.
+"#
+        .to_string()
+    );
+}
+
 #[cfg(test)]
 fn default<T>() -> T
 where
@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use serde::Serialize;
 use std::path::PathBuf;
 use termcolor::Buffer;
 
@@ -28,21 +29,254 @@ pub enum Error {
         modules: Vec<String>,
     },
 
+    SelfImport {
+        module: Name,
+        meta: crate::ast::Meta,
+        path: PathBuf,
+        src: Src,
+    },
+
     DuplicateModule {
         module: Name,
         first: PathBuf,
         second: PathBuf,
     },
 
+    UnknownTargetModule {
+        module: Name,
+        modules: Vec<String>,
+    },
+
     SrcImportingTest {
         path: PathBuf,
         src: Src,
         meta: crate::ast::Meta,
         src_module: Name,
         test_module: Name,
+        test_path: PathBuf,
+    },
+
+    DependencyCycle {
+        modules: Vec<Name>,
+    },
+
+    Internal {
+        message: String,
+    },
+
+    FileIo {
+        path: PathBuf,
+        error: String,
+    },
+
+    InvalidModuleName {
+        path: PathBuf,
+    },
+
+    UnsupportedInlineErlang {
+        path: PathBuf,
+        src: Src,
+        meta: crate::ast::Meta,
+    },
+
+    WarningsAsErrors(Vec<Warning>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    Type {
+        path: PathBuf,
+        src: Src,
+        warning: crate::typ::Warning,
     },
+}
+
+impl Warning {
+    pub fn pretty(&self, buffer: &mut Buffer) {
+        use std::io::Write;
+
+        buffer
+            .write_all(b"\n")
+            .expect("warning pretty buffer write space before");
+
+        match self {
+            Warning::Type { path, src, warning } => match warning {
+                crate::typ::Warning::NonExhaustiveCase { meta, missing } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Non-exhaustive case".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+This case expression does not match these constructors:
+
+    {}
+
+If the value is one of these the program will crash at runtime.
+",
+                        missing.join(", ")
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::UnusedImport { meta, module } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Unused import".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The module {} is imported but never used.
+",
+                        module
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::UnusedUnqualifiedImport { meta, name } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Unused import".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+{} is imported but never used.
+",
+                        name
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::DivisionByZero { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Division by zero".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+This divides by zero, which will crash the program at runtime.
+"
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::Shadowing {
+                    meta,
+                    name,
+                    shadowed,
+                } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Shadowed name".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+This definition of {} shadows the one on line {}, which can no longer be
+referred to here.
+",
+                        name,
+                        src[..shadowed.start].matches('\n').count() + 1
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::Todo { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Todo found".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+This code is incomplete and will crash the program if it is run.
+"
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::UnusedPrivateFn { meta, name } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Unused private function".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The function {} is private and is not used by any other function in this
+module, so it can never be called.
+",
+                        name
+                    )
+                    .expect("warning pretty buffer write");
+                }
+
+                crate::typ::Warning::GenericFn { meta, name, typ } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Generic function".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write_warning(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The function {} has been inferred to have this type:
+
+{}
+
+It accepts and returns values of any type. If this is not intended add type
+annotations to the function.
+",
+                        name,
+                        typ.pretty_print(4)
+                    )
+                    .expect("warning pretty buffer write");
+                }
+            },
+        }
+    }
 
-    DependencyCycle,
+    pub fn pretty_print(&self) {
+        let buffer_writer = termcolor::BufferWriter::stderr(termcolor::ColorChoice::Always);
+        let mut buffer = buffer_writer.buffer();
+        self.pretty(&mut buffer);
+        buffer_writer.print(&buffer).unwrap();
+    }
 }
 
 fn write_project(mut buffer: &mut Buffer, d: ProjectErrorDiagnostic) {
@@ -68,6 +302,39 @@ fn did_you_mean(name: &str, options: &mut Vec<&String>, alt: &'static str) -> St
     }
 }
 
+/// The option most similar to the given name, if any is similar enough to plausibly be what was
+/// meant. Up to a third of the characters may differ.
+///
+fn closest_name<'a>(name: &str, options: &'a [String]) -> Option<&'a String> {
+    let threshold = std::cmp::max(name.len() / 3, 1);
+    options
+        .iter()
+        .map(|option| (strsim::levenshtein(option, name), option))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, option)| option)
+}
+
+#[test]
+fn closest_name_test() {
+    let modules = vec![
+        "one".to_string(),
+        "nested/one".to_string(),
+        "nested/two".to_string(),
+    ];
+    assert_eq!(
+        Some(&"nested/one".to_string()),
+        closest_name("nested/on", &modules)
+    );
+    assert_eq!(
+        Some(&"nested/one".to_string()),
+        closest_name("nestd/one", &modules)
+    );
+    assert_eq!(Some(&"one".to_string()), closest_name("on", &modules));
+    assert_eq!(None, closest_name("unrelated", &modules));
+    assert_eq!(None, closest_name("one", &[]));
+}
+
 impl Error {
     // TODO: Tests.
     pub fn pretty(&self, buffer: &mut Buffer) {
@@ -85,6 +352,7 @@ impl Error {
                 meta,
                 src_module,
                 test_module,
+                test_path,
             } => {
                 let diagnostic = ErrorDiagnostic {
                     title: "App importing test module".to_string(),
@@ -97,14 +365,76 @@ impl Error {
                 write!(
                     buffer,
                     "
-The application module `{}` is importing the test module `{}`.
+The application module `{}` is importing the test module `{}`,
+defined in {}.
 
 Test modules are not included in production builds so test modules
 cannot import them. Perhaps move the `{}` module to the src directory.",
-                    src_module, test_module, test_module,
+                    src_module,
+                    test_module,
+                    test_path.to_string_lossy(),
+                    test_module,
+                )
+                .unwrap();
+            }
+            Error::UnsupportedInlineErlang { path, src, meta } => {
+                let diagnostic = ErrorDiagnostic {
+                    title: "Unsupported inline Erlang".to_string(),
+                    label: "".to_string(),
+                    file: path.to_str().unwrap().to_string(),
+                    src: src.to_string(),
+                    meta: meta.clone(),
+                };
+                write(buffer, diagnostic);
+                write!(
+                    buffer,
+                    "
+Functions defined with inline Erlang cannot be compiled to Core Erlang."
                 )
                 .unwrap();
             }
+            Error::FileIo { path, error } => {
+                let diagnostic = ProjectErrorDiagnostic {
+                    title: "File IO failure".to_string(),
+                    label: format!(
+                        "An error occurred while reading the file {}
+
+{}",
+                        path.to_str().expect("pretty error print PathBuf to_str"),
+                        error,
+                    ),
+                };
+                write_project(buffer, diagnostic);
+            }
+
+            Error::Internal { message } => {
+                let diagnostic = ProjectErrorDiagnostic {
+                    title: "Internal compiler error".to_string(),
+                    label: format!(
+                        "{}
+
+This is a bug in the Gleam compiler. Please report it along with the
+code that caused it.",
+                        message
+                    ),
+                };
+                write_project(buffer, diagnostic);
+            }
+
+            Error::InvalidModuleName { path } => {
+                let diagnostic = ProjectErrorDiagnostic {
+                    title: "Invalid module name".to_string(),
+                    label: format!(
+                        "The file {} cannot be compiled as its name is not a valid module name.
+
+Module names may only contain lowercase letters, numbers, and underscores,
+and must start with a lowercase letter or an underscore.",
+                        path.to_str().expect("pretty error print PathBuf to_str"),
+                    ),
+                };
+                write_project(buffer, diagnostic);
+            }
+
             Error::DuplicateModule {
                 module,
                 first,
@@ -125,6 +455,20 @@ Second: {}",
                 write_project(buffer, diagnostic);
             }
 
+            Error::UnknownTargetModule { module, modules } => {
+                let diagnostic = ProjectErrorDiagnostic {
+                    title: "Unknown module".to_string(),
+                    label: match closest_name(module, modules) {
+                        Some(name) => format!(
+                            "There is no module named `{}` to compile. Did you mean `{}`?",
+                            module, name
+                        ),
+                        None => format!("There is no module named `{}` to compile.", module),
+                    },
+                };
+                write_project(buffer, diagnostic);
+            }
+
             Error::Type { path, src, error } => match error {
                 UnknownLabel {
                     label,
@@ -202,22 +546,26 @@ also be labelled.
                     .unwrap();
                 }
 
-                DuplicateName { meta, name: fun } => {
+                DuplicateName {
+                    name,
+                    first,
+                    second,
+                } => {
                     let diagnostic = ErrorDiagnostic {
                         title: "Duplicate name".to_string(),
                         label: "Redefined here".to_string(),
                         file: path.to_str().unwrap().to_string(),
                         src: src.to_string(),
-                        meta: meta.clone(),
+                        meta: second.clone(),
                     };
                     write(buffer, diagnostic);
                     write!(
                         buffer,
                         "
-A function has already been defined with the name
-`{}` in this module.
+`{}` has already been defined on line {} of this module.
 ",
-                        fun
+                        name,
+                        src[..first.start].matches('\n').count() + 1
                     )
                     .unwrap();
                 }
@@ -241,6 +589,31 @@ The field `{}` has already been defined. Rename this field.
                     .unwrap();
                 }
 
+                DuplicateConstructorAtom {
+                    meta,
+                    name,
+                    other,
+                    atom,
+                } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Duplicate constructor atom".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The constructors `{}` and `{}` would both be compiled to the Erlang atom
+`{}`, so they could not be told apart at runtime. Rename one of them.
+",
+                        other, name, atom
+                    )
+                    .unwrap();
+                }
+
                 DuplicateArgument { meta, label } => {
                     let diagnostic = ErrorDiagnostic {
                         title: "Duplicate argument".to_string(),
@@ -271,56 +644,46 @@ The labelled argument `{}` has already been supplied.
                     write(buffer, diagnostic);
                 }
 
-                NotFn { meta, typ } => {
+                RecursiveTypeAlias { meta, name } => {
                     let diagnostic = ErrorDiagnostic {
-                        title: "Type mismatch".to_string(),
+                        title: "Recursive type alias".to_string(),
                         label: "".to_string(),
                         file: path.to_str().unwrap().to_string(),
                         src: src.to_string(),
                         meta: meta.clone(),
                     };
                     write(buffer, diagnostic);
-
                     write!(
                         buffer,
                         "
-This value is being called as a function but its type is:
-
-{}
+The type alias `{}` refers to itself, so it can never be expanded.
 ",
-                        typ.pretty_print(4)
+                        name
                     )
                     .unwrap();
                 }
 
-                NotModule { meta, typ } => {
+                UnknownExport { meta, name } => {
                     let diagnostic = ErrorDiagnostic {
-                        title: "Not a module".to_string(),
+                        title: "Unknown export".to_string(),
                         label: "".to_string(),
                         file: path.to_str().unwrap().to_string(),
                         src: src.to_string(),
                         meta: meta.clone(),
                     };
                     write(buffer, diagnostic);
-
                     write!(
                         buffer,
                         "
-Fields can only be accessed on modules. This is not a module, it is
-a value with this type:
-
-{}
+The function `{}` cannot be exported as this module has no public
+function with that name.
 ",
-                        typ.pretty_print(4)
+                        name
                     )
                     .unwrap();
                 }
 
-                CouldNotUnify {
-                    meta,
-                    expected,
-                    given,
-                } => {
+                NotFn { meta, typ } => {
                     let diagnostic = ErrorDiagnostic {
                         title: "Type mismatch".to_string(),
                         label: "".to_string(),
@@ -333,7 +696,168 @@ a value with this type:
                     write!(
                         buffer,
                         "
-Expected type:
+This value is being called as a function but its type is:
+
+{}
+",
+                        typ.pretty_print(4)
+                    )
+                    .unwrap();
+                }
+
+                UnknownField {
+                    meta,
+                    label,
+                    typ,
+                    fields,
+                } => {
+                    let mut options: Vec<_> = fields.iter().collect();
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Unknown field".to_string(),
+                        label: did_you_mean(label, &mut options, ""),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The value being accessed has this type:
+
+{}
+
+It does not have a field named `{}`. It has these fields: `{}`.
+",
+                        typ.pretty_print(4),
+                        label,
+                        fields.join("`, `")
+                    )
+                    .unwrap();
+                }
+
+                NotModule { meta, typ } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Not a module".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+
+                    write!(
+                        buffer,
+                        "
+Fields can only be accessed on modules. This is not a module, it is
+a value with this type:
+
+{}
+",
+                        typ.pretty_print(4)
+                    )
+                    .unwrap();
+                }
+
+                NotATuple { meta, typ } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Not a tuple".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+
+                    write!(
+                        buffer,
+                        "
+Elements can only be accessed by index on a value known to be a tuple.
+This value has this type:
+
+{}
+",
+                        typ.pretty_print(4)
+                    )
+                    .unwrap();
+                }
+
+                OutOfBoundsTupleIndex { meta, index, size } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Out of bounds tuple index".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+This tuple has {} elements so the index {} is too large.
+",
+                        size, index
+                    )
+                    .unwrap();
+                }
+
+                CouldNotUnify {
+                    meta,
+                    expected,
+                    given,
+                } => {
+                    let names = &mut im::hashmap![];
+                    let uid = &mut 0;
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Type mismatch".to_string(),
+                        label: format!(
+                            "expected {}, found {}",
+                            expected.to_gleam_doc(names, uid).format(isize::MAX),
+                            given.to_gleam_doc(names, uid).format(isize::MAX)
+                        ),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+
+                    write!(
+                        buffer,
+                        "
+Expected type:
+
+{}
+
+Found type:
+
+{}
+",
+                        expected.pretty_print(4),
+                        given.pretty_print(4)
+                    )
+                    .unwrap();
+                }
+
+                TargetTypeMismatch {
+                    meta,
+                    name,
+                    expected,
+                    given,
+                } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Target type mismatch".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+Each target's definition of `{}` must have the same type.
+
+Expected type:
 
 {}
 
@@ -341,12 +865,31 @@ Found type:
 
 {}
 ",
+                        name,
                         expected.pretty_print(4),
                         given.pretty_print(4)
                     )
                     .unwrap();
                 }
 
+                TargetNotFn { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Invalid target definition".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+Only functions can be defined for a target.
+"
+                    )
+                    .unwrap();
+                }
+
                 IncorrectTypeArity {
                     meta,
                     expected,
@@ -421,7 +964,7 @@ The name `{}` is not in scope here.
                     .unwrap();
                 }
 
-                PrivateTypeLeak { meta, leaked } => {
+                PrivateTypeLeak { meta, name, leaked } => {
                     let diagnostic = ErrorDiagnostic {
                         title: "Private type used in public interface".to_string(),
                         label: "".to_string(),
@@ -439,12 +982,13 @@ The name `{}` is not in scope here.
                     write!(
                         buffer,
                         "
-The following type is private, but is being used by this public export.
+The following type is private, but is being used by the public export `{}`.
 
 {}
 
 Private types can only be used within the module that defines them.
 ",
+                        name,
                         leaked.pretty_print(4),
                     )
                     .unwrap();
@@ -522,6 +1066,180 @@ No module has been imported with the name `{}`.
                     .unwrap();
                 }
 
+                InvalidExternalTarget { meta, target } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Invalid external target".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+`{}` is not a valid Erlang module or function name.
+External functions must refer to a valid Erlang module and function.
+",
+                        target
+                    )
+                    .unwrap();
+                }
+
+                InvalidGuardExpression { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Invalid guard expression".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+Only variables, literals, comparisons, and boolean operators can be used
+in a clause guard. Function calls are not permitted here.
+"
+                    )
+                    .unwrap();
+                }
+
+                InvalidConstant { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Invalid constant".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+Constants can only be Int, Float, or String literals, or other constants.
+"
+                    )
+                    .unwrap();
+                }
+
+                RefutableArgPattern { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Refutable pattern".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+A function argument can only be destructured with a pattern that matches
+every value of its type, such as a tuple or a struct. Use a case expression
+to match on other patterns.
+"
+                    )
+                    .unwrap();
+                }
+
+                NotNumeric { meta, typ } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Type mismatch".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+Only Int and Float values can be negated, but this value has the type:
+
+{}
+",
+                        typ.pretty_print(4)
+                    )
+                    .unwrap();
+                }
+
+                InvalidInlineErlang { meta, name, arity } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Invalid inline Erlang".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The inline Erlang for this function must be a definition of {}/{},
+ending with a full stop.
+",
+                        name, arity
+                    )
+                    .unwrap();
+                }
+
+                InvalidAssertMessage { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Invalid assert message".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+The message of an assert must be a string literal.
+"
+                    )
+                    .unwrap();
+                }
+
+                EmptyBlock { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Empty block".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+A block must end with an expression, the value of which is the value of
+the block.
+"
+                    )
+                    .unwrap();
+                }
+
+                NotStructConstructor { meta } => {
+                    let diagnostic = ErrorDiagnostic {
+                        title: "Not a struct constructor".to_string(),
+                        label: "".to_string(),
+                        file: path.to_str().unwrap().to_string(),
+                        src: src.to_string(),
+                        meta: meta.clone(),
+                    };
+                    write(buffer, diagnostic);
+                    write!(
+                        buffer,
+                        "
+Only a struct constructor referred to by name can be used with the
+Name(..value, field: new_value) syntax to update a struct.
+"
+                    )
+                    .unwrap();
+                }
+
                 UnknownModuleField {
                     meta,
                     name,
@@ -607,7 +1325,23 @@ No module has been imported with the name `{}`.
                         .expect("error pretty buffer write");
                     }
 
-                    ExtraToken { .. } => unimplemented!(),
+                    ExtraToken {
+                        token: (start, _, end),
+                    } => {
+                        let diagnostic = ErrorDiagnostic {
+                            title: "Syntax error".to_string(),
+                            label: "Unexpected token".to_string(),
+                            file: path.to_str().unwrap().to_string(),
+                            src: src.to_string(),
+                            meta: crate::ast::Meta {
+                                start: *start,
+                                end: *end,
+                            },
+                        };
+                        write(buffer, diagnostic);
+                        write!(buffer, "\nNothing more was expected at this point.\n")
+                            .expect("error pretty buffer write");
+                    }
 
                     User { error } => {
                         use crate::parser::Error;
@@ -635,14 +1369,186 @@ but this one uses {}. Rewrite this using the fn({}) {{ ... }} syntax.",
                                 )
                                 .expect("error pretty buffer write");
                             }
+
+                            Error::InvalidTupleIndex { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Invalid tuple index".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+Tuple elements can only be accessed with a whole number written in
+decimal, such as tuple.0"
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::InvalidEscape { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Invalid escape sequence".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+The escape sequences that can be used in a string are \\n, \\r, \\t,
+\\\", \\\\, and \\u{{...}} with a hexadecimal unicode code point."
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::UnterminatedBlockComment { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Unterminated block comment".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+This block comment is not closed. Each /* must have a matching */."
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::InvalidBitSegment { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Invalid bit string segment".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+A segment may be given one of the kinds `int` or `binary`, and one size such
+as `8` or `size(n)`, separated by `-`. A binary segment without a size
+matches the rest of the bit string so it must be the last segment."
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::InvalidLetRec { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Syntax error".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+A let binding is followed by a pattern and then `=`. A recursive function
+is bound with `let rec name = fn(...) {{ ... }}`."
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::FloatOutOfRange { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Float out of range".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+This float is too large to be represented. Floats must be within
+the range of a 64 bit floating point number."
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::UnknownTarget { meta, name } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Unknown target".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+There is no compile target named `{}`. The targets are `erlang` and
+`core_erlang`.",
+                                    name
+                                )
+                                .expect("error pretty buffer write");
+                            }
+
+                            Error::InvalidPattern { meta } => {
+                                let diagnostic = ErrorDiagnostic {
+                                    title: "Invalid pattern".to_string(),
+                                    label: "".to_string(),
+                                    file: path.to_str().unwrap().to_string(),
+                                    src: src.to_string(),
+                                    meta: meta.clone(),
+                                };
+                                write(buffer, diagnostic);
+                                write!(
+                                    buffer,
+                                    "
+An assert followed by `=` must be given a pattern. A boolean assert is not
+followed by `=`, and the pattern of an assert that contains a bit string
+must be written with `let assert`."
+                                )
+                                .expect("error pretty buffer write");
+                            }
                         }
                     }
                 }
             }
 
-            Error::DependencyCycle => {
-                println!("{:?}", self);
-                unimplemented!();
+            Error::WarningsAsErrors(warnings) => {
+                for warning in warnings {
+                    warning.pretty(buffer);
+                }
+                write!(
+                    buffer,
+                    "
+Compilation failed as warnings are being treated as errors.
+"
+                )
+                .expect("error pretty buffer write");
+            }
+
+            Error::DependencyCycle { modules } => {
+                let cycle = modules
+                    .iter()
+                    .chain(modules.first())
+                    .map(|module| format!("`{}`", module))
+                    .join(" imports ");
+                let diagnostic = ProjectErrorDiagnostic {
+                    title: "Import cycle".to_string(),
+                    label: format!(
+                        "These modules import each other so they cannot be compiled:
+
+    {}",
+                        cycle
+                    ),
+                };
+                write_project(buffer, diagnostic);
             }
 
             Error::UnknownImport {
@@ -653,10 +1559,12 @@ but this one uses {}. Rewrite this using the fn({}) {{ ... }} syntax.",
                 src,
                 modules,
             } => {
-                let mut modules: Vec<&String> = modules.iter().collect();
                 let diagnostic = ErrorDiagnostic {
                     title: "Unknown import".to_string(),
-                    label: did_you_mean(import, &mut modules, ""),
+                    label: match closest_name(import, modules) {
+                        Some(name) => format!("Did you mean `{}`?", name),
+                        None => "".to_string(),
+                    },
                     file: path.to_str().unwrap().to_string(),
                     src: src.to_string(),
                     meta: meta.clone(),
@@ -672,6 +1580,31 @@ but it cannot be found.
                 )
                 .expect("error pretty buffer write");
             }
+
+            Error::SelfImport {
+                module,
+                meta,
+                path,
+                src,
+            } => {
+                let diagnostic = ErrorDiagnostic {
+                    title: "Module imports itself".to_string(),
+                    label: "Imported here".to_string(),
+                    file: path.to_str().unwrap().to_string(),
+                    src: src.to_string(),
+                    meta: meta.clone(),
+                };
+                write(buffer, diagnostic);
+                write!(
+                    buffer,
+                    "
+The module `{}` is trying to import itself. The functions and types
+it defines can be used within it without an import.
+",
+                    module
+                )
+                .expect("error pretty buffer write");
+            }
         }
 
         buffer
@@ -687,6 +1620,266 @@ but it cannot be found.
     }
 }
 
+/// Renders the same report as `pretty`, without colour, so that the error can be shown by tools
+/// that embed the compiler.
+///
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buffer = Buffer::no_color();
+        self.pretty(&mut buffer);
+        write!(f, "{}", String::from_utf8_lossy(buffer.as_slice()).trim())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A location independent description of a problem, for tools such as language servers that want
+/// to present errors themselves rather than parse the rendered report.
+///
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Error {
+    /// The problems this error reports, one per location. Parse errors without a precise location
+    /// cover the whole file, errors about a file whose source is not available point to its
+    /// start, and errors that are not about a file, such as a dependency cycle, have none.
+    ///
+    pub fn to_diagnostics(&self) -> Vec<Diagnostic> {
+        let diagnostic = |path: &PathBuf, meta: &crate::ast::Meta| Diagnostic {
+            path: path.clone(),
+            start_byte: meta.start,
+            end_byte: meta.end,
+            severity: Severity::Error,
+            message: heading(|buffer| self.pretty(buffer)),
+        };
+        let whole_file = |path: &PathBuf, src: &str| {
+            diagnostic(
+                path,
+                &crate::ast::Meta {
+                    start: 0,
+                    end: src.len(),
+                },
+            )
+        };
+
+        match self {
+            Error::Parse { path, src, error } => {
+                use lalrpop_util::ParseError::*;
+                match error {
+                    UnrecognizedToken {
+                        token: Some((start, _, end)),
+                        ..
+                    }
+                    | ExtraToken {
+                        token: (start, _, end),
+                    } => vec![diagnostic(
+                        path,
+                        &crate::ast::Meta {
+                            start: *start,
+                            end: *end,
+                        },
+                    )],
+                    InvalidToken { location } => vec![diagnostic(
+                        path,
+                        &crate::ast::Meta {
+                            start: *location,
+                            end: *location + 1,
+                        },
+                    )],
+                    User { error } => vec![diagnostic(path, error.meta())],
+                    UnrecognizedToken { token: None, .. } => vec![whole_file(path, src)],
+                }
+            }
+
+            Error::Type { path, error, .. } => vec![diagnostic(path, error.meta())],
+
+            Error::UnknownImport { path, meta, .. }
+            | Error::SelfImport { path, meta, .. }
+            | Error::SrcImportingTest { path, meta, .. }
+            | Error::UnsupportedInlineErlang { path, meta, .. } => vec![diagnostic(path, meta)],
+
+            Error::DuplicateModule { second: path, .. }
+            | Error::FileIo { path, .. }
+            | Error::InvalidModuleName { path } => {
+                vec![diagnostic(path, &crate::ast::Meta { start: 0, end: 0 })]
+            }
+
+            Error::UnknownTargetModule { .. }
+            | Error::DependencyCycle { .. }
+            | Error::Internal { .. } => {
+                vec![]
+            }
+
+            Error::WarningsAsErrors(warnings) => {
+                warnings.iter().map(Warning::to_diagnostic).collect()
+            }
+        }
+    }
+}
+
+impl Warning {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Warning::Type { path, warning, .. } => Diagnostic {
+                path: path.clone(),
+                start_byte: warning.meta().start,
+                end_byte: warning.meta().end,
+                severity: Severity::Warning,
+                message: heading(|buffer| self.pretty(buffer)),
+            },
+        }
+    }
+}
+
+/// The title of a rendered report, without its "error: " or "warning: " prefix.
+///
+fn heading(render: impl FnOnce(&mut Buffer)) -> String {
+    let mut buffer = Buffer::no_color();
+    render(&mut buffer);
+    let text = String::from_utf8_lossy(buffer.as_slice());
+    let line = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("");
+    match line.split_once(": ") {
+        Some((_, title)) => title.trim().to_string(),
+        None => line.trim().to_string(),
+    }
+}
+
+#[test]
+fn to_diagnostics_test() {
+    let src = "pub fn go() { 1 + 1.0 }".to_string();
+    let error = Error::Type {
+        path: PathBuf::from("/src/one.gleam"),
+        src: src.clone(),
+        error: crate::typ::Error::CouldNotUnify {
+            meta: crate::ast::Meta { start: 18, end: 21 },
+            expected: crate::typ::int(),
+            given: crate::typ::float(),
+        },
+    };
+    assert_eq!(
+        vec![Diagnostic {
+            path: PathBuf::from("/src/one.gleam"),
+            start_byte: 18,
+            end_byte: 21,
+            severity: Severity::Error,
+            message: "Type mismatch".to_string(),
+        }],
+        error.to_diagnostics()
+    );
+
+    let error = Error::UnknownImport {
+        module: "one".to_string(),
+        import: "two".to_string(),
+        meta: crate::ast::Meta { start: 0, end: 10 },
+        path: PathBuf::from("/src/one.gleam"),
+        src: "import two".to_string(),
+        modules: vec!["one".to_string()],
+    };
+    assert_eq!(
+        vec![Diagnostic {
+            path: PathBuf::from("/src/one.gleam"),
+            start_byte: 0,
+            end_byte: 10,
+            severity: Severity::Error,
+            message: "Unknown import".to_string(),
+        }],
+        error.to_diagnostics()
+    );
+
+    // A parse error at the end of the file has no precise span
+    let error = crate::project::try_parse("pub fn go() {").expect_err("should not parse");
+    assert_eq!(
+        vec![Diagnostic {
+            path: PathBuf::from("main.gleam"),
+            start_byte: 0,
+            end_byte: 13,
+            severity: Severity::Error,
+            message: "Syntax error".to_string(),
+        }],
+        error.to_diagnostics()
+    );
+
+    assert_eq!(
+        r#"{"path":"main.gleam","start_byte":0,"end_byte":13,"severity":"error","message":"Syntax error"}"#,
+        serde_json::to_string(&error.to_diagnostics()[0]).unwrap()
+    );
+    assert_eq!(
+        Vec::<Diagnostic>::new(),
+        Error::DependencyCycle {
+            modules: vec!["one".to_string(), "two".to_string()]
+        }
+        .to_diagnostics()
+    );
+}
+
+#[test]
+fn display_test() {
+    let error = crate::project::try_parse("pub fn go() { 1 + }").expect_err("should not parse");
+    let output = error.to_string();
+    assert!(output.starts_with("error: Syntax error"), "{}", output);
+    assert!(output.contains("- <main.gleam>:1:19"), "{}", output);
+    assert!(output.contains("1 | pub fn go() { 1 + }"), "{}", output);
+    assert!(
+        output.contains("  |                   ^ Unexpected token"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn display_dependency_cycle_test() {
+    let error = Error::DependencyCycle {
+        modules: vec!["one".to_string(), "two".to_string()],
+    };
+    assert_eq!(
+        "error: Import cycle
+- These modules import each other so they cannot be compiled:
+
+    `one` imports `two` imports `one`",
+        error.to_string()
+    );
+}
+
+#[test]
+fn display_extra_token_test() {
+    let error = Error::Parse {
+        path: PathBuf::from("/src/one.gleam"),
+        src: "pub fn go() { 1 } }".to_string(),
+        error: lalrpop_util::ParseError::ExtraToken {
+            token: (18, (0, "}".to_string()), 19),
+        },
+    };
+    let output = error.to_string();
+    assert!(output.starts_with("error: Syntax error"), "{}", output);
+    assert!(output.contains("- </src/one.gleam>:1:19"), "{}", output);
+    assert!(
+        output.contains("  |                   ^ Unexpected token"),
+        "{}",
+        output
+    );
+    assert!(
+        output.ends_with("Nothing more was expected at this point."),
+        "{}",
+        output
+    );
+}
+
 struct ErrorDiagnostic {
     file: String,
     meta: crate::ast::Meta,
@@ -695,13 +1888,25 @@ struct ErrorDiagnostic {
     label: String,
 }
 
-fn write(mut buffer: &mut Buffer, d: ErrorDiagnostic) {
+fn write(buffer: &mut Buffer, d: ErrorDiagnostic) {
+    write_diagnostic(buffer, d, codespan_reporting::Severity::Error)
+}
+
+fn write_warning(buffer: &mut Buffer, d: ErrorDiagnostic) {
+    write_diagnostic(buffer, d, codespan_reporting::Severity::Warning)
+}
+
+fn write_diagnostic(
+    mut buffer: &mut Buffer,
+    d: ErrorDiagnostic,
+    severity: codespan_reporting::Severity,
+) {
     use codespan::{CodeMap, Span};
     use codespan_reporting::{Diagnostic, Label};
 
     let mut code_map = CodeMap::new();
     code_map.add_filemap(d.file.into(), d.src);
-    let diagnostic = Diagnostic::new_error(d.title).with_label(
+    let diagnostic = Diagnostic::new(severity, d.title).with_label(
         Label::new_primary(Span::from_offset(
             ((d.meta.start + 1) as u32).into(),
             ((d.meta.end - d.meta.start) as i64).into(),
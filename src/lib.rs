@@ -0,0 +1,28 @@
+pub mod ast;
+pub mod core_erlang;
+pub mod docs;
+pub mod erl;
+pub mod error;
+pub mod new;
+pub mod parser;
+pub mod pretty;
+pub mod project;
+pub mod typ;
+lalrpop_mod!(
+    #[allow(deprecated)]
+    #[allow(clippy::all)]
+    pub grammar
+);
+
+#[macro_use]
+extern crate im;
+
+#[cfg(test)]
+#[macro_use]
+extern crate pretty_assertions;
+
+#[macro_use]
+extern crate lalrpop_util;
+
+#[macro_use]
+extern crate lazy_static;
@@ -1,32 +1,6 @@
-mod ast;
-mod erl;
-mod error;
-mod new;
-mod parser;
-mod pretty;
-mod project;
-mod typ;
-lalrpop_mod!(
-    #[allow(deprecated)]
-    #[allow(clippy::all)]
-    grammar
-);
-
-#[macro_use]
-extern crate im;
-
-#[cfg(test)]
-#[macro_use]
-extern crate pretty_assertions;
-
-#[macro_use]
-extern crate lalrpop_util;
-
-#[macro_use]
-extern crate lazy_static;
-
-use crate::error::Error;
-use crate::project::ModuleOrigin;
+use gleam::error::Error;
+use gleam::project::ModuleOrigin;
+use gleam::{new, project};
 use serde::Deserialize;
 use std::fs::File;
 use std::io::Write;
@@ -45,6 +19,15 @@ enum Command {
         path: String,
     },
 
+    #[structopt(
+        name = "check",
+        about = "Type check a Gleam project without writing any files"
+    )]
+    Check {
+        #[structopt(help = "location of the project root", default_value = ".")]
+        path: String,
+    },
+
     #[structopt(name = "new", about = "Create a new Gleam project")]
     New {
         #[structopt(help = "name of the project")]
@@ -77,21 +60,60 @@ fn main() {
             }
         }
 
+        Command::Check { path } => {
+            if let Err(e) = command_check(path) {
+                e.pretty_print();
+                std::process::exit(1);
+            }
+        }
+
         Command::New {
             name,
             path,
             template,
-        } => crate::new::create(template, name, path, VERSION),
+        } => new::create(template, name, path, VERSION),
     }
 }
 
 fn command_build(root: String) -> Result<(), Error> {
+    let srcs = collect_project_source(&root)?;
+    let options = project::CompileOptions {
+        compiler_version: Some(VERSION.to_string()),
+        ..Default::default()
+    };
+    let mut progress = |name: &str| println!("Compiling {}", name);
+    let package = project::compile_to(srcs, &options, &mut progress, &mut |file| {
+        if let Some(dir_path) = file.path.parent() {
+            std::fs::create_dir_all(dir_path)?;
+        }
+        File::create(&file.path)?.write_all(file.text.as_bytes())
+    })?;
+
+    for warning in package.warnings.iter() {
+        warning.pretty_print();
+    }
+
+    println!("Done!");
+    Ok(())
+}
+
+fn command_check(root: String) -> Result<(), Error> {
+    let srcs = collect_project_source(&root)?;
+    let (_, warnings) = project::check(srcs)?;
+    for warning in warnings.iter() {
+        warning.pretty_print();
+    }
+    println!("Done!");
+    Ok(())
+}
+
+fn collect_project_source(root: &str) -> Result<Vec<project::Input>, Error> {
     let mut srcs = vec![];
 
     // Read gleam.toml
-    let project_config = read_project_config(&root).expect("Could not read gleam.toml");
+    let project_config = read_project_config(root).expect("Could not read gleam.toml");
 
-    let root_path = PathBuf::from(&root);
+    let root_path = PathBuf::from(root);
     let lib_dir = root_path.join("_build").join("default").join("lib");
     let checkouts_dir = root_path.join("_checkouts");
 
@@ -103,39 +125,15 @@ fn command_build(root: String) -> Result<(), Error> {
         .filter(|p| {
             p.file_name().and_then(|os_string| os_string.to_str()) != Some(&project_config.name)
         })
-        .for_each(|p| {
-            crate::project::collect_source(p.join("src"), ModuleOrigin::Dependency, &mut srcs)
-        });
+        .try_for_each(|p| {
+            project::collect_source(p.join("src"), ModuleOrigin::Dependency, &mut srcs)
+        })?;
 
     // Collect source code from top level project
-    crate::project::collect_source(root_path.join("src"), ModuleOrigin::Src, &mut srcs);
-    crate::project::collect_source(root_path.join("test"), ModuleOrigin::Test, &mut srcs);
-
-    let compiled = crate::project::compile(srcs)?;
-
-    for crate::project::Compiled { files, .. } in compiled {
-        for crate::project::OutputFile { text, path } in files {
-            let dir_path = path
-                .parent()
-                .unwrap_or_else(|| panic!("getting output file directory {:?}", path));
-            std::fs::create_dir_all(dir_path).unwrap_or_else(|e| {
-                panic!(
-                    "creating output file directory {:?}: {:?}",
-                    dir_path,
-                    e.to_string()
-                )
-            });
-
-            let mut f = File::create(&path)
-                .unwrap_or_else(|e| panic!("creating output file {:?}: {:?}", path, e.to_string()));
-            f.write_all(text.as_bytes()).unwrap_or_else(|e| {
-                panic!("writing to output file {:?}: {:?}", path, e.to_string())
-            });
-        }
-    }
+    project::collect_source(root_path.join("src"), ModuleOrigin::Src, &mut srcs)?;
+    project::collect_source(root_path.join("test"), ModuleOrigin::Test, &mut srcs)?;
 
-    println!("Done!");
-    Ok(())
+    Ok(srcs)
 }
 
 fn read_project_config(root: &str) -> Result<ProjectConfig, ()> {
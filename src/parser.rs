@@ -4,21 +4,96 @@ pub enum Error {
         meta: crate::ast::Meta,
         count: usize,
     },
+
+    InvalidTupleIndex {
+        meta: crate::ast::Meta,
+    },
+
+    InvalidEscape {
+        meta: crate::ast::Meta,
+    },
+
+    UnterminatedBlockComment {
+        meta: crate::ast::Meta,
+    },
+
+    InvalidBitSegment {
+        meta: crate::ast::Meta,
+    },
+
+    FloatOutOfRange {
+        meta: crate::ast::Meta,
+    },
+
+    InvalidLetRec {
+        meta: crate::ast::Meta,
+    },
+
+    UnknownTarget {
+        meta: crate::ast::Meta,
+        name: String,
+    },
+
+    InvalidPattern {
+        meta: crate::ast::Meta,
+    },
 }
 
-/// Blanks out comments, semicolons, etc
+impl Error {
+    pub fn meta(&self) -> &crate::ast::Meta {
+        match self {
+            Error::TooManyHolesInCapture { meta, .. }
+            | Error::InvalidTupleIndex { meta }
+            | Error::InvalidEscape { meta }
+            | Error::UnterminatedBlockComment { meta }
+            | Error::InvalidBitSegment { meta }
+            | Error::FloatOutOfRange { meta }
+            | Error::InvalidLetRec { meta }
+            | Error::UnknownTarget { meta, .. }
+            | Error::InvalidPattern { meta } => meta,
+        }
+    }
+}
+
+pub fn is_indexable(expr: &crate::ast::UntypedExpr) -> bool {
+    use crate::ast::Expr;
+    matches!(
+        expr,
+        Expr::Var { .. }
+            | Expr::Call { .. }
+            | Expr::FieldSelect { .. }
+            | Expr::TupleIndex { .. }
+            | Expr::AnonStruct { .. }
+            | Expr::Block { .. }
+            | Expr::Case { .. }
+    )
+}
+
+/// Parses a float literal. Literals too large to be represented overflow to infinity, which
+/// has no Erlang literal form, so they are rejected.
 ///
-pub fn strip_extra(src: &str) -> String {
+pub fn float(value: &str, meta: crate::ast::Meta) -> Result<f64, Error> {
+    match value.parse::<f64>() {
+        Ok(f) if f.is_finite() => Ok(f),
+        _ => Err(Error::FloatOutOfRange { meta }),
+    }
+}
+
+/// Blanks out comments, semicolons, etc. Block comments may be nested, and an unterminated block
+/// comment is an error.
+///
+pub fn strip_extra(src: &str) -> Result<String, Error> {
     enum Mode {
         Normal,
         String,
         Comment,
+        BlockComment { start: usize, depth: usize },
     };
 
     let mut buffer = String::with_capacity(src.len());
     let mut mode = Mode::Normal;
-    let mut chars = src.chars();
-    while let Some(c) = chars.next() {
+    let mut chars = src.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
         match mode {
             Mode::Normal => match c {
                 ';' => buffer.push(' '),
@@ -28,17 +103,18 @@ pub fn strip_extra(src: &str) -> String {
                     buffer.push(c);
                 }
 
-                '/' => match chars.next() {
-                    Some('/') => {
+                '/' => match chars.peek() {
+                    Some((_, '/')) => {
+                        chars.next();
                         mode = Mode::Comment;
-                        buffer.push(' ');
-                        buffer.push(' ');
+                        buffer.push_str("  ");
                     }
-                    Some(c2) => {
-                        buffer.push(c);
-                        buffer.push(c2);
+                    Some((_, '*')) => {
+                        chars.next();
+                        mode = Mode::BlockComment { start: i, depth: 1 };
+                        buffer.push_str("  ");
                     }
-                    None => buffer.push(c),
+                    _ => buffer.push(c),
                 },
 
                 _ => buffer.push(c),
@@ -47,7 +123,7 @@ pub fn strip_extra(src: &str) -> String {
             Mode::String => match c {
                 '\\' => {
                     buffer.push(c);
-                    if let Some(c) = chars.next() {
+                    if let Some((_, c)) = chars.next() {
                         buffer.push(c)
                     }
                 }
@@ -67,17 +143,144 @@ pub fn strip_extra(src: &str) -> String {
                 }
                 _ => buffer.push(' '),
             },
+
+            Mode::BlockComment { start, depth } => match (c, chars.peek()) {
+                ('/', Some((_, '*'))) => {
+                    chars.next();
+                    mode = Mode::BlockComment {
+                        start,
+                        depth: depth + 1,
+                    };
+                    buffer.push_str("  ");
+                }
+                ('*', Some((_, '/'))) => {
+                    chars.next();
+                    mode = if depth == 1 {
+                        Mode::Normal
+                    } else {
+                        Mode::BlockComment {
+                            start,
+                            depth: depth - 1,
+                        }
+                    };
+                    buffer.push_str("  ");
+                }
+                ('\n', _) => buffer.push('\n'),
+                // Blank out each byte so that the positions of the following code are unchanged
+                _ => (0..c.len_utf8()).for_each(|_| buffer.push(' ')),
+            },
         }
     }
-    buffer
+
+    match mode {
+        Mode::BlockComment { start, .. } => Err(Error::UnterminatedBlockComment {
+            meta: meta(start, start + 2),
+        }),
+        _ => Ok(buffer),
+    }
 }
 
 #[test]
 fn strip_extra_test() {
-    assert_eq!(strip_extra(&""), "".to_string());
-    assert_eq!(strip_extra(&" ; "), "   ".to_string());
-    assert_eq!(strip_extra(&" // hi\n "), "      \n ".to_string());
-    assert_eq!(strip_extra(&r#""\"//" hi"#), r#""\"//" hi"#.to_string());
+    assert_eq!(strip_extra(&""), Ok("".to_string()));
+    assert_eq!(strip_extra(&" ; "), Ok("   ".to_string()));
+    assert_eq!(strip_extra(&" // hi\n "), Ok("      \n ".to_string()));
+    assert_eq!(strip_extra(&r#""\"//" hi"#), Ok(r#""\"//" hi"#.to_string()));
+    assert_eq!(strip_extra(&"1 / 2"), Ok("1 / 2".to_string()));
+    assert_eq!(strip_extra(&"a /* b */ c"), Ok("a         c".to_string()));
+    assert_eq!(
+        strip_extra(&"a /* b\n /* c */ é */ d"),
+        Ok("a     \n               d".to_string())
+    );
+    assert_eq!(
+        strip_extra(&r#""/* a" /* "b" */ "*/""#),
+        Ok(r#""/* a"           "*/""#.to_string())
+    );
+    assert_eq!(
+        strip_extra(&"a /* b /* c */"),
+        Err(Error::UnterminatedBlockComment { meta: meta(2, 4) })
+    );
+}
+
+/// Replace the escape sequences in the contents of a string literal with the characters they
+/// stand for. `start` is the position of the contents in the source, used to locate any invalid
+/// escape sequence.
+///
+pub fn unescape(raw: &str, start: usize) -> Result<String, Error> {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some((_, 'n')) => Some('\n'),
+            Some((_, 'r')) => Some('\r'),
+            Some((_, 't')) => Some('\t'),
+            Some((_, '"')) => Some('"'),
+            Some((_, '\\')) => Some('\\'),
+            Some((_, 'u')) => match chars.next() {
+                Some((_, '{')) => {
+                    let mut digits = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) if c.is_ascii_hexdigit() && digits.len() < 6 => {
+                                digits.push(c)
+                            }
+                            _ => {
+                                digits.clear();
+                                break;
+                            }
+                        }
+                    }
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        match escaped {
+            Some(c) => value.push(c),
+            None => {
+                let end = chars.next().map_or(raw.len(), |(j, _)| j);
+                return Err(Error::InvalidEscape {
+                    meta: meta(start + i, start + end),
+                });
+            }
+        }
+    }
+    Ok(value)
+}
+
+#[test]
+fn unescape_test() {
+    assert_eq!(Ok("".to_string()), unescape("", 0));
+    assert_eq!(Ok("a\nb\tc\r".to_string()), unescape("a\\nb\\tc\\r", 0));
+    assert_eq!(Ok("\"\\".to_string()), unescape("\\\"\\\\", 0));
+    assert_eq!(
+        Ok("caf\u{e9} \u{1f600}".to_string()),
+        unescape("caf\\u{e9} \\u{1F600}", 0)
+    );
+    assert_eq!(
+        Err(Error::InvalidEscape { meta: meta(12, 14) }),
+        unescape("ab\\qc", 10)
+    );
+    assert_eq!(
+        Err(Error::InvalidEscape { meta: meta(1, 5) }),
+        unescape("a\\u{}b", 0)
+    );
+    assert_eq!(
+        Err(Error::InvalidEscape { meta: meta(0, 10) }),
+        unescape("\\u{110000}", 0)
+    );
+    assert_eq!(
+        Err(Error::InvalidEscape { meta: meta(0, 3) }),
+        unescape("\\u{", 0)
+    );
 }
 
 pub fn seq(mut exprs: Vec<crate::ast::UntypedExpr>) -> crate::ast::UntypedExpr {
@@ -91,6 +294,276 @@ pub fn seq(mut exprs: Vec<crate::ast::UntypedExpr>) -> crate::ast::UntypedExpr {
     })
 }
 
+/// `if condition { then } else { otherwise }` is sugar for a case expression on the Bool condition.
+///
+pub fn if_else(
+    meta: crate::ast::Meta,
+    condition: crate::ast::UntypedExpr,
+    then: crate::ast::UntypedExpr,
+    otherwise: crate::ast::UntypedExpr,
+) -> crate::ast::UntypedExpr {
+    use crate::ast::*;
+
+    let clause = |name: &str, then: UntypedExpr| Clause {
+        meta: then.meta().clone(),
+        patterns: vec![Pattern::Constructor {
+            meta: condition.meta().clone(),
+            name: name.to_string(),
+            args: vec![],
+            module: None,
+            constructor: (),
+        }],
+        guard: None,
+        then,
+    };
+    let clauses = vec![clause("True", then), clause("False", otherwise)];
+
+    Expr::Case {
+        meta,
+        typ: (),
+        subjects: vec![condition],
+        clauses,
+    }
+}
+
+/// `use a, b <- f(x)` followed by an expression is sugar for passing that expression to `f` as a
+/// callback, i.e. `f(x, fn(a, b) { then })`. A `use` of a function that is not called, as in
+/// `use a <- f`, becomes `f(fn(a) { then })`.
+///
+pub fn use_callback(
+    meta: crate::ast::Meta,
+    args: Vec<crate::ast::UntypedArg>,
+    call: crate::ast::UntypedExpr,
+    then: crate::ast::UntypedExpr,
+) -> crate::ast::UntypedExpr {
+    use crate::ast::*;
+
+    let callback = CallArg {
+        label: None,
+        meta: meta.clone(),
+        value: Expr::Fn {
+            meta: meta.clone(),
+            is_capture: false,
+            typ: (),
+            args,
+            body: Box::new(then),
+        },
+    };
+
+    match call {
+        Expr::Call {
+            meta,
+            fun,
+            mut args,
+            ..
+        } => {
+            args.push(callback);
+            Expr::Call {
+                meta,
+                typ: (),
+                fun,
+                args,
+            }
+        }
+
+        fun => Expr::Call {
+            meta,
+            typ: (),
+            fun: Box::new(fun),
+            args: vec![callback],
+        },
+    }
+}
+
+/// An option given after the `:` of a bit string segment, such as the `binary` and `size(n)` of
+/// `body:binary-size(n)`. A plain integer is shorthand for `size(...)`.
+///
+pub enum BitSegmentOption {
+    Kind(crate::ast::BitSegmentKind),
+    Size(crate::ast::BitSegmentSize),
+}
+
+pub fn bit_segment_option(
+    meta: crate::ast::Meta,
+    name: String,
+    size: Option<crate::ast::BitSegmentSize>,
+) -> Result<BitSegmentOption, Error> {
+    use crate::ast::BitSegmentKind;
+
+    match (name.as_str(), size) {
+        ("int", None) => Ok(BitSegmentOption::Kind(BitSegmentKind::Int)),
+        ("binary", None) => Ok(BitSegmentOption::Kind(BitSegmentKind::Binary)),
+        ("size", Some(size)) => Ok(BitSegmentOption::Size(size)),
+        _ => Err(Error::InvalidBitSegment { meta }),
+    }
+}
+
+/// Each segment may be given at most one kind and one size. As in Erlang a binary segment without
+/// a size matches the rest of the bit string, so it may only be the last segment.
+///
+pub fn bit_string_pattern(
+    meta: crate::ast::Meta,
+    segments: Vec<(
+        crate::ast::Meta,
+        crate::ast::UntypedPattern,
+        Vec<BitSegmentOption>,
+    )>,
+) -> Result<crate::ast::UntypedPattern, Error> {
+    use crate::ast::*;
+
+    let count = segments.len();
+    let segments = segments
+        .into_iter()
+        .enumerate()
+        .map(|(i, (meta, value, options))| {
+            let mut kind = None;
+            let mut size = None;
+            for option in options {
+                match option {
+                    BitSegmentOption::Kind(k) if kind.is_none() => kind = Some(k),
+                    BitSegmentOption::Size(s) if size.is_none() => size = Some(s),
+                    _ => return Err(Error::InvalidBitSegment { meta }),
+                }
+            }
+            let kind = kind.unwrap_or(BitSegmentKind::Int);
+            if kind == BitSegmentKind::Binary && size.is_none() && i + 1 != count {
+                return Err(Error::InvalidBitSegment { meta });
+            }
+            Ok(BitSegment {
+                meta,
+                value,
+                kind,
+                size,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Pattern::BitString { meta, segments })
+}
+
+/// A discard such as `_` or `_name`. In an expression it is either a hole of a function capture or
+/// part of an assert pattern that has not yet been told apart from an expression.
+///
+pub fn is_discard(expr: &crate::ast::UntypedExpr) -> bool {
+    match expr {
+        crate::ast::Expr::Var { name, .. } => name.starts_with('_'),
+        _ => false,
+    }
+}
+
+fn is_constructor(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+/// A constructor, possibly qualified by the module that defines it.
+///
+pub fn is_constructor_expr(expr: &crate::ast::UntypedExpr) -> bool {
+    match expr {
+        crate::ast::Expr::Var { name, .. } => is_constructor(name),
+        crate::ast::Expr::FieldSelect { label, .. } => is_constructor(label),
+        _ => false,
+    }
+}
+
+/// The subject of an `assert` without `let` cannot be told apart from the expression of a boolean
+/// assert until the `=` that follows a pattern, so it is parsed as an expression and converted to
+/// a pattern here. Expressions that are not also patterns are rejected.
+///
+pub fn pattern(expr: crate::ast::UntypedExpr) -> Result<crate::ast::UntypedPattern, Error> {
+    use crate::ast::*;
+
+    match expr {
+        Expr::Int { meta, value, .. } => Ok(Pattern::Int { meta, value }),
+        Expr::Float { meta, value, .. } => Ok(Pattern::Float { meta, value }),
+        Expr::String { meta, value, .. } => Ok(Pattern::String { meta, value }),
+        Expr::Nil { meta, .. } => Ok(Pattern::Nil { meta }),
+
+        // The hole of a capture such as `Ok(_)` is a discard
+        Expr::Var { meta, name, .. } if name.starts_with('_') || name == "capture@1" => {
+            Ok(Pattern::Discard { meta })
+        }
+
+        Expr::Var { meta, name, .. } if is_constructor(&name) => Ok(Pattern::Constructor {
+            meta,
+            name,
+            args: vec![],
+            module: None,
+            constructor: (),
+        }),
+
+        Expr::Var { meta, name, .. } => Ok(Pattern::Var { meta, name }),
+
+        Expr::Cons {
+            meta, head, tail, ..
+        } => Ok(Pattern::Cons {
+            meta,
+            head: Box::new(pattern(*head)?),
+            tail: Box::new(pattern(*tail)?),
+        }),
+
+        Expr::AnonStruct { meta, elems, .. } => Ok(Pattern::AnonStruct {
+            meta,
+            elems: elems.into_iter().map(pattern).collect::<Result<_, _>>()?,
+        }),
+
+        Expr::FieldSelect {
+            meta,
+            label,
+            container,
+            ..
+        } => match *container {
+            Expr::Var { name: module, .. }
+                if is_constructor(&label) && !is_constructor(&module) =>
+            {
+                Ok(Pattern::Constructor {
+                    meta,
+                    name: label,
+                    args: vec![],
+                    module: Some(module),
+                    constructor: (),
+                })
+            }
+            _ => Err(Error::InvalidPattern { meta }),
+        },
+
+        Expr::Call {
+            meta, fun, args, ..
+        } => match pattern(*fun)? {
+            Pattern::Constructor {
+                name,
+                module,
+                args: no_args,
+                ..
+            } if no_args.is_empty() => Ok(Pattern::Constructor {
+                meta,
+                name,
+                args: args
+                    .into_iter()
+                    .map(|arg| {
+                        Ok(CallArg {
+                            meta: arg.meta,
+                            label: arg.label,
+                            value: pattern(arg.value)?,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+                module,
+                constructor: (),
+            }),
+            _ => Err(Error::InvalidPattern { meta }),
+        },
+
+        Expr::Fn {
+            is_capture: true,
+            body,
+            ..
+        } => pattern(*body),
+
+        other => Err(Error::InvalidPattern {
+            meta: other.meta().clone(),
+        }),
+    }
+}
+
 pub fn meta(start: usize, end: usize) -> crate::ast::Meta {
     crate::ast::Meta { start, end }
 }
@@ -104,7 +577,7 @@ fn expr_test() {
         Ok(Expr::Int {
             typ: (),
             meta: Meta { start: 0, end: 3 },
-            value: 123
+            value: "123".to_string()
         }),
         ExprParser::new().parse("123"),
     );
@@ -113,11 +586,71 @@ fn expr_test() {
         Ok(Expr::Int {
             typ: (),
             meta: Meta { start: 0, end: 3 },
-            value: -45
+            value: "-45".to_string()
         }),
         ExprParser::new().parse("-45"),
     );
 
+    assert_eq!(
+        Ok(Expr::Int {
+            typ: (),
+            meta: Meta { start: 0, end: 4 },
+            value: "0xFF".to_string()
+        }),
+        ExprParser::new().parse("0xFF"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Int {
+            typ: (),
+            meta: Meta { start: 0, end: 7 },
+            value: "0x1a_2B".to_string()
+        }),
+        ExprParser::new().parse("0x1a_2B"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Int {
+            typ: (),
+            meta: Meta { start: 0, end: 4 },
+            value: "0o77".to_string()
+        }),
+        ExprParser::new().parse("0o77"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Int {
+            typ: (),
+            meta: Meta { start: 0, end: 6 },
+            value: "0b1010".to_string()
+        }),
+        ExprParser::new().parse("0b1010"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Int {
+            typ: (),
+            meta: Meta { start: 0, end: 4 },
+            value: "-0b1".to_string()
+        }),
+        ExprParser::new().parse("-0b1"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Int {
+            typ: (),
+            meta: Meta { start: 0, end: 9 },
+            value: "1_000_000".to_string()
+        }),
+        ExprParser::new().parse("1_000_000"),
+    );
+
+    // The prefix of an int literal must be followed by a digit
+    assert!(ExprParser::new().parse("0x_").is_err());
+    assert!(ExprParser::new().parse("0o_").is_err());
+    assert!(ExprParser::new().parse("0b_").is_err());
+    assert!(ExprParser::new().parse("let 0x__ = x x").is_err());
+
     assert_eq!(
         Ok(Expr::Float {
             typ: (),
@@ -145,6 +678,60 @@ fn expr_test() {
         ExprParser::new().parse("-1.23"),
     );
 
+    assert_eq!(
+        Ok(Expr::Float {
+            typ: (),
+            meta: Meta { start: 0, end: 6 },
+            value: 1.5e10
+        }),
+        ExprParser::new().parse("1.5e10"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Float {
+            typ: (),
+            meta: Meta { start: 0, end: 6 },
+            value: 2.0e-3
+        }),
+        ExprParser::new().parse("2.0e-3"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Float {
+            typ: (),
+            meta: Meta { start: 0, end: 7 },
+            value: -1.0e3
+        }),
+        ExprParser::new().parse("-1.E+03"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Float {
+            typ: (),
+            meta: Meta { start: 0, end: 4 },
+            value: 1.0e10
+        }),
+        ExprParser::new().parse("1e10"),
+    );
+
+    assert_eq!(
+        Ok(Expr::Float {
+            typ: (),
+            meta: Meta { start: 0, end: 4 },
+            value: 2.0e-3
+        }),
+        ExprParser::new().parse("2E-3"),
+    );
+
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::FloatOutOfRange {
+                meta: Meta { start: 0, end: 7 },
+            },
+        }),
+        ExprParser::new().parse("1.0e400"),
+    );
+
     assert_eq!(
         Ok(Expr::String {
             typ: (),
@@ -158,7 +745,7 @@ fn expr_test() {
         Ok(Expr::String {
             typ: (),
             meta: Meta { start: 0, end: 13 },
-            value: "quote -> \\\"".to_string(),
+            value: "quote -> \"".to_string(),
         }),
         ExprParser::new().parse(r#""quote -> \"""#),
     );
@@ -166,8 +753,17 @@ fn expr_test() {
     assert_eq!(
         Ok(Expr::String {
             typ: (),
-            meta: Meta { start: 0, end: 13 },
-            value: "quote -> \\'".to_string(),
+            meta: Meta { start: 0, end: 20 },
+            value: "line\n\u{1F600} \\".to_string(),
+        }),
+        ExprParser::new().parse(r#""line\n\u{1F600} \\""#),
+    );
+
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidEscape {
+                meta: Meta { start: 10, end: 12 },
+            },
         }),
         ExprParser::new().parse(r#""quote -> \'""#),
     );
@@ -196,7 +792,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 6, end: 7 },
-                        value: 1
+                        value: "1".to_string()
                     }
                 },
                 CallArg {
@@ -205,7 +801,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 9, end: 10 },
-                        value: 2
+                        value: "2".to_string()
                     }
                 },
             ]
@@ -238,7 +834,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 5, end: 6 },
-                        value: 1
+                        value: "1".to_string()
                     }
                 },
                 CallArg {
@@ -247,7 +843,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 8, end: 9 },
-                        value: 3
+                        value: "3".to_string()
                     }
                 }
             ]
@@ -271,7 +867,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 9, end: 10 },
-                        value: 1
+                        value: "1".to_string()
                     }
                 },
                 CallArg {
@@ -280,7 +876,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 15, end: 16 },
-                        value: 3
+                        value: "3".to_string()
                     }
                 }
             ]
@@ -304,7 +900,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 6, end: 7 },
-                        value: 1
+                        value: "1".to_string()
                     }
                 },
                 CallArg {
@@ -313,7 +909,7 @@ fn expr_test() {
                     value: Expr::Int {
                         typ: (),
                         meta: Meta { start: 12, end: 13 },
-                        value: 3
+                        value: "3".to_string()
                     }
                 }
             ]
@@ -328,7 +924,7 @@ fn expr_test() {
             head: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 1, end: 2 },
-                value: 1
+                value: "1".to_string()
             }),
             tail: Box::new(Expr::Nil {
                 meta: Meta { start: 5, end: 7 },
@@ -345,7 +941,7 @@ fn expr_test() {
             head: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 1, end: 2 },
-                value: 1
+                value: "1".to_string()
             }),
             tail: Box::new(Expr::Nil {
                 meta: Meta { start: 2, end: 3 },
@@ -357,6 +953,7 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 14 },
             typ: (),
             value: Box::new(Expr::BinOp {
@@ -366,12 +963,12 @@ fn expr_test() {
                 left: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 8, end: 9 },
-                    value: 1
+                    value: "1".to_string()
                 }),
                 right: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 12, end: 13 },
-                    value: 2
+                    value: "2".to_string()
                 }),
             }),
             pattern: Pattern::Var {
@@ -395,12 +992,12 @@ fn expr_test() {
             left: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 0, end: 1 },
-                value: 1
+                value: "1".to_string()
             }),
             right: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 4, end: 5 },
-                value: 2
+                value: "2".to_string()
             }),
         }),
         ExprParser::new().parse("1 + 2"),
@@ -418,18 +1015,18 @@ fn expr_test() {
                 left: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 0, end: 1 },
-                    value: 1
+                    value: "1".to_string()
                 }),
                 right: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 4, end: 5 },
-                    value: 2
+                    value: "2".to_string()
                 }),
             }),
             right: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 8, end: 9 },
-                value: 3
+                value: "3".to_string()
             }),
         }),
         ExprParser::new().parse("1 + 2 + 3"),
@@ -447,18 +1044,18 @@ fn expr_test() {
                 left: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 0, end: 1 },
-                    value: 1
+                    value: "1".to_string()
                 }),
                 right: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 4, end: 5 },
-                    value: 2
+                    value: "2".to_string()
                 }),
             }),
             right: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 9, end: 10 },
-                value: 3
+                value: "3".to_string()
             }),
         }),
         ExprParser::new().parse("1 + 2 +. 3"),
@@ -476,18 +1073,18 @@ fn expr_test() {
                 left: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 0, end: 1 },
-                    value: 1
+                    value: "1".to_string()
                 }),
                 right: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 4, end: 5 },
-                    value: 2
+                    value: "2".to_string()
                 }),
             }),
             right: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 8, end: 9 },
-                value: 3
+                value: "3".to_string()
             }),
         }),
         ExprParser::new().parse("1 * 2 + 3"),
@@ -501,7 +1098,7 @@ fn expr_test() {
             left: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 0, end: 1 },
-                value: 1
+                value: "1".to_string()
             }),
             right: Box::new(Expr::BinOp {
                 meta: Meta { start: 4, end: 9 },
@@ -510,12 +1107,12 @@ fn expr_test() {
                 left: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 4, end: 5 },
-                    value: 2
+                    value: "2".to_string()
                 }),
                 right: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 8, end: 9 },
-                    value: 3
+                    value: "3".to_string()
                 }),
             }),
         }),
@@ -530,12 +1127,12 @@ fn expr_test() {
             left: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 0, end: 1 },
-                value: 1
+                value: "1".to_string()
             }),
             right: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 4, end: 5 },
-                value: 2
+                value: "2".to_string()
             }),
         }),
         ExprParser::new().parse("1 > 2"),
@@ -567,7 +1164,7 @@ fn expr_test() {
             head: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 1, end: 2 },
-                value: 1
+                value: "1".to_string()
             }),
             tail: Box::new(Expr::Cons {
                 meta: Meta { start: 5, end: 6 },
@@ -575,7 +1172,7 @@ fn expr_test() {
                 head: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 4, end: 5 },
-                    value: 2
+                    value: "2".to_string()
                 }),
                 tail: Box::new(Expr::Nil {
                     meta: Meta { start: 5, end: 6 },
@@ -588,12 +1185,13 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 10 },
             typ: (),
             value: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 8, end: 9 },
-                value: 1
+                value: "1".to_string()
             }),
             pattern: Pattern::Var {
                 meta: Meta { start: 4, end: 5 },
@@ -602,7 +1200,7 @@ fn expr_test() {
             then: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 10, end: 11 },
-                value: 2
+                value: "2".to_string()
             })
         }),
         ExprParser::new().parse("let x = 1 2"),
@@ -630,17 +1228,17 @@ fn expr_test() {
                 Expr::Int {
                     typ: (),
                     meta: Meta { start: 7, end: 8 },
-                    value: 1
+                    value: "1".to_string()
                 },
                 Expr::Int {
                     typ: (),
                     meta: Meta { start: 10, end: 11 },
-                    value: 2
+                    value: "2".to_string()
                 },
                 Expr::Int {
                     typ: (),
                     meta: Meta { start: 13, end: 14 },
-                    value: 3
+                    value: "3".to_string()
                 },
             ]
         }),
@@ -656,7 +1254,7 @@ fn expr_test() {
             body: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 7, end: 8 },
-                value: 1
+                value: "1".to_string()
             })
         }),
         ExprParser::new().parse("fn() { 1 }"),
@@ -689,7 +1287,7 @@ fn expr_test() {
                         value: Expr::Int {
                             meta: Meta { start: 2, end: 3 },
                             typ: (),
-                            value: 1
+                            value: "1".to_string()
                         }
                     },
                     CallArg {
@@ -707,7 +1305,7 @@ fn expr_test() {
                         value: Expr::Int {
                             meta: Meta { start: 8, end: 9 },
                             typ: (),
-                            value: 3
+                            value: "3".to_string()
                         }
                     }
                 ]
@@ -742,12 +1340,12 @@ fn expr_test() {
                 first: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 11, end: 12 },
-                    value: 1
+                    value: "1".to_string()
                 }),
                 then: Box::new(Expr::Int {
                     typ: (),
                     meta: Meta { start: 13, end: 14 },
-                    value: 2
+                    value: "2".to_string()
                 })
             })
         }),
@@ -756,21 +1354,22 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 10 },
             typ: (),
             value: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 8, end: 9 },
-                value: 1
+                value: "1".to_string()
             }),
             pattern: Pattern::Int {
                 meta: Meta { start: 4, end: 5 },
-                value: 0,
+                value: "0".to_string(),
             },
             then: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 10, end: 11 },
-                value: 2
+                value: "2".to_string()
             })
         }),
         ExprParser::new().parse("let 0 = 1 2"),
@@ -778,12 +1377,13 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 12 },
             typ: (),
             value: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 10, end: 11 },
-                value: 1,
+                value: "1".to_string(),
             }),
             pattern: Pattern::Float {
                 meta: Meta { start: 4, end: 7 },
@@ -792,7 +1392,7 @@ fn expr_test() {
             then: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 12, end: 13 },
-                value: 2
+                value: "2".to_string()
             })
         }),
         ExprParser::new().parse("let 1.0 = 1 2"),
@@ -800,12 +1400,13 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 12 },
             typ: (),
             value: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 10, end: 11 },
-                value: 1
+                value: "1".to_string()
             }),
             pattern: Pattern::String {
                 meta: Meta { start: 4, end: 7 },
@@ -814,7 +1415,7 @@ fn expr_test() {
             then: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 12, end: 13 },
-                value: 2
+                value: "2".to_string()
             })
         }),
         ExprParser::new().parse("let \"a\" = 1 2"),
@@ -822,6 +1423,7 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 23 },
             typ: (),
             pattern: Pattern::Constructor {
@@ -841,12 +1443,12 @@ fn expr_test() {
             value: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 21, end: 22 },
-                value: 1
+                value: "1".to_string()
             }),
             then: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 23, end: 24 },
-                value: 2
+                value: "2".to_string()
             })
         }),
         ExprParser::new().parse("let Some(body:  a) = 1 2"),
@@ -854,6 +1456,7 @@ fn expr_test() {
 
     assert_eq!(
         Ok(Expr::Let {
+            annotation: None,
             meta: Meta { start: 0, end: 23 },
             typ: (),
             pattern: Pattern::Constructor {
@@ -873,12 +1476,12 @@ fn expr_test() {
             value: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 21, end: 22 },
-                value: 1
+                value: "1".to_string()
             }),
             then: Box::new(Expr::Int {
                 typ: (),
                 meta: Meta { start: 23, end: 24 },
-                value: 2
+                value: "2".to_string()
             })
         }),
         ExprParser::new().parse("let option.Some(a) = 1 2"),
@@ -898,30 +1501,330 @@ fn expr_test() {
                     meta: Meta { start: 9, end: 16 },
                     patterns: vec![Pattern::Int {
                         meta: Meta { start: 9, end: 10 },
-                        value: 1
+                        value: "1".to_string()
                     }],
+                    guard: None,
                     then: Expr::Int {
                         meta: Meta { start: 14, end: 16 },
                         typ: (),
-                        value: 10
+                        value: "10".to_string()
                     }
                 },
                 Clause {
                     meta: Meta { start: 17, end: 24 },
                     patterns: vec![Pattern::Int {
                         meta: Meta { start: 17, end: 18 },
-                        value: 2
+                        value: "2".to_string()
                     }],
+                    guard: None,
                     then: Expr::Int {
                         meta: Meta { start: 22, end: 24 },
                         typ: (),
-                        value: 20
+                        value: "20".to_string()
                     }
                 }
             ]
         }),
         ExprParser::new().parse("case x { 1 -> 10 2 -> 20 }"),
     );
+
+    // A minus following an expression in a sequence is subtraction rather than negation
+    assert_eq!(
+        Ok(Expr::Negate {
+            meta: Meta { start: 0, end: 2 },
+            typ: (),
+            value: Box::new(Expr::Var {
+                meta: Meta { start: 1, end: 2 },
+                constructor: (),
+                name: "x".to_string(),
+            }),
+        }),
+        ExprParser::new().parse("-x"),
+    );
+    match ExprParser::new().parse("let y = 1 y -x") {
+        Ok(Expr::Let { then, .. }) => match *then {
+            Expr::BinOp {
+                name: BinOp::SubInt,
+                ..
+            } => (),
+            other => panic!("expected subtraction, got {:?}", other),
+        },
+        other => panic!("expected a let expression, got {:?}", other),
+    }
+
+    // if else is parsed as a case expression, and the else branch is required
+    match ExprParser::new().parse("if x { 1 } else { 2 }") {
+        Ok(Expr::Case {
+            subjects, clauses, ..
+        }) => {
+            assert_eq!(1, subjects.len());
+            let patterns: Vec<_> = clauses
+                .iter()
+                .map(|clause| match clause.patterns.as_slice() {
+                    [Pattern::Constructor { name, .. }] => name.as_str(),
+                    _ => panic!("expected a constructor pattern"),
+                })
+                .collect();
+            assert_eq!(vec!["True", "False"], patterns);
+        }
+        other => panic!("expected a case expression, got {:?}", other),
+    }
+    assert!(ExprParser::new().parse("if x { 1 }").is_err());
+
+    // Tuple elements are accessed by a decimal index
+    match ExprParser::new().parse("pair.1") {
+        Ok(Expr::TupleIndex { index, tuple, .. }) => {
+            assert_eq!(1, index);
+            assert_eq!(Meta { start: 0, end: 4 }, *tuple.meta());
+        }
+        other => panic!("expected a tuple index, got {:?}", other),
+    }
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidTupleIndex {
+                meta: Meta { start: 0, end: 8 },
+            },
+        }),
+        ExprParser::new().parse("pair.0x1"),
+    );
+    match ExprParser::new().parse("#(1, 2).0") {
+        Ok(Expr::TupleIndex { index: 0, .. }) => (),
+        other => panic!("expected a tuple index, got {:?}", other),
+    }
+    // Number literals cannot be indexed, so `1..2` is not read as `(1.).2`
+    assert!(ExprParser::new().parse("1..2").is_err());
+
+    // `rec` is only special after `let` when followed by the name of the function
+    match ExprParser::new().parse("let rec go = fn() { go() } go") {
+        Ok(Expr::LetRec { name, .. }) => assert_eq!("go", name),
+        other => panic!("expected a recursive let, got {:?}", other),
+    }
+    match ExprParser::new().parse("let rec = 1 rec") {
+        Ok(Expr::Let { .. }) => (),
+        other => panic!("expected a let, got {:?}", other),
+    }
+    assert!(crate::grammar::ModuleParser::new()
+        .parse("fn rec(rec) { rec }")
+        .is_ok());
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidLetRec {
+                meta: Meta { start: 4, end: 7 },
+            },
+        }),
+        ExprParser::new().parse("let foo go = fn() { 1 } go"),
+    );
+
+    // use passes the expression that follows it to the called function as a callback
+    match ExprParser::new().parse("use file <- with_file(\"a\") let size = 1 size") {
+        Ok(Expr::Call { fun, args, .. }) => {
+            match *fun {
+                Expr::Var { name, .. } => assert_eq!("with_file", name),
+                other => panic!("expected a variable, got {:?}", other),
+            }
+            assert_eq!(2, args.len());
+            match &args[1].value {
+                Expr::Fn { args, body, .. } => {
+                    let names: Vec<_> = args
+                        .iter()
+                        .map(|arg| match &arg.names {
+                            ArgNames::Named { name } => name.as_str(),
+                            _ => panic!("expected a named argument"),
+                        })
+                        .collect();
+                    assert_eq!(vec!["file"], names);
+                    match body.as_ref() {
+                        Expr::Let { then, .. } => match then.as_ref() {
+                            Expr::Var { name, .. } => assert_eq!("size", name),
+                            other => panic!("expected a variable, got {:?}", other),
+                        },
+                        other => panic!("expected a let expression, got {:?}", other),
+                    }
+                }
+                other => panic!("expected a callback function, got {:?}", other),
+            }
+        }
+        other => panic!("expected a call, got {:?}", other),
+    }
+    match ExprParser::new().parse("use <- defer 1") {
+        Ok(Expr::Call { args, .. }) => match args.as_slice() {
+            [CallArg {
+                label: None,
+                value: Expr::Fn { args, .. },
+                ..
+            }] => assert!(args.is_empty()),
+            other => panic!("expected a single callback, got {:?}", other),
+        },
+        other => panic!("expected a call, got {:?}", other),
+    }
+
+    // Bit string segments may only be given valid options, and a binary segment without a size
+    // must be last
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidBitSegment {
+                meta: Meta { start: 8, end: 13 },
+            },
+        }),
+        ExprParser::new().parse("let <<a:float>> = x a"),
+    );
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidBitSegment {
+                meta: Meta { start: 6, end: 17 },
+            },
+        }),
+        ExprParser::new().parse("let <<a:8-size(4)>> = x a"),
+    );
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidBitSegment {
+                meta: Meta { start: 6, end: 14 },
+            },
+        }),
+        ExprParser::new().parse("let <<a:binary, b>> = x a"),
+    );
+    assert!(ExprParser::new()
+        .parse("let <<a:binary-size(2), b:binary>> = x a")
+        .is_ok());
+
+    // `as` is only the message of a todo or panic when it is a word of its own
+    match ExprParser::new().parse("let x = todo\n assert Ok(y) = r\n y") {
+        Ok(Expr::Let { value, then, .. }) => {
+            match *value {
+                Expr::Todo { message: None, .. } => (),
+                other => panic!("expected a todo, got {:?}", other),
+            }
+            match *then {
+                Expr::Assert { message: None, .. } => (),
+                other => panic!("expected an assert, got {:?}", other),
+            }
+        }
+        other => panic!("expected a let expression, got {:?}", other),
+    }
+    match ExprParser::new().parse("fn(r) { panic\n assoc(r) }") {
+        Ok(Expr::Fn { body, .. }) => match *body {
+            Expr::Seq { first, then, .. } => {
+                match *first {
+                    Expr::Panic { message: None, .. } => (),
+                    other => panic!("expected a panic, got {:?}", other),
+                }
+                match *then {
+                    Expr::Call { fun, .. } => match *fun {
+                        Expr::Var { name, .. } => assert_eq!("assoc", name),
+                        other => panic!("expected a variable, got {:?}", other),
+                    },
+                    other => panic!("expected a call, got {:?}", other),
+                }
+            }
+            other => panic!("expected a sequence, got {:?}", other),
+        },
+        other => panic!("expected a function, got {:?}", other),
+    }
+    // The message after a panic in an assert belongs to the panic
+    match ExprParser::new().parse("assert Ok(y) = panic as \"m\" y") {
+        Ok(Expr::Assert { value, message, .. }) => {
+            assert!(message.is_none());
+            match *value {
+                Expr::Panic { message, .. } => assert_eq!(Some("m".to_string()), message),
+                other => panic!("expected a panic, got {:?}", other),
+            }
+        }
+        other => panic!("expected an assert, got {:?}", other),
+    }
+    match ExprParser::new().parse("assert Ok(y) = r as \"m\" y") {
+        Ok(Expr::Assert { message, .. }) => assert!(message.is_some()),
+        other => panic!("expected an assert, got {:?}", other),
+    }
+
+    // Without `let` an assert is a boolean assert unless its expression is followed by `=`, in
+    // which case the expression must also be a pattern
+    match ExprParser::new().parse("assert x == 5") {
+        Ok(Expr::AssertTrue { source, .. }) => assert_eq!("x == 5", source),
+        other => panic!("expected a boolean assert, got {:?}", other),
+    }
+    let assert_pattern = |src| match ExprParser::new().parse(src) {
+        Ok(Expr::Assert { pattern, .. }) => pattern,
+        other => panic!("expected an assert, got {:?}", other),
+    };
+    assert_eq!(
+        Pattern::Constructor {
+            meta: Meta { start: 7, end: 20 },
+            name: "Pair".to_string(),
+            args: vec![
+                CallArg {
+                    meta: Meta { start: 12, end: 13 },
+                    label: None,
+                    value: Pattern::Var {
+                        meta: Meta { start: 12, end: 13 },
+                        name: "a".to_string(),
+                    },
+                },
+                CallArg {
+                    meta: Meta { start: 15, end: 19 },
+                    label: Some("b".to_string()),
+                    value: Pattern::Discard {
+                        meta: Meta { start: 18, end: 19 },
+                    },
+                },
+            ],
+            module: None,
+            constructor: (),
+        },
+        assert_pattern("assert Pair(a, b: _) = p a"),
+    );
+    match assert_pattern("assert Pair(_, _) = p 1") {
+        Pattern::Constructor { args, .. } => assert!(args
+            .iter()
+            .all(|arg| matches!(arg.value, Pattern::Discard { .. }))),
+        other => panic!("expected a constructor, got {:?}", other),
+    }
+    match assert_pattern("assert Ok(_) = r 1") {
+        Pattern::Constructor { name, args, .. } => {
+            assert_eq!("Ok", name);
+            match args.as_slice() {
+                [CallArg {
+                    value: Pattern::Discard { .. },
+                    ..
+                }] => (),
+                other => panic!("expected a discard, got {:?}", other),
+            }
+        }
+        other => panic!("expected a constructor, got {:?}", other),
+    }
+    match assert_pattern("assert one.Box(#(_x, [y | _]), 1.0, \"a\", []) = b y") {
+        Pattern::Constructor { module, args, .. } => {
+            assert_eq!(Some("one".to_string()), module);
+            assert_eq!(4, args.len());
+        }
+        other => panic!("expected a constructor, got {:?}", other),
+    }
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidPattern {
+                meta: Meta { start: 7, end: 11 },
+            },
+        }),
+        ExprParser::new().parse("assert f(x) = y y"),
+    );
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::InvalidPattern {
+                meta: Meta { start: 7, end: 12 },
+            },
+        }),
+        ExprParser::new().parse("assert a + b = c c"),
+    );
+    // A call with more than one discard is only a pattern when it is a constructor
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::TooManyHolesInCapture {
+                meta: Meta { start: 0, end: 7 },
+                count: 2,
+            },
+        }),
+        ExprParser::new().parse("f(_, _)"),
+    );
 }
 
 #[test]
@@ -944,6 +1847,7 @@ fn module_test() {
             name: vec![],
             statements: vec![Statement::Import {
                 meta: Meta { start: 7, end: 12 },
+                public: false,
                 module: vec!["magic".to_string()],
                 unqualified: vec![],
                 as_name: None,
@@ -966,6 +1870,24 @@ fn module_test() {
         ModuleParser::new().parse("external type Conn"),
     );
 
+    assert_eq!(
+        Ok(Module {
+            type_info: (),
+            name: vec![],
+            statements: vec![Statement::Const {
+                meta: Meta { start: 0, end: 22 },
+                public: true,
+                name: "max_retries".to_string(),
+                value: Box::new(Expr::Int {
+                    meta: Meta { start: 24, end: 25 },
+                    typ: (),
+                    value: "3".to_string(),
+                }),
+            }]
+        }),
+        ModuleParser::new().parse("pub const max_retries = 3"),
+    );
+
     assert_eq!(
         Ok(Module {
             type_info: (),
@@ -994,6 +1916,65 @@ fn module_test() {
         ModuleParser::new().parse("external type Vector(a, b)"),
     );
 
+    assert_eq!(
+        Ok(Module {
+            type_info: (),
+            name: vec![],
+            statements: vec![Statement::TypeAlias {
+                meta: Meta { start: 0, end: 16 },
+                public: true,
+                name: "Pair".to_string(),
+                args: vec!["a".to_string()],
+                typ: TypeAst::AnonStruct {
+                    meta: Meta { start: 19, end: 26 },
+                    elems: vec![
+                        TypeAst::Var {
+                            meta: Meta { start: 21, end: 22 },
+                            name: "a".to_string(),
+                        },
+                        TypeAst::Var {
+                            meta: Meta { start: 24, end: 25 },
+                            name: "a".to_string(),
+                        },
+                    ],
+                },
+            }]
+        }),
+        ModuleParser::new().parse("pub type Pair(a) = #(a, a)"),
+    );
+
+    // Arguments may be given as patterns
+    let module = ModuleParser::new()
+        .parse("fn go(x, #(a, _): #(Int, Int)) { a }")
+        .expect("should parse");
+    match module.statements.as_slice() {
+        [Statement::Fn { args, body, .. }] => {
+            assert_eq!(
+                ArgNames::Named {
+                    name: "x".to_string()
+                },
+                args[0].names
+            );
+            match &args[1].names {
+                ArgNames::Pattern {
+                    pattern: Pattern::AnonStruct { elems, .. },
+                } => assert_eq!(2, elems.len()),
+                other => panic!("expected a pattern, got {:?}", other),
+            }
+            assert_eq!(Meta { start: 9, end: 29 }, args[1].meta);
+            assert!(args[1].annotation.is_some());
+            assert_eq!(
+                Expr::Var {
+                    meta: Meta { start: 33, end: 34 },
+                    constructor: (),
+                    name: "a".to_string(),
+                },
+                *body
+            );
+        }
+        other => panic!("expected a function, got {:?}", other),
+    }
+
     assert_eq!(
         Ok(Module {
             type_info: (),
@@ -1024,12 +2005,12 @@ fn module_test() {
                     first: Box::new(Expr::Int {
                         typ: (),
                         meta: Meta { start: 19, end: 20 },
-                        value: 1
+                        value: "1".to_string()
                     }),
                     then: Box::new(Expr::Int {
                         typ: (),
                         meta: Meta { start: 21, end: 22 },
-                        value: 2
+                        value: "2".to_string()
                     })
                 }
             }]
@@ -1052,26 +2033,26 @@ fn module_test() {
                     first: Box::new(Expr::Int {
                         typ: (),
                         meta: Meta { start: 11, end: 12 },
-                        value: 1
+                        value: "1".to_string()
                     }),
                     then: Box::new(Expr::Seq {
                         typ: (),
                         first: Box::new(Expr::Int {
                             typ: (),
                             meta: Meta { start: 13, end: 14 },
-                            value: 2
+                            value: "2".to_string()
                         }),
                         then: Box::new(Expr::Seq {
                             typ: (),
                             first: Box::new(Expr::Int {
                                 typ: (),
                                 meta: Meta { start: 15, end: 16 },
-                                value: 3
+                                value: "3".to_string()
                             }),
                             then: Box::new(Expr::Int {
                                 typ: (),
                                 meta: Meta { start: 17, end: 18 },
-                                value: 4
+                                value: "4".to_string()
                             })
                         })
                     })
@@ -1094,7 +2075,7 @@ fn module_test() {
                 body: Expr::Int {
                     typ: (),
                     meta: Meta { start: 14, end: 15 },
-                    value: 1
+                    value: "1".to_string()
                 },
             }]
         }),
@@ -1149,6 +2130,7 @@ fn module_test() {
             statements: vec![Statement::Enum {
                 meta: Meta { start: 0, end: 16 },
                 public: true,
+                opaque: false,
                 args: vec!["a".to_string()],
                 constructors: vec![EnumConstructor {
                     meta: Meta { start: 18, end: 26 },
@@ -1175,6 +2157,7 @@ fn module_test() {
                 Statement::Enum {
                     meta: Meta { start: 0, end: 27 },
                     public: true,
+                    opaque: false,
                     args: vec!["x".to_string()],
                     constructors: vec![EnumConstructor {
                         meta: Meta { start: 29, end: 48 },
@@ -1205,6 +2188,7 @@ fn module_test() {
                         }
                     }],
                     body: Expr::Let {
+                        annotation: None,
                         meta: Meta {
                             start: 98,
                             end: 141
@@ -1271,6 +2255,7 @@ fn module_test() {
                 Statement::Enum {
                     meta: Meta { start: 0, end: 16 },
                     public: true,
+                    opaque: false,
                     args: vec!["x".to_string()],
                     constructors: vec![EnumConstructor {
                         meta: Meta { start: 18, end: 26 },
@@ -1301,6 +2286,7 @@ fn module_test() {
                         }
                     }],
                     body: Expr::Let {
+                        annotation: None,
                         meta: Meta {
                             start: 76,
                             end: 108
@@ -1364,6 +2350,7 @@ fn module_test() {
                     }
                 }],
                 body: Expr::Let {
+                    annotation: None,
                     meta: Meta { start: 33, end: 59 },
                     typ: (),
                     value: Box::new(Expr::Var {
@@ -1407,6 +2394,7 @@ fn module_test() {
                     }
                 }],
                 body: Expr::Let {
+                    annotation: None,
                     meta: Meta { start: 33, end: 63 },
                     typ: (),
                     value: Box::new(Expr::Var {
@@ -1464,6 +2452,7 @@ fn module_test() {
                     }
                 }],
                 body: Expr::Let {
+                    annotation: None,
                     meta: Meta { start: 34, end: 72 },
                     typ: (),
                     value: Box::new(Expr::Var {
@@ -1510,6 +2499,7 @@ fn module_test() {
             name: vec![],
             statements: vec![Statement::Import {
                 meta: Meta { start: 7, end: 20 },
+                public: false,
                 module: vec!["one".to_string(), "two".to_string(), "three".to_string(),],
                 unqualified: vec![],
                 as_name: None,
@@ -1560,6 +2550,7 @@ fn module_test() {
             name: vec![],
             statements: vec![Statement::Import {
                 meta: Meta { start: 7, end: 21 },
+                public: false,
                 unqualified: vec![],
                 module: vec!["one".to_string(), "two".to_string(), "three".to_string(),],
                 as_name: Some("something".to_string()),
@@ -1567,4 +2558,29 @@ fn module_test() {
         }),
         ModuleParser::new().parse("import one/two/three as something"),
     );
+
+    // Definitions may be annotated with the target they are compiled for
+    match ModuleParser::new().parse("@target(core_erlang) fn go() { 1 }") {
+        Ok(Module { statements, .. }) => match statements.as_slice() {
+            [Statement::Target {
+                meta,
+                target: crate::project::CompileTarget::CoreErlang,
+                statement,
+            }] => {
+                assert_eq!(&Meta { start: 0, end: 21 }, meta);
+                assert!(matches!(statement.as_ref(), Statement::Fn { name, .. } if name == "go"));
+            }
+            other => panic!("expected a single target statement, got {:?}", other),
+        },
+        other => panic!("expected a module, got {:?}", other),
+    }
+    assert_eq!(
+        Err(lalrpop_util::ParseError::User {
+            error: Error::UnknownTarget {
+                meta: Meta { start: 8, end: 14 },
+                name: "elixir".to_string(),
+            },
+        }),
+        ModuleParser::new().parse("@target(elixir) fn go() { 1 }"),
+    );
 }
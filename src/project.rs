@@ -1,10 +1,12 @@
-use crate::error::Error;
+use crate::error::{Error, Warning};
 use crate::typ::ModuleTypeInfo;
-use petgraph::Graph;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use petgraph::{graph::NodeIndex, visit::Reversed, Graph};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Input {
     pub source_base_path: PathBuf,
     pub path: PathBuf,
@@ -26,6 +28,92 @@ pub struct OutputFile {
     pub path: PathBuf,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct Package {
+    pub modules: Vec<Compiled>,
+    pub files: Vec<OutputFile>,
+    pub warnings: Vec<Warning>,
+    pub stats: Option<CompileStats>,
+}
+
+/// How long each phase of compilation took for each module.
+#[derive(Debug, PartialEq, Default)]
+pub struct CompileStats {
+    pub modules: Vec<ModuleStats>,
+}
+
+impl CompileStats {
+    pub fn total(&self) -> Duration {
+        self.modules.iter().map(|m| m.total()).sum()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ModuleStats {
+    pub name: Vec<String>,
+    pub parse: Duration,
+    pub type_check: Duration,
+    pub codegen: Duration,
+}
+
+impl ModuleStats {
+    pub fn total(&self) -> Duration {
+        self.parse + self.type_check + self.codegen
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompileOptions {
+    /// Generate an OTP application resource file listing the compiled src modules.
+    pub otp_app: Option<OtpApp>,
+    pub write_interfaces: bool,
+    pub write_record_headers: bool,
+    /// Dependency modules with an interface here are not compiled again.
+    pub interfaces: HashMap<String, ModuleTypeInfo>,
+    pub file_attributes: bool,
+    pub write_manifest: bool,
+    pub warnings_as_errors: bool,
+    /// Generate code as normal but discard the text of each output file.
+    pub dry_run: bool,
+    pub collect_stats: bool,
+    pub write_docs: bool,
+    /// Generate a `gleam_test_runner` module that calls each public `*_test` function of the test
+    /// modules.
+    pub write_test_runner: bool,
+    pub warn_generic_fns: bool,
+    pub warn_unused_fns: bool,
+    pub target: CompileTarget,
+    /// Prefix src and test module names with this namespace, so that `http/client` in `mypkg` is
+    /// compiled to `mypkg@http@client`. Imports still use the unprefixed names.
+    pub namespace: Option<String>,
+    pub compiler_version: Option<String>,
+    pub erlang_indent: Option<usize>,
+    /// Only type check and compile these modules and the modules they depend on.
+    pub targets: Option<std::collections::HashSet<String>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum CompileTarget {
+    #[default]
+    Erlang,
+    CoreErlang,
+}
+
+impl CompileTarget {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompileTarget::Erlang => "erl",
+            CompileTarget::CoreErlang => "core",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OtpApp {
+    pub name: String,
+    pub version: String,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum ModuleOrigin {
     Src,
@@ -42,282 +130,1285 @@ impl ModuleOrigin {
     }
 }
 
-pub fn compile(srcs: Vec<Input>) -> Result<Vec<Compiled>, Error> {
-    struct Module {
-        src: String,
-        path: PathBuf,
-        source_base_path: PathBuf,
-        origin: ModuleOrigin,
-        module: crate::ast::UntypedModule,
-    }
-    let module_count = srcs.len();
-    let mut deps_graph = Graph::new();
-    let mut indexes = HashMap::new();
-    let mut modules: HashMap<_, Module> = HashMap::new();
-
-    for Input {
-        source_base_path,
-        path,
-        src,
-        origin,
-    } in srcs
-    {
-        let name = path
-            .strip_prefix(source_base_path.clone())
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join(path.file_stem().unwrap())
-            .to_str()
-            .unwrap()
-            .to_string();
-        let mut module = crate::grammar::ModuleParser::new()
-            .parse(&crate::parser::strip_extra(&src))
-            .map_err(|e| Error::Parse {
-                path: path.clone(),
-                src: src.clone(),
-                error: e.map_token(|crate::grammar::Token(a, b)| (a, b.to_string())),
-            })?;
+/// Type check a project without generating any code.
+///
+pub fn check(srcs: Vec<Input>) -> Result<(Vec<ModuleTypeInfo>, Vec<Warning>), Error> {
+    let (analysed, warnings) = analyse(srcs, &Default::default(), &mut |_| ())?;
+    let modules = analysed
+        .into_iter()
+        .map(|analysed| analysed.module.type_info)
+        .collect();
+    Ok((modules, warnings))
+}
 
-        if let Some(Module {
-            path: first_path, ..
-        }) = indexes.get(&name).and_then(|i| modules.get(i))
-        {
-            return Err(Error::DuplicateModule {
-                module: name,
-                first: first_path.clone(),
-                second: path,
-            });
-        }
+pub fn compile(srcs: Vec<Input>, options: &CompileOptions) -> Result<Package, Error> {
+    compile_with_progress(srcs, options, &mut |_| ())
+}
 
-        module.name = name.split('/').map(|s| s.to_string()).collect();
+/// Compile as `compile` does, calling `progress` with the name of each module as it is compiled.
+///
+pub fn compile_with_progress(
+    srcs: Vec<Input>,
+    options: &CompileOptions,
+    progress: &mut dyn FnMut(&str),
+) -> Result<Package, Error> {
+    compile_with_sink(srcs, options, progress, &mut |_| Ok(()))
+}
 
-        let index = deps_graph.add_node(name.clone());
-        indexes.insert(name.clone(), index);
-        modules.insert(
-            index,
-            Module {
-                src,
-                path,
-                module,
-                origin,
-                source_base_path,
-            },
-        );
-    }
-
-    // Register each module's deps so that we can determine a correct order to compile the modules.
-    for module in modules.values() {
-        let module_name = module.module.name_string();
-        let src = module.src.clone();
-        let path = module.path.clone();
-        let deps = module.module.dependencies();
-        let module_index = indexes
-            .get(&module_name)
-            .expect("Unable to find module index");
-        let module = modules
-            .get(&module_index)
-            .expect("Unable to find module for index");
-
-        for (dep, meta) in deps {
-            let dep_index = indexes.get(&dep).ok_or_else(|| Error::UnknownImport {
-                module: module_name.clone(),
-                import: dep.clone(),
-                src: src.clone(),
-                path: path.clone(),
-                modules: modules.values().map(|m| m.module.name_string()).collect(),
-                meta: meta.clone(),
+/// Compile as `compile_with_progress` does, but give each output file to `writer` as soon as it is
+/// generated rather than holding the output of the whole project in memory.
+///
+pub fn compile_to(
+    srcs: Vec<Input>,
+    options: &CompileOptions,
+    progress: &mut dyn FnMut(&str),
+    writer: &mut dyn FnMut(&OutputFile) -> std::io::Result<()>,
+) -> Result<Package, Error> {
+    compile_with_sink(srcs, options, progress, &mut |files| {
+        for file in files.drain(..) {
+            writer(&file).map_err(|e| Error::FileIo {
+                path: file.path.clone(),
+                error: e.to_string(),
             })?;
-
-            if module.origin == ModuleOrigin::Src
-                && modules
-                    .get(&dep_index)
-                    .expect("Unable to find module for dep index")
-                    .origin
-                    == ModuleOrigin::Test
-            {
-                return Err(Error::SrcImportingTest {
-                    path: path.clone(),
-                    src: src.clone(),
-                    meta,
-                    src_module: module_name,
-                    test_module: dep,
-                });
-            }
-
-            deps_graph.add_edge(dep_index.clone(), module_index.clone(), ());
         }
-    }
-
-    let mut modules_type_infos = HashMap::new();
-    let mut compiled_modules = Vec::with_capacity(module_count);
+        Ok(())
+    })
+}
 
-    struct Out {
-        name_string: String,
-        name: Vec<String>,
-        origin: ModuleOrigin,
-        files: Vec<OutputFile>,
-    }
+fn compile_with_sink(
+    srcs: Vec<Input>,
+    options: &CompileOptions,
+    progress: &mut dyn FnMut(&str),
+    sink: &mut dyn FnMut(&mut Vec<OutputFile>) -> Result<(), Error>,
+) -> Result<Package, Error> {
+    let mut emit = |files: &mut Vec<OutputFile>| {
+        if options.dry_run {
+            files.iter_mut().for_each(|file| file.text = String::new());
+        }
+        sink(files)
+    };
+    let mut src_gen_dir = None;
+    let mut test_gen_dir = None;
+    let mut project_gen_dir = None;
+    let mut manifest = std::collections::BTreeMap::new();
+    let mut modules = vec![];
+    let mut stats = CompileStats::default();
+    let (analysed, warnings) = analyse(srcs, options, progress)?;
+    let mut docs = vec![];
+    let documented: std::collections::HashSet<_> = analysed
+        .iter()
+        .filter(|a| a.origin == ModuleOrigin::Src)
+        .map(|a| module_name(&a.path, &a.source_base_path))
+        .collect::<Result<_, _>>()?;
 
-    for i in petgraph::algo::toposort(&deps_graph, None)
-        .map_err(|_| Error::DependencyCycle)?
-        .into_iter()
+    for Analysed {
+        src,
+        path,
+        origin,
+        source_base_path,
+        module,
+        parse_time,
+        type_check_time,
+    } in analysed
     {
-        let Module {
-            src,
-            path,
-            module,
-            origin,
-            source_base_path,
-        } = modules.remove(&i).expect("Unknown graph index");
+        let start = Instant::now();
         let name = module.name.clone();
-        let name_string = module.name_string();
-
-        println!("Compiling {}", name_string);
+        let type_info = module.type_info.clone();
+        let gen_dir = gen_dir(&source_base_path, &origin)?;
+        let erl_module_name = module.name.join("@");
 
-        let module = crate::typ::infer_module(module, &modules_type_infos)
-            .map_err(|error| Error::Type { path, src, error })?;
+        if origin == ModuleOrigin::Src {
+            src_gen_dir = Some(gen_dir.clone());
+        }
 
-        modules_type_infos.insert(name_string.clone(), module.type_info.clone());
+        if origin == ModuleOrigin::Test {
+            test_gen_dir = Some(gen_dir.clone());
+        }
 
-        let gen_dir = source_base_path
-            .parent()
-            .unwrap()
-            .join("gen")
-            .join(origin.dir_name());
-        let erl_module_name = module.name.join("@");
+        if origin != ModuleOrigin::Dependency {
+            project_gen_dir = gen_dir.parent().map(|dir| dir.to_path_buf());
+        }
 
-        let mut files: Vec<_> = crate::erl::records(&module)
+        let records = crate::erl::records(&module);
+        let header = if options.write_record_headers && !records.is_empty() {
+            Some(OutputFile {
+                path: header_path(&gen_dir, &erl_module_name),
+                text: records.iter().map(|(_, text)| text.as_str()).collect(),
+            })
+        } else {
+            None
+        };
+        let mut files: Vec<_> = records
             .into_iter()
             .map(|(name, text)| OutputFile {
-                path: gen_dir.join(format!("{}_{}.erl", erl_module_name, name)),
+                path: record_path(&gen_dir, &erl_module_name, name),
                 text,
             })
+            .chain(header)
             .collect();
 
-        files.push(OutputFile {
-            path: gen_dir.join(format!("{}.erl", erl_module_name)),
-            text: crate::erl::module(module),
+        if options.write_interfaces {
+            files.push(write_interface(&gen_dir, &module.type_info));
+        }
+
+        let module_docs = match (options.write_docs, &origin, gen_dir.parent()) {
+            (true, ModuleOrigin::Src, Some(project_gen_dir)) => Some(OutputFile {
+                path: docs_path(
+                    project_gen_dir,
+                    &module_name(&path, &source_base_path)?.replace('/', "@"),
+                ),
+                text: crate::docs::module_html(
+                    &module.clone().for_target(options.target),
+                    &documented,
+                ),
+            }),
+            _ => None,
+        };
+
+        files.push(match options.target {
+            CompileTarget::Erlang => {
+                let header = match &options.compiler_version {
+                    Some(version) => {
+                        crate::erl::header(version, &source_path(&path, &source_base_path))
+                    }
+                    None => String::new(),
+                };
+                let path_string = path.to_string_lossy();
+                let source = if options.file_attributes {
+                    Some((path_string.as_ref(), src.as_str()))
+                } else {
+                    None
+                };
+                let indent = options.erlang_indent.unwrap_or(crate::erl::DEFAULT_INDENT);
+                let text = crate::erl::module_with_options(module, source, indent);
+                OutputFile {
+                    path: module_path(&gen_dir, &erl_module_name, options.target),
+                    text: header + &text,
+                }
+            }
+            CompileTarget::CoreErlang => {
+                if let Some(meta) = module.statements.iter().find_map(|s| match s {
+                    crate::ast::Statement::InlineErlangFn { meta, .. } => Some(meta.clone()),
+                    _ => None,
+                }) {
+                    return Err(Error::UnsupportedInlineErlang { path, src, meta });
+                }
+                OutputFile {
+                    path: module_path(&gen_dir, &erl_module_name, options.target),
+                    text: crate::core_erlang::module(module),
+                }
+            }
         });
 
-        compiled_modules.push(Out {
+        if options.collect_stats {
+            stats.modules.push(ModuleStats {
+                name: name.clone(),
+                parse: parse_time,
+                type_check: type_check_time,
+                codegen: start.elapsed(),
+            });
+        }
+
+        if options.write_manifest {
+            let entry = ManifestEntry {
+                module: name.join("/"),
+                files: files
+                    .iter()
+                    .chain(module_docs.iter())
+                    .map(|file| file.path.clone())
+                    .collect(),
+            };
+            manifest.insert(path, entry);
+        }
+
+        emit(&mut files)?;
+        docs.extend(module_docs);
+
+        modules.push(Compiled {
             name,
-            name_string,
             origin,
             files,
+            type_info,
         });
     }
 
-    Ok(compiled_modules
-        .into_iter()
-        .map(
-            |Out {
-                 name,
-                 name_string,
-                 origin,
-                 files,
-             }| Compiled {
-                name,
-                files,
-                origin,
-                type_info: modules_type_infos
-                    .remove(&name_string)
-                    .expect("merging module type info"),
-            },
-        )
-        .collect())
+    let mut files = docs;
+
+    if let (Some(app), Some(gen_dir)) = (&options.otp_app, src_gen_dir) {
+        let mut app_modules: Vec<_> = modules
+            .iter()
+            .filter(|m| m.origin == ModuleOrigin::Src)
+            .map(|m| m.name.join("@"))
+            .collect();
+        app_modules.sort();
+        files.push(OutputFile {
+            path: app_path(&gen_dir, app),
+            text: crate::erl::app_resource(&app.name, &app.version, &app_modules),
+        });
+    }
+
+    if let (true, Some(gen_dir)) = (options.write_test_runner, test_gen_dir) {
+        let mut tests: Vec<_> = modules
+            .iter()
+            .filter(|m| m.origin == ModuleOrigin::Test)
+            .flat_map(|m| {
+                m.type_info
+                    .value_constructors
+                    .iter()
+                    .filter(move |(name, constructor)| {
+                        name.ends_with("_test")
+                            && match &constructor.variant {
+                                crate::typ::ValueConstructorVariant::ModuleFn {
+                                    module,
+                                    arity: 0,
+                                    ..
+                                } => module == &m.name,
+                                _ => false,
+                            }
+                    })
+                    .map(move |(name, _)| (m.name.join("@"), name.clone()))
+            })
+            .collect();
+        tests.sort();
+        files.push(OutputFile {
+            path: test_runner_path(&gen_dir),
+            text: crate::erl::test_runner(&tests),
+        });
+    }
+
+    if let (true, Some(gen_dir)) = (options.write_manifest, project_gen_dir) {
+        files.push(OutputFile {
+            path: manifest_path(&gen_dir),
+            text: serde_json::to_string_pretty(&manifest).expect("manifest serialization"),
+        });
+    }
+
+    emit(&mut files)?;
+
+    Ok(Package {
+        modules,
+        files,
+        warnings,
+        stats: if options.collect_stats {
+            Some(stats)
+        } else {
+            None
+        },
+    })
 }
 
-pub fn collect_source(src_dir: PathBuf, origin: ModuleOrigin, srcs: &mut Vec<Input>) {
-    let src_dir = match src_dir.canonicalize() {
-        Ok(d) => d,
-        Err(_) => return,
-    };
-    let is_gleam_path = |e: &walkdir::DirEntry| {
-        use regex::Regex;
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new("^([a-z_]+/)*[a-z_]+\\.gleam$").expect("collect_source RE regex");
+/// The paths of the files that `compile` would generate, found without type checking any module.
+///
+pub fn generated_paths(srcs: &[Input], options: &CompileOptions) -> Result<Vec<PathBuf>, Error> {
+    let mut src_gen_dir = None;
+    let mut test_gen_dir = None;
+    let mut project_gen_dir = None;
+    let mut paths = vec![];
+    let mut docs = vec![];
+    let mut graph = DependencyGraph::new(uncompiled(srcs.to_vec(), options)?)?;
+    let required = graph.required(&options.targets)?;
+
+    for i in graph.order()? {
+        if let Some(required) = &required {
+            if !required.contains(&i) {
+                continue;
+            }
         }
 
-        RE.is_match(
-            e.path()
-                .strip_prefix(&*src_dir)
-                .expect("collect_source strip_prefix")
-                .to_str()
-                .unwrap_or(""),
-        )
-    };
+        let Parsed {
+            input:
+                Input {
+                    source_base_path,
+                    path,
+                    origin,
+                    ..
+                },
+            module,
+            ..
+        } = internal(graph.modules.remove(&i), "Unknown graph index")?;
+        let name = module_name(&path, &source_base_path)?;
+        let gen_dir = gen_dir(&source_base_path, &origin)?;
+        let erl_module_name =
+            namespaced_name(module.name.clone(), &origin, &options.namespace).join("@");
 
-    walkdir::WalkDir::new(src_dir.clone())
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file())
-        .filter(is_gleam_path)
-        .for_each(|dir_entry| {
-            let src = std::fs::read_to_string(dir_entry.path())
-                .unwrap_or_else(|_| panic!("Unable to read {:?}", dir_entry.path()));
-
-            srcs.push(Input {
-                path: dir_entry
-                    .path()
-                    .canonicalize()
-                    .expect("collect_source path canonicalize"),
-                source_base_path: src_dir.clone(),
-                origin: origin.clone(),
-                src,
-            })
-        });
+        if origin == ModuleOrigin::Src {
+            src_gen_dir = Some(gen_dir.clone());
+        }
+
+        if origin == ModuleOrigin::Test {
+            test_gen_dir = Some(gen_dir.clone());
+        }
+
+        if origin != ModuleOrigin::Dependency {
+            project_gen_dir = gen_dir.parent().map(|dir| dir.to_path_buf());
+        }
+
+        let records = crate::erl::records(&module);
+        for (record, _) in &records {
+            paths.push(record_path(&gen_dir, &erl_module_name, record));
+        }
+
+        if options.write_record_headers && !records.is_empty() {
+            paths.push(header_path(&gen_dir, &erl_module_name));
+        }
+
+        if options.write_interfaces {
+            paths.push(interface_path(&gen_dir, &erl_module_name));
+        }
+
+        if let (true, ModuleOrigin::Src, Some(project_gen_dir)) =
+            (options.write_docs, &origin, gen_dir.parent())
+        {
+            docs.push(docs_path(project_gen_dir, &name.replace('/', "@")));
+        }
+
+        paths.push(module_path(&gen_dir, &erl_module_name, options.target));
+    }
+
+    paths.append(&mut docs);
+
+    if let (Some(app), Some(gen_dir)) = (&options.otp_app, src_gen_dir) {
+        paths.push(app_path(&gen_dir, app));
+    }
+
+    if let (true, Some(gen_dir)) = (options.write_test_runner, test_gen_dir) {
+        paths.push(test_runner_path(&gen_dir));
+    }
+
+    if let (true, Some(gen_dir)) = (options.write_manifest, project_gen_dir) {
+        paths.push(manifest_path(&gen_dir));
+    }
+
+    Ok(paths)
+}
+
+/// Unwraps a value that the compiler's own bookkeeping guarantees is present, returning an
+/// internal error describing the broken invariant rather than panicking if it is not.
+///
+fn internal<T>(value: Option<T>, message: &str) -> Result<T, Error> {
+    value.ok_or_else(|| Error::Internal {
+        message: message.to_string(),
+    })
 }
 
 #[test]
-fn compile_test() {
-    struct Case {
-        input: Vec<Input>,
-        expected: Result<Vec<Output>, Error>,
+fn internal_test() {
+    assert_eq!(Ok(1), internal(Some(1), "Unknown graph index"));
+    assert_eq!(
+        Err(Error::Internal {
+            message: "Unknown graph index".to_string()
+        }),
+        internal::<usize>(None, "Unknown graph index")
+    );
+}
+
+/// The modules of dependencies are not namespaced.
+///
+fn namespaced_name(
+    name: Vec<String>,
+    origin: &ModuleOrigin,
+    namespace: &Option<String>,
+) -> Vec<String> {
+    match (namespace, origin) {
+        (Some(namespace), ModuleOrigin::Src) | (Some(namespace), ModuleOrigin::Test) => namespace
+            .split('/')
+            .map(|s| s.to_string())
+            .chain(name)
+            .collect(),
+        _ => name,
     }
-    #[derive(Debug, PartialEq)]
-    struct Output {
-        name: Vec<String>,
-        files: Vec<OutputFile>,
-        origin: ModuleOrigin,
+}
+
+fn module_name(path: &Path, source_base_path: &Path) -> Result<String, Error> {
+    let path = internal(
+        path.strip_prefix(source_base_path).ok(),
+        "Source path is not within its source directory",
+    )?;
+    // Module names are separated by forward slashes whatever separator the OS uses
+    Ok(path.with_extension("").to_string_lossy().replace('\\', "/"))
+}
+
+/// The path of a source file such as `src/one.gleam`, with forward slashes on every OS.
+///
+fn source_path(path: &Path, source_base_path: &Path) -> String {
+    let root = source_base_path.parent().unwrap_or(source_base_path);
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+#[test]
+fn module_name_test() {
+    let src = PathBuf::from("/src");
+    assert_eq!(
+        Ok("one".to_string()),
+        module_name(&PathBuf::from("/src/one.gleam"), &src)
+    );
+    assert_eq!(
+        Ok("a/b/c/d".to_string()),
+        module_name(&PathBuf::from("/src/a/b/c/d.gleam"), &src)
+    );
+    assert_eq!(
+        Ok("a/b/c".to_string()),
+        module_name(&PathBuf::from("/src/a\\b\\c.gleam"), &src)
+    );
+    assert_eq!(
+        Err(Error::Internal {
+            message: "Source path is not within its source directory".to_string()
+        }),
+        module_name(&PathBuf::from("/test/one.gleam"), &src)
+    );
+}
+
+/// Parse a module from source code that may be malformed in any way, such as input from a fuzzer,
+/// returning an error rather than panicking.
+///
+pub fn try_parse(src: &str) -> Result<crate::ast::UntypedModule, Error> {
+    parse_source(Path::new("main.gleam"), src)
+}
+
+#[test]
+fn try_parse_test() {
+    assert!(try_parse("pub fn go() { 1.5 }").is_ok());
+
+    let malformed = [
+        "fn",
+        "fn go( { }",
+        "fn go() { 1..2 }",
+        "fn go() { -1... }",
+        "fn go(x) { case x { 1..0 -> 1 } }",
+        "const x = 1..2",
+        "fn go() { \"unterminated }",
+        "fn go() { \"\\",
+        "fn go() { go(_, _) }",
+        "fn go() { #(1, 2 }",
+        "import a/",
+        "import a.{",
+        "pub opaque",
+        "enum X {}",
+        "fn go() { let }",
+        "fn go() { } // {",
+        "\u{0}\u{feff}é€",
+        "}}}",
+        "pub fn go() { 1 } /* /* */",
+        "/",
+    ];
+    for src in malformed.iter() {
+        assert!(try_parse(src).is_err(), "{:?} should not parse", src);
     }
+}
 
-    let cases = vec![
-        Case {
-            input: vec![],
-            expected: Ok(vec![]),
+fn parse_source(path: &Path, src: &str) -> Result<crate::ast::UntypedModule, Error> {
+    let parse_error = |error| Error::Parse {
+        path: path.to_path_buf(),
+        src: src.to_string(),
+        error,
+    };
+    let stripped = crate::parser::strip_extra(src)
+        .map_err(|error| parse_error(lalrpop_util::ParseError::User { error }))?;
+    crate::grammar::ModuleParser::new()
+        .parse(&stripped)
+        .map_err(|e| parse_error(e.map_token(|crate::grammar::Token(a, b)| (a, b.to_string()))))
+}
+
+fn gen_dir(source_base_path: &Path, origin: &ModuleOrigin) -> Result<PathBuf, Error> {
+    let root = internal(
+        source_base_path.parent(),
+        "Source directory has no parent directory",
+    )?;
+    Ok(root.join("gen").join(origin.dir_name()))
+}
+
+fn module_path(gen_dir: &Path, erl_module_name: &str, target: CompileTarget) -> PathBuf {
+    gen_dir.join(format!("{}.{}", erl_module_name, target.extension()))
+}
+
+fn record_path(gen_dir: &Path, erl_module_name: &str, record: &str) -> PathBuf {
+    gen_dir.join(format!("{}_{}.erl", erl_module_name, record))
+}
+
+fn header_path(gen_dir: &Path, erl_module_name: &str) -> PathBuf {
+    gen_dir.join(format!("{}.hrl", erl_module_name))
+}
+
+fn interface_path(gen_dir: &Path, erl_module_name: &str) -> PathBuf {
+    gen_dir.join(format!("{}.gleam_module", erl_module_name))
+}
+
+fn app_path(gen_dir: &Path, app: &OtpApp) -> PathBuf {
+    gen_dir.join(format!("{}.app.src", app.name))
+}
+
+fn docs_path(project_gen_dir: &Path, erl_module_name: &str) -> PathBuf {
+    project_gen_dir
+        .join("docs")
+        .join(format!("{}.html", erl_module_name))
+}
+
+fn test_runner_path(gen_dir: &Path) -> PathBuf {
+    gen_dir.join("gleam_test_runner.erl")
+}
+
+fn manifest_path(gen_dir: &Path) -> PathBuf {
+    gen_dir.join("manifest.json")
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct ManifestEntry {
+    module: String,
+    files: Vec<PathBuf>,
+}
+
+/// Compile a single in-memory module named `main`, returning the generated Erlang.
+///
+pub fn compile_source(src: &str) -> Result<String, Error> {
+    compile_named_source(src, "main")
+}
+
+/// As `compile_source`, but the module is named `name` and errors are reported against the file
+/// `name.gleam`.
+///
+pub fn compile_named_source(src: &str, name: &str) -> Result<String, Error> {
+    use regex::Regex;
+    lazy_static! {
+        static ref RE: Regex = Regex::new("^([a-z_][a-z0-9_]*/)*[a-z_][a-z0-9_]*$")
+            .expect("compile_named_source RE regex");
+    }
+
+    let path = PathBuf::from(format!("{}.gleam", name));
+    if !RE.is_match(name) {
+        return Err(Error::InvalidModuleName { path });
+    }
+    let mut module = parse_source(&path, src)?;
+    module.name = name.split('/').map(|s| s.to_string()).collect();
+    let module =
+        crate::typ::infer_module(module, &HashMap::new(), &mut vec![]).map_err(|error| {
+            Error::Type {
+                path,
+                src: src.to_string(),
+                error,
+            }
+        })?;
+    Ok(crate::erl::module(module))
+}
+
+/// Compile a single expression into an Erlang escript that evaluates it and prints the result. The
+/// expression is wrapped in `eval:main/0`, so type errors refer to the wrapped source.
+///
+pub fn eval_expr(src: &str) -> Result<String, Error> {
+    let path = PathBuf::from("eval.gleam");
+    let src = format!("pub fn main() {{\n{}\n}}\n", src);
+    let mut module = parse_source(&path, &src)?;
+    module.name = vec!["eval".to_string()];
+    let module =
+        crate::typ::infer_module(module, &HashMap::new(), &mut vec![]).map_err(|error| {
+            Error::Type {
+                path,
+                src: src.clone(),
+                error,
+            }
+        })?;
+    let result_format = match module
+        .type_info
+        .value_constructors
+        .get("main")
+        .map(|main| &main.typ)
+    {
+        Some(crate::typ::Type::Fn { retrn, .. }) if is_string(retrn) => "~ts~n",
+        _ => "~p~n",
+    };
+    Ok(crate::erl::escript(module, result_format))
+}
+
+fn is_string(typ: &crate::typ::Type) -> bool {
+    match typ {
+        crate::typ::Type::App { module, name, .. } => module.is_empty() && name == "String",
+        crate::typ::Type::Var { typ } => match &*typ.borrow() {
+            crate::typ::TypeVar::Link { typ } => is_string(typ),
+            _ => false,
         },
-        Case {
-            input: vec![
-                Input {
-                    origin: ModuleOrigin::Src,
-                    source_base_path: PathBuf::from("/src"),
-                    path: PathBuf::from("/src/one.gleam"),
-                    src: "".to_string(),
-                },
-                Input {
-                    origin: ModuleOrigin::Src,
-                    source_base_path: PathBuf::from("/src"),
-                    path: PathBuf::from("/src/two.gleam"),
-                    src: "".to_string(),
-                },
-            ],
-            expected: Ok(vec![
-                Output {
-                    origin: ModuleOrigin::Src,
-                    name: vec!["two".to_string()],
-                    files: vec![OutputFile {
-                        path: PathBuf::from("/gen/src/two.erl"),
-                        text: "-module(two).\n-compile(no_auto_import).\n\n\n".to_string(),
-                    }],
-                },
-                Output {
-                    origin: ModuleOrigin::Src,
+        _ => false,
+    }
+}
+
+#[test]
+fn eval_expr_test() {
+    let escript = eval_expr("1 + 2").expect("should compile");
+    assert!(escript.starts_with("#!/usr/bin/env escript\n-module(eval)."));
+    assert!(escript.contains("-export([main/1]).\n"));
+    assert!(escript.contains("main() ->\n    1 + 2."));
+    assert!(escript.contains("main(_) ->\n    io:format(\"~p~n\", [main()])."));
+
+    let escript = eval_expr("\"hello\"").expect("should compile");
+    assert!(escript.contains("io:format(\"~ts~n\", [main()])."));
+
+    match eval_expr("1 + 1.0") {
+        Err(Error::Type { path, .. }) => assert_eq!(PathBuf::from("eval.gleam"), path),
+        other => panic!("expected a type error, got {:?}", other),
+    }
+}
+
+struct Analysed {
+    src: String,
+    path: PathBuf,
+    origin: ModuleOrigin,
+    source_base_path: PathBuf,
+    module: crate::ast::TypedModule,
+    parse_time: Duration,
+    type_check_time: Duration,
+}
+
+/// A module parsed from its source file.
+struct Parsed {
+    input: Input,
+    module: crate::ast::UntypedModule,
+    parse_time: Duration,
+}
+
+/// The parsed modules of a project and the graph of their imports, in which each edge points from
+/// a module to a module that imports it. Imports of modules that are not in the graph are left for
+/// the caller to check.
+///
+struct DependencyGraph {
+    graph: Graph<String, ()>,
+    indexes: HashMap<String, NodeIndex>,
+    modules: HashMap<NodeIndex, Parsed>,
+}
+
+impl DependencyGraph {
+    fn new(srcs: Vec<Input>) -> Result<Self, Error> {
+        let mut graph = Graph::new();
+        let mut indexes = HashMap::new();
+        let mut modules: HashMap<_, Parsed> = HashMap::new();
+
+        for input in srcs {
+            let name = module_name(&input.path, &input.source_base_path)?;
+
+            let start = Instant::now();
+            let mut module = parse_source(&input.path, &input.src)?;
+            let parse_time = start.elapsed();
+
+            if let Some(first) = indexes.get(&name).and_then(|i| modules.get(i)) {
+                return Err(Error::DuplicateModule {
+                    module: name,
+                    first: first.input.path.clone(),
+                    second: input.path,
+                });
+            }
+
+            module.name = name.split('/').map(|s| s.to_string()).collect();
+
+            let index = graph.add_node(name.clone());
+            indexes.insert(name, index);
+            modules.insert(
+                index,
+                Parsed {
+                    input,
+                    module,
+                    parse_time,
+                },
+            );
+        }
+
+        for (index, parsed) in modules.iter() {
+            let module_name = parsed.module.name_string();
+            let Input { path, src, .. } = &parsed.input;
+
+            for (dep, meta) in parsed.module.dependencies() {
+                if dep == module_name {
+                    return Err(Error::SelfImport {
+                        module: module_name,
+                        meta,
+                        path: path.clone(),
+                        src: src.clone(),
+                    });
+                }
+
+                let dep_index = match indexes.get(&dep) {
+                    Some(dep_index) => dep_index,
+                    None => continue,
+                };
+                let dep_module = internal(
+                    modules.get(dep_index),
+                    "Unable to find module for dep index",
+                )?;
+
+                if parsed.input.origin == ModuleOrigin::Src
+                    && dep_module.input.origin == ModuleOrigin::Test
+                {
+                    return Err(Error::SrcImportingTest {
+                        path: path.clone(),
+                        src: src.clone(),
+                        meta,
+                        src_module: module_name,
+                        test_module: dep,
+                        test_path: dep_module.input.path.clone(),
+                    });
+                }
+
+                graph.add_edge(*dep_index, *index, ());
+            }
+        }
+
+        Ok(DependencyGraph {
+            graph,
+            indexes,
+            modules,
+        })
+    }
+
+    /// The modules in an order in which each comes after the modules it imports.
+    ///
+    fn order(&self) -> Result<Vec<NodeIndex>, Error> {
+        petgraph::algo::toposort(&self.graph, None).map_err(|cycle| Error::DependencyCycle {
+            modules: self.cycle(cycle.node_id()),
+        })
+    }
+
+    /// The names of the modules in an import cycle through the given module, starting with the
+    /// first in alphabetical order. Each module imports the next, and the last imports the first.
+    ///
+    fn cycle(&self, start: NodeIndex) -> Vec<String> {
+        let mut importers: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back(start);
+        while let Some(node) = queue.pop_front() {
+            for dep in self
+                .graph
+                .neighbors_directed(node, petgraph::Direction::Incoming)
+            {
+                if dep == start {
+                    let mut cycle = vec![self.graph[node].clone()];
+                    let mut node = node;
+                    while let Some(importer) = importers.get(&node) {
+                        cycle.push(self.graph[*importer].clone());
+                        node = *importer;
+                    }
+                    cycle.reverse();
+                    let first = (0..cycle.len()).min_by_key(|i| &cycle[*i]).unwrap_or(0);
+                    cycle.rotate_left(first);
+                    return cycle;
+                }
+                if let std::collections::hash_map::Entry::Vacant(entry) = importers.entry(dep) {
+                    entry.insert(node);
+                    queue.push_back(dep);
+                }
+            }
+        }
+        vec![self.graph[start].clone()]
+    }
+
+    /// The given modules and the modules they depend on, or `None` if no modules are given.
+    ///
+    fn required(
+        &self,
+        targets: &Option<HashSet<String>>,
+    ) -> Result<Option<HashSet<NodeIndex>>, Error> {
+        let targets = match targets {
+            None => return Ok(None),
+            Some(targets) => targets,
+        };
+        let mut required = HashSet::new();
+        for target in targets.iter() {
+            let index = self
+                .indexes
+                .get(target)
+                .ok_or_else(|| Error::UnknownTargetModule {
+                    module: target.clone(),
+                    modules: self.indexes.keys().cloned().collect(),
+                })?;
+            let mut dfs = petgraph::visit::Dfs::new(Reversed(&self.graph), *index);
+            while let Some(dep) = dfs.next(Reversed(&self.graph)) {
+                required.insert(dep);
+            }
+        }
+        Ok(Some(required))
+    }
+}
+
+/// Dependency modules with an interface in the options are not compiled again.
+///
+fn uncompiled(srcs: Vec<Input>, options: &CompileOptions) -> Result<Vec<Input>, Error> {
+    let mut uncompiled = Vec::with_capacity(srcs.len());
+    for input in srcs {
+        let name = module_name(&input.path, &input.source_base_path)?;
+        if input.origin != ModuleOrigin::Dependency || !options.interfaces.contains_key(&name) {
+            uncompiled.push(input);
+        }
+    }
+    Ok(uncompiled)
+}
+
+/// Parse and type check the given modules, returning them in dependency order along with any
+/// warnings.
+///
+fn analyse(
+    srcs: Vec<Input>,
+    options: &CompileOptions,
+    progress: &mut dyn FnMut(&str),
+) -> Result<(Vec<Analysed>, Vec<Warning>), Error> {
+    let interfaces = &options.interfaces;
+    let mut graph = DependencyGraph::new(uncompiled(srcs, options)?)?;
+
+    for parsed in graph.modules.values() {
+        for (dep, meta) in parsed.module.dependencies() {
+            if !graph.indexes.contains_key(&dep) && !interfaces.contains_key(&dep) {
+                return Err(Error::UnknownImport {
+                    module: parsed.module.name_string(),
+                    import: dep,
+                    src: parsed.input.src.clone(),
+                    path: parsed.input.path.clone(),
+                    modules: graph
+                        .modules
+                        .values()
+                        .map(|m| m.module.name_string())
+                        .chain(interfaces.keys().cloned())
+                        .collect(),
+                    meta,
+                });
+            }
+        }
+    }
+
+    // Modules that the targets do not depend on are neither type checked nor compiled
+    let required = graph.required(&options.targets)?;
+
+    let mut modules_type_infos = interfaces.clone();
+    let mut analysed = Vec::with_capacity(graph.modules.len());
+    let mut warnings = vec![];
+
+    for i in graph.order()? {
+        if let Some(required) = &required {
+            if !required.contains(&i) {
+                continue;
+            }
+        }
+
+        let Parsed {
+            input:
+                Input {
+                    src,
+                    path,
+                    origin,
+                    source_base_path,
+                },
+            mut module,
+            parse_time,
+        } = internal(graph.modules.remove(&i), "Unknown graph index")?;
+        let name_string = module.name_string();
+
+        progress(&name_string);
+
+        // Other modules import this one by its name without the namespace, so it is still
+        // registered under that name below
+        module.name = namespaced_name(module.name, &origin, &options.namespace);
+
+        let start = Instant::now();
+        let mut type_warnings = vec![];
+        let module = match crate::typ::infer_module(module, &modules_type_infos, &mut type_warnings)
+        {
+            Ok(module) => module,
+            Err(error) => return Err(Error::Type { path, src, error }),
+        };
+        let type_check_time = start.elapsed();
+        if options.warn_generic_fns && origin != ModuleOrigin::Dependency {
+            type_warnings.extend(crate::typ::generic_fn_warnings(&module));
+        }
+        if options.warn_unused_fns && origin != ModuleOrigin::Dependency {
+            type_warnings.extend(crate::typ::unused_fn_warnings(&module));
+        }
+        warnings.extend(type_warnings.into_iter().map(|warning| Warning::Type {
+            path: path.clone(),
+            src: src.clone(),
+            warning,
+        }));
+
+        modules_type_infos.insert(name_string, module.type_info.clone());
+
+        analysed.push(Analysed {
+            src,
+            path,
+            origin,
+            source_base_path,
+            module,
+            parse_time,
+            type_check_time,
+        });
+    }
+
+    if options.warnings_as_errors && !warnings.is_empty() {
+        return Err(Error::WarningsAsErrors(warnings));
+    }
+
+    Ok((analysed, warnings))
+}
+
+/// Type check the modules at the given paths again, and the modules that depend on them if their
+/// public interface changed. The type information in `prior` is used for all other modules.
+///
+pub fn recheck(
+    changed: &[PathBuf],
+    prior: &HashMap<String, ModuleTypeInfo>,
+    srcs: &[Input],
+) -> Result<HashMap<String, ModuleTypeInfo>, Error> {
+    let mut graph = DependencyGraph::new(srcs.to_vec())?;
+    let mut dirty: HashSet<_> = graph
+        .modules
+        .iter()
+        .filter(|(_, parsed)| changed.contains(&parsed.input.path))
+        .map(|(index, _)| *index)
+        .collect();
+    let mut interfaces = prior.clone();
+    let mut rechecked = HashMap::new();
+
+    for i in graph.order()? {
+        if !dirty.contains(&i) {
+            continue;
+        }
+        let Parsed { input, module, .. } =
+            internal(graph.modules.remove(&i), "Unknown graph index")?;
+        let name = module.name_string();
+        let module =
+            crate::typ::infer_module(module, &interfaces, &mut vec![]).map_err(|error| {
+                Error::Type {
+                    path: input.path.clone(),
+                    src: input.src.clone(),
+                    error,
+                }
+            })?;
+
+        if prior.get(&name) != Some(&module.type_info) {
+            dirty.extend(
+                graph
+                    .graph
+                    .neighbors_directed(i, petgraph::Direction::Outgoing),
+            );
+        }
+        interfaces.insert(name.clone(), module.type_info.clone());
+        rechecked.insert(name, module.type_info);
+    }
+
+    Ok(rechecked)
+}
+
+/// Group the modules by their depth in the dependency graph. Modules in the same level do not depend
+/// on each other, so they could be compiled in parallel.
+///
+pub fn dependency_levels(srcs: &[Input]) -> Result<Vec<Vec<String>>, Error> {
+    let graph = DependencyGraph::new(srcs.to_vec())?;
+    let mut depths = HashMap::new();
+    let mut levels: Vec<Vec<String>> = vec![];
+
+    for i in graph.order()? {
+        let depth = graph
+            .graph
+            .neighbors_directed(i, petgraph::Direction::Incoming)
+            .map(|dep| depths[&dep] + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(i, depth);
+        if levels.len() <= depth {
+            levels.resize(depth + 1, vec![]);
+        }
+        levels[depth].push(graph.graph[i].clone());
+    }
+
+    for level in levels.iter_mut() {
+        level.sort();
+    }
+    Ok(levels)
+}
+
+/// The name and location of each module imported by the given source, found by parsing it only.
+///
+pub fn module_imports(src: &str) -> Result<Vec<(String, crate::ast::Meta)>, Error> {
+    Ok(parse_source(Path::new(""), src)?.dependencies())
+}
+
+/// The functions exported by each compiled src module, with their arities.
+///
+pub fn public_api(compiled: &[Compiled]) -> HashMap<String, Vec<(String, usize)>> {
+    compiled
+        .iter()
+        .filter(|module| module.origin == ModuleOrigin::Src)
+        .map(|module| {
+            let mut functions: Vec<_> = module
+                .type_info
+                .value_constructors
+                .iter()
+                .filter_map(|(name, constructor)| match constructor.variant {
+                    crate::typ::ValueConstructorVariant::ModuleFn { arity, .. } => {
+                        Some((name.clone(), arity))
+                    }
+                    _ => None,
+                })
+                .collect();
+            functions.sort();
+            (module.name.join("/"), functions)
+        })
+        .collect()
+}
+
+/// Render the public interface of a module as JSON so that dependent modules can be type checked
+/// without recompiling it.
+///
+pub fn write_interface(gen_dir: &Path, info: &ModuleTypeInfo) -> OutputFile {
+    OutputFile {
+        path: interface_path(gen_dir, &info.name.join("@")),
+        text: serde_json::to_string(info).expect("module interface serialization"),
+    }
+}
+
+pub fn collect_source(
+    src_dir: PathBuf,
+    origin: ModuleOrigin,
+    srcs: &mut Vec<Input>,
+) -> Result<(), Error> {
+    let src_dir = match src_dir.canonicalize() {
+        Ok(d) => d,
+        Err(_) => return Ok(()),
+    };
+    let is_gleam_path = |e: &walkdir::DirEntry| e.path().extension() == Some("gleam".as_ref());
+    let is_valid_module_path = |e: &walkdir::DirEntry| {
+        use regex::Regex;
+        lazy_static! {
+            static ref RE: Regex = Regex::new("^([a-z_][a-z0-9_]*/)*[a-z_][a-z0-9_]*\\.gleam$")
+                .expect("collect_source RE regex");
+        }
+
+        RE.is_match(
+            &e.path()
+                .strip_prefix(&*src_dir)
+                .expect("collect_source strip_prefix")
+                .to_str()
+                .unwrap_or("")
+                .replace('\\', "/"),
+        )
+    };
+
+    let file_io_error = |path: &Path, error: std::io::Error| Error::FileIo {
+        path: path.to_path_buf(),
+        error: error.to_string(),
+    };
+
+    for dir_entry in walkdir::WalkDir::new(src_dir.clone()) {
+        let dir_entry = dir_entry.map_err(|e| Error::FileIo {
+            path: e.path().unwrap_or(&src_dir).to_path_buf(),
+            error: e.to_string(),
+        })?;
+        if !dir_entry.file_type().is_file() || !is_gleam_path(&dir_entry) {
+            continue;
+        }
+        if !is_valid_module_path(&dir_entry) {
+            return Err(Error::InvalidModuleName {
+                path: dir_entry.path().to_path_buf(),
+            });
+        }
+
+        let path = dir_entry
+            .path()
+            .canonicalize()
+            .map_err(|e| file_io_error(dir_entry.path(), e))?;
+        let src = std::fs::read_to_string(&path).map_err(|e| file_io_error(&path, e))?;
+
+        srcs.push(Input {
+            path,
+            source_base_path: src_dir.clone(),
+            origin: origin.clone(),
+            src,
+        })
+    }
+
+    Ok(())
+}
+
+/// Collect the source of each root in order. When `shadow` is set a module hides any module with
+/// the same name in a later root, so that a local module can override a vendored one.
+///
+pub fn collect_source_roots(
+    roots: Vec<(PathBuf, ModuleOrigin)>,
+    shadow: bool,
+    srcs: &mut Vec<Input>,
+) -> Result<(), Error> {
+    let mut seen: std::collections::HashSet<_> = srcs
+        .iter()
+        .map(|input| module_name(&input.path, &input.source_base_path))
+        .collect::<Result<_, _>>()?;
+
+    for (src_dir, origin) in roots {
+        let mut root_srcs = vec![];
+        collect_source(src_dir, origin, &mut root_srcs)?;
+        let root_names: Vec<_> = root_srcs
+            .iter()
+            .map(|input| module_name(&input.path, &input.source_base_path))
+            .collect::<Result<_, _>>()?;
+        for (input, name) in root_srcs.into_iter().zip(root_names.iter()) {
+            if !shadow || !seen.contains(name) {
+                srcs.push(input);
+            }
+        }
+        seen.extend(root_names);
+    }
+
+    Ok(())
+}
+
+/// A directory in the system temp dir that is removed when dropped, so that it is cleaned up even
+/// when a test fails.
+///
+#[cfg(test)]
+struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn collect_source_test() {
+    let dir = TempDir::new("gleam_collect_source");
+    let src_dir = dir.0.join("src");
+    std::fs::create_dir_all(&src_dir).unwrap();
+    std::fs::write(src_dir.join("one.gleam"), "pub fn one() { 1 }").unwrap();
+    std::fs::write(src_dir.join("notes.txt"), "not gleam").unwrap();
+    std::fs::create_dir_all(src_dir.join("http2")).unwrap();
+    std::fs::write(src_dir.join("http2/v2_frame.gleam"), "pub fn two() { 2 }").unwrap();
+
+    let mut srcs = vec![];
+    collect_source(src_dir.clone(), ModuleOrigin::Src, &mut srcs).expect("should collect");
+    let mut collected: Vec<_> = srcs.iter().map(|input| input.src.as_str()).collect();
+    collected.sort();
+    assert_eq!(vec!["pub fn one() { 1 }", "pub fn two() { 2 }"], collected);
+
+    // Gleam files with names that are not valid module names are an error rather than ignored
+    for name in &["myModule.gleam", "my-module.gleam", "2fast.gleam"] {
+        let invalid = src_dir.join(name);
+        std::fs::write(&invalid, "pub fn one() { 1 }").unwrap();
+        let result = collect_source(src_dir.clone(), ModuleOrigin::Src, &mut vec![]);
+        std::fs::remove_file(&invalid).unwrap();
+        match result {
+            Err(Error::InvalidModuleName { path }) => {
+                assert_eq!(Some(name.as_ref()), path.file_name())
+            }
+            result => panic!("expected an InvalidModuleName error, got {:?}", result),
+        }
+    }
+
+    // A file that cannot be read as UTF-8 text is an error rather than a panic
+    let unreadable = src_dir.join("two.gleam");
+    std::fs::write(&unreadable, [0xff, 0xfe, 0xfd]).unwrap();
+    match collect_source(src_dir.clone(), ModuleOrigin::Src, &mut vec![]) {
+        Err(Error::FileIo { path, .. }) => assert_eq!(Some("two.gleam".as_ref()), path.file_name()),
+        result => panic!("expected a FileIo error, got {:?}", result),
+    }
+}
+
+#[test]
+fn collect_source_roots_test() {
+    let dir = TempDir::new("gleam_source_roots");
+    let src_dir = dir.0.join("src");
+    let deps_dir = dir.0.join("deps");
+    std::fs::create_dir_all(&src_dir).unwrap();
+    std::fs::create_dir_all(&deps_dir).unwrap();
+    std::fs::write(src_dir.join("one.gleam"), "pub fn one() { 1.0 }").unwrap();
+    std::fs::write(deps_dir.join("one.gleam"), "pub fn one() { 1 }").unwrap();
+    std::fs::write(deps_dir.join("two.gleam"), "pub fn two() { 2 }").unwrap();
+    let roots = || {
+        vec![
+            (src_dir.clone(), ModuleOrigin::Src),
+            (deps_dir.clone(), ModuleOrigin::Dependency),
+        ]
+    };
+    let collected = |shadow| {
+        let mut srcs = vec![];
+        collect_source_roots(roots(), shadow, &mut srcs).expect("should collect");
+        let mut collected: Vec<_> = srcs
+            .into_iter()
+            .map(|input| (input.src, input.origin))
+            .collect();
+        collected.sort_by(|a, b| a.0.cmp(&b.0));
+        collected
+    };
+
+    // The local module overrides the vendored one
+    assert_eq!(
+        vec![
+            ("pub fn one() { 1.0 }".to_string(), ModuleOrigin::Src),
+            ("pub fn two() { 2 }".to_string(), ModuleOrigin::Dependency),
+        ],
+        collected(true)
+    );
+
+    // Without shadowing both are collected and compiling them is an error
+    assert_eq!(3, collected(false).len());
+    let mut srcs = vec![];
+    collect_source_roots(roots(), false, &mut srcs).expect("should collect");
+    match compile(srcs, &CompileOptions::default()) {
+        Err(Error::DuplicateModule { module, .. }) => assert_eq!("one", module),
+        result => panic!("expected a DuplicateModule error, got {:?}", result),
+    }
+}
+
+#[test]
+fn compile_test() {
+    struct Case {
+        input: Vec<Input>,
+        expected: Result<Vec<Output>, Error>,
+    }
+    #[derive(Debug, PartialEq)]
+    struct Output {
+        name: Vec<String>,
+        files: Vec<OutputFile>,
+        origin: ModuleOrigin,
+    }
+
+    let cases = vec![
+        Case {
+            input: vec![],
+            expected: Ok(vec![]),
+        },
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    source_base_path: PathBuf::from("/src"),
+                    path: PathBuf::from("/src/one.gleam"),
+                    src: "".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    source_base_path: PathBuf::from("/src"),
+                    path: PathBuf::from("/src/two.gleam"),
+                    src: "".to_string(),
+                },
+            ],
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n".to_string(),
+                    }],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
             ]),
@@ -334,7 +1425,7 @@ fn compile_test() {
                 name: vec!["one".to_string()],
                 files: vec![OutputFile {
                     path: PathBuf::from("/gen/test/one.erl"),
-                    text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                    text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                 }],
             }]),
         },
@@ -359,6 +1450,7 @@ fn compile_test() {
                 meta: crate::ast::Meta { start: 7, end: 10 },
                 src_module: "one".to_string(),
                 test_module: "two".to_string(),
+                test_path: PathBuf::from("/test/two.gleam"),
             }),
         },
         Case {
@@ -382,7 +1474,7 @@ fn compile_test() {
                     name: vec!["two".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/two.erl"),
-                        text: "-module(two).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(two).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -390,7 +1482,7 @@ fn compile_test() {
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
             ]),
@@ -416,7 +1508,7 @@ fn compile_test() {
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -424,7 +1516,7 @@ fn compile_test() {
                     name: vec!["two".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/two.erl"),
-                        text: "-module(two).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(two).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
             ]),
@@ -450,7 +1542,7 @@ fn compile_test() {
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -486,7 +1578,7 @@ unbox(X) ->\n    {box, I} = X,\n    I.\n"
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -513,7 +1605,7 @@ box(X) ->\n    {box, X}.\n"
                 name: vec!["one".to_string(), "two".to_string()],
                 files: vec![OutputFile {
                     path: PathBuf::from("/gen/src/one@two.erl"),
-                    text: "-module(one@two).\n-compile(no_auto_import).\n\n\n".to_string(),
+                    text: "-module(one@two).\n-compile(no_auto_import).\n".to_string(),
                 }],
             }]),
         },
@@ -538,7 +1630,7 @@ box(X) ->\n    {box, X}.\n"
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -614,7 +1706,7 @@ call() ->
                     name: vec!["nested".to_string(), "one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/nested@one.erl"),
-                        text: "-module(nested@one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(nested@one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -629,6 +1721,44 @@ call() ->
                 },
             ]),
         },
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/two.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "import a/b/c\npub fn go() { c.id(c.Box(1)) }".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/a/b/c.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "pub enum Box { Box(Int) } pub fn id(x) { x }".to_string(),
+                },
+            ],
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/a@b@c.erl"),
+                        text: "-module(a@b@c).\n-compile(no_auto_import).\n\n-export([id/1]).\n
+id(X) ->\n    X.\n"
+                            .to_string(),
+                    }],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n\n-export([go/0]).\n
+go() ->\n    a@b@c:id({box, 1}).\n"
+                            .to_string(),
+                    }],
+                },
+            ]),
+        },
         Case {
             input: vec![
                 Input {
@@ -651,7 +1781,7 @@ call() ->
                     name: vec!["nested".to_string(), "one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/nested@one.erl"),
-                        text: "-module(nested@one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(nested@one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -692,7 +1822,7 @@ call() ->
                         path: PathBuf::from("/gen/src/nested@one.erl"),
                         text:
                             "-module(nested@one).\n-compile(no_auto_import).\n\n-export([go/0]).\n
-go() ->\n    1.\n"
+-export_type([thing/0]).\n\n-type thing() :: any().\n\ngo() ->\n    1.\n"
                                 .to_string(),
                     }],
                 },
@@ -760,7 +1890,7 @@ thing() ->\n    thing:new().\n"
                         },
                         OutputFile {
                             path: PathBuf::from("/gen/src/one.erl"),
-                            text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                            text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                         },
                     ],
                 },
@@ -800,7 +1930,7 @@ x(P) ->\n    {point, X, _} = P,\n    X.\n"
                     name: vec!["one".to_string()],
                     files: vec![OutputFile {
                         path: PathBuf::from("/gen/src/one.erl"),
-                        text: "-module(one).\n-compile(no_auto_import).\n\n\n".to_string(),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
                     }],
                 },
                 Output {
@@ -932,18 +2062,1629 @@ funky() ->
                 },
             ]),
         },
-    ];
-
-    for Case { input, expected } in cases.into_iter() {
-        let output = compile(input).map(|mods| {
-            mods.into_iter()
-                .map(|compiled| Output {
-                    name: compiled.name,
-                    files: compiled.files,
-                    origin: compiled.origin,
-                })
-                .collect::<Vec<_>>()
-        });
-        assert_eq!(expected, output);
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/one.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "pub fn triple() { #(1, \"a\", 2.0) }".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/two.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "import one
+pub fn second() -> String { let #(_, x, _) = one.triple() x }"
+                        .to_string(),
+                },
+            ],
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["one".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/one.erl"),
+                        text: "-module(one).\n-compile(no_auto_import).\n\n-export([triple/0]).\n
+triple() ->\n    {1, <<\"a\">>, 2.0}.\n"
+                            .to_string(),
+                    }],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n\n-export([second/0]).\n
+second() ->\n    {_, X, _} = one:triple(),\n    X.\n"
+                            .to_string(),
+                    }],
+                },
+            ]),
+        },
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/one.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "pub struct Point { x: Int y: Int }".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/two.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "import one.{Point}
+pub fn move(p) { Point(..p, x: 5) }"
+                        .to_string(),
+                },
+            ],
+            // Only the updated `x` field is set, `y` is carried over from `p`
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["one".to_string()],
+                    files: vec![
+                        OutputFile {
+                            path: PathBuf::from("/gen/src/one_Point.erl"),
+                            text: "-record(point, {x, y}).\n".to_string(),
+                        },
+                        OutputFile {
+                            path: PathBuf::from("/gen/src/one.erl"),
+                            text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
+                        },
+                    ],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n\n-export([move/1]).\n
+move(P) ->\n    erlang:setelement(2, P, 5).\n"
+                            .to_string(),
+                    }],
+                },
+            ]),
+        },
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/one.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "pub enum Box { Box(Int) }".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/two.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "import one.{Box}
+pub fn unbox(x) { let Box(i) = x i }
+pub fn box() { Box(1) }"
+                        .to_string(),
+                },
+            ],
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["one".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/one.erl"),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
+                    }],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n\n-export([box/0, unbox/1]).\n
+unbox(X) ->\n    {box, I} = X,\n    I.\n
+box() ->\n    {box, 1}.\n"
+                            .to_string(),
+                    }],
+                },
+            ]),
+        },
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/one.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "pub enum Box(a) { Box(a, Int) Empty }".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/two.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "import one.{Box, Empty}
+fn apply(f, x) { f(x, 1) }
+pub fn box() { apply(Box, 1) }
+pub fn empty(x) { case x { y if y == Empty -> 1 _ -> 0 } }"
+                        .to_string(),
+                },
+            ],
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["one".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/one.erl"),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
+                    }],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n\n-export([box/0, empty/1]).\n
+apply(F, X) ->\n    F(X, 1).\n
+box() ->\n    apply(fun(A, B) -> {box, A, B} end, 1).\n
+empty(X) ->\n    case X of\n        Y when Y =:= empty ->\n            1;\n\n        _ ->\n            0\n    end.\n"
+                            .to_string(),
+                    }],
+                },
+            ]),
+        },
+        Case {
+            input: vec![
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/one.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "pub const max_retries = 3".to_string(),
+                },
+                Input {
+                    origin: ModuleOrigin::Src,
+                    path: PathBuf::from("/src/two.gleam"),
+                    source_base_path: PathBuf::from("/src"),
+                    src: "import one.{max_retries}
+pub fn retries() -> Int { one.max_retries + max_retries }"
+                        .to_string(),
+                },
+            ],
+            expected: Ok(vec![
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["one".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/one.erl"),
+                        text: "-module(one).\n-compile(no_auto_import).\n".to_string(),
+                    }],
+                },
+                Output {
+                    origin: ModuleOrigin::Src,
+                    name: vec!["two".to_string()],
+                    files: vec![OutputFile {
+                        path: PathBuf::from("/gen/src/two.erl"),
+                        text: "-module(two).\n-compile(no_auto_import).\n\n-export([retries/0]).\n
+retries() ->\n    3 + 3.\n"
+                            .to_string(),
+                    }],
+                },
+            ]),
+        },
+    ];
+
+    for Case { input, expected } in cases.into_iter() {
+        let output = compile(input, &Default::default()).map(|package| {
+            package
+                .modules
+                .into_iter()
+                .map(|compiled| Output {
+                    name: compiled.name,
+                    files: compiled.files,
+                    origin: compiled.origin,
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(expected, output);
+    }
+}
+
+/// The input for the module `name` of the src, test, or dependency directory.
+///
+#[cfg(test)]
+fn module_input(origin: ModuleOrigin, name: &str, src: &str) -> Input {
+    let source_base_path = PathBuf::from(match origin {
+        ModuleOrigin::Src => "/src",
+        ModuleOrigin::Test => "/test",
+        ModuleOrigin::Dependency => "/dep/src",
+    });
+    Input {
+        path: source_base_path.join(format!("{}.gleam", name)),
+        source_base_path,
+        origin,
+        src: src.to_string(),
+    }
+}
+
+#[test]
+fn compile_otp_app_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", ""),
+        module_input(ModuleOrigin::Src, "nested/two", "import one"),
+        module_input(ModuleOrigin::Test, "one_test", "import one"),
+    ];
+    let options = CompileOptions {
+        otp_app: Some(OtpApp {
+            name: "my_app".to_string(),
+            version: "1.0.0".to_string(),
+        }),
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    assert_eq!(
+        package.files,
+        vec![OutputFile {
+            path: PathBuf::from("/gen/src/my_app.app.src"),
+            text: crate::erl::app_resource(
+                "my_app",
+                "1.0.0",
+                &["nested@two".to_string(), "one".to_string()]
+            ),
+        }]
+    );
+}
+
+#[test]
+fn interface_round_trip_test() {
+    let src = r#"
+pub enum Box(a) { Box(a) Empty }
+pub struct Pair(a, b) { first: a second: b }
+pub external type Thing
+pub external fn make(label: Int) -> Thing = "thing" "make"
+pub fn unbox(box, default) { case box { Box(x) -> x Empty -> default } }
+pub fn pair(x) { Pair(first: x, second: [1.0]) }
+"#;
+    let input = vec![module_input(ModuleOrigin::Src, "one", src)];
+    let options = CompileOptions {
+        write_interfaces: true,
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    let module = &package.modules[0];
+    let interface = write_interface(Path::new("/gen/src"), &module.type_info);
+
+    assert_eq!(PathBuf::from("/gen/src/one.gleam_module"), interface.path);
+    assert!(module.files.contains(&interface));
+
+    let info: ModuleTypeInfo =
+        serde_json::from_str(&interface.text).expect("interface deserialization");
+    assert_eq!(module.type_info, info);
+
+    // The output is stable so that unchanged modules produce identical interface files
+    assert_eq!(interface.text, serde_json::to_string(&info).unwrap());
+}
+
+#[test]
+fn compile_with_interfaces_test() {
+    let dep = module_input(ModuleOrigin::Dependency, "dep", "pub fn id(x) { x }");
+    let interface = compile(vec![dep], &Default::default())
+        .expect("dep should compile")
+        .modules
+        .remove(0)
+        .type_info;
+    let mut interfaces = HashMap::new();
+    interfaces.insert("dep".to_string(), interface);
+    let options = CompileOptions {
+        interfaces,
+        ..Default::default()
+    };
+
+    // The dependency source is not needed when its interface is supplied
+    let input = vec![module_input(
+        ModuleOrigin::Src,
+        "one",
+        "import dep pub fn go() { dep.id(1) }",
+    )];
+    let package = compile(input, &options).expect("should compile");
+    assert_eq!(
+        vec![vec!["one".to_string()]],
+        package
+            .modules
+            .into_iter()
+            .map(|m| m.name)
+            .collect::<Vec<_>>()
+    );
+
+    // Dependency sources with a supplied interface are skipped entirely
+    let input = vec![
+        module_input(ModuleOrigin::Dependency, "dep", "this would not parse"),
+        module_input(
+            ModuleOrigin::Src,
+            "one",
+            "import dep pub fn go() { dep.id(1) }",
+        ),
+    ];
+    let package = compile(input, &options).expect("should compile");
+    assert_eq!(1, package.modules.len());
+
+    // Imports that are neither supplied nor compiled are still reported
+    let input = vec![module_input(ModuleOrigin::Src, "one", "import other")];
+    assert_eq!(
+        Err(Error::UnknownImport {
+            module: "one".to_string(),
+            import: "other".to_string(),
+            src: "import other".to_string(),
+            path: PathBuf::from("/src/one.gleam"),
+            modules: vec!["one".to_string(), "dep".to_string()],
+            meta: crate::ast::Meta { start: 7, end: 12 },
+        }),
+        compile(input, &options).map(|_| ())
+    );
+}
+
+#[test]
+fn compile_self_import_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "other", "pub fn go() { 1 }"),
+        module_input(
+            ModuleOrigin::Src,
+            "nested/one",
+            "import other import nested/one pub fn go() { other.go() }",
+        ),
+    ];
+    assert_eq!(
+        Err(Error::SelfImport {
+            module: "nested/one".to_string(),
+            meta: crate::ast::Meta { start: 20, end: 30 },
+            path: PathBuf::from("/src/nested/one.gleam"),
+            src: "import other import nested/one pub fn go() { other.go() }".to_string(),
+        }),
+        compile(input, &CompileOptions::default()).map(|_| ())
+    );
+}
+
+#[test]
+fn compile_import_cycle_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "two", "import three pub fn go() { 1 }"),
+        module_input(ModuleOrigin::Src, "one", "import two pub fn go() { 1 }"),
+        module_input(ModuleOrigin::Src, "three", "import one pub fn go() { 1 }"),
+    ];
+    let error = compile(input, &CompileOptions::default()).expect_err("should not compile");
+    assert_eq!(
+        Error::DependencyCycle {
+            modules: vec!["one".to_string(), "two".to_string(), "three".to_string()]
+        },
+        error
+    );
+    assert!(error
+        .to_string()
+        .contains("`one` imports `two` imports `three` imports `one`"));
+}
+
+#[test]
+fn check_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn id(x) { x }"),
+        module_input(
+            ModuleOrigin::Test,
+            "one_test",
+            "import one pub fn go() { one.id(1) }",
+        ),
+    ];
+    let (infos, warnings) = check(input).expect("should check");
+    assert_eq!(
+        vec![vec!["one".to_string()], vec!["one_test".to_string()]],
+        infos.into_iter().map(|info| info.name).collect::<Vec<_>>()
+    );
+    assert!(warnings.is_empty());
+
+    let input = vec![module_input(
+        ModuleOrigin::Src,
+        "one",
+        "pub fn go() { 1 + 1.0 }",
+    )];
+    match check(input) {
+        Err(Error::Type { path, .. }) => assert_eq!(PathBuf::from("/src/one.gleam"), path),
+        other => panic!("expected a type error, got {:?}", other),
+    }
+}
+
+#[test]
+fn compile_test_modules_only_test() {
+    let dep = module_input(ModuleOrigin::Dependency, "dep", "pub fn id(x) { x }");
+    let mut interfaces = HashMap::new();
+    for info in check(vec![dep]).expect("dep should check").0 {
+        interfaces.insert(info.name.join("/"), info);
+    }
+    let options = CompileOptions {
+        interfaces,
+        otp_app: Some(OtpApp {
+            name: "my_app".to_string(),
+            version: "1.0.0".to_string(),
+        }),
+        ..Default::default()
+    };
+
+    let input = vec![module_input(
+        ModuleOrigin::Test,
+        "one_test",
+        "import dep pub fn go() { dep.id(1) }",
+    )];
+    let package = compile(input, &options).expect("should compile");
+
+    // There are no src modules so there is no application to describe
+    assert_eq!(Vec::<OutputFile>::new(), package.files);
+    assert_eq!(
+        vec![OutputFile {
+            path: PathBuf::from("/gen/test/one_test.erl"),
+            text: "-module(one_test).\n-compile(no_auto_import).\n\n-export([go/0]).\n
+go() ->\n    dep:id(1).\n"
+                .to_string(),
+        }],
+        package
+            .modules
+            .into_iter()
+            .flat_map(|m| m.files)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn recheck_test() {
+    let input = |one: &str| {
+        vec![
+            module_input(ModuleOrigin::Src, "one", one),
+            module_input(
+                ModuleOrigin::Src,
+                "two",
+                "import one pub fn two() { one.one() }",
+            ),
+            module_input(ModuleOrigin::Src, "three", "pub fn three() { 3 }"),
+        ]
+    };
+    let prior: HashMap<_, _> = check(input("pub fn one() { 1 }"))
+        .expect("should check")
+        .0
+        .into_iter()
+        .map(|info| (info.name.join("/"), info))
+        .collect();
+    let changed = vec![PathBuf::from("/src/one.gleam")];
+    let rechecked_names = |src| {
+        let mut names: Vec<_> = recheck(&changed, &prior, &input(src))
+            .expect("should recheck")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        names
+    };
+
+    // When the interface of the changed module is the same its dependents are not checked
+    assert_eq!(vec!["one"], rechecked_names("pub fn one() { 1 + 1 }"));
+
+    // When the interface has changed the dependents are checked too
+    assert_eq!(vec!["one", "two"], rechecked_names("pub fn one() { 1.0 }"));
+
+    // Errors in dependents are reported
+    match recheck(&changed, &prior, &input("pub fn won() { 1 }")) {
+        Err(Error::Type { path, .. }) => assert_eq!(PathBuf::from("/src/two.gleam"), path),
+        result => panic!("expected a type error in two, got {:?}", result),
+    }
+}
+
+#[test]
+fn dependency_levels_test() {
+    let input = |modules: &[(&str, &str)]| {
+        modules
+            .iter()
+            .map(|(name, src)| module_input(ModuleOrigin::Src, name, src))
+            .collect::<Vec<_>>()
+    };
+
+    let srcs = input(&[
+        (
+            "app",
+            "import one import two/three import four pub fn go() { 1 }",
+        ),
+        ("one", "import four pub fn go() { 1 }"),
+        ("two/three", "import one pub fn go() { 1 }"),
+        ("four", "pub fn go() { 1 }"),
+        ("five", "import other pub fn go() { 1 }"),
+    ]);
+    assert_eq!(
+        Ok(vec![
+            vec!["five".to_string(), "four".to_string()],
+            vec!["one".to_string()],
+            vec!["two/three".to_string()],
+            vec!["app".to_string()],
+        ]),
+        dependency_levels(&srcs)
+    );
+
+    assert_eq!(Ok(vec![]), dependency_levels(&[]));
+
+    let srcs = input(&[
+        ("one", "import two pub fn go() { 1 }"),
+        ("two", "import one pub fn go() { 1 }"),
+    ]);
+    assert_eq!(
+        Err(Error::DependencyCycle {
+            modules: vec!["one".to_string(), "two".to_string()]
+        }),
+        dependency_levels(&srcs)
+    );
+}
+
+#[test]
+fn module_imports_test() {
+    assert_eq!(
+        Ok(vec![
+            ("one".to_string(), crate::ast::Meta { start: 7, end: 10 }),
+            (
+                "two/three".to_string(),
+                crate::ast::Meta { start: 18, end: 28 }
+            ),
+        ]),
+        module_imports("import one import two/three as three pub fn go() { 1 }")
+    );
+    assert!(module_imports("import").is_err());
+}
+
+#[test]
+fn public_api_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "one/two",
+            "pub struct Point { x: Int y: Int }
+                  pub const origin = 0
+                  fn private() { 1 }
+                  pub fn go(x, y) { Point(x, y) }
+                  pub external fn size(String) -> Int = \"erlang\" \"byte_size\"",
+        ),
+        module_input(ModuleOrigin::Test, "one_test", "pub fn one_test() { 1 }"),
+    ];
+    let package = compile(input, &Default::default()).expect("should compile");
+    let mut expected = HashMap::new();
+    expected.insert(
+        "one/two".to_string(),
+        vec![("go".to_string(), 2), ("size".to_string(), 1)],
+    );
+    assert_eq!(expected, public_api(&package.modules));
+}
+
+#[test]
+fn compile_test_runner_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn src_test() { 1 }"),
+        module_input(
+            ModuleOrigin::Test,
+            "one_test",
+            "pub fn one_test() { 1 }
+                  fn private_test() { 1 }
+                  pub fn arity_test(x) { x }
+                  pub fn helper() { 1 }",
+        ),
+        module_input(
+            ModuleOrigin::Test,
+            "nested/two_test",
+            "pub fn two_test() { 2 }",
+        ),
+    ];
+    let options = CompileOptions {
+        write_test_runner: true,
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    assert_eq!(
+        vec![OutputFile {
+            path: PathBuf::from("/gen/test/gleam_test_runner.erl"),
+            text: r#"-module(gleam_test_runner).
+-compile(no_auto_import).
+
+-export([main/0]).
+
+-dialyzer({nowarn_function, [main/0, passes/2]}).
+
+main() ->
+    Tests = [{nested@two_test, two_test}, {one_test, one_test}],
+    [Test || {Module, Function} = Test <- Tests, not passes(Module, Function)].
+
+passes(Module, Function) ->
+    try Module:Function() of
+        _ ->
+            true
+    catch
+        _:_ ->
+            false
+    end.
+"#
+            .to_string(),
+        }],
+        package.files
+    );
+}
+
+#[test]
+fn compile_core_erlang_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn id(x) { x }"),
+        module_input(
+            ModuleOrigin::Src,
+            "two/three",
+            "import one pub fn go() { one.id(1) }",
+        ),
+    ];
+    let options = CompileOptions {
+        target: CompileTarget::CoreErlang,
+        ..Default::default()
+    };
+    let package = compile(input.clone(), &options).expect("should compile");
+    let files: Vec<_> = package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .collect();
+    assert_eq!(
+        vec![
+            &OutputFile {
+                path: PathBuf::from("/gen/src/one.core"),
+                text: "module 'one' ['id'/1]
+    attributes []
+
+'id'/1 =
+    fun (_x) ->
+        _x
+
+end
+"
+                .to_string(),
+            },
+            &OutputFile {
+                path: PathBuf::from("/gen/src/two@three.core"),
+                text: "module 'two@three' ['go'/0]
+    attributes []
+
+'go'/0 =
+    fun () ->
+        call 'one':'id'(1)
+
+end
+"
+                .to_string(),
+            },
+        ],
+        files
+    );
+    assert_eq!(
+        vec![
+            PathBuf::from("/gen/src/one.core"),
+            PathBuf::from("/gen/src/two@three.core"),
+        ],
+        generated_paths(&input, &options).expect("should list paths")
+    );
+
+    // Inline Erlang cannot be included in Core Erlang output
+    let src = r#"@external_erlang("go() -> 1.") external fn go() -> Int"#;
+    assert_eq!(
+        Err(Error::UnsupportedInlineErlang {
+            path: PathBuf::from("/src/one.gleam"),
+            src: src.to_string(),
+            meta: crate::ast::Meta { start: 0, end: 54 },
+        }),
+        compile(vec![module_input(ModuleOrigin::Src, "one", src)], &options).map(|_| ())
+    );
+}
+
+#[test]
+fn compile_namespace_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "http/client", "pub fn get(url) { url }"),
+        module_input(
+            ModuleOrigin::Src,
+            "app",
+            "import http/client.{get} pub fn go() { client.get(1) + get(2) }",
+        ),
+    ];
+    let options = CompileOptions {
+        namespace: Some("mypkg".to_string()),
+        ..Default::default()
+    };
+    let package = compile(input.clone(), &options).expect("should compile");
+    let files: Vec<_> = package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .collect();
+    assert_eq!(
+        vec![
+            &OutputFile {
+                path: PathBuf::from("/gen/src/mypkg@http@client.erl"),
+                text: "-module(mypkg@http@client).
+-compile(no_auto_import).
+
+-export([get/1]).
+
+get(Url) ->
+    Url.
+"
+                .to_string(),
+            },
+            &OutputFile {
+                path: PathBuf::from("/gen/src/mypkg@app.erl"),
+                text: "-module(mypkg@app).
+-compile(no_auto_import).
+
+-export([go/0]).
+
+go() ->
+    mypkg@http@client:get(1) + mypkg@http@client:get(2).
+"
+                .to_string(),
+            },
+        ],
+        files
+    );
+    assert_eq!(
+        vec![
+            PathBuf::from("/gen/src/mypkg@http@client.erl"),
+            PathBuf::from("/gen/src/mypkg@app.erl"),
+        ],
+        generated_paths(&input, &options).expect("should list paths")
+    );
+}
+
+#[test]
+fn compile_to_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "one",
+            "pub struct Point { x: Int y: Int }",
+        ),
+        module_input(
+            ModuleOrigin::Src,
+            "two",
+            "import one pub fn go() { one.Point(1, 2) }",
+        ),
+    ];
+    let options = CompileOptions {
+        otp_app: Some(OtpApp {
+            name: "my_app".to_string(),
+            version: "1.0.0".to_string(),
+        }),
+        write_manifest: true,
+        ..Default::default()
+    };
+
+    let mut streamed = vec![];
+    let package = compile_to(input.clone(), &options, &mut |_| (), &mut |file| {
+        streamed.push(OutputFile {
+            path: file.path.clone(),
+            text: file.text.clone(),
+        });
+        Ok(())
+    })
+    .expect("should compile");
+    assert!(package.files.is_empty());
+    assert!(package.modules.iter().all(|m| m.files.is_empty()));
+
+    let package = compile(input.clone(), &options).expect("should compile");
+    let batch: Vec<_> = package
+        .modules
+        .into_iter()
+        .flat_map(|m| m.files)
+        .chain(package.files)
+        .collect();
+    assert_eq!(batch, streamed);
+
+    // An error from the writer stops compilation
+    let result = compile_to(input.clone(), &options, &mut |_| (), &mut |_| {
+        Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+    });
+    assert_eq!(
+        Err(Error::FileIo {
+            path: PathBuf::from("/gen/src/one_Point.erl"),
+            error: "disk full".to_string(),
+        }),
+        result
+    );
+}
+
+#[test]
+fn compile_progress_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "two",
+            "import one pub fn two() { one.one() + 1 }",
+        ),
+        module_input(ModuleOrigin::Src, "one", "pub fn one() { 1 }"),
+        module_input(
+            ModuleOrigin::Test,
+            "one_test",
+            "import one pub fn one_test() { one.one() }",
+        ),
+    ];
+
+    // Each module is reported once, in the order it is compiled
+    let mut events = vec![];
+    compile_with_progress(input, &CompileOptions::default(), &mut |name| {
+        events.push(name.to_string())
+    })
+    .expect("should compile");
+    assert_eq!(3, events.len());
+    assert_eq!(Some("one"), events.first().map(|name| name.as_str()));
+    let mut sorted = events.clone();
+    sorted.sort();
+    assert_eq!(vec!["one", "one_test", "two"], sorted);
+}
+
+#[test]
+fn compile_source_test() {
+    let erl = compile_source("pub fn go() { 1 }").expect("should compile");
+    assert!(erl.starts_with("-module(main)."));
+    assert!(erl.contains("go() ->"));
+
+    match compile_source("pub fn go() { 1 + 1.0 }") {
+        Err(Error::Type { path, .. }) => assert_eq!(PathBuf::from("main.gleam"), path),
+        other => panic!("expected a type error, got {:?}", other),
+    }
+}
+
+#[test]
+fn compile_named_source_test() {
+    let erl = compile_named_source("pub fn go() { 1 }", "one/two").expect("should compile");
+    assert!(erl.starts_with("-module(one@two)."));
+    assert!(erl.contains("go() ->"));
+
+    match compile_named_source("pub fn go() { 1 + 1.0 }", "one/two") {
+        Err(Error::Type { path, .. }) => assert_eq!(PathBuf::from("one/two.gleam"), path),
+        other => panic!("expected a type error, got {:?}", other),
+    }
+
+    for name in &["", "One", "one/", "../one", "one two"] {
+        match compile_named_source("pub fn go() { 1 }", name) {
+            Err(Error::InvalidModuleName { .. }) => (),
+            other => panic!(
+                "expected an invalid name error for {:?}, got {:?}",
+                name, other
+            ),
+        }
+    }
+}
+
+#[test]
+fn compile_opaque_type_test() {
+    let one = "pub opaque enum Box { Box(Int) }
+               pub opaque struct Point { x: Int y: Int }
+               pub struct Pair { a: Int b: Int }
+               pub fn new(i) { Box(i) }
+               pub fn unbox(box) { let Box(i) = box i }";
+    let input = |src: &str| {
+        vec![
+            module_input(ModuleOrigin::Src, "one", one),
+            module_input(ModuleOrigin::Src, "two", src),
+        ]
+    };
+
+    // The type can be used through the functions of the defining module
+    let options = CompileOptions {
+        write_record_headers: true,
+        ..Default::default()
+    };
+    let package = compile(
+        input("import one pub fn go() -> one.Box { one.new(1) }"),
+        &options,
+    )
+    .expect("should compile");
+    // Opaque structs do not get an Erlang record, only the public non-opaque struct does
+    let mut paths: Vec<_> = package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .map(|f| f.path.clone())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        vec![
+            PathBuf::from("/gen/src/one.erl"),
+            PathBuf::from("/gen/src/one.hrl"),
+            PathBuf::from("/gen/src/one_Pair.erl"),
+            PathBuf::from("/gen/src/two.erl"),
+        ],
+        paths
+    );
+    let header = package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .find(|f| f.path.ends_with("one.hrl"))
+        .expect("header");
+    assert_eq!("-record(pair, {a, b}).\n", header.text);
+
+    // The constructors cannot be used from other modules
+    for src in &[
+        "import one pub fn go() { one.Box(1) }",
+        "import one pub fn go(b) { let one.Box(i) = b i }",
+        "import one pub fn go() { one.Point(x: 1, y: 2) }",
+        "import one.{Box} pub fn go() { Box(1) }",
+    ] {
+        match compile(input(src), &Default::default()) {
+            Err(Error::Type { path, .. }) => assert_eq!(PathBuf::from("/src/two.gleam"), path),
+            other => panic!("expected a type error for {}, got {:?}", src, other),
+        }
+    }
+}
+
+#[test]
+fn compile_reexport_test() {
+    let package = compile(
+        vec![
+            module_input(
+                ModuleOrigin::Src,
+                "a",
+                "pub enum Thing { Thing(Int) } pub fn go(x) { Thing(x) }",
+            ),
+            module_input(ModuleOrigin::Src, "b", "pub import a.{Thing, go}"),
+            module_input(
+                ModuleOrigin::Src,
+                "c",
+                "import b pub fn run() -> b.Thing { b.go(1) }",
+            ),
+        ],
+        &Default::default(),
+    )
+    .expect("should compile");
+    let text = |name: &str| {
+        package
+            .modules
+            .iter()
+            .flat_map(|m| m.files.iter())
+            .find(|f| f.path.ends_with(format!("{}.erl", name)))
+            .map(|f| f.text.clone())
+            .expect("output file")
+    };
+    assert_eq!(
+        "-module(b).\n-compile(no_auto_import).\n\n-export([go/1]).\n\n-dialyzer({nowarn_function, [go/1]}).\ngo(A) ->\n    a:go(A).\n",
+        text("b")
+    );
+    assert_eq!(
+        "-module(c).\n-compile(no_auto_import).\n\n-export([run/0]).\n\nrun() ->\n    b:go(1).\n",
+        text("c")
+    );
+}
+
+#[test]
+fn compile_reexport_dialyzer_test() {
+    // Only the generated forwarder of a re-exported function is excluded from Dialyzer warnings,
+    // not the functions of the module itself
+    let package = compile(
+        vec![
+            module_input(ModuleOrigin::Src, "a", "pub fn go(x) { x }"),
+            module_input(
+                ModuleOrigin::Src,
+                "b",
+                "pub import a.{go} pub fn id(x) { x }",
+            ),
+        ],
+        &Default::default(),
+    )
+    .expect("should compile");
+    let b = package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .find(|f| f.path.ends_with("b.erl"))
+        .expect("output file");
+    assert_eq!(
+        "-module(b).
+-compile(no_auto_import).
+
+-export([go/1, id/1]).
+
+-dialyzer({nowarn_function, [go/1]}).
+go(A) ->
+    a:go(A).
+
+id(X) ->
+    X.
+",
+        b.text
+    );
+}
+
+#[test]
+fn compile_manifest_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "one",
+            "pub struct Point { x: Int y: Int }",
+        ),
+        module_input(ModuleOrigin::Test, "one_test", "import one"),
+    ];
+    let options = CompileOptions {
+        write_manifest: true,
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    assert_eq!(
+        vec![OutputFile {
+            path: PathBuf::from("/gen/manifest.json"),
+            text: r#"{
+  "/src/one.gleam": {
+    "module": "one",
+    "files": [
+      "/gen/src/one_Point.erl",
+      "/gen/src/one.erl"
+    ]
+  },
+  "/test/one_test.gleam": {
+    "module": "one_test",
+    "files": [
+      "/gen/test/one_test.erl"
+    ]
+  }
+}"#
+            .to_string(),
+        }],
+        package.files
+    );
+}
+
+#[test]
+fn compile_docs_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "one",
+            "pub struct Point { x: Int y: Int }",
+        ),
+        module_input(
+            ModuleOrigin::Src,
+            "two/three",
+            "import one pub fn origin() { one.Point(x: 0, y: 0) }",
+        ),
+        module_input(ModuleOrigin::Test, "one_test", "import one"),
+    ];
+    let options = CompileOptions {
+        write_docs: true,
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    let paths: Vec<_> = package.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        vec![
+            PathBuf::from("/gen/docs/one.html"),
+            PathBuf::from("/gen/docs/two@three.html"),
+        ],
+        paths
+    );
+    let one = &package.files[0].text;
+    assert!(one.contains("<h1>one</h1>"));
+    assert!(one.contains(r#"<section id="type-Point">"#));
+    let three = &package.files[1].text;
+    assert!(three.contains("<h1>two/three</h1>"));
+    assert!(three.contains(r#"<a href="one.html">one</a>"#));
+    assert!(three.contains(r#"<section id="origin">"#));
+    assert!(three.contains("<pre>fn() -&gt; Point</pre>"));
+}
+
+#[test]
+fn compile_manifest_docs_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn go() { 1 }"),
+        module_input(ModuleOrigin::Test, "one_test", "import one"),
+    ];
+    let options = CompileOptions {
+        write_docs: true,
+        write_manifest: true,
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    let paths: Vec<_> = package.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        vec![
+            PathBuf::from("/gen/docs/one.html"),
+            PathBuf::from("/gen/manifest.json"),
+        ],
+        paths
+    );
+    assert_eq!(
+        r#"{
+  "/src/one.gleam": {
+    "module": "one",
+    "files": [
+      "/gen/src/one.erl",
+      "/gen/docs/one.html"
+    ]
+  },
+  "/test/one_test.gleam": {
+    "module": "one_test",
+    "files": [
+      "/gen/test/one_test.erl"
+    ]
+  }
+}"#,
+        package.files[1].text
+    );
+}
+
+#[test]
+fn compile_warnings_as_errors_test() {
+    let src = "fn go(x) { case x { True -> 1 } }";
+    let input = vec![module_input(ModuleOrigin::Src, "one", src)];
+    compile(input.clone(), &Default::default()).expect("should compile with warnings");
+
+    let options = CompileOptions {
+        warnings_as_errors: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        Err(Error::WarningsAsErrors(vec![Warning::Type {
+            path: PathBuf::from("/src/one.gleam"),
+            src: src.to_string(),
+            warning: crate::typ::Warning::NonExhaustiveCase {
+                meta: crate::ast::Meta { start: 11, end: 31 },
+                missing: vec!["False".to_string()],
+            },
+        }])),
+        compile(input.clone(), &options).map(|_| ())
+    );
+}
+
+#[test]
+fn compile_warn_generic_fns_test() {
+    let src = "pub fn id(x) { x } pub fn inc(x) { x + 1 }";
+    let input = vec![module_input(ModuleOrigin::Src, "one", src)];
+    let package = compile(input.clone(), &Default::default()).expect("should compile");
+    assert_eq!(Vec::<Warning>::new(), package.warnings);
+
+    let options = CompileOptions {
+        warn_generic_fns: true,
+        ..Default::default()
+    };
+    let package = compile(input.clone(), &options).expect("should compile");
+    let warnings: Vec<_> = package
+        .warnings
+        .iter()
+        .map(|warning| match warning {
+            Warning::Type {
+                warning: crate::typ::Warning::GenericFn { meta, name, .. },
+                ..
+            } => (meta.clone(), name.as_str()),
+            _ => panic!("unexpected warning {:?}", warning),
+        })
+        .collect();
+    assert_eq!(
+        vec![(crate::ast::Meta { start: 0, end: 18 }, "id")],
+        warnings
+    );
+}
+
+#[test]
+fn compile_warn_unused_fns_test() {
+    let src = "fn unused() { 1 } fn used() { 2 } pub fn go() { used() }";
+    let input = vec![module_input(ModuleOrigin::Src, "one", src)];
+    let package = compile(input.clone(), &Default::default()).expect("should compile");
+    assert_eq!(Vec::<Warning>::new(), package.warnings);
+
+    let options = CompileOptions {
+        warn_unused_fns: true,
+        ..Default::default()
+    };
+    let package = compile(input.clone(), &options).expect("should compile");
+    assert_eq!(
+        vec![Warning::Type {
+            path: PathBuf::from("/src/one.gleam"),
+            src: src.to_string(),
+            warning: crate::typ::Warning::UnusedPrivateFn {
+                meta: crate::ast::Meta { start: 0, end: 17 },
+                name: "unused".to_string(),
+            },
+        }],
+        package.warnings
+    );
+}
+
+#[test]
+fn compile_dry_run_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "one",
+            "pub struct Point { x: Int y: Int } pub fn go() { Point(x: 1, y: 2) }",
+        ),
+        module_input(
+            ModuleOrigin::Src,
+            "two",
+            "import one pub fn go() { one.go() }",
+        ),
+    ];
+    let options = CompileOptions {
+        otp_app: Some(OtpApp {
+            name: "my_app".to_string(),
+            version: "1.0.0".to_string(),
+        }),
+        write_interfaces: true,
+        write_manifest: true,
+        ..Default::default()
+    };
+    let paths = |package: &Package| {
+        package
+            .modules
+            .iter()
+            .flat_map(|m| m.files.iter())
+            .chain(package.files.iter())
+            .map(|f| (f.path.clone(), f.text.is_empty()))
+            .collect::<Vec<_>>()
+    };
+
+    let package = compile(input.clone(), &options).expect("should compile");
+    let expected: Vec<_> = paths(&package)
+        .into_iter()
+        .map(|(path, empty)| {
+            assert!(!empty);
+            (path, true)
+        })
+        .collect();
+
+    let dry_run_options = CompileOptions {
+        dry_run: true,
+        ..options
+    };
+    let package = compile(input.clone(), &dry_run_options).expect("should compile");
+    assert_eq!(expected, paths(&package));
+}
+
+#[test]
+fn compile_stats_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn go() { 1 }"),
+        module_input(
+            ModuleOrigin::Test,
+            "two",
+            "import one pub fn go() { one.go() }",
+        ),
+    ];
+
+    let package = compile(input.clone(), &Default::default()).expect("should compile");
+    assert_eq!(None, package.stats);
+
+    let options = CompileOptions {
+        collect_stats: true,
+        ..Default::default()
+    };
+    let package = compile(input.clone(), &options).expect("should compile");
+    let stats = package.stats.expect("should collect stats");
+    assert_eq!(
+        vec![vec!["one".to_string()], vec!["two".to_string()]],
+        stats
+            .modules
+            .iter()
+            .map(|m| m.name.clone())
+            .collect::<Vec<_>>()
+    );
+    assert!(stats
+        .modules
+        .iter()
+        .all(|m| m.total() > Duration::from_secs(0)));
+    assert_eq!(
+        stats.total(),
+        stats.modules.iter().map(|m| m.total()).sum::<Duration>()
+    );
+}
+
+#[test]
+fn compile_unused_import_warning_test() {
+    let input = |src: &str| {
+        vec![
+            module_input(
+                ModuleOrigin::Src,
+                "one",
+                "pub fn id(x) { x } pub struct Box { inner: Int }",
+            ),
+            module_input(ModuleOrigin::Src, "two", src),
+        ]
+    };
+
+    let src = "import one\npub fn go() { 1 }";
+    let package = compile(input(src), &Default::default()).expect("should compile");
+    assert_eq!(
+        vec![Warning::Type {
+            path: PathBuf::from("/src/two.gleam"),
+            src: src.to_string(),
+            warning: crate::typ::Warning::UnusedImport {
+                meta: crate::ast::Meta { start: 7, end: 10 },
+                module: "one".to_string(),
+            },
+        }],
+        package.warnings
+    );
+
+    let options = CompileOptions {
+        warnings_as_errors: true,
+        ..Default::default()
+    };
+    match compile(input(src), &options) {
+        Err(Error::WarningsAsErrors(warnings)) => assert_eq!(1, warnings.len()),
+        other => panic!("expected unused import to be an error, got {:?}", other),
+    }
+
+    // Imports used by a value, a type annotation, a pattern, or for unqualified imports do not
+    // produce warnings
+    for src in &[
+        "import one pub fn go() { one.id(1) }",
+        "import one pub fn go(x: one.Box) { x }",
+        "import one pub fn go(x) { let one.Box(i) = x i }",
+        "import one.{id} pub fn go() { id(1) }",
+        "import one.{Box} pub fn go(x: Box) { x }",
+        "pub import one.{id}",
+    ] {
+        let package = compile(input(src), &options).expect("should compile without warnings");
+        assert!(package.warnings.is_empty());
+    }
+
+    // Each unused unqualified import is warned about
+    let src = "import one.{id, Box}\npub fn go(x: Box) { x }";
+    let package = compile(input(src), &Default::default()).expect("should compile");
+    assert_eq!(
+        vec![Warning::Type {
+            path: PathBuf::from("/src/two.gleam"),
+            src: src.to_string(),
+            warning: crate::typ::Warning::UnusedUnqualifiedImport {
+                meta: crate::ast::Meta { start: 12, end: 14 },
+                name: "id".to_string(),
+            },
+        }],
+        package.warnings
+    );
+}
+
+#[test]
+fn generated_paths_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Dependency,
+            "dep",
+            "pub struct Box { inner: Int }",
+        ),
+        module_input(
+            ModuleOrigin::Src,
+            "one/two",
+            "import dep pub struct Point { x: Int y: Int } struct Private { x: Int }",
+        ),
+        module_input(ModuleOrigin::Test, "two_test", "import one/two"),
+    ];
+    let options = CompileOptions {
+        otp_app: Some(OtpApp {
+            name: "my_app".to_string(),
+            version: "1.0.0".to_string(),
+        }),
+        write_interfaces: true,
+        write_manifest: true,
+        ..Default::default()
+    };
+
+    let written_paths = |package: Package| -> Vec<_> {
+        package
+            .modules
+            .into_iter()
+            .flat_map(|compiled| compiled.files)
+            .chain(package.files)
+            .map(|file| file.path)
+            .collect()
+    };
+
+    let mut paths = generated_paths(&input, &options).expect("should find paths");
+    let mut compiled_paths = written_paths(compile(input, &options).expect("should compile"));
+
+    paths.sort();
+    compiled_paths.sort();
+    assert_eq!(compiled_paths, paths);
+    assert_eq!(
+        vec![
+            PathBuf::from("/dep/gen/src/dep.erl"),
+            PathBuf::from("/dep/gen/src/dep.gleam_module"),
+            PathBuf::from("/dep/gen/src/dep_Box.erl"),
+            PathBuf::from("/gen/manifest.json"),
+            PathBuf::from("/gen/src/my_app.app.src"),
+            PathBuf::from("/gen/src/one@two.erl"),
+            PathBuf::from("/gen/src/one@two.gleam_module"),
+            PathBuf::from("/gen/src/one@two_Point.erl"),
+            PathBuf::from("/gen/test/two_test.erl"),
+            PathBuf::from("/gen/test/two_test.gleam_module"),
+        ],
+        paths
+    );
+
+    // Only the targets and the modules they depend on are compiled
+    let input = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn go() { 1 }"),
+        module_input(
+            ModuleOrigin::Src,
+            "three",
+            "import two pub fn go() { two.go() }",
+        ),
+        module_input(ModuleOrigin::Src, "two", "pub fn go() { 2 }"),
+    ];
+    let options = CompileOptions {
+        targets: Some(vec!["three".to_string()].into_iter().collect()),
+        ..Default::default()
+    };
+
+    let paths = generated_paths(&input, &options).expect("should find paths");
+    let compiled_paths = written_paths(compile(input, &options).expect("should compile"));
+
+    assert_eq!(compiled_paths, paths);
+    assert_eq!(
+        vec![
+            PathBuf::from("/gen/src/two.erl"),
+            PathBuf::from("/gen/src/three.erl"),
+        ],
+        paths
+    );
+}
+
+#[test]
+fn compile_header_test() {
+    let input = vec![
+        module_input(ModuleOrigin::Src, "nested/one", "pub fn id(x) { x }"),
+        module_input(ModuleOrigin::Test, "two", ""),
+    ];
+    let options = CompileOptions {
+        compiler_version: Some("1.2.3".to_string()),
+        ..Default::default()
+    };
+    let package = compile(input.clone(), &options).expect("should compile");
+    let headers: Vec<_> = package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .map(|file| file.text.lines().next().unwrap_or("").to_string())
+        .collect();
+    assert_eq!(
+        vec![
+            "%% Generated by Gleam 1.2.3 from test/two.gleam".to_string(),
+            "%% Generated by Gleam 1.2.3 from src/nested/one.gleam".to_string(),
+        ],
+        headers
+    );
+
+    // The header does not change between compilations
+    assert_eq!(
+        package,
+        compile(input.clone(), &options).expect("should compile again")
+    );
+}
+
+#[test]
+fn compile_record_headers_test() {
+    let input = vec![
+        module_input(
+            ModuleOrigin::Src,
+            "one",
+            "pub struct Point { x: Int y: Int }",
+        ),
+        module_input(ModuleOrigin::Src, "two", "pub fn id(x) { x }"),
+    ];
+    let headers = |options: &CompileOptions| -> Vec<OutputFile> {
+        compile(input.clone(), options)
+            .expect("should compile")
+            .modules
+            .into_iter()
+            .flat_map(|m| m.files.into_iter())
+            .filter(|file| file.path.extension() == Some("hrl".as_ref()))
+            .collect()
+    };
+
+    assert_eq!(Vec::<OutputFile>::new(), headers(&Default::default()));
+
+    // Only modules with public structs get a header
+    let options = CompileOptions {
+        write_record_headers: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        vec![OutputFile {
+            path: PathBuf::from("/gen/src/one.hrl"),
+            text: "-record(point, {x, y}).\n".to_string(),
+        }],
+        headers(&options)
+    );
+    assert!(generated_paths(&input, &options)
+        .expect("should list paths")
+        .contains(&PathBuf::from("/gen/src/one.hrl")));
+}
+
+#[test]
+fn compile_targets_test() {
+    let inputs = vec![
+        module_input(ModuleOrigin::Src, "one", "pub fn id(x) { x }"),
+        module_input(
+            ModuleOrigin::Src,
+            "two",
+            "import one pub fn go() { one.id(1) }",
+        ),
+        // Not depended upon by the target, so its type error is not found
+        module_input(ModuleOrigin::Src, "three", "pub fn go() { 1 + 1.0 }"),
+    ];
+    let options = CompileOptions {
+        targets: Some(vec!["two".to_string()].into_iter().collect()),
+        ..Default::default()
+    };
+    let package = compile(inputs.clone(), &options).expect("should compile");
+    let mut names: Vec<_> = package.modules.iter().map(|m| m.name.join("/")).collect();
+    names.sort();
+    assert_eq!(vec!["one".to_string(), "two".to_string()], names);
+    assert!(package
+        .modules
+        .iter()
+        .flat_map(|m| m.files.iter())
+        .all(|file| !file.path.ends_with("three.erl")));
+
+    let options = CompileOptions {
+        targets: Some(vec!["thre".to_string()].into_iter().collect()),
+        ..Default::default()
+    };
+    match compile(inputs.clone(), &options) {
+        Err(Error::UnknownTargetModule { module, .. }) => assert_eq!("thre", module),
+        other => panic!("expected an unknown target module error, got {:?}", other),
+    }
+}
+
+#[test]
+fn compile_indent_test() {
+    let input = vec![module_input(
+        ModuleOrigin::Src,
+        "one",
+        "pub fn go(x) { case x { 1 -> 2 _ -> x } }",
+    )];
+    let options = CompileOptions {
+        erlang_indent: Some(2),
+        ..Default::default()
+    };
+    let package = compile(input, &options).expect("should compile");
+    assert_eq!(
+        "-module(one).
+-compile(no_auto_import).
+
+-export([go/1]).
+
+go(X) ->
+  case X of
+    1 ->
+      2;
+
+    _ ->
+      X
+  end.
+",
+        package.modules[0].files[0].text
+    );
+}
+
+#[test]
+fn compile_target_extension_test() {
+    let input = vec![module_input(
+        ModuleOrigin::Src,
+        "one/two",
+        "pub fn id(x) { x }",
+    )];
+    for (target, path) in [
+        (CompileTarget::Erlang, "/gen/src/one@two.erl"),
+        (CompileTarget::CoreErlang, "/gen/src/one@two.core"),
+    ]
+    .iter()
+    {
+        let options = CompileOptions {
+            target: *target,
+            ..Default::default()
+        };
+        let package = compile(input.clone(), &options).expect("should compile");
+        let paths: Vec<_> = package
+            .modules
+            .iter()
+            .flat_map(|m| m.files.iter().map(|f| f.path.clone()))
+            .collect();
+        assert_eq!(vec![PathBuf::from(path)], paths);
     }
 }
@@ -1,17 +1,19 @@
 use crate::ast::{
-    Arg, ArgNames, BinOp, CallArg, Clause, Expr, Meta, Module, Pattern, Statement, StructField,
-    TypeAst, TypedExpr, TypedModule, TypedPattern, UnqualifiedImport, UntypedExpr, UntypedModule,
-    UntypedPattern,
+    Arg, ArgNames, BinOp, BitSegment, BitSegmentKind, BitSegmentSize, CallArg, Clause, Expr,
+    ExternalFnArg, Literal, Meta, Module, Pattern, Statement, StructField, TypeAst, TypedArg,
+    TypedClause, TypedExpr, TypedModule, TypedPattern, TypedStatement, UnqualifiedImport,
+    UntypedArg, UntypedExpr, UntypedModule, UntypedPattern, UntypedStatement, UpdateArg,
 };
 use crate::pretty::*;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 const INDENT: isize = 2;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     App {
         public: bool,
@@ -42,13 +44,19 @@ impl Type {
         for _ in 0..initial_indent {
             b.push(' ');
         }
-        let mut initial_indent = initial_indent;
         b.to_doc()
-            .append(self.to_gleam_doc(&mut im::hashmap![], &mut initial_indent))
+            .append(self.to_gleam_doc(&mut im::hashmap![], &mut 0))
             .nest(initial_indent as isize)
             .format(80)
     }
 
+    /// Render a Type as Gleam syntax on a single line, such as `fn(Int, a) -> List(a)`.
+    ///
+    pub fn to_gleam_string(&self) -> String {
+        self.to_gleam_doc(&mut im::hashmap![], &mut 0)
+            .format(isize::MAX)
+    }
+
     pub fn to_gleam_doc(
         &self,
         names: &mut im::HashMap<usize, String>,
@@ -80,6 +88,19 @@ impl Type {
         }
     }
 
+    /// The number of arguments of a function type, or None if the type is not a function.
+    ///
+    pub fn fn_arity(&self) -> Option<usize> {
+        match self {
+            Type::Fn { args, .. } => Some(args.len()),
+            Type::Var { typ } => match &*typ.borrow() {
+                TypeVar::Link { typ } => typ.fn_arity(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn collapse_links(self) -> Type {
         if let Type::Var { typ } = &self {
             if let TypeVar::Link { typ } = &*typ.borrow() {
@@ -322,7 +343,7 @@ fn args_to_gleam_doc(
     uid: &mut usize,
 ) -> Document {
     match args.len() {
-        0 => nil(),
+        0 => crate::pretty::nil(),
         _ => args
             .iter()
             .map(|t| t.to_gleam_doc(names, uid).group())
@@ -335,6 +356,56 @@ fn args_to_gleam_doc(
     }
 }
 
+#[test]
+fn to_gleam_string_test() {
+    let generic = |id| Type::Var {
+        typ: Rc::new(RefCell::new(TypeVar::Generic { id })),
+    };
+
+    assert_eq!(
+        "fn(Int, String) -> Bool",
+        Type::Fn {
+            args: vec![int(), string()],
+            retrn: Box::new(bool()),
+        }
+        .to_gleam_string()
+    );
+
+    assert_eq!(
+        "fn(a, b, a) -> Result(b, a)",
+        Type::Fn {
+            args: vec![generic(7), generic(3), generic(7)],
+            retrn: Box::new(result(generic(3), generic(7))),
+        }
+        .to_gleam_string()
+    );
+
+    assert_eq!(
+        "struct(Int, struct(List(a), struct(Float, a)))",
+        Type::AnonStruct {
+            elems: vec![
+                int(),
+                Type::AnonStruct {
+                    elems: vec![
+                        list(generic(1)),
+                        Type::AnonStruct {
+                            elems: vec![float(), generic(1)],
+                        },
+                    ],
+                },
+            ],
+        }
+        .to_gleam_string()
+    );
+
+    // Long types are not broken over multiple lines
+    let long = Type::Fn {
+        args: (0..20).map(|_| string()).collect(),
+        retrn: Box::new(string()),
+    };
+    assert!(!long.to_gleam_string().contains('\n'));
+}
+
 #[test]
 fn to_gleam_doc_test() {
     macro_rules! assert_string {
@@ -445,21 +516,36 @@ fn to_gleam_doc_test() {
     );
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TypeVar {
     Unbound { id: usize, level: usize },
     Link { typ: Box<Type> },
     Generic { id: usize },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TypeConstructorInfo {
     pub public: bool,
     pub module: Vec<String>,
     pub arity: usize,
+    /// The value constructors of the type, empty for types such as Int and external types.
+    pub constructors: Vec<String>,
+    /// Whether the constructors of the type are hidden from other modules.
+    pub opaque: bool,
+    /// For a type alias, the type that the alias stands for.
+    pub alias: Option<TypeAlias>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The type a type alias is expanded to, in which the generic variables with the ids in
+/// `parameters` are replaced by the arguments given to the alias.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeAlias {
+    pub parameters: Vec<usize>,
+    pub typ: Type,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ValueConstructor {
     pub variant: ValueConstructorVariant,
     pub typ: Type,
@@ -476,9 +562,10 @@ impl ValueConstructor {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldMap {
     arity: usize,
+    #[serde(serialize_with = "serialize_sorted_map")]
     fields: HashMap<String, usize>,
 }
 
@@ -499,6 +586,10 @@ impl FieldMap {
         }
     }
 
+    pub fn index(&self, label: &str) -> Option<usize> {
+        self.fields.get(label).cloned()
+    }
+
     pub fn into_option(self) -> Option<Self> {
         if self.fields.is_empty() {
             None
@@ -522,7 +613,11 @@ impl FieldMap {
             });
         }
 
-        for i in 0..args.len() {
+        // The original index of each argument, swapped along with the arguments
+        let mut origins: Vec<usize> = (0..args.len()).collect();
+
+        let mut i = 0;
+        while i < args.len() {
             let (label, meta) = match &args[i].label {
                 // A labelled argument, we may need to reposition it in the array vector
                 Some(l) => {
@@ -532,11 +627,12 @@ impl FieldMap {
 
                 // Not a labelled argument
                 None => {
-                    if labelled_arguments_given {
+                    if labelled_arguments_given && origins[i] == i {
                         return Err(Error::PositionalArgumentAfterLabelled {
                             meta: args[i].meta.clone(),
                         });
                     }
+                    i += 1;
                     continue;
                 }
             };
@@ -549,22 +645,26 @@ impl FieldMap {
                         label: label.to_string(),
                     })
                 }
-                Some(p) => p,
+                Some(p) => *p,
             };
 
-            if *position == i {
+            if position == i {
+                seen.insert(position);
+                i += 1;
                 continue;
             }
 
-            if seen.contains(position) {
+            if seen.contains(&position) {
                 return Err(Error::DuplicateArgument {
                     meta: meta.clone(),
                     label: label.to_string(),
                 });
             }
 
-            seen.insert(*position);
-            args.swap(*position, i);
+            // The argument swapped into position `i` is checked on the next iteration
+            seen.insert(position);
+            args.swap(position, i);
+            origins.swap(position, i);
         }
         Ok(())
     }
@@ -572,8 +672,8 @@ impl FieldMap {
 
 #[test]
 fn field_map_reorder_test() {
-    let int = |value| Expr::Int {
-        value,
+    let int = |value: &str| Expr::Int {
+        value: value.to_string(),
         typ: (),
         meta: Meta { start: 0, end: 0 },
     };
@@ -615,17 +715,17 @@ fn field_map_reorder_test() {
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(1),
+                value: int("1"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(2),
+                value: int("2"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(3),
+                value: int("3"),
             },
         ],
         expected_result: Ok(()),
@@ -633,17 +733,17 @@ fn field_map_reorder_test() {
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(1),
+                value: int("1"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(2),
+                value: int("2"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(3),
+                value: int("3"),
             },
         ],
     }
@@ -656,17 +756,17 @@ fn field_map_reorder_test() {
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(1),
+                value: int("1"),
             },
             CallArg {
                 meta: Default::default(),
                 label: Some("last".to_string()),
-                value: int(2),
+                value: int("2"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(3),
+                value: int("3"),
             },
         ],
         expected_result: Ok(()),
@@ -674,17 +774,17 @@ fn field_map_reorder_test() {
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(1),
+                value: int("1"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(3),
+                value: int("3"),
             },
             CallArg {
                 meta: Default::default(),
                 label: Some("last".to_string()),
-                value: int(2),
+                value: int("2"),
             },
         ],
     }
@@ -697,17 +797,17 @@ fn field_map_reorder_test() {
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(1),
+                value: int("1"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(2),
+                value: int("2"),
             },
             CallArg {
                 meta: Default::default(),
                 label: Some("last".to_string()),
-                value: int(3),
+                value: int("3"),
             },
         ],
         expected_result: Ok(()),
@@ -715,24 +815,66 @@ fn field_map_reorder_test() {
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(1),
+                value: int("1"),
             },
             CallArg {
                 meta: Default::default(),
                 label: None,
-                value: int(2),
+                value: int("2"),
             },
             CallArg {
                 meta: Default::default(),
                 label: Some("last".to_string()),
-                value: int(3),
+                value: int("3"),
+            },
+        ],
+    }
+    .test();
+
+    // An unknown label is found even when it is swapped out of place by a correct one
+    Case {
+        arity: 2,
+        fields: [("a".to_string(), 0), ("b".to_string(), 1)]
+            .iter()
+            .cloned()
+            .collect(),
+        args: vec![
+            CallArg {
+                meta: Default::default(),
+                label: Some("b".to_string()),
+                value: int("1"),
+            },
+            CallArg {
+                meta: Default::default(),
+                label: Some("c".to_string()),
+                value: int("2"),
+            },
+        ],
+        expected_result: Err(Error::UnknownLabel {
+            meta: Default::default(),
+            label: "c".to_string(),
+            labels: [("a".to_string(), 0), ("b".to_string(), 1)]
+                .iter()
+                .cloned()
+                .collect(),
+        }),
+        expected_args: vec![
+            CallArg {
+                meta: Default::default(),
+                label: Some("c".to_string()),
+                value: int("2"),
+            },
+            CallArg {
+                meta: Default::default(),
+                label: Some("b".to_string()),
+                value: int("1"),
             },
         ],
     }
     .test();
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ValueConstructorVariant {
     /// A locally defined variable or function parameter
     LocalVariable,
@@ -756,6 +898,9 @@ pub enum ValueConstructorVariant {
         field_map: FieldMap,
         arity: usize,
     },
+
+    /// A module constant, the value of which is inlined where it is used
+    ModuleConst { literal: Literal },
 }
 
 impl ValueConstructorVariant {
@@ -767,6 +912,10 @@ impl ValueConstructorVariant {
                 ModuleValueConstructor::Struct { name: name.clone() }
             }
 
+            ValueConstructorVariant::ModuleConst { literal } => ModuleValueConstructor::Const {
+                literal: literal.clone(),
+            },
+
             ValueConstructorVariant::LocalVariable { .. }
             | ValueConstructorVariant::ModuleFn { .. } => ModuleValueConstructor::Fn,
         }
@@ -776,17 +925,102 @@ impl ValueConstructorVariant {
 #[derive(Debug, Clone, PartialEq)]
 pub enum ModuleValueConstructor {
     Struct { name: String },
+    Const { literal: Literal },
     Enum,
     Fn,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ModuleTypeInfo {
     pub name: Vec<String>,
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub type_constructors: HashMap<String, TypeConstructorInfo>,
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub value_constructors: HashMap<String, ValueConstructor>,
 }
 
+/// Serialize a map with its keys in order so that the output is the same on each compile.
+///
+fn serialize_sorted_map<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    map.iter()
+        .collect::<std::collections::BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+/// The types and constructors that are in scope in every module without being imported.
+///
+pub fn prelude() -> ModuleTypeInfo {
+    let mut type_constructors = HashMap::new();
+    let mut value_constructors = HashMap::new();
+
+    let mut add_type = |name: &str, arity, constructors: &[&str]| {
+        type_constructors.insert(
+            name.to_string(),
+            TypeConstructorInfo {
+                arity,
+                module: vec![],
+                public: true,
+                constructors: constructors.iter().map(|c| c.to_string()).collect(),
+                opaque: false,
+                alias: None,
+            },
+        );
+    };
+    add_type("Int", 0, &[]);
+    add_type("Float", 0, &[]);
+    add_type("String", 0, &[]);
+    add_type("BitString", 0, &[]);
+    add_type("Bool", 0, &["True", "False"]);
+    add_type("List", 1, &[]);
+    add_type("Result", 2, &["Ok", "Error"]);
+    add_type("Nil", 0, &["Nil"]);
+
+    let mut add_value = |name: &str, arity, typ| {
+        value_constructors.insert(
+            name.to_string(),
+            ValueConstructor {
+                variant: ValueConstructorVariant::Enum {
+                    field_map: None,
+                    arity,
+                },
+                typ,
+            },
+        );
+    };
+    let generic = |id| Type::Var {
+        typ: Rc::new(RefCell::new(TypeVar::Generic { id })),
+    };
+    add_value("True", 0, bool());
+    add_value("False", 0, bool());
+    add_value("Nil", 0, nil());
+    add_value(
+        "Ok",
+        1,
+        Type::Fn {
+            args: vec![generic(0)],
+            retrn: Box::new(result(generic(0), generic(1))),
+        },
+    );
+    add_value(
+        "Error",
+        1,
+        Type::Fn {
+            args: vec![generic(1)],
+            retrn: Box::new(result(generic(0), generic(1))),
+        },
+    );
+
+    ModuleTypeInfo {
+        name: vec![],
+        type_constructors,
+        value_constructors,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PatternConstructor {
     Enum,
@@ -802,6 +1036,14 @@ pub struct Env<'a> {
     imported_modules: HashMap<String, ModuleTypeInfo>,
     type_constructors: HashMap<String, TypeConstructorInfo>,
     public_module_value_constructors: HashMap<String, ValueConstructor>,
+    unused_imports: HashMap<String, (Meta, String)>,
+    unused_unqualified_imports: HashMap<String, Meta>,
+    reexported_types: HashSet<String>,
+    struct_constructors: HashMap<(Vec<String>, String), ValueConstructor>,
+    // The type of each function defined for a single target, which each of the other targets'
+    // definitions of the function must share
+    target_fn_types: HashMap<String, Type>,
+    warnings: Vec<Warning>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -818,101 +1060,23 @@ impl<'a> Env<'a> {
             type_constructors: HashMap::new(),
             public_module_value_constructors: HashMap::new(),
             imported_modules: HashMap::new(),
+            unused_imports: HashMap::new(),
+            unused_unqualified_imports: HashMap::new(),
+            reexported_types: HashSet::new(),
+            struct_constructors: HashMap::new(),
+            target_fn_types: HashMap::new(),
             variables: hashmap![],
             importable_modules,
+            warnings: vec![],
         };
 
-        env.insert_type_constructor(
-            "Int".to_string(),
-            TypeConstructorInfo {
-                arity: 0,
-                module: vec![],
-                public: true,
-            },
-        );
-
-        env.insert_variable(
-            "True".to_string(),
-            ValueConstructorVariant::Enum {
-                field_map: None,
-                arity: 0,
-            },
-            bool(),
-        );
-        env.insert_variable(
-            "False".to_string(),
-            ValueConstructorVariant::Enum {
-                field_map: None,
-                arity: 0,
-            },
-            bool(),
-        );
-        env.insert_type_constructor(
-            "Bool".to_string(),
-            TypeConstructorInfo {
-                arity: 0,
-                module: vec![],
-                public: true,
-            },
-        );
-
-        env.insert_type_constructor(
-            "List".to_string(),
-            TypeConstructorInfo {
-                arity: 1,
-                module: vec![],
-                public: true,
-            },
-        );
-
-        env.insert_type_constructor(
-            "Float".to_string(),
-            TypeConstructorInfo {
-                arity: 0,
-                module: vec![],
-                public: true,
-            },
-        );
-
-        env.insert_type_constructor(
-            "String".to_string(),
-            TypeConstructorInfo {
-                arity: 0,
-                module: vec![],
-                public: true,
-            },
-        );
-
-        env.insert_type_constructor(
-            "Result".to_string(),
-            TypeConstructorInfo {
-                arity: 2,
-                module: vec![],
-                public: true,
-            },
-        );
-
-        env.insert_variable(
-            "Nil".to_string(),
-            ValueConstructorVariant::Enum {
-                field_map: None,
-                arity: 0,
-            },
-            Type::App {
-                args: vec![],
-                public: true,
-                name: "Nil".to_string(),
-                module: vec![],
-            },
-        );
-        env.insert_type_constructor(
-            "Nil".to_string(),
-            TypeConstructorInfo {
-                arity: 0,
-                module: vec![],
-                public: true,
-            },
-        );
+        let prelude = prelude();
+        for (name, info) in prelude.type_constructors {
+            env.insert_type_constructor(name, info);
+        }
+        for (name, constructor) in prelude.value_constructors {
+            env.insert_variable(name, constructor.variant, constructor.typ);
+        }
 
         env.insert_variable(
             "+".to_string(),
@@ -1022,6 +1186,15 @@ impl<'a> Env<'a> {
             },
         );
 
+        env.insert_variable(
+            "<>".to_string(),
+            ValueConstructorVariant::LocalVariable,
+            Type::Fn {
+                args: vec![string(), string()],
+                retrn: Box::new(string()),
+            },
+        );
+
         let a = env.new_generic_var();
         let b = env.new_generic_var();
         let f = Type::Fn {
@@ -1129,41 +1302,6 @@ impl<'a> Env<'a> {
             },
         );
 
-        let result = |ok, error| Type::App {
-            name: "Result".to_string(),
-            module: vec![],
-            public: true,
-            args: vec![ok, error],
-        };
-
-        let ok = env.new_generic_var();
-        let error = env.new_generic_var();
-        env.insert_variable(
-            "Ok".to_string(),
-            ValueConstructorVariant::Enum {
-                field_map: None,
-                arity: 1,
-            },
-            Type::Fn {
-                args: vec![ok.clone()],
-                retrn: Box::new(result(ok, error)),
-            },
-        );
-
-        let ok = env.new_generic_var();
-        let error = env.new_generic_var();
-        env.insert_variable(
-            "Error".to_string(),
-            ValueConstructorVariant::Enum {
-                field_map: None,
-                arity: 1,
-            },
-            Type::Fn {
-                args: vec![error.clone()],
-                retrn: Box::new(result(ok, error)),
-            },
-        );
-
         env
     }
 
@@ -1201,39 +1339,75 @@ impl<'a> Env<'a> {
 
     /// Map a variable in the current scope.
     ///
+    /// The constructor of a struct type, whether defined in this module or a public struct of
+    /// another module.
+    ///
+    fn struct_constructor(&self, module: &[String], name: &str) -> Option<&ValueConstructor> {
+        let constructor = match self
+            .struct_constructors
+            .get(&(module.to_vec(), name.to_string()))
+        {
+            Some(constructor) => constructor,
+            None => self
+                .importable_modules
+                .get(&module.join("/"))?
+                .value_constructors
+                .get(name)?,
+        };
+        match constructor.variant {
+            ValueConstructorVariant::Struct { .. } => Some(constructor),
+            _ => None,
+        }
+    }
+
     pub fn insert_variable(&mut self, name: String, variant: ValueConstructorVariant, typ: Type) {
         self.variables
             .insert(name, ValueConstructor { variant, typ });
     }
 
-    /// Lookup a variable in the current scope.
-    ///
-    pub fn get_variable(&self, name: &str) -> Option<&ValueConstructor> {
-        self.variables.get(name)
-    }
-
     /// Map a type in the current scope.
     ///
     pub fn insert_type_constructor(&mut self, name: String, info: TypeConstructorInfo) {
         self.type_constructors.insert(name, info);
     }
 
+    /// Lookup the constructor info of a type by the module it was defined in, as found in a
+    /// Type::App, rather than by the name it has in the current scope.
+    ///
+    fn get_type_constructor_by_module(
+        &self,
+        module: &[String],
+        name: &str,
+    ) -> Option<&TypeConstructorInfo> {
+        match self.importable_modules.get(&module.join("/")) {
+            Some(info) => info.type_constructors.get(name),
+            None => self
+                .type_constructors
+                .get(name)
+                .filter(|info| info.module.as_slice() == module),
+        }
+    }
+
     /// Lookup a type in the current scope.
     ///
     pub fn get_type_constructor(
-        &self,
+        &mut self,
         module_alias: &Option<String>,
         name: &str,
     ) -> Result<&TypeConstructorInfo, GetTypeConstructorError> {
         match module_alias {
-            None => self.type_constructors.get(name).ok_or_else(|| {
-                GetTypeConstructorError::UnknownType {
-                    name: name.to_string(),
-                    type_constructors: self.type_constructors.clone(),
-                }
-            }),
+            None => {
+                self.unused_unqualified_imports.remove(name);
+                self.type_constructors.get(name).ok_or_else(|| {
+                    GetTypeConstructorError::UnknownType {
+                        name: name.to_string(),
+                        type_constructors: self.type_constructors.clone(),
+                    }
+                })
+            }
 
             Some(m) => {
+                self.unused_imports.remove(m);
                 let module = &self.imported_modules.get(m).ok_or_else(|| {
                     GetTypeConstructorError::UnknownModule {
                         name: name.to_string(),
@@ -1254,12 +1428,13 @@ impl<'a> Env<'a> {
     /// Lookup a value constructor in the current scope.
     ///
     fn get_value_constructor(
-        &self,
+        &mut self,
         module: Option<&String>,
         name: &str,
     ) -> Result<&ValueConstructor, GetValueConstructorError> {
         match module {
             None => {
+                self.unused_unqualified_imports.remove(name);
                 self.variables
                     .get(name)
                     .ok_or_else(|| GetValueConstructorError::UnknownVariable {
@@ -1269,6 +1444,7 @@ impl<'a> Env<'a> {
             }
 
             Some(module) => {
+                self.unused_imports.remove(module);
                 let module = self.imported_modules.get(&*module).ok_or_else(|| {
                     GetValueConstructorError::UnknownModule {
                         name: name.to_string(),
@@ -1311,7 +1487,8 @@ impl<'a> Env<'a> {
                     .collect::<Result<Vec<_>, _>>()?;
                 let info = self
                     .get_type_constructor(module, name)
-                    .map_err(|e| convert_get_type_constructor_error(e, &meta))?;
+                    .map_err(|e| convert_get_type_constructor_error(e, meta))?
+                    .clone();
                 if args.len() != info.arity {
                     return Err(Error::IncorrectTypeArity {
                         meta: meta.clone(),
@@ -1320,6 +1497,11 @@ impl<'a> Env<'a> {
                         given: args.len(),
                     });
                 }
+                // Aliases are replaced by the type they stand for
+                if let Some(TypeAlias { parameters, typ }) = info.alias {
+                    let mut ids = parameters.into_iter().zip(args).collect();
+                    return Ok(instantiate(typ, 0, &mut ids, self));
+                }
                 Ok(Type::App {
                     name: name.to_string(),
                     module: info.module.clone(),
@@ -1368,14 +1550,69 @@ impl<'a> Env<'a> {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+    NonExhaustiveCase {
+        meta: Meta,
+        missing: Vec<String>,
+    },
+    UnusedImport {
+        meta: Meta,
+        module: String,
+    },
+    UnusedUnqualifiedImport {
+        meta: Meta,
+        name: String,
+    },
+    GenericFn {
+        meta: Meta,
+        name: String,
+        typ: Type,
+    },
+    DivisionByZero {
+        meta: Meta,
+    },
+    Shadowing {
+        meta: Meta,
+        name: String,
+        shadowed: Meta,
+    },
+    Todo {
+        meta: Meta,
+    },
+    UnusedPrivateFn {
+        meta: Meta,
+        name: String,
+    },
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
+    InvalidInlineErlang {
+        meta: Meta,
+        name: String,
+        arity: usize,
+    },
+
+    InvalidAssertMessage {
+        meta: Meta,
+    },
+
     UnknownLabel {
         meta: Meta,
         label: String,
         labels: HashMap<String, usize>,
     },
 
+    EmptyBlock {
+        meta: Meta,
+    },
+
+    NotNumeric {
+        meta: Meta,
+        typ: Type,
+    },
+
     UnknownVariable {
         meta: Meta,
         name: String,
@@ -1426,13 +1663,31 @@ pub enum Error {
         typ: Type,
     },
 
-    IncorrectArity {
+    UnknownField {
         meta: Meta,
-        expected: usize,
-        given: usize,
+        label: String,
+        typ: Type,
+        fields: Vec<String>,
     },
 
-    IncorrectTypeArity {
+    NotATuple {
+        meta: Meta,
+        typ: Type,
+    },
+
+    OutOfBoundsTupleIndex {
+        meta: Meta,
+        index: u64,
+        size: usize,
+    },
+
+    IncorrectArity {
+        meta: Meta,
+        expected: usize,
+        given: usize,
+    },
+
+    IncorrectTypeArity {
         meta: Meta,
         name: String,
         expected: usize,
@@ -1449,9 +1704,31 @@ pub enum Error {
         meta: Meta,
     },
 
-    DuplicateName {
+    RecursiveTypeAlias {
+        meta: Meta,
+        name: String,
+    },
+
+    UnknownExport {
+        meta: Meta,
+        name: String,
+    },
+
+    TargetTypeMismatch {
+        meta: Meta,
+        name: String,
+        expected: Type,
+        given: Type,
+    },
+
+    TargetNotFn {
         meta: Meta,
+    },
+
+    DuplicateName {
         name: String,
+        first: Meta,
+        second: Meta,
     },
 
     DuplicateArgument {
@@ -1464,8 +1741,16 @@ pub enum Error {
         label: String,
     },
 
+    DuplicateConstructorAtom {
+        meta: Meta,
+        name: String,
+        other: String,
+        atom: String,
+    },
+
     PrivateTypeLeak {
         meta: Meta,
+        name: String,
         leaked: Type,
     },
 
@@ -1477,6 +1762,87 @@ pub enum Error {
     PositionalArgumentAfterLabelled {
         meta: Meta,
     },
+
+    InvalidExternalTarget {
+        meta: Meta,
+        target: String,
+    },
+
+    InvalidGuardExpression {
+        meta: Meta,
+    },
+
+    NotStructConstructor {
+        meta: Meta,
+    },
+
+    InvalidConstant {
+        meta: Meta,
+    },
+
+    RefutableArgPattern {
+        meta: Meta,
+    },
+}
+
+impl Warning {
+    pub fn meta(&self) -> &Meta {
+        match self {
+            Warning::NonExhaustiveCase { meta, .. }
+            | Warning::UnusedImport { meta, .. }
+            | Warning::UnusedUnqualifiedImport { meta, .. }
+            | Warning::GenericFn { meta, .. }
+            | Warning::DivisionByZero { meta }
+            | Warning::Shadowing { meta, .. }
+            | Warning::Todo { meta }
+            | Warning::UnusedPrivateFn { meta, .. } => meta,
+        }
+    }
+}
+
+impl Error {
+    /// The location of the error. For a duplicate name this is the second definition.
+    ///
+    pub fn meta(&self) -> &Meta {
+        match self {
+            Error::DuplicateName { second, .. } => second,
+            Error::InvalidInlineErlang { meta, .. }
+            | Error::InvalidAssertMessage { meta }
+            | Error::UnknownLabel { meta, .. }
+            | Error::EmptyBlock { meta }
+            | Error::NotNumeric { meta, .. }
+            | Error::UnknownVariable { meta, .. }
+            | Error::UnknownType { meta, .. }
+            | Error::UnknownModule { meta, .. }
+            | Error::UnknownModuleType { meta, .. }
+            | Error::UnknownModuleValue { meta, .. }
+            | Error::UnknownModuleField { meta, .. }
+            | Error::NotFn { meta, .. }
+            | Error::NotModule { meta, .. }
+            | Error::UnknownField { meta, .. }
+            | Error::NotATuple { meta, .. }
+            | Error::OutOfBoundsTupleIndex { meta, .. }
+            | Error::IncorrectArity { meta, .. }
+            | Error::IncorrectTypeArity { meta, .. }
+            | Error::CouldNotUnify { meta, .. }
+            | Error::RecursiveType { meta }
+            | Error::RecursiveTypeAlias { meta, .. }
+            | Error::UnknownExport { meta, .. }
+            | Error::TargetTypeMismatch { meta, .. }
+            | Error::TargetNotFn { meta }
+            | Error::DuplicateArgument { meta, .. }
+            | Error::DuplicateField { meta, .. }
+            | Error::DuplicateConstructorAtom { meta, .. }
+            | Error::PrivateTypeLeak { meta, .. }
+            | Error::UnexpectedLabelledArg { meta, .. }
+            | Error::PositionalArgumentAfterLabelled { meta }
+            | Error::InvalidExternalTarget { meta, .. }
+            | Error::InvalidGuardExpression { meta }
+            | Error::NotStructConstructor { meta }
+            | Error::InvalidConstant { meta }
+            | Error::RefutableArgPattern { meta } => meta,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -1580,230 +1946,476 @@ fn convert_get_type_constructor_error(e: GetTypeConstructorError, meta: &Meta) -
     }
 }
 
-/// Crawl the AST, annotating each node with the inferred type or
-/// returning an error.
+/// The inferred types of the expressions of a module, indexed by their location in the source. Built
+/// from an already inferred module so that inference does no extra work when it is not wanted.
 ///
-pub fn infer_module(
-    module: UntypedModule,
-    modules: &HashMap<String, ModuleTypeInfo>,
-) -> Result<TypedModule, Error> {
-    let mut env = Env::new(modules);
-    let module_name = &module.name;
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TypeSpans {
+    spans: Vec<(Meta, Type)>,
+}
 
-    let statements: Vec<Statement<_, _, _, Type>> = module
-        .statements
-        .into_iter()
-        .map(|s| match s {
-            Statement::Fn {
-                meta,
-                name,
-                public,
-                args,
-                body,
-                return_annotation,
-            } => {
-                let level = 1;
+impl TypeSpans {
+    pub fn from_module(module: &TypedModule) -> Self {
+        let mut spans = Self::default();
+        for statement in module.statements.iter() {
+            if let Statement::Fn { body, .. } = statement {
+                spans.register(body);
+            }
+        }
+        spans
+    }
 
-                // Ensure function has not already been defined in this module
-                if let Some(ValueConstructor {
-                    variant: ValueConstructorVariant::ModuleFn { .. },
-                    ..
-                }) = env.get_variable(&name)
-                {
-                    return Err(Error::DuplicateName { meta, name });
-                };
+    /// The type of the smallest expression enclosing the given source offset.
+    ///
+    pub fn type_at(&self, offset: usize) -> Option<&Type> {
+        self.spans
+            .iter()
+            .filter(|(meta, _)| meta.start <= offset && offset < meta.end)
+            .min_by_key(|(meta, _)| meta.end - meta.start)
+            .map(|(_, typ)| typ)
+    }
 
-                let mut field_map = FieldMap::new(args.len());
-                for (i, arg) in args.iter().enumerate() {
-                    if let ArgNames::NamedLabelled { label, .. } = &arg.names {
-                        field_map
-                            .insert(label.clone(), i)
-                            .map_err(|_| Error::DuplicateField {
-                                label: label.to_string(),
-                                meta: meta.clone(),
-                            })?;
-                    }
-                }
-                let field_map = field_map.into_option();
+    fn register(&mut self, expr: &TypedExpr) {
+        match expr {
+            // These take their location from their final expression so are not recorded
+            // themselves.
+            Expr::Seq { first, then, .. } => {
+                self.register(first);
+                self.register(then);
+                return;
+            }
 
-                // Register a var for the function so that it can call itself recursively
-                let rec = env.new_unbound_var(level + 1);
-                env.insert_variable(
-                    name.clone(),
-                    ValueConstructorVariant::ModuleFn {
-                        field_map: field_map.clone(),
-                        module: module_name.clone(),
-                        arity: args.len(),
-                    },
-                    rec.clone(),
-                );
+            Expr::Let { value, then, .. }
+            | Expr::LetRec { value, then, .. }
+            | Expr::Try { value, then, .. }
+            | Expr::Assert { value, then, .. } => {
+                self.register(value);
+                self.register(then);
+                return;
+            }
 
-                // Infer the type
-                let (args_types, body) =
-                    infer_fun(&args, body, &return_annotation, level + 1, &mut env)?;
-                let typ = Type::Fn {
-                    args: args_types,
-                    retrn: Box::new(body.typ().clone()),
-                };
+            Expr::Int { .. }
+            | Expr::Float { .. }
+            | Expr::String { .. }
+            | Expr::Var { .. }
+            | Expr::Nil { .. }
+            | Expr::ModuleSelect { .. }
+            | Expr::Todo { .. }
+            | Expr::Panic { .. } => (),
 
-                // Assert that the inferred type matches the type of any recursive call
-                unify(&rec, &typ, &mut env).map_err(|e| convert_unify_error(e, &meta))?;
-                let typ = generalise(typ, level);
+            Expr::Fn { body, .. } => self.register(body),
 
-                // Insert the function into the module's interface
-                if public {
-                    if let Some(leaked) = typ.find_private_type() {
-                        return Err(Error::PrivateTypeLeak {
-                            meta: meta.clone(),
-                            leaked,
-                        });
+            Expr::TupleIndex { tuple, .. } => self.register(tuple),
+
+            Expr::Cons { head, tail, .. } => {
+                self.register(head);
+                self.register(tail);
+            }
+
+            Expr::Call { fun, args, .. } => {
+                self.register(fun);
+                args.iter().for_each(|arg| self.register(&arg.value));
+            }
+
+            Expr::BinOp { left, right, .. } => {
+                self.register(left);
+                self.register(right);
+            }
+
+            Expr::Case {
+                subjects, clauses, ..
+            } => {
+                subjects.iter().for_each(|subject| self.register(subject));
+                for clause in clauses.iter() {
+                    if let Some(guard) = &clause.guard {
+                        self.register(guard);
                     }
-                    env.public_module_value_constructors.insert(
-                        name.clone(),
-                        ValueConstructor {
-                            typ: typ.clone(),
-                            variant: ValueConstructorVariant::ModuleFn {
-                                field_map: field_map.clone(),
-                                module: module_name.clone(),
-                                arity: args.len(),
-                            },
-                        },
-                    );
+                    self.register(&clause.then);
                 }
+            }
 
-                // Insert the function into the environment
-                env.insert_variable(
-                    name.clone(),
-                    ValueConstructorVariant::ModuleFn {
-                        field_map,
-                        module: module_name.clone(),
-                        arity: args.len(),
-                    },
-                    typ,
-                );
+            Expr::FieldSelect { container, .. } => self.register(container),
 
-                Ok(Statement::Fn {
-                    meta,
-                    name,
-                    public,
-                    args,
-                    body,
-                    return_annotation,
-                })
+            Expr::AnonStruct { elems, .. } => elems.iter().for_each(|elem| self.register(elem)),
+
+            Expr::StructUpdate { spread, args, .. } => {
+                self.register(spread);
+                args.iter().for_each(|arg| self.register(&arg.value));
             }
 
-            Statement::ExternalFn {
-                meta,
-                name,
-                public,
-                args,
-                retrn,
-                module,
-                fun,
+            Expr::Block { body, .. } => body.iter().for_each(|expr| self.register(expr)),
+
+            Expr::Negate { value, .. } | Expr::AssertTrue { value, .. } => self.register(value),
+        }
+        self.spans.push((expr.meta().clone(), expr.typ().clone()));
+    }
+}
+
+#[test]
+fn type_at_test() {
+    let src = "fn go(x) { let y = [x, 1] struct(y, 2.5) }";
+    let module = infer_src(src, &HashMap::new(), &mut vec![]).expect("should successfully infer");
+    let spans = TypeSpans::from_module(&module);
+    let type_at = |offset| spans.type_at(offset).map(|t| t.to_gleam_string());
+
+    // The literal 1
+    assert_eq!(Some("Int".to_string()), type_at(23));
+    // The x in the list
+    assert_eq!(Some("Int".to_string()), type_at(20));
+    // The list, which is located at its closing bracket
+    assert_eq!(Some("List(Int)".to_string()), type_at(24));
+    // The literal 2.5
+    assert_eq!(Some("Float".to_string()), type_at(37));
+    // The struct, at its keyword
+    assert_eq!(Some("struct(List(Int), Float)".to_string()), type_at(26));
+    // Outside of any expression
+    assert_eq!(None, type_at(0));
+}
+
+fn assert_no_recursive_aliases(statements: &[UntypedStatement]) -> Result<(), Error> {
+    fn refers_to<'a>(
+        typ: &'a TypeAst,
+        target: &str,
+        aliases: &HashMap<&str, &'a TypeAst>,
+        seen: &mut HashSet<&'a str>,
+    ) -> bool {
+        match typ {
+            TypeAst::Constructor {
+                module: None, name, ..
+            } if name == target => true,
+
+            TypeAst::Constructor {
+                module, name, args, ..
             } => {
-                // Construct type of function from AST
-                let mut type_vars = hashmap![];
-                let retrn_type =
-                    env.type_from_ast(&retrn, &mut type_vars, NewTypeAction::MakeGeneric)?;
-                let mut args_types = Vec::with_capacity(args.len());
-                let mut field_map = FieldMap::new(args.len());
-                for (i, arg) in args.iter().enumerate() {
-                    let t =
-                        env.type_from_ast(&arg.typ, &mut type_vars, NewTypeAction::MakeGeneric)?;
-                    args_types.push(t);
-                    if let Some(label) = &arg.label {
-                        field_map
-                            .insert(label.clone(), i)
-                            .map_err(|_| Error::DuplicateField {
-                                label: label.to_string(),
-                                meta: meta.clone(),
-                            })?;
+                let through_alias = match aliases.get(name.as_str()) {
+                    Some(typ) if module.is_none() && seen.insert(name) => {
+                        refers_to(typ, target, aliases, seen)
                     }
-                }
-                let field_map = field_map.into_option();
-                let typ = Type::Fn {
-                    args: args_types,
-                    retrn: Box::new(retrn_type),
+                    _ => false,
                 };
+                through_alias || args.iter().any(|t| refers_to(t, target, aliases, seen))
+            }
 
-                // Insert function into module's public interface
-                if public {
-                    if let Some(leaked) = typ.find_private_type() {
-                        return Err(Error::PrivateTypeLeak {
-                            meta: meta.clone(),
-                            leaked,
-                        });
-                    }
-                    env.public_module_value_constructors.insert(
-                        name.clone(),
-                        ValueConstructor {
-                            typ: typ.clone(),
-                            variant: ValueConstructorVariant::ModuleFn {
-                                field_map: field_map.clone(),
-                                module: module_name.clone(),
-                                arity: args.len(),
-                            },
-                        },
-                    );
+            TypeAst::Fn { args, retrn, .. } => {
+                args.iter().any(|t| refers_to(t, target, aliases, seen))
+                    || refers_to(retrn, target, aliases, seen)
+            }
+
+            TypeAst::AnonStruct { elems, .. } => {
+                elems.iter().any(|t| refers_to(t, target, aliases, seen))
+            }
+
+            TypeAst::Var { .. } => false,
+        }
+    }
+
+    let aliases: HashMap<_, _> = statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::TypeAlias { name, typ, .. } => Some((name.as_str(), typ)),
+            _ => None,
+        })
+        .collect();
+
+    for statement in statements {
+        if let Statement::TypeAlias {
+            meta, name, typ, ..
+        } = statement
+        {
+            if refers_to(typ, name, &aliases, &mut HashSet::new()) {
+                return Err(Error::RecursiveTypeAlias {
+                    meta: meta.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Types and values have separate namespaces, so an enum may have a constructor of the same name.
+///
+fn assert_unique_names(statements: &[UntypedStatement]) -> Result<(), Error> {
+    fn register(names: &mut HashMap<String, Meta>, name: &str, meta: &Meta) -> Result<(), Error> {
+        match names.insert(name.to_string(), meta.clone()) {
+            Some(first) => Err(Error::DuplicateName {
+                name: name.to_string(),
+                first,
+                second: meta.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    let mut values = HashMap::new();
+    let mut types = HashMap::new();
+    // Functions defined for a single target, which may share a name with one for another target
+    let mut targeted: HashMap<_, HashMap<String, Meta>> = HashMap::new();
+
+    for statement in statements {
+        match statement {
+            Statement::Target {
+                target, statement, ..
+            } => match statement.as_ref() {
+                Statement::Fn { meta, name, .. }
+                | Statement::ExternalFn { meta, name, .. }
+                | Statement::InlineErlangFn { meta, name, .. } => {
+                    register(targeted.entry(*target).or_default(), name, meta)?
                 }
+                _ => (),
+            },
 
-                // Insert function into module's internal scope
-                env.insert_variable(
-                    name.clone(),
-                    ValueConstructorVariant::ModuleFn {
-                        module: module_name.clone(),
-                        arity: args.len(),
-                        field_map,
-                    },
-                    typ,
-                );
-                Ok(Statement::ExternalFn {
-                    meta,
-                    name,
-                    public,
-                    args,
-                    retrn,
-                    module,
-                    fun,
-                })
+            Statement::Fn { meta, name, .. }
+            | Statement::ExternalFn { meta, name, .. }
+            | Statement::InlineErlangFn { meta, name, .. }
+            | Statement::Const { meta, name, .. } => register(&mut values, name, meta)?,
+
+            Statement::ExternalType { meta, name, .. }
+            | Statement::TypeAlias { meta, name, .. } => register(&mut types, name, meta)?,
+
+            Statement::Struct { meta, name, .. } => {
+                register(&mut types, name, meta)?;
+                register(&mut values, name, meta)?;
             }
 
-            Statement::Struct {
+            Statement::Enum {
                 meta,
-                public,
                 name,
-                type_args,
-                fields,
+                constructors,
+                ..
             } => {
-                // Register type
-                env.insert_type_constructor(
-                    name.clone(),
-                    TypeConstructorInfo {
-                        module: module_name.clone(),
-                        public,
-                        arity: type_args.len(),
-                    },
-                );
-                // Build return type and collect type vars that can be used by the constructor
-                let mut type_vars = hashmap![];
-                let type_args_types: Vec<_> = type_args
+                register(&mut types, name, meta)?;
+                for constructor in constructors {
+                    register(&mut values, &constructor.name, &constructor.meta)?;
+                }
+            }
+
+            Statement::Import { .. } | Statement::Behaviour { .. } | Statement::Export { .. } => (),
+        }
+    }
+
+    // A definition without a target is compiled for every target so its name cannot be reused
+    for (name, meta) in targeted.values().flat_map(|names| names.iter()) {
+        if let Some(other) = values.get(name) {
+            let (first, second) = if other.start < meta.start {
+                (other.clone(), meta.clone())
+            } else {
+                (meta.clone(), other.clone())
+            };
+            return Err(Error::DuplicateName {
+                name: name.clone(),
+                first,
+                second,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// When a module has an export annotation only the public functions it lists are exported.
+///
+fn restrict_exports(statements: Vec<UntypedStatement>) -> Result<Vec<UntypedStatement>, Error> {
+    let mut exports: Option<HashSet<String>> = None;
+    for statement in statements.iter() {
+        if let Statement::Export { meta, names } = statement {
+            for name in names {
+                let is_public_fn = statements
                     .iter()
-                    .map(|arg| TypeAst::Var {
+                    .any(|s| public_fn_name(s) == Some(name.as_str()));
+                if !is_public_fn {
+                    return Err(Error::UnknownExport {
                         meta: meta.clone(),
-                        name: arg.to_string(),
-                    })
-                    .map(|ast| env.type_from_ast(&ast, &mut type_vars, NewTypeAction::MakeGeneric))
-                    .collect::<Result<_, _>>()?;
-
-                let retrn = Type::App {
-                    public,
-                    module: module_name.clone(),
-                    name: name.clone(),
-                    args: type_args_types,
-                };
-                // Create FieldMap which later can be used to rewrite labelled arguments
-                let mut field_map = FieldMap::new(fields.len());
-                for (i, StructField { label, meta, .. }) in fields.iter().enumerate() {
+                        name: name.clone(),
+                    });
+                }
+            }
+            exports
+                .get_or_insert_with(HashSet::new)
+                .extend(names.iter().cloned());
+        }
+    }
+
+    let exports = match exports {
+        None => return Ok(statements),
+        Some(exports) => exports,
+    };
+
+    fn restrict(statement: &mut UntypedStatement, exports: &HashSet<String>) {
+        match statement {
+            Statement::Fn { name, public, .. }
+            | Statement::ExternalFn { name, public, .. }
+            | Statement::InlineErlangFn { name, public, .. } => {
+                *public = *public && exports.contains(name.as_str())
+            }
+            Statement::Target { statement, .. } => restrict(statement, exports),
+            _ => (),
+        }
+    }
+
+    Ok(statements
+        .into_iter()
+        .map(|mut statement| {
+            restrict(&mut statement, &exports);
+            statement
+        })
+        .collect())
+}
+
+fn public_fn_name<A, B, C, D>(statement: &Statement<A, B, C, D>) -> Option<&str> {
+    match statement {
+        Statement::Fn {
+            name, public: true, ..
+        }
+        | Statement::ExternalFn {
+            name, public: true, ..
+        }
+        | Statement::InlineErlangFn {
+            name, public: true, ..
+        } => Some(name),
+        Statement::Target { statement, .. } => public_fn_name(statement),
+        _ => None,
+    }
+}
+
+/// Whether two types are the same, with type variables matched up one to one so that
+/// `fn(a) -> a` is the same as `fn(b) -> b` but not `fn(a) -> b`.
+///
+fn same_type(one: &Type, other: &Type, vars: &mut Vec<(usize, usize)>) -> bool {
+    if let Type::Var { typ } = one {
+        if let TypeVar::Link { typ } = &*typ.borrow() {
+            return same_type(typ, other, vars);
+        }
+    }
+    if let Type::Var { typ } = other {
+        if let TypeVar::Link { typ } = &*typ.borrow() {
+            return same_type(one, typ, vars);
+        }
+    }
+    let same_types = |one: &[Type], other: &[Type], vars: &mut Vec<(usize, usize)>| {
+        one.len() == other.len() && one.iter().zip(other).all(|(a, b)| same_type(a, b, vars))
+    };
+    match (one, other) {
+        (
+            Type::App {
+                module, name, args, ..
+            },
+            Type::App {
+                module: other_module,
+                name: other_name,
+                args: other_args,
+                ..
+            },
+        ) => module == other_module && name == other_name && same_types(args, other_args, vars),
+
+        (
+            Type::Fn { args, retrn },
+            Type::Fn {
+                args: other_args,
+                retrn: other_retrn,
+            },
+        ) => same_types(args, other_args, vars) && same_type(retrn, other_retrn, vars),
+
+        (Type::AnonStruct { elems }, Type::AnonStruct { elems: other_elems }) => {
+            same_types(elems, other_elems, vars)
+        }
+
+        (Type::Var { typ }, Type::Var { typ: other_typ }) => {
+            let ids = match (&*typ.borrow(), &*other_typ.borrow()) {
+                (TypeVar::Generic { id }, TypeVar::Generic { id: other_id })
+                | (TypeVar::Unbound { id, .. }, TypeVar::Unbound { id: other_id, .. }) => {
+                    (*id, *other_id)
+                }
+                _ => return false,
+            };
+            match vars.iter().find(|(a, b)| *a == ids.0 || *b == ids.1) {
+                Some(pair) => *pair == ids,
+                None => {
+                    vars.push(ids);
+                    true
+                }
+            }
+        }
+
+        _ => false,
+    }
+}
+
+/// Crawl the AST, annotating each node with the inferred type or
+/// returning an error.
+///
+pub fn infer_module(
+    module: UntypedModule,
+    modules: &HashMap<String, ModuleTypeInfo>,
+    warnings: &mut Vec<Warning>,
+) -> Result<TypedModule, Error> {
+    let mut env = Env::new(modules);
+    let module_name = &module.name;
+
+    assert_unique_names(&module.statements)?;
+    assert_no_recursive_aliases(&module.statements)?;
+
+    let statements: Vec<Statement<_, _, _, Type>> = restrict_exports(module.statements)?
+        .into_iter()
+        .map(|s| infer_statement(s, module_name, &mut env))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Remove private and imported type constructors to create the public interface, keeping
+    // any that have been re-exported with `pub import`
+    let reexported_types = &env.reexported_types;
+    env.type_constructors.retain(|name, info| {
+        (info.public && &info.module == module_name) || reexported_types.contains(name)
+    });
+
+    let mut unused_imports: Vec<_> = env
+        .unused_imports
+        .values()
+        .cloned()
+        .map(|(meta, module)| Warning::UnusedImport { meta, module })
+        .chain(env.unused_unqualified_imports.iter().map(|(name, meta)| {
+            Warning::UnusedUnqualifiedImport {
+                meta: meta.clone(),
+                name: name.clone(),
+            }
+        }))
+        .collect();
+    unused_imports.sort_by_key(|warning| warning.meta().start);
+    env.warnings.extend(unused_imports);
+
+    env.warnings.extend(shadowing_warnings(&statements));
+
+    warnings.extend(env.warnings);
+
+    Ok(Module {
+        name: module.name.clone(),
+        statements,
+        type_info: ModuleTypeInfo {
+            name: module.name,
+            type_constructors: env.type_constructors,
+            value_constructors: env.public_module_value_constructors,
+        },
+    })
+}
+
+fn infer_statement(
+    statement: UntypedStatement,
+    module_name: &[String],
+    env: &mut Env,
+) -> Result<TypedStatement, Error> {
+    match statement {
+        Statement::Fn {
+            meta,
+            name,
+            public,
+            args,
+            body,
+            return_annotation,
+        } => {
+            let level = 1;
+
+            let mut field_map = FieldMap::new(args.len());
+            for (i, arg) in args.iter().enumerate() {
+                if let ArgNames::NamedLabelled { label, .. } = &arg.names {
                     field_map
                         .insert(label.clone(), i)
                         .map_err(|_| Error::DuplicateField {
@@ -1811,267 +2423,1023 @@ pub fn infer_module(
                             meta: meta.clone(),
                         })?;
                 }
-                let constructor_variant = ValueConstructorVariant::Struct {
-                    name: name.clone(),
-                    arity: fields.len(),
+            }
+            let field_map = field_map.into_option();
+
+            // Register a var for the function so that it can call itself recursively
+            let rec = env.new_unbound_var(level + 1);
+            env.insert_variable(
+                name.clone(),
+                ValueConstructorVariant::ModuleFn {
+                    field_map: field_map.clone(),
+                    module: module_name.to_vec(),
+                    arity: args.len(),
+                },
+                rec.clone(),
+            );
+
+            // Infer the type
+            let (args, args_types, body) =
+                infer_fun(args, body, &return_annotation, level + 1, env)?;
+            let typ = Type::Fn {
+                args: args_types,
+                retrn: Box::new(body.typ().clone()),
+            };
+
+            // Assert that the inferred type matches the type of any recursive call
+            unify(&rec, &typ, env).map_err(|e| convert_unify_error(e, &meta))?;
+            let typ = generalise(typ, level);
+
+            // Insert the function into the module's interface
+            if public {
+                if let Some(leaked) = typ.find_private_type() {
+                    return Err(Error::PrivateTypeLeak {
+                        meta: meta.clone(),
+                        name: name.clone(),
+                        leaked,
+                    });
+                }
+                env.public_module_value_constructors.insert(
+                    name.clone(),
+                    ValueConstructor {
+                        typ: typ.clone(),
+                        variant: ValueConstructorVariant::ModuleFn {
+                            field_map: field_map.clone(),
+                            module: module_name.to_vec(),
+                            arity: args.len(),
+                        },
+                    },
+                );
+            }
+
+            // Insert the function into the environment
+            env.insert_variable(
+                name.clone(),
+                ValueConstructorVariant::ModuleFn {
                     field_map,
-                };
-                // Register constructor
-                let args_types = fields
-                    .iter()
-                    .map(|StructField { typ: arg, .. }| {
-                        env.type_from_ast(&arg, &mut type_vars, NewTypeAction::Disallow)
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
+                    module: module_name.to_vec(),
+                    arity: args.len(),
+                },
+                typ,
+            );
+
+            Ok(Statement::Fn {
+                meta,
+                name,
+                public,
+                args,
+                body,
+                return_annotation,
+            })
+        }
+
+        Statement::ExternalFn {
+            meta,
+            name,
+            public,
+            args,
+            retrn,
+            module,
+            fun,
+        } => {
+            // Ensure the Erlang module and function names are valid
+            for target in [&module, &fun].iter() {
+                if !is_valid_external_target(target) {
+                    return Err(Error::InvalidExternalTarget {
+                        meta,
+                        target: target.to_string(),
+                    });
+                }
+            }
+
+            register_external_fn(&meta, &name, public, &args, &retrn, module_name, env)?;
+            Ok(Statement::ExternalFn {
+                meta,
+                name,
+                public,
+                args,
+                retrn,
+                module,
+                fun,
+            })
+        }
+
+        Statement::InlineErlangFn {
+            meta,
+            name,
+            public,
+            args,
+            retrn,
+            erlang,
+        } => {
+            // The verbatim Erlang must define a function of the same name and arity
+            if !is_inline_erlang_fn(&erlang, &name, args.len()) {
+                return Err(Error::InvalidInlineErlang {
+                    meta,
+                    name,
+                    arity: args.len(),
+                });
+            }
+            register_external_fn(&meta, &name, public, &args, &retrn, module_name, env)?;
+            Ok(Statement::InlineErlangFn {
+                meta,
+                name,
+                public,
+                args,
+                retrn,
+                erlang,
+            })
+        }
+
+        Statement::Struct {
+            meta,
+            public,
+            opaque,
+            name,
+            type_args,
+            fields,
+        } => {
+            // Register type
+            env.insert_type_constructor(
+                name.clone(),
+                TypeConstructorInfo {
+                    module: module_name.to_vec(),
+                    public,
+                    arity: type_args.len(),
+                    constructors: vec![name.clone()],
+                    opaque,
+                    alias: None,
+                },
+            );
+            // Build return type and collect type vars that can be used by the constructor
+            let mut type_vars = hashmap![];
+            let type_args_types: Vec<_> = type_args
+                .iter()
+                .map(|arg| TypeAst::Var {
+                    meta: meta.clone(),
+                    name: arg.to_string(),
+                })
+                .map(|ast| env.type_from_ast(&ast, &mut type_vars, NewTypeAction::MakeGeneric))
+                .collect::<Result<_, _>>()?;
+
+            let retrn = Type::App {
+                public,
+                module: module_name.to_vec(),
+                name: name.clone(),
+                args: type_args_types,
+            };
+            // Create FieldMap which later can be used to rewrite labelled arguments
+            let mut field_map = FieldMap::new(fields.len());
+            for (i, StructField { label, meta, .. }) in fields.iter().enumerate() {
+                field_map
+                    .insert(label.clone(), i)
+                    .map_err(|_| Error::DuplicateField {
+                        label: label.to_string(),
+                        meta: meta.clone(),
+                    })?;
+            }
+            let constructor_variant = ValueConstructorVariant::Struct {
+                name: name.clone(),
+                arity: fields.len(),
+                field_map,
+            };
+            // Register constructor
+            let args_types = fields
+                .iter()
+                .map(|StructField { typ: arg, .. }| {
+                    env.type_from_ast(&arg, &mut type_vars, NewTypeAction::Disallow)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // Insert constructor function into module scope
+            let typ = match fields.len() {
+                0 => retrn.clone(),
+                _ => Type::Fn {
+                    args: args_types,
+                    retrn: Box::new(retrn.clone()),
+                },
+            };
+            // If the struct is public then record it so that it can be used in other modules
+            if public && !opaque {
+                if let Some(leaked) = typ.find_private_type() {
+                    return Err(Error::PrivateTypeLeak {
+                        meta: meta.clone(),
+                        name: name.clone(),
+                        leaked,
+                    });
+                }
+                env.public_module_value_constructors.insert(
+                    name.clone(),
+                    ValueConstructor {
+                        typ: typ.clone(),
+                        variant: constructor_variant.clone(),
+                    },
+                );
+            };
+            env.struct_constructors.insert(
+                (module_name.to_vec(), name.clone()),
+                ValueConstructor {
+                    typ: typ.clone(),
+                    variant: constructor_variant.clone(),
+                },
+            );
+            env.insert_variable(name.clone(), constructor_variant, typ);
+            Ok(Statement::Struct {
+                meta,
+                public,
+                opaque,
+                name,
+                type_args,
+                fields,
+            })
+        }
+
+        Statement::Enum {
+            meta,
+            public,
+            opaque,
+            name,
+            args,
+            constructors,
+        } => {
+            // Register type
+            env.insert_type_constructor(
+                name.clone(),
+                TypeConstructorInfo {
+                    module: module_name.to_vec(),
+                    public,
+                    arity: args.len(),
+                    constructors: constructors.iter().map(|c| c.name.clone()).collect(),
+                    opaque,
+                    alias: None,
+                },
+            );
+            // Build return type and collect type vars that can be used in constructors
+            let mut type_vars = hashmap![];
+            let args_types: Vec<_> = args
+                .iter()
+                .map(|arg| TypeAst::Var {
+                    meta: meta.clone(),
+                    name: arg.to_string(),
+                })
+                .map(|ast| env.type_from_ast(&ast, &mut type_vars, NewTypeAction::MakeGeneric))
+                .collect::<Result<_, _>>()?;
+
+            let retrn = Type::App {
+                public,
+                module: module_name.to_vec(),
+                name: name.clone(),
+                args: args_types,
+            };
+            // Check and register constructors
+            let mut atoms: HashMap<String, &str> = HashMap::new();
+            for constructor in constructors.iter() {
+                let atom = crate::erl::constructor_atom(&constructor.name);
+                if let Some(other) = atoms.insert(atom.clone(), &constructor.name) {
+                    return Err(Error::DuplicateConstructorAtom {
+                        meta: constructor.meta.clone(),
+                        name: constructor.name.clone(),
+                        other: other.to_string(),
+                        atom,
+                    });
+                }
+                let mut field_map = FieldMap::new(constructor.args.len());
+                let mut args_types = Vec::with_capacity(constructor.args.len());
+                for (i, (label, arg)) in constructor.args.iter().enumerate() {
+                    let t = env.type_from_ast(&arg, &mut type_vars, NewTypeAction::Disallow)?;
+                    args_types.push(t);
+                    if let Some(label) = label {
+                        field_map
+                            .insert(label.clone(), i)
+                            .map_err(|_| Error::DuplicateField {
+                                label: label.to_string(),
+                                meta: meta.clone(),
+                            })?;
+                    }
+                }
+                let field_map = field_map.into_option();
                 // Insert constructor function into module scope
-                let typ = match fields.len() {
+                let typ = match constructor.args.len() {
                     0 => retrn.clone(),
                     _ => Type::Fn {
                         args: args_types,
                         retrn: Box::new(retrn.clone()),
                     },
                 };
-                // If the struct is public then record it so that it can be used in other modules
-                if public {
+                if public && !opaque {
                     if let Some(leaked) = typ.find_private_type() {
                         return Err(Error::PrivateTypeLeak {
-                            meta: meta.clone(),
+                            meta: constructor.meta.clone(),
+                            name: constructor.name.clone(),
                             leaked,
                         });
                     }
                     env.public_module_value_constructors.insert(
-                        name.clone(),
+                        constructor.name.clone(),
                         ValueConstructor {
                             typ: typ.clone(),
-                            variant: constructor_variant.clone(),
+                            variant: ValueConstructorVariant::Enum {
+                                arity: constructor.args.len(),
+                                field_map: field_map.clone(),
+                            },
                         },
                     );
                 };
-                env.insert_variable(name.clone(), constructor_variant, typ);
-                Ok(Statement::Struct {
-                    meta,
+                env.insert_variable(
+                    constructor.name.clone(),
+                    ValueConstructorVariant::Enum {
+                        arity: constructor.args.len(),
+                        field_map,
+                    },
+                    typ,
+                );
+            }
+            Ok(Statement::Enum {
+                meta,
+                public,
+                opaque,
+                name,
+                args,
+                constructors,
+            })
+        }
+
+        Statement::ExternalType {
+            meta,
+            public,
+            name,
+            args,
+        } => {
+            // Register type
+            env.insert_type_constructor(
+                name.clone(),
+                TypeConstructorInfo {
+                    module: module_name.to_vec(),
                     public,
-                    name,
-                    type_args,
-                    fields,
-                })
+                    arity: args.len(),
+                    constructors: vec![],
+                    opaque: true,
+                    alias: None,
+                },
+            );
+            // Check contained types are valid
+            let mut type_vars = hashmap![];
+            for arg in args.iter() {
+                let var = TypeAst::Var {
+                    meta: meta.clone(),
+                    name: arg.to_string(),
+                };
+                env.type_from_ast(&var, &mut type_vars, NewTypeAction::MakeGeneric)?;
+            }
+            Ok(Statement::ExternalType {
+                meta,
+                public,
+                name,
+                args,
+            })
+        }
+
+        Statement::TypeAlias {
+            meta,
+            public,
+            name,
+            args,
+            typ,
+        } => {
+            let mut type_vars = hashmap![];
+            for arg in args.iter() {
+                let var = TypeAst::Var {
+                    meta: meta.clone(),
+                    name: arg.to_string(),
+                };
+                env.type_from_ast(&var, &mut type_vars, NewTypeAction::MakeGeneric)?;
+            }
+            let parameters = args.iter().map(|arg| type_vars[arg].0).collect();
+            let target = env.type_from_ast(&typ, &mut type_vars, NewTypeAction::Disallow)?;
+            if public {
+                if let Some(leaked) = target.find_private_type() {
+                    return Err(Error::PrivateTypeLeak {
+                        meta: meta.clone(),
+                        name: name.clone(),
+                        leaked,
+                    });
+                }
             }
+            env.insert_type_constructor(
+                name.clone(),
+                TypeConstructorInfo {
+                    module: module_name.to_vec(),
+                    public,
+                    arity: args.len(),
+                    constructors: vec![],
+                    opaque: false,
+                    alias: Some(TypeAlias {
+                        parameters,
+                        typ: target,
+                    }),
+                },
+            );
+            Ok(Statement::TypeAlias {
+                meta,
+                public,
+                name,
+                args,
+                typ,
+            })
+        }
+
+        Statement::Behaviour { meta, name } => Ok(Statement::Behaviour { meta, name }),
+
+        Statement::Export { meta, names } => Ok(Statement::Export { meta, names }),
+
+        Statement::Target {
+            meta,
+            target,
+            statement,
+        } => {
+            let statement = infer_statement(*statement, module_name, env)?;
+            let given = match &statement {
+                Statement::Fn { name, .. }
+                | Statement::ExternalFn { name, .. }
+                | Statement::InlineErlangFn { name, .. } => env
+                    .variables
+                    .get(name)
+                    .map(|variable| (name.clone(), variable.typ.clone())),
+                _ => None,
+            };
+            let (name, given) = match given {
+                Some(given) => given,
+                None => return Err(Error::TargetNotFn { meta }),
+            };
+            match env.target_fn_types.get(&name) {
+                Some(expected) if !same_type(expected, &given, &mut vec![]) => {
+                    return Err(Error::TargetTypeMismatch {
+                        meta,
+                        name,
+                        expected: expected.clone(),
+                        given,
+                    })
+                }
+                Some(_) => (),
+                None => {
+                    env.target_fn_types.insert(name, given);
+                }
+            }
+            Ok(Statement::Target {
+                meta,
+                target,
+                statement: Box::new(statement),
+            })
+        }
+
+        Statement::Const {
+            meta,
+            name,
+            public,
+            value,
+        } => {
+            let value = infer(*value, 1, env)?;
+            let literal = constant_literal(&value)?;
+            let constructor = ValueConstructor {
+                typ: value.typ().clone(),
+                variant: ValueConstructorVariant::ModuleConst { literal },
+            };
+            if public {
+                if let Some(leaked) = constructor.typ.find_private_type() {
+                    return Err(Error::PrivateTypeLeak {
+                        meta: meta.clone(),
+                        name: name.clone(),
+                        leaked,
+                    });
+                }
+                env.public_module_value_constructors
+                    .insert(name.clone(), constructor.clone());
+            }
+            env.insert_variable(name.clone(), constructor.variant, constructor.typ);
+            Ok(Statement::Const {
+                meta,
+                name,
+                public,
+                value: Box::new(value),
+            })
+        }
+
+        Statement::Import {
+            meta,
+            public,
+            module,
+            as_name,
+            unqualified,
+        } => {
+            // Find imported module
+            let module_info = env.importable_modules.get(&module.join("/")).expect(
+                "COMPILER BUG: Typer could not find a module being imported.
+This should not be possible. Please report this crash",
+            );
+
+            // Determine local alias of imported module
+            let module_name = match &as_name {
+                None => module[module.len() - 1].clone(),
+                Some(name) => name.clone(),
+            };
+
+            // Insert unqualified imports into scope
+            for UnqualifiedImport { name, meta } in &unqualified {
+                let mut imported = false;
+
+                if let Some(value) = module_info.value_constructors.get(name) {
+                    env.insert_variable(name.clone(), value.variant.clone(), value.typ.clone());
+                    if public {
+                        env.public_module_value_constructors
+                            .insert(name.clone(), value.clone());
+                    }
+                    imported = true;
+                }
+
+                if let Some(typ) = module_info.type_constructors.get(name) {
+                    env.insert_type_constructor(name.clone(), typ.clone());
+                    if public {
+                        env.reexported_types.insert(name.clone());
+                    }
+                    imported = true;
+                }
+
+                if !imported {
+                    return Err(Error::UnknownModuleField {
+                        meta: meta.clone(),
+                        name: name.clone(),
+                        module_name: module.clone(),
+                        value_constructors: module_info.value_constructors.clone(),
+                        type_constructors: module_info.type_constructors.clone(),
+                    });
+                }
+
+                // Re-exported names are used by the modules importing this one
+                if !public {
+                    env.unused_unqualified_imports
+                        .insert(name.clone(), meta.clone());
+                }
+            }
+
+            // Record imports made only to refer to the module by name so that a warning can be
+            // emitted if the module is never referred to
+            if unqualified.is_empty() {
+                env.unused_imports
+                    .insert(module_name.clone(), (meta.clone(), module.join("/")));
+            }
+
+            // Insert imported module into scope
+            env.imported_modules
+                .insert(module_name, module_info.clone());
+
+            Ok(Statement::Import {
+                meta,
+                public,
+                module,
+                as_name,
+                unqualified,
+            })
+        }
+    }
+}
+
+#[test]
+fn infer_module_type_retention_test() {
+    let module: UntypedModule = crate::ast::Module {
+        name: vec!["ok".to_string()],
+        statements: vec![],
+        type_info: (),
+    };
+
+    let module = infer_module(module, &HashMap::new(), &mut vec![]).expect("Should infer OK");
+
+    assert_eq!(
+        module.type_info,
+        ModuleTypeInfo {
+            name: vec!["ok".to_string()],
+            type_constructors: HashMap::new(), // Core type constructors like String and Int are not included
+            value_constructors: HashMap::new(),
+        }
+    );
+}
+
+type Bindings = im::HashMap<String, Meta>;
+
+/// Warn about each binding that shadows an unqualified import, an argument of an enclosing
+/// function, or a variable from outside the current scope. Rebinding within a scope is allowed.
+///
+fn shadowing_warnings(statements: &[TypedStatement]) -> Vec<Warning> {
+    let mut imports = Bindings::new();
+    for statement in statements {
+        if let Statement::Import { unqualified, .. } = statement {
+            for UnqualifiedImport { name, meta } in unqualified {
+                if name.starts_with(|c: char| c.is_lowercase()) {
+                    imports.insert(name.clone(), meta.clone());
+                }
+            }
+        }
+    }
+
+    let mut warnings = vec![];
+    for statement in statements {
+        if let Statement::Fn { args, body, .. } = statement {
+            let outer = shadowing_args(args, &imports, &mut warnings);
+            shadowing_expr(body, &outer, &Bindings::new(), &mut warnings);
+        }
+    }
+    warnings
+}
+
+fn shadowing_bind(
+    name: &str,
+    meta: &Meta,
+    outer: &Bindings,
+    bindings: &mut Bindings,
+    warnings: &mut Vec<Warning>,
+) {
+    if let Some(shadowed) = outer.get(name) {
+        warnings.push(Warning::Shadowing {
+            meta: meta.clone(),
+            name: name.to_string(),
+            shadowed: shadowed.clone(),
+        });
+    }
+    bindings.insert(name.to_string(), meta.clone());
+}
+
+fn shadowing_args(args: &[TypedArg], outer: &Bindings, warnings: &mut Vec<Warning>) -> Bindings {
+    let mut bindings = outer.clone();
+    for arg in args {
+        match &arg.names {
+            ArgNames::Named { name } | ArgNames::NamedLabelled { name, .. } => {
+                shadowing_bind(name, &arg.meta, outer, &mut bindings, warnings)
+            }
+            ArgNames::Pattern { pattern } => {
+                shadowing_pattern(pattern, outer, &mut bindings, warnings)
+            }
+            ArgNames::Discard => (),
+        }
+    }
+    bindings
+}
+
+fn shadowing_pattern(
+    pattern: &TypedPattern,
+    outer: &Bindings,
+    bindings: &mut Bindings,
+    warnings: &mut Vec<Warning>,
+) {
+    match pattern {
+        Pattern::Var { name, meta } => shadowing_bind(name, meta, outer, bindings, warnings),
+
+        Pattern::Cons { head, tail, .. } => {
+            shadowing_pattern(head, outer, bindings, warnings);
+            shadowing_pattern(tail, outer, bindings, warnings);
+        }
+
+        Pattern::Constructor { args, .. } => {
+            for arg in args {
+                shadowing_pattern(&arg.value, outer, bindings, warnings);
+            }
+        }
+
+        Pattern::AnonStruct { elems, .. } => {
+            for elem in elems {
+                shadowing_pattern(elem, outer, bindings, warnings);
+            }
+        }
+
+        Pattern::BitString { segments, .. } => {
+            for segment in segments {
+                shadowing_pattern(&segment.value, outer, bindings, warnings);
+            }
+        }
+
+        Pattern::Int { .. }
+        | Pattern::Float { .. }
+        | Pattern::String { .. }
+        | Pattern::Discard { .. }
+        | Pattern::Nil { .. } => (),
+    }
+}
+
+fn shadowing_expr(
+    expr: &TypedExpr,
+    outer: &Bindings,
+    local: &Bindings,
+    warnings: &mut Vec<Warning>,
+) {
+    // Everything visible here is outside of any nested scope entered from here
+    let nested = || local.clone().union(outer.clone());
+
+    match expr {
+        Expr::Int { .. }
+        | Expr::Float { .. }
+        | Expr::String { .. }
+        | Expr::Var { .. }
+        | Expr::Nil { .. }
+        | Expr::ModuleSelect { .. }
+        | Expr::Todo { .. }
+        | Expr::Panic { .. } => (),
+
+        Expr::Let {
+            value,
+            pattern,
+            then,
+            ..
+        }
+        | Expr::Try {
+            value,
+            pattern,
+            then,
+            ..
+        } => {
+            shadowing_expr(value, outer, local, warnings);
+            let mut local = local.clone();
+            shadowing_pattern(pattern, outer, &mut local, warnings);
+            shadowing_expr(then, outer, &local, warnings);
+        }
+
+        Expr::Assert {
+            value,
+            pattern,
+            message,
+            then,
+            ..
+        } => {
+            shadowing_expr(value, outer, local, warnings);
+            if let Some(message) = message {
+                shadowing_expr(message, outer, local, warnings);
+            }
+            let mut local = local.clone();
+            shadowing_pattern(pattern, outer, &mut local, warnings);
+            shadowing_expr(then, outer, &local, warnings);
+        }
+
+        Expr::LetRec {
+            meta,
+            name,
+            value,
+            then,
+            ..
+        } => {
+            let mut local = local.clone();
+            shadowing_bind(name, meta, outer, &mut local, warnings);
+            shadowing_expr(value, outer, &local, warnings);
+            shadowing_expr(then, outer, &local, warnings);
+        }
 
-            Statement::Enum {
-                meta,
-                public,
-                name,
-                args,
-                constructors,
-            } => {
-                // Register type
-                env.insert_type_constructor(
-                    name.clone(),
-                    TypeConstructorInfo {
-                        module: module_name.clone(),
-                        public,
-                        arity: args.len(),
-                    },
-                );
-                // Build return type and collect type vars that can be used in constructors
-                let mut type_vars = hashmap![];
-                let args_types: Vec<_> = args
-                    .iter()
-                    .map(|arg| TypeAst::Var {
-                        meta: meta.clone(),
-                        name: arg.to_string(),
-                    })
-                    .map(|ast| env.type_from_ast(&ast, &mut type_vars, NewTypeAction::MakeGeneric))
-                    .collect::<Result<_, _>>()?;
+        Expr::Fn { args, body, .. } => {
+            let outer = shadowing_args(args, &nested(), warnings);
+            shadowing_expr(body, &outer, &Bindings::new(), warnings);
+        }
 
-                let retrn = Type::App {
-                    public,
-                    module: module_name.clone(),
-                    name: name.clone(),
-                    args: args_types,
-                };
-                // Check and register constructors
-                for constructor in constructors.iter() {
-                    let mut field_map = FieldMap::new(constructor.args.len());
-                    let mut args_types = Vec::with_capacity(constructor.args.len());
-                    for (i, (label, arg)) in constructor.args.iter().enumerate() {
-                        let t = env.type_from_ast(&arg, &mut type_vars, NewTypeAction::Disallow)?;
-                        args_types.push(t);
-                        if let Some(label) = label {
-                            field_map.insert(label.clone(), i).map_err(|_| {
-                                Error::DuplicateField {
-                                    label: label.to_string(),
-                                    meta: meta.clone(),
-                                }
-                            })?;
-                        }
-                    }
-                    let field_map = field_map.into_option();
-                    // Insert constructor function into module scope
-                    let typ = match constructor.args.len() {
-                        0 => retrn.clone(),
-                        _ => Type::Fn {
-                            args: args_types,
-                            retrn: Box::new(retrn.clone()),
-                        },
-                    };
-                    if public {
-                        if let Some(leaked) = typ.find_private_type() {
-                            return Err(Error::PrivateTypeLeak {
-                                meta: constructor.meta.clone(),
-                                leaked,
-                            });
-                        }
-                        env.public_module_value_constructors.insert(
-                            constructor.name.clone(),
-                            ValueConstructor {
-                                typ: typ.clone(),
-                                variant: ValueConstructorVariant::Enum {
-                                    arity: args.len(),
-                                    field_map: field_map.clone(),
-                                },
-                            },
-                        );
-                    };
-                    env.insert_variable(
-                        constructor.name.clone(),
-                        ValueConstructorVariant::Enum {
-                            arity: constructor.args.len(),
-                            field_map,
-                        },
-                        typ,
-                    );
-                }
-                Ok(Statement::Enum {
-                    meta,
-                    public,
-                    name,
-                    args,
-                    constructors,
-                })
+        Expr::Block { body, .. } => {
+            let outer = nested();
+            for expr in body {
+                shadowing_expr(expr, &outer, &Bindings::new(), warnings);
             }
+        }
 
-            Statement::ExternalType {
-                meta,
-                public,
-                name,
-                args,
-            } => {
-                // Register type
-                env.insert_type_constructor(
-                    name.clone(),
-                    TypeConstructorInfo {
-                        module: module_name.clone(),
-                        public,
-                        arity: args.len(),
-                    },
-                );
-                // Check contained types are valid
-                let mut type_vars = hashmap![];
-                for arg in args.iter() {
-                    let var = TypeAst::Var {
-                        meta: meta.clone(),
-                        name: arg.to_string(),
-                    };
-                    env.type_from_ast(&var, &mut type_vars, NewTypeAction::MakeGeneric)?;
+        Expr::Case {
+            subjects, clauses, ..
+        } => {
+            for subject in subjects {
+                shadowing_expr(subject, outer, local, warnings);
+            }
+            let outer = nested();
+            for clause in clauses {
+                // Variables bound by the clause's patterns are local to the clause
+                let mut local = Bindings::new();
+                for pattern in clause.patterns.iter() {
+                    shadowing_pattern(pattern, &Bindings::new(), &mut local, warnings);
                 }
-                Ok(Statement::ExternalType {
-                    meta,
-                    public,
-                    name,
-                    args,
-                })
+                if let Some(guard) = &clause.guard {
+                    shadowing_expr(guard, &outer, &local, warnings);
+                }
+                shadowing_expr(&clause.then, &outer, &local, warnings);
             }
+        }
 
-            Statement::Import {
-                meta,
-                module,
-                as_name,
-                unqualified,
-            } => {
-                // Find imported module
-                let module_info = env.importable_modules.get(&module.join("/")).expect(
-                    "COMPILER BUG: Typer could not find a module being imported.
-This should not be possible. Please report this crash",
-                );
+        Expr::Seq { first, then, .. } => {
+            shadowing_expr(first, outer, local, warnings);
+            shadowing_expr(then, outer, local, warnings);
+        }
 
-                // Determine local alias of imported module
-                let module_name = match &as_name {
-                    None => module[module.len() - 1].clone(),
-                    Some(name) => name.clone(),
-                };
+        Expr::Cons { head, tail, .. } => {
+            shadowing_expr(head, outer, local, warnings);
+            shadowing_expr(tail, outer, local, warnings);
+        }
+
+        Expr::Call { fun, args, .. } => {
+            shadowing_expr(fun, outer, local, warnings);
+            for arg in args {
+                shadowing_expr(&arg.value, outer, local, warnings);
+            }
+        }
 
-                // Insert unqualified imports into scope
-                for UnqualifiedImport { name, meta } in &unqualified {
-                    let mut imported = false;
+        Expr::BinOp { left, right, .. } => {
+            shadowing_expr(left, outer, local, warnings);
+            shadowing_expr(right, outer, local, warnings);
+        }
 
-                    if let Some(value) = module_info.value_constructors.get(name) {
-                        env.insert_variable(name.clone(), value.variant.clone(), value.typ.clone());
-                        imported = true;
-                    }
+        Expr::FieldSelect { container, .. } => shadowing_expr(container, outer, local, warnings),
 
-                    if let Some(typ) = module_info.type_constructors.get(name) {
-                        env.insert_type_constructor(name.clone(), typ.clone());
-                        imported = true;
-                    }
+        Expr::TupleIndex { tuple, .. } => shadowing_expr(tuple, outer, local, warnings),
 
-                    if !imported {
-                        return Err(Error::UnknownModuleField {
+        Expr::AnonStruct { elems, .. } => {
+            for elem in elems {
+                shadowing_expr(elem, outer, local, warnings);
+            }
+        }
+
+        Expr::StructUpdate {
+            constructor,
+            spread,
+            args,
+            ..
+        } => {
+            shadowing_expr(constructor, outer, local, warnings);
+            shadowing_expr(spread, outer, local, warnings);
+            for arg in args {
+                shadowing_expr(&arg.value, outer, local, warnings);
+            }
+        }
+
+        Expr::Negate { value, .. } | Expr::AssertTrue { value, .. } => {
+            shadowing_expr(value, outer, local, warnings)
+        }
+    }
+}
+
+/// Warn about each public function without type annotations that was inferred to take and return
+/// only type variables, such as `fn(a) -> a`.
+///
+pub fn generic_fn_warnings(module: &TypedModule) -> Vec<Warning> {
+    let is_var = |typ: &Type| matches!(resolve_links(typ), Type::Var { .. });
+
+    module
+        .statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Fn {
+                meta,
+                name,
+                args,
+                public: true,
+                return_annotation: None,
+                ..
+            } if args.iter().all(|arg| arg.annotation.is_none()) => {
+                let typ = &module.type_info.value_constructors.get(name)?.typ;
+                match typ {
+                    Type::Fn { args, retrn } if args.iter().all(is_var) && is_var(retrn) => {
+                        Some(Warning::GenericFn {
                             meta: meta.clone(),
                             name: name.clone(),
-                            module_name: module.clone(),
-                            value_constructors: module_info.value_constructors.clone(),
-                            type_constructors: module_info.type_constructors.clone(),
-                        });
+                            typ: typ.clone(),
+                        })
                     }
+                    _ => None,
                 }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn generic_fn_warnings_test() {
+    let warnings = |src| {
+        let module = infer_src(src, &HashMap::new(), &mut vec![]).expect("should infer");
+        generic_fn_warnings(&module)
+            .into_iter()
+            .map(|warning| match warning {
+                Warning::GenericFn { name, typ, .. } => (name, typ.to_gleam_string()),
+                _ => panic!("unexpected warning {:?}", warning),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        warnings("pub fn id(x) { x }"),
+        vec![("id".to_string(), "fn(a) -> a".to_string())]
+    );
+    assert_eq!(
+        warnings("pub fn first(x, y) { x }"),
+        vec![("first".to_string(), "fn(a, b) -> a".to_string())]
+    );
+    assert_eq!(warnings("fn id(x) { x }"), vec![]);
+    assert_eq!(warnings("pub fn id(x: a) -> a { x }"), vec![]);
+    assert_eq!(warnings("pub fn inc(x) { x + 1 }"), vec![]);
+    assert_eq!(warnings("pub fn wrap(x) { [x] }"), vec![]);
+}
 
-                // Insert imported module into scope
-                env.imported_modules
-                    .insert(module_name, module_info.clone());
+/// Warn about each private function that is not referenced by any other function or constant in
+/// its module. A function that only calls itself is still unused.
+///
+pub fn unused_fn_warnings(module: &TypedModule) -> Vec<Warning> {
+    struct References<'a> {
+        module: &'a [String],
+        current: Option<String>,
+        used: std::collections::HashSet<String>,
+    }
 
-                Ok(Statement::Import {
-                    meta,
-                    module,
-                    as_name,
-                    unqualified,
-                })
+    impl<'a> crate::ast::Visitor<ValueConstructor, ModuleValueConstructor, PatternConstructor, Type>
+        for References<'a>
+    {
+        fn visit_statement(&mut self, statement: &TypedStatement) {
+            self.current = match statement {
+                Statement::Fn { name, .. } => Some(name.clone()),
+                _ => None,
+            };
+        }
+
+        fn visit_expr(&mut self, expr: &TypedExpr) {
+            if let Expr::Var {
+                name,
+                constructor:
+                    ValueConstructor {
+                        variant: ValueConstructorVariant::ModuleFn { module, .. },
+                        ..
+                    },
+                ..
+            } = expr
+            {
+                if module.as_slice() == self.module && self.current.as_ref() != Some(name) {
+                    self.used.insert(name.clone());
+                }
             }
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+        }
+    }
 
-    // Remove private and imported type constructors to create the public interface
-    env.type_constructors
-        .retain(|_, info| info.public && &info.module == module_name);
+    let mut references = References {
+        module: &module.name,
+        current: None,
+        used: Default::default(),
+    };
+    crate::ast::walk(&mut references, module);
 
-    Ok(Module {
-        name: module.name.clone(),
-        statements,
-        type_info: ModuleTypeInfo {
-            name: module.name,
-            type_constructors: env.type_constructors,
-            value_constructors: env.public_module_value_constructors,
-        },
-    })
+    module
+        .statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Fn {
+                meta,
+                name,
+                public: false,
+                ..
+            }
+            | Statement::ExternalFn {
+                meta,
+                name,
+                public: false,
+                ..
+            } if !references.used.contains(name) => Some(Warning::UnusedPrivateFn {
+                meta: meta.clone(),
+                name: name.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
 }
 
 #[test]
-fn infer_module_type_retention_test() {
-    let module: UntypedModule = crate::ast::Module {
-        name: vec!["ok".to_string()],
-        statements: vec![],
-        type_info: (),
+fn unused_fn_warnings_test() {
+    let warnings = |src| {
+        let module = infer_src(src, &HashMap::new(), &mut vec![]).expect("should infer");
+        unused_fn_warnings(&module)
     };
 
-    let module = infer_module(module, &HashMap::new()).expect("Should infer OK");
+    assert_eq!(
+        warnings("fn unused() { 1 } pub fn go() { 2 }"),
+        vec![Warning::UnusedPrivateFn {
+            meta: Meta { start: 0, end: 17 },
+            name: "unused".to_string(),
+        }]
+    );
+    assert_eq!(warnings("fn used() { 1 } pub fn go() { used() }"), vec![]);
+    assert_eq!(warnings("fn used() { 1 } pub fn go() { used }"), vec![]);
+    assert_eq!(warnings("pub fn go() { 1 }"), vec![]);
 
+    // Calls to itself do not make a function used
     assert_eq!(
-        module.type_info,
-        ModuleTypeInfo {
-            name: vec!["ok".to_string()],
-            type_constructors: HashMap::new(), // Core type constructors like String and Int are not included
-            value_constructors: HashMap::new(),
-        }
+        warnings("fn loop(x) { loop(x) } pub fn go() { 1 }"),
+        vec![Warning::UnusedPrivateFn {
+            meta: Meta { start: 0, end: 22 },
+            name: "loop".to_string(),
+        }]
+    );
+    assert_eq!(
+        warnings("fn loop(x) { loop(x) } pub fn go() { loop(1) }"),
+        vec![]
+    );
+
+    // A local variable with the same name is not a reference to the function
+    assert_eq!(
+        warnings(r#"external fn size() -> Int = "a" "b" pub fn go(size) { size }"#),
+        vec![Warning::UnusedPrivateFn {
+            meta: Meta { start: 0, end: 35 },
+            name: "size".to_string(),
+        }]
     );
 }
 
@@ -2098,56 +3466,186 @@ pub fn infer(expr: UntypedExpr, level: usize, env: &mut Env) -> Result<TypedExpr
             typ: string(),
         }),
 
-        Expr::Nil { meta, .. } => Ok(Expr::Nil {
-            meta,
-            typ: list(env.new_unbound_var(level)),
-        }),
+        Expr::Nil { meta, .. } => Ok(Expr::Nil {
+            meta,
+            typ: list(env.new_unbound_var(level)),
+        }),
+
+        // Todo and panic crash rather than return, so they can be used where a value of any type
+        // is expected
+        Expr::Todo { meta, message, .. } => {
+            env.warnings.push(Warning::Todo { meta: meta.clone() });
+            Ok(Expr::Todo {
+                meta,
+                message,
+                typ: env.new_unbound_var(level),
+            })
+        }
+
+        Expr::Panic { meta, message, .. } => Ok(Expr::Panic {
+            meta,
+            message,
+            typ: env.new_unbound_var(level),
+        }),
+
+        Expr::Seq { first, then, .. } => {
+            let first = infer(*first, level, env)?;
+            let then = infer(*then, level, env)?;
+            Ok(Expr::Seq {
+                typ: then.typ().clone(),
+                first: Box::new(first),
+                then: Box::new(then),
+            })
+        }
+
+        Expr::Fn {
+            meta,
+            is_capture,
+            args,
+            body,
+            ..
+        } => {
+            let (args, args_types, body) = infer_fun(args, *body, &None, level, env)?;
+            let typ = Type::Fn {
+                args: args_types,
+                retrn: Box::new(body.typ().clone()),
+            };
+
+            Ok(Expr::Fn {
+                meta,
+                typ,
+                is_capture,
+                args,
+                body: Box::new(body),
+            })
+        }
+
+        Expr::Let {
+            meta,
+            pattern,
+            annotation,
+            value,
+            then,
+            ..
+        } => {
+            let value = infer(*value, level + 1, env)?;
+
+            // Check that any type annotation is accurate.
+            if let Some(ann) = &annotation {
+                let ann_typ =
+                    env.type_from_ast(ann, &mut hashmap![], NewTypeAction::MakeGeneric)?;
+                unify(&ann_typ, value.typ(), env)
+                    .map_err(|e| convert_unify_error(e, ann.meta()))?;
+            }
+
+            let value_typ = generalise(value.typ().clone(), level + 1);
+            let pattern = unify_pattern(pattern, &value_typ, level, env)?;
+            let then = infer(*then, level, env)?;
+            let typ = then.typ().clone();
+            Ok(Expr::Let {
+                meta,
+                typ,
+                pattern,
+                annotation,
+                value: Box::new(value),
+                then: Box::new(then),
+            })
+        }
+
+        Expr::LetRec {
+            meta,
+            name,
+            value,
+            then,
+            ..
+        } => {
+            // Register a var for the function so that it can call itself recursively
+            let rec = env.new_unbound_var(level + 1);
+            env.insert_variable(
+                name.clone(),
+                ValueConstructorVariant::LocalVariable,
+                rec.clone(),
+            );
+
+            let value = infer(*value, level + 1, env)?;
+            unify(&rec, value.typ(), env).map_err(|e| convert_unify_error(e, value.meta()))?;
+            let value_typ = generalise(value.typ().clone(), level + 1);
+            env.insert_variable(
+                name.clone(),
+                ValueConstructorVariant::LocalVariable,
+                value_typ,
+            );
 
-        Expr::Seq { first, then, .. } => {
-            let first = infer(*first, level, env)?;
             let then = infer(*then, level, env)?;
-            Ok(Expr::Seq {
-                typ: then.typ().clone(),
-                first: Box::new(first),
+            let typ = then.typ().clone();
+            Ok(Expr::LetRec {
+                meta,
+                typ,
+                name,
+                value: Box::new(value),
                 then: Box::new(then),
             })
         }
 
-        Expr::Fn {
+        Expr::Assert {
             meta,
-            is_capture,
-            args,
-            body,
+            pattern,
+            value,
+            message,
+            then,
             ..
         } => {
-            let (args_types, body) = infer_fun(&args, *body, &None, level, env)?;
-            let typ = Type::Fn {
-                args: args_types,
-                retrn: Box::new(body.typ().clone()),
+            let value = infer(*value, level + 1, env)?;
+            let value_typ = generalise(value.typ().clone(), level + 1);
+            let pattern = unify_pattern(pattern, &value_typ, level, env)?;
+
+            // The message is embedded in the error the assert crashes with, so it must be a
+            // string literal
+            let message = match message.map(|message| *message) {
+                Some(message @ Expr::String { .. }) => Some(Box::new(infer(message, level, env)?)),
+                Some(message) => {
+                    return Err(Error::InvalidAssertMessage {
+                        meta: message.meta().clone(),
+                    })
+                }
+                None => None,
             };
 
-            Ok(Expr::Fn {
+            let then = infer(*then, level, env)?;
+            let typ = then.typ().clone();
+            Ok(Expr::Assert {
                 meta,
                 typ,
-                is_capture,
-                args,
-                body: Box::new(body),
+                pattern,
+                value: Box::new(value),
+                message,
+                then: Box::new(then),
             })
         }
 
-        Expr::Let {
+        Expr::Try {
             meta,
             pattern,
             value,
             then,
             ..
         } => {
-            let value = infer(*value, level + 1, env)?;
-            let value_typ = generalise(value.typ().clone(), level + 1);
-            let pattern = unify_pattern(pattern, &value_typ, level, env)?;
+            let value = infer(*value, level, env)?;
+            let ok = env.new_unbound_var(level);
+            let error = env.new_unbound_var(level);
+            unify(&result(ok.clone(), error.clone()), value.typ(), env)
+                .map_err(|e| convert_unify_error(e, value.meta()))?;
+            let pattern = unify_pattern(pattern, &ok, level, env)?;
+
+            // The rest of the function must also return a Result with the same error type so
+            // that the error value can be returned in its place
             let then = infer(*then, level, env)?;
+            let then_ok = env.new_unbound_var(level);
+            unify(&result(then_ok, error), then.typ(), env)
+                .map_err(|e| convert_unify_error(e, then.meta()))?;
+
             let typ = then.typ().clone();
-            Ok(Expr::Let {
+            Ok(Expr::Try {
                 meta,
                 typ,
                 pattern,
@@ -2189,17 +3687,28 @@ pub fn infer(expr: UntypedExpr, level: usize, env: &mut Env) -> Result<TypedExpr
                     typed_patterns.push(pattern);
                 }
 
+                let guard = match clause.guard {
+                    Some(guard) => Some(infer_clause_guard(guard, level, env)?),
+                    None => None,
+                };
+
                 let then = infer(clause.then, level, env)?;
                 unify(&return_type, then.typ(), env)
                     .map_err(|e| convert_unify_error(e, then.meta()))?;
                 typed_clauses.push(Clause {
                     meta: clause.meta,
                     patterns: typed_patterns,
+                    guard,
                     then,
                 });
 
                 env.variables = vars;
             }
+
+            if let [subject] = typed_subjects.as_slice() {
+                check_case_exhaustiveness(&meta, subject.typ(), &typed_clauses, env);
+            }
+
             Ok(Expr::Case {
                 meta,
                 typ: return_type,
@@ -2213,8 +3722,14 @@ pub fn infer(expr: UntypedExpr, level: usize, env: &mut Env) -> Result<TypedExpr
         } => {
             let head = infer(*head, level, env)?;
             let tail = infer(*tail, level, env)?;
-            unify(tail.typ(), &list(head.typ().clone()), env)
-                .map_err(|e| convert_unify_error(e, &meta))?;
+            // Report a mismatch at the next element of the list rather than the whole list, with
+            // the type of the earlier elements as the one expected
+            let tail_meta = match &tail {
+                Expr::Cons { head, .. } => head.meta(),
+                _ => tail.meta(),
+            };
+            unify(&list(head.typ().clone()), tail.typ(), env)
+                .map_err(|e| convert_unify_error(e, tail_meta))?;
             Ok(Expr::Cons {
                 meta,
                 typ: tail.typ().clone(),
@@ -2252,34 +3767,126 @@ pub fn infer(expr: UntypedExpr, level: usize, env: &mut Env) -> Result<TypedExpr
             left,
             right,
             ..
-        } => {
-            let fun = Expr::Var {
-                meta: meta.clone(),
-                constructor: (),
-                name: bin_op_name(&name),
-            };
-            let args = vec![
-                CallArg {
-                    meta: Default::default(),
-                    label: None,
-                    value: *left,
-                },
-                CallArg {
-                    meta: Default::default(),
-                    label: None,
-                    value: *right,
+        } => match (name, *right) {
+            // Piping into a call inserts the value as the first argument, so
+            // `x |> f(a, b)` is the same as `f(x, a, b)`
+            (
+                BinOp::Pipe,
+                Expr::Call {
+                    meta: call_meta,
+                    fun,
+                    mut args,
+                    ..
                 },
-            ];
-            let (_fun, mut args, typ) = infer_call(fun, args, level, &meta, env)?;
-            Ok(Expr::BinOp {
+            ) => {
+                args.insert(
+                    0,
+                    CallArg {
+                        meta: left.meta().clone(),
+                        label: None,
+                        value: *left,
+                    },
+                );
+                let (fun, args, typ) = infer_call(*fun, args, level, &call_meta, env)?;
+                Ok(Expr::Call {
+                    meta,
+                    typ,
+                    args,
+                    fun: Box::new(fun),
+                })
+            }
+
+            (name, right) => {
+                if is_division(&name) && is_literal_zero(&right) {
+                    env.warnings.push(Warning::DivisionByZero {
+                        meta: right.meta().clone(),
+                    });
+                }
+                let fun = Expr::Var {
+                    meta: meta.clone(),
+                    constructor: (),
+                    name: bin_op_name(&name),
+                };
+                let args = vec![
+                    CallArg {
+                        meta: Default::default(),
+                        label: None,
+                        value: *left,
+                    },
+                    CallArg {
+                        meta: Default::default(),
+                        label: None,
+                        value: right,
+                    },
+                ];
+                let (_fun, mut args, typ) = infer_call(fun, args, level, &meta, env)?;
+                Ok(Expr::BinOp {
+                    meta,
+                    name,
+                    typ,
+                    right: Box::new(args.pop().unwrap().value),
+                    left: Box::new(args.pop().unwrap().value),
+                })
+            }
+        },
+
+        Expr::StructUpdate {
+            meta,
+            constructor,
+            spread,
+            args,
+            ..
+        } => infer_struct_update(*constructor, *spread, args, level, meta, env),
+
+        Expr::AssertTrue {
+            meta,
+            value,
+            source,
+            ..
+        } => {
+            let value = infer(*value, level, env)?;
+            unify(&bool(), value.typ(), env).map_err(|e| convert_unify_error(e, value.meta()))?;
+            Ok(Expr::AssertTrue {
                 meta,
-                name,
-                typ,
-                right: Box::new(args.pop().unwrap().value),
-                left: Box::new(args.pop().unwrap().value),
+                typ: nil(),
+                value: Box::new(value),
+                source,
             })
         }
 
+        Expr::Negate { meta, value, .. } => {
+            let value = infer(*value, level, env)?;
+            // Both Ints and Floats can be negated. If the type of the value is not yet known it is
+            // taken to be an Int.
+            let typ = resolve_links(value.typ());
+            if typ != float() {
+                unify(&int(), &typ, env).map_err(|_| Error::NotNumeric {
+                    meta: value.meta().clone(),
+                    typ,
+                })?;
+            }
+            Ok(Expr::Negate {
+                meta,
+                typ: value.typ().clone(),
+                value: Box::new(value),
+            })
+        }
+
+        Expr::Block { meta, body, .. } => {
+            // Variables defined within the block are not in scope after it
+            let vars = env.variables.clone();
+            let body = body
+                .into_iter()
+                .map(|expr| infer(expr, level, env))
+                .collect::<Result<Vec<_>, _>>()?;
+            env.variables = vars;
+            let typ = match body.last() {
+                Some(last) => last.typ().clone(),
+                None => return Err(Error::EmptyBlock { meta }),
+            };
+            Ok(Expr::Block { meta, typ, body })
+        }
+
         Expr::Var { meta, name, .. } => {
             let constructor = infer_var(&name, level, &meta, env)?;
             Ok(Expr::Var {
@@ -2302,6 +3909,10 @@ pub fn infer(expr: UntypedExpr, level: usize, env: &mut Env) -> Result<TypedExpr
             _ => infer_value_field_select(*container, label, level, select_meta, env),
         },
 
+        Expr::TupleIndex {
+            meta, index, tuple, ..
+        } => infer_tuple_index(*tuple, index, meta, level, env),
+
         // This node is not created by the parser, it is constructed by the typer from
         // the more general FieldSelect. Because of this it should never be present in AST
         // being inferred.
@@ -2314,6 +3925,173 @@ https://github.com/lpil/gleam/issues
     }
 }
 
+/// Warn if a case on a single subject does not match each constructor of its type. Clauses with
+/// guards or nested refutable patterns do not count as covering a constructor.
+///
+fn check_case_exhaustiveness(
+    meta: &Meta,
+    subject_type: &Type,
+    clauses: &[TypedClause],
+    env: &mut Env,
+) {
+    let (module, name) = match resolve_links(subject_type) {
+        Type::App { module, name, .. } => (module, name),
+        _ => return,
+    };
+    let mut missing = match env.get_type_constructor_by_module(&module, &name) {
+        Some(info) if !info.constructors.is_empty() => info.constructors.clone(),
+        _ => return,
+    };
+
+    for clause in clauses.iter().filter(|clause| clause.guard.is_none()) {
+        match clause.patterns.as_slice() {
+            [Pattern::Var { .. }] | [Pattern::Discard { .. }] => return,
+
+            [Pattern::Constructor { name, args, .. }]
+                if args.iter().all(|arg| {
+                    matches!(arg.value, Pattern::Var { .. } | Pattern::Discard { .. })
+                }) =>
+            {
+                missing.retain(|constructor| constructor != name)
+            }
+
+            _ => (),
+        }
+    }
+
+    if !missing.is_empty() {
+        env.warnings.push(Warning::NonExhaustiveCase {
+            meta: meta.clone(),
+            missing,
+        });
+    }
+}
+
+fn is_division(name: &BinOp) -> bool {
+    matches!(name, BinOp::DivInt | BinOp::DivFloat | BinOp::ModuloInt)
+}
+
+fn is_literal_zero(expr: &UntypedExpr) -> bool {
+    match expr {
+        Expr::Int { value, .. } => {
+            let digits = match value.get(..2) {
+                Some("0x") | Some("0o") | Some("0b") => &value[2..],
+                _ => &value[..],
+            };
+            digits.chars().all(|c| c == '0' || c == '_')
+        }
+        Expr::Float { value, .. } => *value == 0.0,
+        _ => false,
+    }
+}
+
+fn resolve_links(typ: &Type) -> Type {
+    if let Type::Var { typ } = typ {
+        if let TypeVar::Link { typ } = &*typ.borrow() {
+            return resolve_links(typ);
+        }
+    }
+    typ.clone()
+}
+
+/// Module constants must be literals or other constants, as they are inlined where used.
+///
+fn constant_literal(value: &TypedExpr) -> Result<Literal, Error> {
+    match value {
+        Expr::Int { value, .. } => Ok(Literal::Int {
+            value: value.clone(),
+        }),
+
+        Expr::Float { value, .. } => Ok(Literal::Float { value: *value }),
+
+        Expr::String { value, .. } => Ok(Literal::String {
+            value: value.clone(),
+        }),
+
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::ModuleConst { literal },
+                    ..
+                },
+            ..
+        }
+        | Expr::ModuleSelect {
+            constructor: ModuleValueConstructor::Const { literal },
+            ..
+        } => Ok(literal.clone()),
+
+        _ => Err(Error::InvalidConstant {
+            meta: value.meta().clone(),
+        }),
+    }
+}
+
+/// Clause guards are compiled to Erlang guards, so they must evaluate to a Bool and may only
+/// contain the subset of expressions that Erlang permits in a guard.
+///
+fn infer_clause_guard(guard: UntypedExpr, level: usize, env: &mut Env) -> Result<TypedExpr, Error> {
+    let guard = infer(guard, level, env)?;
+    unify(&bool(), guard.typ(), env).map_err(|e| convert_unify_error(e, guard.meta()))?;
+    assert_guard_safe(&guard)?;
+    Ok(guard)
+}
+
+fn assert_guard_safe(expr: &TypedExpr) -> Result<(), Error> {
+    match expr {
+        Expr::Int { .. } | Expr::Float { .. } | Expr::String { .. } | Expr::Nil { .. } => Ok(()),
+
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::LocalVariable,
+                    ..
+                },
+            ..
+        }
+        | Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::ModuleConst { .. },
+                    ..
+                },
+            ..
+        }
+        | Expr::ModuleSelect {
+            constructor: ModuleValueConstructor::Const { .. },
+            ..
+        }
+        | Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::Enum { arity: 0, .. },
+                    ..
+                },
+            ..
+        } => Ok(()),
+
+        Expr::Cons { head, tail, .. } => {
+            assert_guard_safe(head)?;
+            assert_guard_safe(tail)
+        }
+
+        Expr::AnonStruct { elems, .. } => elems.iter().try_for_each(assert_guard_safe),
+
+        Expr::TupleIndex { tuple, .. } => assert_guard_safe(tuple),
+
+        Expr::BinOp {
+            name, left, right, ..
+        } if *name != BinOp::Pipe => {
+            assert_guard_safe(left)?;
+            assert_guard_safe(right)
+        }
+
+        _ => Err(Error::InvalidGuardExpression {
+            meta: expr.meta().clone(),
+        }),
+    }
+}
+
 fn infer_module_select(
     module_alias: &str,
     label: String,
@@ -2323,6 +4101,7 @@ fn infer_module_select(
     env: &mut Env,
 ) -> Result<TypedExpr, Error> {
     let (module_name, constructor) = {
+        env.unused_imports.remove(module_alias);
         let module_info =
             env.imported_modules
                 .get(&*module_alias)
@@ -2354,17 +4133,161 @@ fn infer_module_select(
     })
 }
 
+fn infer_struct_update(
+    constructor: UntypedExpr,
+    spread: UntypedExpr,
+    args: Vec<UpdateArg<UntypedExpr>>,
+    level: usize,
+    meta: Meta,
+    env: &mut Env,
+) -> Result<TypedExpr, Error> {
+    let constructor = infer(constructor, level, env)?;
+    let field_map = match &constructor {
+        Expr::Var {
+            constructor:
+                ValueConstructor {
+                    variant: ValueConstructorVariant::Struct { field_map, .. },
+                    ..
+                },
+            ..
+        } => field_map.clone(),
+
+        _ => {
+            return Err(Error::NotStructConstructor {
+                meta: constructor.meta().clone(),
+            })
+        }
+    };
+    let (field_types, typ) = match constructor.typ().clone() {
+        Type::Fn { args, retrn } => (args, *retrn),
+        typ => (vec![], typ),
+    };
+
+    let spread = infer(spread, level, env)?;
+    unify(&typ, spread.typ(), env).map_err(|e| convert_unify_error(e, spread.meta()))?;
+
+    let mut updated = std::collections::HashSet::new();
+    let args = args
+        .into_iter()
+        .map(|UpdateArg { label, meta, value }| {
+            let index = match field_map.index(&label) {
+                Some(index) => index,
+                None => {
+                    return Err(Error::UnknownLabel {
+                        meta,
+                        label,
+                        labels: field_map.fields.clone(),
+                    })
+                }
+            };
+            if !updated.insert(index) {
+                return Err(Error::DuplicateArgument { meta, label });
+            }
+            let value = infer(value, level, env)?;
+            unify(&field_types[index], value.typ(), env)
+                .map_err(|e| convert_unify_error(e, value.meta()))?;
+            Ok(UpdateArg { label, meta, value })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Expr::StructUpdate {
+        meta,
+        typ,
+        constructor: Box::new(constructor),
+        spread: Box::new(spread),
+        args,
+    })
+}
+
+/// Struct fields are accessed by their position in the tuple representing the struct, so field
+/// access is inferred as a tuple index.
+///
 fn infer_value_field_select(
     container: UntypedExpr,
-    _label: String,
+    label: String,
     level: usize,
-    _meta: Meta,
+    meta: Meta,
     env: &mut Env,
 ) -> Result<TypedExpr, Error> {
-    Err(Error::NotModule {
-        meta: container.meta().clone(),
-        typ: infer(container, level, env)?.typ().clone(),
-    })
+    let container = infer(container, level, env)?;
+    let typ = container.typ().clone().collapse_links();
+
+    let constructor = match &typ {
+        Type::App { module, name, .. } => env.struct_constructor(module, name).cloned(),
+        _ => None,
+    };
+    let (constructor_typ, field_map) = match constructor {
+        Some(ValueConstructor {
+            typ,
+            variant: ValueConstructorVariant::Struct { field_map, .. },
+        }) => (typ, field_map),
+        _ => {
+            return Err(Error::NotModule {
+                meta: container.meta().clone(),
+                typ,
+            })
+        }
+    };
+
+    let index = match field_map.fields.get(&label) {
+        Some(index) => *index,
+        None => {
+            let mut fields: Vec<_> = field_map.fields.keys().cloned().collect();
+            fields.sort();
+            return Err(Error::UnknownField {
+                meta,
+                label,
+                typ,
+                fields,
+            });
+        }
+    };
+
+    match instantiate(constructor_typ, level, &mut hashmap![], env) {
+        Type::Fn { args, retrn } => {
+            unify(&retrn, &typ, env).map_err(|e| convert_unify_error(e, container.meta()))?;
+            Ok(Expr::TupleIndex {
+                meta,
+                typ: args[index].clone(),
+                index: index as u64 + 1,
+                tuple: Box::new(container),
+            })
+        }
+        _ => unreachable!("struct with fields has a constructor function"),
+    }
+}
+
+/// The type of a tuple must already be known when one of its elements is accessed by index, as
+/// the number of elements cannot be inferred from the index alone.
+///
+fn infer_tuple_index(
+    tuple: UntypedExpr,
+    index: u64,
+    meta: Meta,
+    level: usize,
+    env: &mut Env,
+) -> Result<TypedExpr, Error> {
+    let tuple = infer(tuple, level, env)?;
+    match tuple.typ().clone().collapse_links() {
+        Type::AnonStruct { elems } => match elems.get(index as usize) {
+            Some(typ) => Ok(Expr::TupleIndex {
+                meta,
+                typ: typ.clone(),
+                index,
+                tuple: Box::new(tuple),
+            }),
+            None => Err(Error::OutOfBoundsTupleIndex {
+                meta,
+                index,
+                size: elems.len(),
+            }),
+        },
+
+        typ => Err(Error::NotATuple {
+            meta: tuple.meta().clone(),
+            typ,
+        }),
+    }
 }
 
 /// When we have an assignment or a case expression we unify the pattern with the
@@ -2449,6 +4372,36 @@ fn unify_pattern(
             }
         },
 
+        Pattern::BitString { meta, segments } => {
+            unify(typ, &bit_string(), env).map_err(|e| convert_unify_error(e, &meta))?;
+            let segments = segments
+                .into_iter()
+                .map(|segment| {
+                    // A size variable must already be bound, either earlier in this pattern or in
+                    // an enclosing scope
+                    if let Some(BitSegmentSize::Var { meta, name }) = &segment.size {
+                        let size_typ = env
+                            .get_value_constructor(None, name)
+                            .map_err(|e| convert_get_value_constructor_error(e, meta))?
+                            .typ
+                            .clone();
+                        unify(&int(), &size_typ, env).map_err(|e| convert_unify_error(e, meta))?;
+                    }
+                    let value_typ = match segment.kind {
+                        BitSegmentKind::Int => int(),
+                        BitSegmentKind::Binary => bit_string(),
+                    };
+                    Ok(BitSegment {
+                        meta: segment.meta,
+                        value: unify_pattern(segment.value, &value_typ, level, env)?,
+                        kind: segment.kind,
+                        size: segment.size,
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Pattern::BitString { meta, segments })
+        }
+
         Pattern::Constructor {
             meta,
             module,
@@ -2475,6 +4428,7 @@ fn unify_pattern(
                     PatternConstructor::Struct { name: name.clone() }
                 }
                 ValueConstructorVariant::LocalVariable
+                | ValueConstructorVariant::ModuleConst { .. }
                 | ValueConstructorVariant::ModuleFn { .. } => panic!(
                     "Unexpected value constructor type for a constructor pattern.
 This is a bug in the Gleam compiler.
@@ -2539,20 +4493,147 @@ Please report this to https://github.com/lpil/gleam/issues"
     }
 }
 
+fn register_external_fn(
+    meta: &Meta,
+    name: &str,
+    public: bool,
+    args: &[ExternalFnArg],
+    retrn: &TypeAst,
+    module_name: &[String],
+    env: &mut Env,
+) -> Result<(), Error> {
+    // Construct type of function from AST
+    let mut type_vars = hashmap![];
+    let retrn_type = env.type_from_ast(retrn, &mut type_vars, NewTypeAction::MakeGeneric)?;
+    let mut args_types = Vec::with_capacity(args.len());
+    let mut field_map = FieldMap::new(args.len());
+    for (i, arg) in args.iter().enumerate() {
+        let t = env.type_from_ast(&arg.typ, &mut type_vars, NewTypeAction::MakeGeneric)?;
+        args_types.push(t);
+        if let Some(label) = &arg.label {
+            field_map
+                .insert(label.clone(), i)
+                .map_err(|_| Error::DuplicateField {
+                    label: label.to_string(),
+                    meta: meta.clone(),
+                })?;
+        }
+    }
+    let field_map = field_map.into_option();
+    let typ = Type::Fn {
+        args: args_types,
+        retrn: Box::new(retrn_type),
+    };
+
+    // Insert function into module's public interface
+    if public {
+        if let Some(leaked) = typ.find_private_type() {
+            return Err(Error::PrivateTypeLeak {
+                meta: meta.clone(),
+                name: name.to_string(),
+                leaked,
+            });
+        }
+        env.public_module_value_constructors.insert(
+            name.to_string(),
+            ValueConstructor {
+                typ: typ.clone(),
+                variant: ValueConstructorVariant::ModuleFn {
+                    field_map: field_map.clone(),
+                    module: module_name.to_vec(),
+                    arity: args.len(),
+                },
+            },
+        );
+    }
+
+    // Insert function into module's internal scope
+    env.insert_variable(
+        name.to_string(),
+        ValueConstructorVariant::ModuleFn {
+            module: module_name.to_vec(),
+            arity: args.len(),
+            field_map,
+        },
+        typ,
+    );
+    Ok(())
+}
+
+/// Whether the Erlang source defines a function with the given name and arity, judged by the head
+/// of its first clause.
+///
+fn is_inline_erlang_fn(erlang: &str, name: &str, arity: usize) -> bool {
+    let erlang = erlang.trim();
+    let rest = match erlang
+        .strip_prefix(name)
+        .or_else(|| erlang.strip_prefix(&format!("'{}'", name)))
+        .and_then(|rest| rest.strip_prefix('('))
+    {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut depth = 0;
+    let mut commas = 0;
+    let mut empty = true;
+    let mut chars = rest.char_indices();
+    let close = loop {
+        match chars.next() {
+            None => return false,
+            Some((i, ')')) if depth == 0 => break i,
+            Some((_, '(')) | Some((_, '[')) | Some((_, '{')) => depth += 1,
+            Some((_, ')')) | Some((_, ']')) | Some((_, '}')) => depth -= 1,
+            Some((_, ',')) if depth == 0 => commas += 1,
+            Some((_, '$')) => {
+                chars.next();
+            }
+            Some((_, quote)) if quote == '"' || quote == '\'' => loop {
+                match chars.next() {
+                    None => return false,
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some((_, c)) if c == quote => break,
+                    Some(_) => (),
+                }
+            },
+            Some((_, c)) if c.is_whitespace() => continue,
+            Some(_) => (),
+        }
+        empty = false;
+    };
+
+    let params = if empty { 0 } else { commas + 1 };
+    let after = rest[close + 1..].trim_start();
+    params == arity
+        && (after.starts_with("->") || after.starts_with("when"))
+        && erlang.ends_with('.')
+}
+
+/// External functions are called using the Erlang module and function names given, so these
+/// must be atoms that can be written in the generated Erlang.
+///
+fn is_valid_external_target(name: &str) -> bool {
+    use regex::Regex;
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_@.]*$").unwrap();
+    }
+    RE.is_match(name)
+}
+
 fn infer_var(
     name: &str,
     level: usize,
     meta: &Meta,
     env: &mut Env,
 ) -> Result<ValueConstructor, Error> {
-    let ValueConstructor { variant, typ } =
-        env.get_variable(name)
-            .cloned()
-            .ok_or_else(|| Error::UnknownVariable {
-                meta: meta.clone(),
-                name: name.to_string(),
-                variables: env.variables.clone(),
-            })?;
+    // Constructors are resolved the same way here as in patterns so that any constructor
+    // usable in an expression can also be matched on.
+    let ValueConstructor { variant, typ } = env
+        .get_value_constructor(None, name)
+        .map_err(|e| convert_get_value_constructor_error(e, meta))?
+        .clone();
     let typ = instantiate(typ, level, &mut hashmap![], env);
     Ok(ValueConstructor { variant, typ })
 }
@@ -2602,7 +4683,7 @@ fn assert_no_labelled_arguments<A>(args: &[CallArg<A>]) -> Result<(), Error> {
 
 fn get_field_map<'a>(
     constructor: &TypedExpr,
-    env: &'a Env,
+    env: &'a mut Env,
 ) -> Result<Option<&'a FieldMap>, GetValueConstructorError> {
     let (module, name) = match constructor {
         Expr::ModuleSelect {
@@ -2620,12 +4701,12 @@ fn get_field_map<'a>(
 }
 
 fn infer_fun(
-    args: &[Arg],
+    args: Vec<UntypedArg>,
     body: UntypedExpr,
     return_annotation: &Option<TypeAst>,
     level: usize,
     env: &mut Env,
-) -> Result<(Vec<Type>, TypedExpr), Error> {
+) -> Result<(Vec<TypedArg>, Vec<Type>, TypedExpr), Error> {
     // Construct an initial type for each argument of the function- either an unbound type variable
     // or a type provided by an annotation.
     let mut type_vars = hashmap![];
@@ -2648,16 +4729,11 @@ fn infer_fun(
 
     // Insert arguments into function body scope.
     let previous_vars = env.variables.clone();
-    for (arg, t) in args.iter().zip(args_types.iter()) {
-        match &arg.names {
-            ArgNames::Named { name } | ArgNames::NamedLabelled { name, .. } => env.insert_variable(
-                name.to_string(),
-                ValueConstructorVariant::LocalVariable,
-                (*t).clone(),
-            ),
-            ArgNames::Discard => (),
-        };
-    }
+    let args = args
+        .into_iter()
+        .zip(args_types.iter())
+        .map(|(arg, t)| infer_arg(arg, t, level, env))
+        .collect::<Result<_, _>>()?;
 
     let body = infer(body, level, env)?;
 
@@ -2670,7 +4746,96 @@ fn infer_fun(
     // Reset the env now that the scope of the function has ended.
     env.variables = previous_vars;
     env.annotated_generic_types = previous_annotated_generic_types;
-    Ok((args_types, body))
+    Ok((args, args_types, body))
+}
+
+/// An argument given as a pattern is matched when the function is called, so it must match any
+/// value of its type.
+///
+fn infer_arg(arg: UntypedArg, typ: &Type, level: usize, env: &mut Env) -> Result<TypedArg, Error> {
+    let names = match arg.names {
+        ArgNames::Discard => ArgNames::Discard,
+
+        ArgNames::Named { name } => {
+            env.insert_variable(
+                name.clone(),
+                ValueConstructorVariant::LocalVariable,
+                typ.clone(),
+            );
+            ArgNames::Named { name }
+        }
+
+        ArgNames::NamedLabelled { name, label } => {
+            env.insert_variable(
+                name.clone(),
+                ValueConstructorVariant::LocalVariable,
+                typ.clone(),
+            );
+            ArgNames::NamedLabelled { name, label }
+        }
+
+        ArgNames::Pattern { pattern } => {
+            let pattern = unify_pattern(pattern, typ, level, env)?;
+            if !is_irrefutable(&pattern, env) {
+                return Err(Error::RefutableArgPattern {
+                    meta: pattern.meta().clone(),
+                });
+            }
+            ArgNames::Pattern { pattern }
+        }
+    };
+    Ok(Arg {
+        names,
+        meta: arg.meta,
+        annotation: arg.annotation,
+    })
+}
+
+fn is_irrefutable(pattern: &TypedPattern, env: &mut Env) -> bool {
+    match pattern {
+        Pattern::Var { .. } | Pattern::Discard { .. } => true,
+
+        Pattern::AnonStruct { elems, .. } => elems.iter().all(|elem| is_irrefutable(elem, env)),
+
+        Pattern::Constructor {
+            name,
+            module,
+            args,
+            constructor,
+            ..
+        } => {
+            let only_constructor = match constructor {
+                PatternConstructor::Struct { .. } => true,
+                PatternConstructor::Enum => is_only_constructor(module.as_ref(), name, env),
+            };
+            only_constructor && args.iter().all(|arg| is_irrefutable(&arg.value, env))
+        }
+
+        Pattern::Int { .. }
+        | Pattern::Float { .. }
+        | Pattern::String { .. }
+        | Pattern::Nil { .. }
+        | Pattern::Cons { .. }
+        | Pattern::BitString { .. } => false,
+    }
+}
+
+fn is_only_constructor(module: Option<&String>, name: &str, env: &mut Env) -> bool {
+    let typ = match env.get_value_constructor(module, name) {
+        Ok(constructor) => constructor.typ.clone(),
+        Err(_) => return false,
+    };
+    let retrn = match typ {
+        Type::Fn { retrn, .. } => *retrn,
+        typ => typ,
+    };
+    match retrn {
+        Type::App { module, name, .. } => matches!(
+            env.get_type_constructor_by_module(&module, &name),
+            Some(info) if info.constructors.len() == 1
+        ),
+        _ => false,
+    }
 }
 
 fn bin_op_name(name: &BinOp) -> String {
@@ -2697,6 +4862,7 @@ fn bin_op_name(name: &BinOp) -> String {
         BinOp::DivInt => "/".to_string(),
         BinOp::DivFloat => "/.".to_string(),
         BinOp::ModuloInt => "%".to_string(),
+        BinOp::Concatenate => "<>".to_string(),
     }
 }
 
@@ -3121,6 +5287,15 @@ pub fn bool() -> Type {
     }
 }
 
+pub fn nil() -> Type {
+    Type::App {
+        args: vec![],
+        public: true,
+        name: "Nil".to_string(),
+        module: vec![],
+    }
+}
+
 pub fn string() -> Type {
     Type::App {
         args: vec![],
@@ -3130,6 +5305,24 @@ pub fn string() -> Type {
     }
 }
 
+pub fn bit_string() -> Type {
+    Type::App {
+        args: vec![],
+        public: true,
+        name: "BitString".to_string(),
+        module: vec![],
+    }
+}
+
+pub fn result(ok: Type, error: Type) -> Type {
+    Type::App {
+        public: true,
+        name: "Result".to_string(),
+        module: vec![],
+        args: vec![ok, error],
+    }
+}
+
 pub fn list(t: Type) -> Type {
     Type::App {
         public: true,
@@ -3149,13 +5342,7 @@ fn infer_test() {
             let result =
                 infer(ast, 1, &mut Env::new(&HashMap::new())).expect("should successfully infer");
             assert_eq!(
-                (
-                    $src,
-                    result
-                        .typ()
-                        .to_gleam_doc(&mut hashmap![], &mut 0)
-                        .format(80)
-                ),
+                ($src, result.typ().to_gleam_string()),
                 ($src, $typ.to_string()),
             );
         };
@@ -3165,8 +5352,27 @@ fn infer_test() {
     assert_infer!("False", "Bool");
     assert_infer!("1", "Int");
     assert_infer!("-2", "Int");
+    assert_infer!("0xFF", "Int");
+    assert_infer!("0o17", "Int");
+    assert_infer!("0b10", "Int");
+    assert_infer!("1_000", "Int");
     assert_infer!("1.0", "Float");
     assert_infer!("-8.0", "Float");
+
+    // arithmetic
+    assert_infer!("1 + 2", "Int");
+    assert_infer!("1 - 2", "Int");
+    assert_infer!("1 * 2", "Int");
+    assert_infer!("1 / 2", "Int");
+    assert_infer!("7 % 2", "Int");
+    assert_infer!("fn(x, y) { x / y }", "fn(Int, Int) -> Int");
+    assert_infer!("fn(x, y) { x % y }", "fn(Int, Int) -> Int");
+    assert_infer!("1.0 +. 2.0", "Float");
+    assert_infer!("1.0 -. 2.0", "Float");
+    assert_infer!("1.0 *. 2.0", "Float");
+    assert_infer!("1.0 /. 2.0", "Float");
+    assert_infer!("fn(x) { x *. 2.0 }", "fn(Float) -> Float");
+
     assert_infer!("\"ok\"", "String");
     assert_infer!("\"ok\"", "String");
     assert_infer!("[]", "List(a)");
@@ -3175,11 +5381,54 @@ fn infer_test() {
     assert_infer!("4 >= 1", "Bool");
     assert_infer!("4 <= 1", "Bool");
     assert_infer!("4 < 1", "Bool");
+    assert_infer!("4.0 >. 1.0", "Bool");
+    assert_infer!("4.0 >=. 1.0", "Bool");
+    assert_infer!("4.0 <=. 1.0", "Bool");
+    assert_infer!("4.0 <. 1.0", "Bool");
+    assert_infer!("True && False", "Bool");
+    assert_infer!("True || False", "Bool");
+    assert_infer!("fn(a, b) { a && b }", "fn(Bool, Bool) -> Bool");
+    assert_infer!("1 == 2", "Bool");
+    assert_infer!("\"a\" != \"b\"", "Bool");
+    assert_infer!("struct(1, []) == struct(2, [1.0])", "Bool");
+    assert_infer!("fn(a, b) { a == b }", "fn(a, a) -> Bool");
+    assert_infer!("fn(a, b) { a != b }", "fn(a, a) -> Bool");
+    assert_infer!("\"a\" <> \"b\"", "String");
+    assert_infer!("\"a\" <> \"b\" <> \"c\"", "String");
+
+    // todo and panic
+    assert_infer!("todo", "a");
+    assert_infer!("panic as \"unreachable\"", "a");
+    assert_infer!("fn() { todo as \"later\" }", "fn() -> a");
+    assert_infer!("let x: Int = panic x", "Int");
+    assert_infer!("case 1 { 1 -> 1.0 _ -> panic }", "Float");
+    assert_infer!("[1, todo, 3]", "List(Int)");
+
+    // bit string patterns
+    assert_infer!(
+        "fn(x) { case x { <<version:8, _:binary>> -> version } }",
+        "fn(BitString) -> Int"
+    );
+    assert_infer!(
+        "fn(x) { case x { <<_:8, rest:binary>> -> rest } }",
+        "fn(BitString) -> BitString"
+    );
+    assert_infer!(
+        "fn(x) { case x { <<len, body:binary-size(len), _:binary>> -> body } }",
+        "fn(BitString) -> BitString"
+    );
+    assert_infer!(
+        "fn(x, size) { let <<a:size(size), 1:4>> = x a }",
+        "fn(BitString, Int) -> Int"
+    );
 
     // let
     assert_infer!("let x = 1 2", "Int");
     assert_infer!("let x = 1 x", "Int");
     assert_infer!("let x = 2.0 x", "Float");
+    assert_infer!("let x: Int = 1 x", "Int");
+    assert_infer!("let x: List(Int) = [] x", "List(Int)");
+    assert_infer!("let f: fn(Int) -> Int = fn(x) { x } f", "fn(Int) -> Int");
     assert_infer!("let x = 2 let y = x y", "Int");
 
     // list
@@ -3198,6 +5447,10 @@ fn infer_test() {
     assert_infer!("[fn(x) { x } | []]", "List(fn(a) -> a)");
     assert_infer!("let f = fn(x) { x } [f, f]", "List(fn(a) -> a)");
     assert_infer!("let x = [1 | []] [2 | x]", "List(Int)");
+    assert_infer!("let x = [2] [1, ..x]", "List(Int)");
+    assert_infer!("let x = [3] [1, 2, ..x]", "List(Int)");
+    assert_infer!("[1, 2, ..[]]", "List(Int)");
+    assert_infer!("fn(x) { [1, ..x] }", "fn(List(Int)) -> List(Int)");
     assert_infer!("[struct([], [])]", "List(struct(List(a), List(b)))");
 
     // anon structs
@@ -3208,6 +5461,14 @@ fn infer_test() {
         "struct(1, 2.0, struct(1, 1))",
         "struct(Int, Float, struct(Int, Int))",
     );
+    assert_infer!("#(1, \"a\", 2.0)", "struct(Int, String, Float)");
+    assert_infer!("#(1, #(2.0))", "struct(Int, struct(Float))");
+    assert_infer!("#(1, \"a\", 2.0).1", "String");
+    assert_infer!("let pair = #(1, #(2.0)) pair.1", "struct(Float)");
+    assert_infer!(
+        "fn(pair: #(Int, Float)) { pair.0 }",
+        "fn(struct(Int, Float)) -> Int"
+    );
 
     // fn
     assert_infer!("fn(x) { x }", "fn(a) -> a");
@@ -3256,52 +5517,244 @@ fn infer_test() {
         "let id = fn(a) { a } fn(x) { x(id) }",
         "fn(fn(fn(a) -> a) -> b) -> b",
     );
-    assert_infer!("let add = fn(x, y) { x + y } add(_, 2)", "fn(Int) -> Int");
-    assert_infer!("fn(x) { struct(1, x) }", "fn(a) -> struct(Int, a)");
-    assert_infer!("fn(x, y) { struct(x, y) }", "fn(a, b) -> struct(a, b)");
-    assert_infer!("fn(x) { struct(x, x) }", "fn(a) -> struct(a, a)");
+    assert_infer!("let add = fn(x, y) { x + y } add(_, 2)", "fn(Int) -> Int");
+    assert_infer!("fn(x) { struct(1, x) }", "fn(a) -> struct(Int, a)");
+    assert_infer!("fn(x, y) { struct(x, y) }", "fn(a, b) -> struct(a, b)");
+    assert_infer!("fn(x) { struct(x, x) }", "fn(a) -> struct(a, a)");
+
+    // case
+    assert_infer!("case 1 { a -> 1 }", "Int");
+    assert_infer!("case 1 { a -> 1.0 b -> 2.0 c -> 3.0 }", "Float");
+    assert_infer!("case 1 { a -> a }", "Int");
+    assert_infer!("case 1 { 1 -> 10 2 -> 20 x -> x * 10 }", "Int");
+    assert_infer!("case 2.0 { 2.0 -> 1 x -> 0 }", "Int");
+    assert_infer!(r#"case "ok" { "ko" -> 1 x -> 0 }"#, "Int");
+
+    // Multiple subject case
+    assert_infer!("case 1, 2.0 { a, b -> a }", "Int");
+    assert_infer!("case 1, 2.0 { a, b -> b }", "Float");
+    assert_infer!("case 1, 2.0, 3 { a, b, c -> a + c }", "Int");
+
+    // case with guards
+    assert_infer!("case 1 { a if a > 0 -> 1 _ -> 0 }", "Int");
+    assert_infer!(
+        "case 1, 2 { a, b if a == b && b < 3 -> 1.0 _, _ -> 0.0 }",
+        "Float"
+    );
+
+    // let
+    assert_infer!("let [] = [] 1", "Int");
+    assert_infer!("let [a] = [1] a", "Int");
+    assert_infer!("let [a, 2] = [1] a", "Int");
+    assert_infer!("let [a | [b | []]] = [1] a", "Int");
+    assert_infer!("fn(x) { let [a] = x a }", "fn(List(a)) -> a");
+    assert_infer!("fn(x) { let [a] = x a + 1 }", "fn(List(Int)) -> Int");
+    assert_infer!("let _x = 1 2.0", "Float");
+    assert_infer!("let _ = 1 2.0", "Float");
+    assert_infer!("let struct(tag, x) = struct(1.0, 1) x", "Int");
+    assert_infer!("fn(x) { let struct(a, b) = x a }", "fn(struct(a, b)) -> a");
+    assert_infer!("let #(tag, x) = #(1.0, 1) x", "Int");
+
+    // boolean assert
+    assert_infer!("assert 1 == 1", "Nil");
+    assert_infer!("fn(x) { assert x > 0 x }", "fn(Int) -> Int");
+
+    // let rec
+    assert_infer!(
+        "let rec fact = fn(n) { case n { 0 -> 1 n -> n * fact(n - 1) } } fact(5)",
+        "Int"
+    );
+    assert_infer!("let rec loop = fn(x) { loop(x) } loop", "fn(a) -> b");
+
+    // local named functions
+    assert_infer!("let y = 1 fn add(x) { x + y } add(2)", "Int");
+    assert_infer!(
+        "fn count(n) { case n { 0 -> [] n -> [n | count(n - 1)] } } count",
+        "fn(Int) -> List(Int)"
+    );
+
+    // negation
+    assert_infer!("-1", "Int");
+    assert_infer!("-1.5", "Float");
+    assert_infer!("let x = 1 let y = -x y", "Int");
+    assert_infer!("let x = 1.0 let y = -x y", "Float");
+    assert_infer!("let x = 1 let y = - -x y", "Int");
+    assert_infer!("let x = 2 let y = -x * 3 y", "Int");
+    assert_infer!("let x = 2 { -x }", "Int");
+    assert_infer!("fn(x) { -x }", "fn(Int) -> Int");
+    assert_infer!(
+        "fn(x) { let y = x +. 1.0 let z = -x z }",
+        "fn(Float) -> Float"
+    );
+
+    // if else
+    assert_infer!("if True { 1 } else { 2 }", "Int");
+    assert_infer!("if 1 > 2 { let x = 1.0 x } else { 2.0 }", "Float");
+    assert_infer!(
+        "fn(x) { if x { 1 } else if x { 2 } else { 3 } }",
+        "fn(Bool) -> Int"
+    );
+    assert_infer!(
+        "fn(x, y) { if x == y { [] } else { [x] } }",
+        "fn(a, a) -> List(a)"
+    );
+
+    // block
+    assert_infer!("{ 1 }", "Int");
+    assert_infer!("{ 1 2.0 }", "Float");
+    assert_infer!("{ let x = 1 x + 1 }", "Int");
+    assert_infer!("let x = 1.0 let y = { let x = 1 x } x", "Float");
+    assert_infer!("case 1 { 1 -> { let y = 2 y } _ -> 0 }", "Int");
+    assert_infer!(
+        "fn(x) { case x { True -> { let x = 1 x } False -> 0 } }",
+        "fn(Bool) -> Int"
+    );
+}
+
+#[test]
+fn infer_error_test() {
+    macro_rules! assert_error {
+        ($src:expr, $error:expr $(,)?) => {
+            let ast = crate::grammar::ExprParser::new()
+                .parse($src)
+                .expect("syntax error");
+            let result =
+                infer(ast, 1, &mut Env::new(&HashMap::new())).expect_err("should infer an error");
+            assert_eq!(($src, $error), ($src, result));
+        };
+    }
+
+    assert_error!(
+        "-\"hello\"",
+        Error::NotNumeric {
+            meta: Meta { start: 1, end: 8 },
+            typ: string(),
+        },
+    );
+
+    assert_error!(
+        "let x = True let y = -x y",
+        Error::NotNumeric {
+            meta: Meta { start: 22, end: 23 },
+            typ: bool(),
+        },
+    );
+
+    assert_error!(
+        "if 1 { 1 } else { 2 }",
+        Error::CouldNotUnify {
+            meta: Meta { start: 3, end: 4 },
+            expected: int(),
+            given: bool(),
+        },
+    );
+
+    assert_error!(
+        "if True { 1 } else { 2.0 }",
+        Error::CouldNotUnify {
+            meta: Meta { start: 21, end: 24 },
+            expected: int(),
+            given: float(),
+        },
+    );
+
+    assert_error!(
+        "let msg = \"no\" assert 1 = 1 as msg 2",
+        Error::InvalidAssertMessage {
+            meta: Meta { start: 31, end: 34 },
+        },
+    );
+
+    assert_error!(
+        "assert 1 + 1",
+        Error::CouldNotUnify {
+            meta: Meta { start: 7, end: 12 },
+            expected: bool(),
+            given: int(),
+        },
+    );
+
+    assert_error!(
+        "case 1 { 1 -> {} _ -> 0 }",
+        Error::EmptyBlock {
+            meta: Meta { start: 14, end: 16 },
+        },
+    );
+
+    assert_error!(
+        "1 + 1.0",
+        Error::CouldNotUnify {
+            meta: Meta { start: 4, end: 7 },
+            expected: int(),
+            given: float(),
+        },
+    );
+
+    assert_error!(
+        "let x: Int = \"a\" x",
+        Error::CouldNotUnify {
+            meta: Meta { start: 7, end: 10 },
+            expected: int(),
+            given: string(),
+        },
+    );
+
+    assert_error!(
+        "let x: Thing = 1 x",
+        Error::UnknownType {
+            meta: Meta { start: 7, end: 12 },
+            name: "Thing".to_string(),
+            types: Env::new(&HashMap::new()).type_constructors,
+        },
+    );
+
+    assert_error!(
+        "1 + \"a\"",
+        Error::CouldNotUnify {
+            meta: Meta { start: 4, end: 7 },
+            expected: int(),
+            given: string(),
+        },
+    );
 
-    // case
-    assert_infer!("case 1 { a -> 1 }", "Int");
-    assert_infer!("case 1 { a -> 1.0 b -> 2.0 c -> 3.0 }", "Float");
-    assert_infer!("case 1 { a -> a }", "Int");
-    assert_infer!("case 1 { 1 -> 10 2 -> 20 x -> x * 10 }", "Int");
-    assert_infer!("case 2.0 { 2.0 -> 1 x -> 0 }", "Int");
-    assert_infer!(r#"case "ok" { "ko" -> 1 x -> 0 }"#, "Int");
+    assert_error!(
+        "[1, \"a\"]",
+        Error::CouldNotUnify {
+            meta: Meta { start: 4, end: 7 },
+            expected: int(),
+            given: string(),
+        },
+    );
 
-    // Multiple subject case
-    assert_infer!("case 1, 2.0 { a, b -> a }", "Int");
-    assert_infer!("case 1, 2.0 { a, b -> b }", "Float");
-    assert_infer!("case 1, 2.0, 3 { a, b, c -> a + c }", "Int");
+    assert_error!(
+        "[1, 2 | [\"a\"]]",
+        Error::CouldNotUnify {
+            meta: Meta { start: 9, end: 12 },
+            expected: int(),
+            given: string(),
+        },
+    );
 
-    // let
-    assert_infer!("let [] = [] 1", "Int");
-    assert_infer!("let [a] = [1] a", "Int");
-    assert_infer!("let [a, 2] = [1] a", "Int");
-    assert_infer!("let [a | [b | []]] = [1] a", "Int");
-    assert_infer!("fn(x) { let [a] = x a }", "fn(List(a)) -> a");
-    assert_infer!("fn(x) { let [a] = x a + 1 }", "fn(List(Int)) -> Int");
-    assert_infer!("let _x = 1 2.0", "Float");
-    assert_infer!("let _ = 1 2.0", "Float");
-    assert_infer!("let struct(tag, x) = struct(1.0, 1) x", "Int");
-    assert_infer!("fn(x) { let struct(a, b) = x a }", "fn(struct(a, b)) -> a");
-}
+    assert_error!(
+        "let tail = [\"a\"] [1, 2, ..tail]",
+        Error::CouldNotUnify {
+            meta: Meta { start: 26, end: 30 },
+            expected: int(),
+            given: string(),
+        },
+    );
 
-#[test]
-fn infer_error_test() {
-    macro_rules! assert_error {
-        ($src:expr, $error:expr $(,)?) => {
-            let ast = crate::grammar::ExprParser::new()
-                .parse($src)
-                .expect("syntax error");
-            let result =
-                infer(ast, 1, &mut Env::new(&HashMap::new())).expect_err("should infer an error");
-            assert_eq!(($src, $error), ($src, result));
-        };
-    }
+    assert_error!(
+        "1 * 2.0",
+        Error::CouldNotUnify {
+            meta: Meta { start: 4, end: 7 },
+            expected: int(),
+            given: float(),
+        },
+    );
 
     assert_error!(
-        "1 + 1.0",
+        "5 / 2.0",
         Error::CouldNotUnify {
             meta: Meta { start: 4, end: 7 },
             expected: int(),
@@ -3309,6 +5762,15 @@ fn infer_error_test() {
         },
     );
 
+    assert_error!(
+        "1.0 /. 2",
+        Error::CouldNotUnify {
+            meta: Meta { start: 7, end: 8 },
+            expected: float(),
+            given: int(),
+        },
+    );
+
     assert_error!(
         "1 +. 1.0",
         Error::CouldNotUnify {
@@ -3318,6 +5780,24 @@ fn infer_error_test() {
         },
     );
 
+    assert_error!(
+        "\"a\" <> 1",
+        Error::CouldNotUnify {
+            meta: Meta { start: 7, end: 8 },
+            expected: string(),
+            given: int(),
+        },
+    );
+
+    assert_error!(
+        "1.0 <> \"b\"",
+        Error::CouldNotUnify {
+            meta: Meta { start: 0, end: 3 },
+            expected: string(),
+            given: float(),
+        },
+    );
+
     assert_error!(
         "1 == 1.0",
         Error::CouldNotUnify {
@@ -3327,6 +5807,42 @@ fn infer_error_test() {
         },
     );
 
+    assert_error!(
+        "1 != 1.0",
+        Error::CouldNotUnify {
+            meta: Meta { start: 5, end: 8 },
+            expected: int(),
+            given: float(),
+        },
+    );
+
+    assert_error!(
+        "True && 1",
+        Error::CouldNotUnify {
+            meta: Meta { start: 8, end: 9 },
+            expected: bool(),
+            given: int(),
+        },
+    );
+
+    assert_error!(
+        "1 || False",
+        Error::CouldNotUnify {
+            meta: Meta { start: 0, end: 1 },
+            expected: bool(),
+            given: int(),
+        },
+    );
+
+    assert_error!(
+        "\"a\" < \"b\"",
+        Error::CouldNotUnify {
+            meta: Meta { start: 0, end: 3 },
+            expected: int(),
+            given: string(),
+        },
+    );
+
     assert_error!(
         "1 > 1.0",
         Error::CouldNotUnify {
@@ -3363,6 +5879,34 @@ fn infer_error_test() {
         },
     );
 
+    // A discard outside of a pattern or capture is not a variable
+    assert_error!(
+        "[_]",
+        Error::UnknownVariable {
+            meta: Meta { start: 1, end: 2 },
+            name: "_".to_string(),
+            variables: Env::new(&HashMap::new()).variables,
+        },
+    );
+
+    assert_error!(
+        "let <<a:size(n)>> = todo a",
+        Error::UnknownVariable {
+            meta: Meta { start: 13, end: 14 },
+            name: "n".to_string(),
+            variables: Env::new(&HashMap::new()).variables,
+        },
+    );
+
+    assert_error!(
+        "let <<a:binary-size(2), b:size(a)>> = todo b",
+        Error::CouldNotUnify {
+            meta: Meta { start: 31, end: 32 },
+            expected: int(),
+            given: bit_string(),
+        },
+    );
+
     assert_error!(
         "let id = fn(x) { x } id()",
         Error::IncorrectArity {
@@ -3498,6 +6042,23 @@ fn infer_error_test() {
         },
     );
 
+    assert_error!(
+        "#(1, 2.0).2",
+        Error::OutOfBoundsTupleIndex {
+            meta: Meta { start: 0, end: 11 },
+            index: 2,
+            size: 2,
+        },
+    );
+
+    assert_error!(
+        "let x = 1 x.0",
+        Error::NotATuple {
+            meta: Meta { start: 10, end: 11 },
+            typ: int(),
+        },
+    );
+
     assert_error!(
         "let x = 1 x.whatever",
         Error::NotModule {
@@ -3520,19 +6081,95 @@ fn infer_error_test() {
     );
 }
 
+#[test]
+fn prelude_test() {
+    let prelude = prelude();
+    let mut types: Vec<_> = prelude.type_constructors.keys().cloned().collect();
+    types.sort();
+    assert_eq!(
+        vec![
+            "BitString",
+            "Bool",
+            "Float",
+            "Int",
+            "List",
+            "Nil",
+            "Result",
+            "String"
+        ],
+        types
+    );
+    let mut values: Vec<_> = prelude
+        .value_constructors
+        .iter()
+        .map(|(k, v)| (k.clone(), v.typ.to_gleam_string()))
+        .collect();
+    values.sort();
+    assert_eq!(
+        vec![
+            ("Error".to_string(), "fn(a) -> Result(b, a)".to_string()),
+            ("False".to_string(), "Bool".to_string()),
+            ("Nil".to_string(), "Nil".to_string()),
+            ("Ok".to_string(), "fn(a) -> Result(a, b)".to_string()),
+            ("True".to_string(), "Bool".to_string()),
+        ],
+        values
+    );
+
+    // Prelude types and constructors can be used without being imported
+    let module = infer_src(
+        "pub fn go(x: Bool) -> Result(Bool, String) { case x { True -> Ok(x) False -> Error(\"no\") } }",
+        &HashMap::new(),
+        &mut vec![],
+    )
+    .expect("should infer");
+    assert_eq!(
+        "fn(Bool) -> Result(Bool, String)",
+        module.type_info.value_constructors["go"]
+            .typ
+            .to_gleam_string()
+    );
+}
+
+/// Parses and infers a module, with `modules` available for it to import.
+///
+#[cfg(test)]
+fn infer_src(
+    src: &str,
+    modules: &HashMap<String, ModuleTypeInfo>,
+    warnings: &mut Vec<Warning>,
+) -> Result<TypedModule, Error> {
+    let ast = crate::grammar::ModuleParser::new()
+        .parse(src)
+        .expect("syntax error");
+    infer_module(ast, modules, warnings)
+}
+
+/// The modules available for import when a module named `one` has the given source.
+///
+#[cfg(test)]
+fn module_one(src: &str) -> HashMap<String, ModuleTypeInfo> {
+    let mut ast = crate::grammar::ModuleParser::new()
+        .parse(src)
+        .expect("syntax error");
+    ast.name = vec!["one".to_string()];
+    let one = infer_module(ast, &HashMap::new(), &mut vec![]).expect("should infer");
+    let mut modules = HashMap::new();
+    modules.insert("one".to_string(), one.type_info);
+    modules
+}
+
 #[test]
 fn infer_module_test() {
     macro_rules! assert_infer {
         ($src:expr, $module:expr $(,)?) => {
-            let ast = crate::grammar::ModuleParser::new()
-                .parse($src)
-                .expect("syntax error");
-            let result = infer_module(ast, &HashMap::new()).expect("should successfully infer");
+            let result =
+                infer_src($src, &HashMap::new(), &mut vec![]).expect("should successfully infer");
             let mut constructors: Vec<(_, _)> = result
                 .type_info
                 .value_constructors
                 .iter()
-                .map(|(k, v)| (k.clone(), v.typ.pretty_print(0)))
+                .map(|(k, v)| (k.clone(), v.typ.to_gleam_string()))
                 .collect();
             constructors.sort();
             let expected: Vec<_> = $module
@@ -3571,6 +6208,18 @@ fn infer_module_test() {
         ],
     );
 
+    assert_infer!(
+        r#"pub external type Handle
+           pub enum Secret { A(Handle) }
+           pub external fn open() -> Handle = "m" "f"
+           pub fn go() { A(open()) }"#,
+        vec![
+            ("A", "fn(Handle) -> Secret"),
+            ("go", "fn() -> Secret"),
+            ("open", "fn() -> Handle"),
+        ],
+    );
+
     assert_infer!(
         "pub enum Num { I(Int) }
          pub fn one() { I(1) }",
@@ -3623,48 +6272,59 @@ fn infer_module_test() {
     );
 
     assert_infer!(
-        "pub external fn go(String) -> String = \"\" \"\"",
+        "pub external fn go(String) -> String = \"m\" \"f\"",
         vec![("go", "fn(String) -> String")],
     );
 
     assert_infer!(
-        "pub external fn go(Int) -> Float = \"\" \"\"",
+        "pub external fn go(Int) -> Float = \"m\" \"f\"",
         vec![("go", "fn(Int) -> Float")],
     );
 
     assert_infer!(
-        "pub external fn go(Int) -> Int = \"\" \"\"",
+        "pub external fn go(Int) -> Int = \"m\" \"f\"",
         vec![("go", "fn(Int) -> Int")],
     );
 
     assert_infer!(
-        "pub external fn ok() -> fn(Int) -> Int = \"\" \"\"",
+        "pub external fn ok() -> fn(Int) -> Int = \"m\" \"f\"",
         vec![("ok", "fn() -> fn(Int) -> Int")],
     );
 
     assert_infer!(
-        "pub external fn go(Int) -> b = \"\" \"\"",
+        "pub external fn go(Int) -> b = \"m\" \"f\"",
         vec![("go", "fn(Int) -> a")],
     );
 
     assert_infer!(
-        "pub external fn go(Bool) -> b = \"\" \"\"",
+        r#"@external_erlang("add(A, B) -> A + B.") pub external fn add(Int, Int) -> Int"#,
+        vec![("add", "fn(Int, Int) -> Int")],
+    );
+
+    assert_infer!(
+        r#"@external_erlang("first({A, _}, [\"(\" | _]) when A > 0 -> A; first(_, _) -> 0.")
+        pub external fn first(x, List(String)) -> Int"#,
+        vec![("first", "fn(a, List(String)) -> Int")],
+    );
+
+    assert_infer!(
+        "pub external fn go(Bool) -> b = \"m\" \"f\"",
         vec![("go", "fn(Bool) -> a")],
     );
 
     assert_infer!(
-        "pub external fn go(List(a)) -> a = \"\" \"\"",
+        "pub external fn go(List(a)) -> a = \"m\" \"f\"",
         vec![("go", "fn(List(a)) -> a")],
     );
 
     assert_infer!(
-        "external fn go(Int) -> b = \"\" \"\"
+        "external fn go(Int) -> b = \"m\" \"f\"
         pub fn x() { go(1) }",
         vec![("x", "fn() -> a")],
     );
 
     assert_infer!(
-        "external fn id(a) -> a = \"\" \"\"
+        "external fn id(a) -> a = \"m\" \"f\"
         pub fn i(x) { id(x) }
         pub fn a() { id(1) }
         pub fn b() { id(1.0) }",
@@ -3676,19 +6336,19 @@ fn infer_module_test() {
     );
 
     assert_infer!(
-        "pub external fn len(List(a)) -> Int = \"\" \"\"",
+        "pub external fn len(List(a)) -> Int = \"m\" \"f\"",
         vec![("len", "fn(List(a)) -> Int")],
     );
 
     assert_infer!(
         "pub external type Connection\n
-         pub external fn is_open(Connection) -> Bool = \"\" \"\"",
+         pub external fn is_open(Connection) -> Bool = \"m\" \"f\"",
         vec![("is_open", "fn(Connection) -> Bool")],
     );
 
     assert_infer!(
         "pub external type Pair(thing, thing)\n
-         pub external fn pair(a) -> Pair(a, a) = \"\" \"\"",
+         pub external fn pair(a) -> Pair(a, a) = \"m\" \"f\"",
         vec![("pair", "fn(a) -> Pair(a, a)")],
     );
 
@@ -3732,6 +6392,40 @@ fn infer_module_test() {
         vec![("go", "fn(List(String)) -> List(String)")],
     );
     assert_infer!("pub fn go(x: b, y: c) { x }", vec![("go", "fn(a, b) -> a")],);
+
+    // try
+    assert_infer!(
+        r#"pub fn parse(x) { case x { 0 -> Error("zero") n -> Ok(n) } }
+           pub fn add(a, b) { try x = parse(a) try y = parse(b) Ok(x + y) }"#,
+        vec![
+            ("add", "fn(Int, Int) -> Result(Int, String)"),
+            ("parse", "fn(Int) -> Result(Int, String)"),
+        ],
+    );
+    assert_infer!(
+        "pub fn go(x) { try y = x Error(y) }",
+        vec![("go", "fn(Result(a, a)) -> Result(b, a)")],
+    );
+
+    // assert
+    assert_infer!(
+        "pub fn go(x) { assert Ok(y) = x y + 1 }",
+        vec![("go", "fn(Result(Int, a)) -> Int")],
+    );
+    assert_infer!(
+        "pub fn head(x) { assert [y | _] = x y }",
+        vec![("head", "fn(List(a)) -> a")],
+    );
+
+    // labelled arguments given out of order
+    assert_infer!(
+        r#"pub fn replace(in string, each pattern, with replacement) { string <> pattern <> replacement }
+           pub fn go() { replace(with: "c", in: "a", each: "b") }"#,
+        vec![
+            ("go", "fn() -> String"),
+            ("replace", "fn(String, String, String) -> String"),
+        ],
+    );
     assert_infer!("pub fn go(x) -> Int { x }", vec![("go", "fn(Int) -> Int")],);
 
     // // Type aliases
@@ -3814,35 +6508,166 @@ fn infer_module_test() {
     );
 
     assert_infer!(
-        "pub external fn ok(Int) -> struct(Int, Int) = \"\" \"\"",
+        "pub external fn ok(Int) -> struct(Int, Int) = \"m\" \"f\"",
         vec![("ok", "fn(Int) -> struct(Int, Int)")],
     );
 
     assert_infer!(
-        "pub external fn go(struct(a, c)) -> c = \"\" \"\"",
+        "pub external fn go(struct(a, c)) -> c = \"m\" \"f\"",
+        vec![("go", "fn(struct(a, b)) -> b")],
+    );
+
+    assert_infer!(
+        "pub external fn go(#(a, c)) -> c = \"m\" \"f\"",
         vec![("go", "fn(struct(a, b)) -> b")],
     );
+
+    assert_infer!(
+        "fn inc(x) { x + 1 }
+        fn add(x, y) { x + y }
+        pub fn go(x) { x |> inc |> add(2) }",
+        vec![("go", "fn(Int) -> Int")],
+    );
+
+    assert_infer!(
+        "fn join(a, with b) { a <> b }
+        pub fn go(x) { x |> join(with: \"!\") |> join(_, \"?\") }",
+        vec![("go", "fn(String) -> String")],
+    );
+
+    assert_infer!(
+        "pub const max_retries = 3
+        pub fn go() { max_retries + 1 }",
+        vec![("go", "fn() -> Int"), ("max_retries", "Int")],
+    );
+
+    assert_infer!(
+        "const pi = 3.14159
+        pub const tau = pi
+        pub const name = \"gleam\"
+        pub fn go(x) { case x { 1.0 -> tau _ -> pi } }",
+        vec![
+            ("go", "fn(Float) -> Float"),
+            ("name", "String"),
+            ("tau", "Float")
+        ],
+    );
+
+    assert_infer!(
+        "pub struct Point { x: Int y: Int }
+        pub fn go(p) { Point(..p, y: 2) }",
+        vec![
+            ("Point", "fn(Int, Int) -> Point"),
+            ("go", "fn(Point) -> Point")
+        ],
+    );
+
+    assert_infer!(
+        "pub struct Box(a) { inner: a tag: String }
+        pub fn go(b) { Box(..b, tag: \"new\") }",
+        vec![
+            ("Box", "fn(a, String) -> Box(a)"),
+            ("go", "fn(Box(a)) -> Box(a)")
+        ],
+    );
+
+    assert_infer!(
+        "pub struct Point { x: Int y: Float }
+        pub fn x(p: Point) { p.x }
+        pub fn y() { let p = Point(y: 2.0, x: 1) p.y }",
+        vec![
+            ("Point", "fn(Int, Float) -> Point"),
+            ("x", "fn(Point) -> Int"),
+            ("y", "fn() -> Float"),
+        ],
+    );
+
+    assert_infer!(
+        "pub struct Box(a) { inner: a tag: String }
+        pub fn inner(b: Box(x)) { b.inner }
+        pub fn go() { Box(1, \"one\").inner + 1 }",
+        vec![
+            ("Box", "fn(a, String) -> Box(a)"),
+            ("go", "fn() -> Int"),
+            ("inner", "fn(Box(a)) -> a"),
+        ],
+    );
+    assert_infer!(
+        "pub enum Shape { Circle(radius: Float) Rect(width: Float, height: Float) }
+        pub fn go() { Rect(height: 2.0, width: 1.0) }
+        pub fn width(shape) { case shape { Rect(height: _, width: w) -> w Circle(radius: r) -> r } }",
+        vec![
+            ("Circle", "fn(Float) -> Shape"),
+            ("Rect", "fn(Float, Float) -> Shape"),
+            ("go", "fn() -> Shape"),
+            ("width", "fn(Shape) -> Float"),
+        ],
+    );
+
+    assert_infer!(
+        "pub external fn print(String) -> Nil = \"io\" \"format\"
+        pub fn go() { print(\"a\") print(\"b\") }
+        pub fn nothing() { Nil }
+        pub fn is_nil(x) { x == Nil }",
+        vec![
+            ("go", "fn() -> Nil"),
+            ("is_nil", "fn(Nil) -> Bool"),
+            ("nothing", "fn() -> Nil"),
+            ("print", "fn(String) -> Nil"),
+        ],
+    );
+
+    // Arguments can be destructured with irrefutable patterns
+    assert_infer!(
+        "pub struct Box { inner: Int }
+        pub enum Wrap(a) { Wrap(a) }
+        pub fn first(#(a, _)) { a }
+        pub fn unbox(Box(x)) { x }
+        pub fn unwrap(Wrap(#(x, Box(y))): Wrap(#(Float, Box))) { #(x, y) }
+        pub fn apply() { let f = fn(Wrap(x), #(y, z)) { x + y + z } f(Wrap(1), #(2, 3)) }",
+        vec![
+            ("Box", "fn(Int) -> Box"),
+            ("Wrap", "fn(a) -> Wrap(a)"),
+            ("apply", "fn() -> Int"),
+            ("first", "fn(struct(a, b)) -> a"),
+            ("unbox", "fn(Box) -> Int"),
+            (
+                "unwrap",
+                "fn(Wrap(struct(Float, Box))) -> struct(Float, Int)"
+            ),
+        ],
+    );
 }
 
 #[test]
 fn infer_module_error_test() {
     macro_rules! assert_error {
         ($src:expr, $error:expr $(,)?) => {
-            let ast = crate::grammar::ModuleParser::new()
-                .parse($src)
-                .expect("syntax error");
-            let result = infer_module(ast, &HashMap::new()).expect_err("should infer an error");
+            let result =
+                infer_src($src, &HashMap::new(), &mut vec![]).expect_err("should infer an error");
             assert_eq!(($src, $error), ($src, result));
         };
 
         ($src:expr) => {
-            let ast = crate::grammar::ModuleParser::new()
-                .parse($src)
-                .expect("syntax error");
-            infer_module(ast, &HashMap::new()).expect_err("should infer an error");
+            infer_src($src, &HashMap::new(), &mut vec![]).expect_err("should infer an error");
         };
     }
 
+    assert_error!(
+        "struct Point { x: Int y: Int } fn go(p: Point) { p.z }",
+        Error::UnknownField {
+            meta: Meta { start: 49, end: 52 },
+            label: "z".to_string(),
+            typ: Type::App {
+                public: false,
+                module: vec![],
+                name: "Point".to_string(),
+                args: vec![],
+            },
+            fields: vec!["x".to_string(), "y".to_string()],
+        },
+    );
+
     assert_error!(
         "fn go() { 1 + 2.0 }",
         Error::CouldNotUnify {
@@ -3874,7 +6699,7 @@ pub fn x() { id(1, 1.0) }
     );
 
     assert_error!(
-        "external fn go(List(a, b)) -> a = \"\" \"\"",
+        "external fn go(List(a, b)) -> a = \"m\" \"f\"",
         Error::IncorrectTypeArity {
             meta: Meta { start: 15, end: 25 },
             name: "List".to_string(),
@@ -3887,8 +6712,9 @@ pub fn x() { id(1, 1.0) }
         "fn dupe() { 1 }
          fn dupe() { 2 }",
         Error::DuplicateName {
-            meta: Meta { start: 25, end: 40 },
             name: "dupe".to_string(),
+            first: Meta { start: 0, end: 15 },
+            second: Meta { start: 25, end: 40 },
         }
     );
 
@@ -3896,16 +6722,68 @@ pub fn x() { id(1, 1.0) }
         "fn dupe() { 1 }
          fn dupe(x) { x }",
         Error::DuplicateName {
-            meta: Meta { start: 25, end: 41 },
             name: "dupe".to_string(),
+            first: Meta { start: 0, end: 15 },
+            second: Meta { start: 25, end: 41 },
+        }
+    );
+
+    assert_error!(
+        "pub fn go() { 1 }
+         pub external fn go() -> Int = \"m\" \"f\"",
+        Error::DuplicateName {
+            name: "go".to_string(),
+            first: Meta { start: 0, end: 17 },
+            second: Meta { start: 27, end: 64 },
+        }
+    );
+
+    assert_error!(
+        "enum Box { Box(Int) }
+         enum Crate { Box(Float) }",
+        Error::DuplicateName {
+            name: "Box".to_string(),
+            first: Meta { start: 11, end: 19 },
+            second: Meta { start: 44, end: 54 },
+        }
+    );
+
+    assert_error!(
+        "enum Box { Box(Int) }
+         external type Box",
+        Error::DuplicateName {
+            name: "Box".to_string(),
+            first: Meta { start: 0, end: 9 },
+            second: Meta { start: 31, end: 48 },
+        }
+    );
+
+    assert_error!(
+        "enum Empty { Point }
+         struct Point { x: Int y: Int }",
+        Error::DuplicateName {
+            name: "Point".to_string(),
+            first: Meta { start: 13, end: 18 },
+            second: Meta { start: 30, end: 60 },
+        }
+    );
+
+    assert_error!(
+        "enum X { HTTPResponse HttpResponse }",
+        Error::DuplicateConstructorAtom {
+            meta: Meta { start: 22, end: 34 },
+            name: "HttpResponse".to_string(),
+            other: "HTTPResponse".to_string(),
+            atom: "http_response".to_string(),
         }
     );
 
     assert_error!(
         r#"external type PrivateType
-           pub external fn leak_type() -> PrivateType = "" """#,
+           pub external fn leak_type() -> PrivateType = "m" "f""#,
         Error::PrivateTypeLeak {
-            meta: Meta { start: 37, end: 87 },
+            meta: Meta { start: 37, end: 89 },
+            name: "leak_type".to_string(),
             leaked: Type::App {
                 args: vec![],
                 public: false,
@@ -3917,13 +6795,14 @@ pub fn x() { id(1, 1.0) }
 
     assert_error!(
         r#"external type PrivateType
-           external fn go() -> PrivateType = "" ""
+           external fn go() -> PrivateType = "m" "f"
            pub fn leak_type() { go() }"#,
         Error::PrivateTypeLeak {
             meta: Meta {
-                start: 88,
-                end: 115,
+                start: 90,
+                end: 117,
             },
+            name: "leak_type".to_string(),
             leaked: Type::App {
                 args: vec![],
                 public: false,
@@ -3935,13 +6814,14 @@ pub fn x() { id(1, 1.0) }
 
     assert_error!(
         r#"external type PrivateType
-           external fn go() -> PrivateType = "" ""
+           external fn go() -> PrivateType = "m" "f"
            pub fn leak_type() { [go()] }"#,
         Error::PrivateTypeLeak {
             meta: Meta {
-                start: 88,
-                end: 117,
+                start: 90,
+                end: 119,
             },
+            name: "leak_type".to_string(),
             leaked: Type::App {
                 args: vec![],
                 public: false,
@@ -3953,9 +6833,10 @@ pub fn x() { id(1, 1.0) }
 
     assert_error!(
         r#"external type PrivateType
-                    pub external fn go(PrivateType) -> Int = "" """#,
+                    pub external fn go(PrivateType) -> Int = "m" "f""#,
         Error::PrivateTypeLeak {
-            meta: Meta { start: 46, end: 92 },
+            meta: Meta { start: 46, end: 94 },
+            name: "go".to_string(),
             leaked: Type::App {
                 args: vec![],
                 public: false,
@@ -3970,6 +6851,7 @@ pub fn x() { id(1, 1.0) }
            pub enum LeakType { Variant(PrivateType) }"#,
         Error::PrivateTypeLeak {
             meta: Meta { start: 57, end: 77 },
+            name: "Variant".to_string(),
             leaked: Type::App {
                 args: vec![],
                 public: false,
@@ -3979,6 +6861,21 @@ pub fn x() { id(1, 1.0) }
         }
     );
 
+    assert_error!(
+        "enum Secret { A }
+           pub fn go() { A }",
+        Error::PrivateTypeLeak {
+            meta: Meta { start: 29, end: 46 },
+            name: "go".to_string(),
+            leaked: Type::App {
+                args: vec![],
+                public: false,
+                module: vec![],
+                name: "Secret".to_string(),
+            },
+        }
+    );
+
     assert_error!(
         r#"fn id(x) { x } fn y() { id(x: 4) }"#,
         Error::UnexpectedLabelledArg {
@@ -3995,6 +6892,44 @@ pub fn x() { id(1, 1.0) }
         }
     );
 
+    assert_error!(
+        r#"fn replace(in string, each pattern, with replacement) { string }
+           fn go() { replace(each: 1, on: 2, with: 3) }"#,
+        Error::UnknownLabel {
+            meta: Meta {
+                start: 103,
+                end: 108
+            },
+            label: "on".to_string(),
+            labels: {
+                let mut labels = HashMap::new();
+                labels.insert("in".to_string(), 0);
+                labels.insert("each".to_string(), 1);
+                labels.insert("with".to_string(), 2);
+                labels
+            },
+        }
+    );
+
+    // try requires a Result, and the rest of the function must return a Result with the same
+    // error type. The error types contain unbound type variables so only the location is checked.
+    for (src, expected_meta) in [
+        ("fn go() { try x = 1 Ok(x) }", Meta { start: 18, end: 19 }),
+        (
+            r#"fn go() { try x = Error(1) Error("no") }"#,
+            Meta { start: 27, end: 38 },
+        ),
+        ("fn go() { try x = Ok(1) x }", Meta { start: 24, end: 25 }),
+    ]
+    .iter()
+    .cloned()
+    {
+        match infer_src(src, &HashMap::new(), &mut vec![]) {
+            Err(Error::CouldNotUnify { meta, .. }) => assert_eq!((src, expected_meta), (src, meta)),
+            other => panic!("{}: expected a unify error, got {:?}", src, other),
+        }
+    }
+
     assert_error!(
         "struct X {} fn x() { X(one: 1) }",
         Error::IncorrectArity {
@@ -4017,6 +6952,9 @@ pub fn x() { id(1, 1.0) }
                         public: false,
                         module: vec![],
                         arity: 0,
+                        constructors: vec!["Thing".to_string()],
+                        opaque: false,
+                        alias: None,
                     },
                 );
                 types
@@ -4024,7 +6962,596 @@ pub fn x() { id(1, 1.0) }
         }
     );
 
+    assert_error!(
+        r#"external fn go() -> Int = "" "go""#,
+        Error::InvalidExternalTarget {
+            meta: Meta { start: 0, end: 33 },
+            target: "".to_string(),
+        }
+    );
+
+    assert_error!(
+        r#"external fn go() -> Int = "my module" "go""#,
+        Error::InvalidExternalTarget {
+            meta: Meta { start: 0, end: 42 },
+            target: "my module".to_string(),
+        }
+    );
+
+    assert_error!(
+        r#"external fn go() -> Int = "erlang" "go!""#,
+        Error::InvalidExternalTarget {
+            meta: Meta { start: 0, end: 40 },
+            target: "go!".to_string(),
+        }
+    );
+
+    assert_error!(
+        r#"@external_erlang("add(A) -> A.") external fn add(Int, Int) -> Int"#,
+        Error::InvalidInlineErlang {
+            meta: Meta { start: 0, end: 65 },
+            name: "add".to_string(),
+            arity: 2,
+        }
+    );
+
+    assert_error!(
+        r#"@external_erlang("sub(A, B) -> A - B.") external fn add(Int, Int) -> Int"#,
+        Error::InvalidInlineErlang {
+            meta: Meta { start: 0, end: 72 },
+            name: "add".to_string(),
+            arity: 2,
+        }
+    );
+
+    assert_error!(
+        r#"@external_erlang("go() -> 1") external fn go() -> Int"#,
+        Error::InvalidInlineErlang {
+            meta: Meta { start: 0, end: 53 },
+            name: "go".to_string(),
+            arity: 0,
+        }
+    );
+
+    assert_error!(
+        "fn go(x) { case x { n if n + 1 -> 1 _ -> 0 } }",
+        Error::CouldNotUnify {
+            meta: Meta { start: 25, end: 30 },
+            expected: bool(),
+            given: int(),
+        }
+    );
+
+    assert_error!(
+        "fn is_one(x) { x == 1 } fn go(x) { case x { n if is_one(n) -> 1 _ -> 0 } }",
+        Error::InvalidGuardExpression {
+            meta: Meta { start: 49, end: 58 },
+        }
+    );
+
+    assert_error!(
+        "struct Point { x: Int y: Int }
+        fn go(p) { Point(..p, z: 1) }",
+        Error::UnknownLabel {
+            meta: Meta { start: 61, end: 65 },
+            label: "z".to_string(),
+            labels: {
+                let mut labels = HashMap::new();
+                labels.insert("x".to_string(), 0);
+                labels.insert("y".to_string(), 1);
+                labels
+            },
+        }
+    );
+
+    assert_error!(
+        "struct Point { x: Int y: Int }
+        fn go(p) { Point(..p, x: 1.0) }",
+        Error::CouldNotUnify {
+            meta: Meta { start: 64, end: 67 },
+            expected: int(),
+            given: float(),
+        }
+    );
+
+    assert_error!(
+        "struct Point { x: Int y: Int }
+        fn go() { Point(..1, x: 1) }",
+        Error::CouldNotUnify {
+            meta: Meta { start: 57, end: 58 },
+            expected: Type::App {
+                public: false,
+                module: vec![],
+                name: "Point".to_string(),
+                args: vec![],
+            },
+            given: int(),
+        }
+    );
+
+    assert_error!(
+        "struct Point { x: Int y: Int }
+        fn go(p) { Point(..p, x: 1, x: 2) }",
+        Error::DuplicateArgument {
+            meta: Meta { start: 67, end: 71 },
+            label: "x".to_string(),
+        }
+    );
+
+    assert_error!(
+        "enum Shape { Square(size: Int) }
+        fn go(s) { Square(..s, size: 1) }",
+        Error::NotStructConstructor {
+            meta: Meta { start: 52, end: 58 },
+        }
+    );
+
+    assert_error!(
+        "fn go() { 1 |> 2(3) }",
+        Error::NotFn {
+            meta: Meta { start: 15, end: 16 },
+            typ: int(),
+        }
+    );
+
+    assert_error!(
+        "fn add(x, y) { x + y } fn go() { 1.0 |> add(2) }",
+        Error::CouldNotUnify {
+            meta: Meta { start: 33, end: 36 },
+            expected: int(),
+            given: float(),
+        }
+    );
+
+    assert_error!(
+        "fn go() { 1 } const x = go()",
+        Error::InvalidConstant {
+            meta: Meta { start: 24, end: 28 },
+        }
+    );
+
+    assert_error!(
+        "const x = [1]",
+        Error::InvalidConstant {
+            meta: Meta { start: 12, end: 13 },
+        }
+    );
+
+    assert_error!(
+        "fn x() { 1 } const x = 2",
+        Error::DuplicateName {
+            name: "x".to_string(),
+            first: Meta { start: 0, end: 12 },
+            second: Meta { start: 13, end: 21 },
+        }
+    );
+
+    // Patterns in function arguments must match every value of the argument's type
+    assert_error!(
+        "enum Shape { Square Circle(Float) } fn r(Circle(r)) { r }",
+        Error::RefutableArgPattern {
+            meta: Meta { start: 41, end: 50 },
+        }
+    );
+    assert_error!(
+        "fn go(#(1, b)) { b }",
+        Error::RefutableArgPattern {
+            meta: Meta { start: 6, end: 13 },
+        }
+    );
+    assert_error!(
+        "fn go(x) { fn(#(a, \"b\")) { a } }",
+        Error::RefutableArgPattern {
+            meta: Meta { start: 14, end: 23 },
+        }
+    );
+
     // Cases were we can't so easily check for equality-
     // i.e. because the contents of the error are non-deterministic.
     assert_error!("fn inc(x: a) { x + 1 }");
+    assert_error!("fn go() { 1 |> 2 }");
+    assert_error!("fn go() { let y = { let x = 1 x } x }");
+    assert_error!("fn go() { let fact = fn(n) { fact(n) } fact(1) }");
+    assert_error!("fn go() { let rec loop = fn(x) { loop(x) + 1.0 } loop(1) + 1 }");
+}
+
+#[test]
+fn infer_module_warnings_test() {
+    macro_rules! assert_warnings {
+        ($src:expr, $warnings:expr $(,)?) => {
+            let mut warnings = vec![];
+            infer_src($src, &HashMap::new(), &mut warnings).expect("should successfully infer");
+            let expected: Vec<Warning> = $warnings;
+            assert_eq!(($src, warnings), ($src, expected));
+        };
+    }
+
+    assert_warnings!(
+        "enum Shape { Square Circle Line }
+        fn go(x) { case x { Square -> 1 Circle -> 2 } }",
+        vec![Warning::NonExhaustiveCase {
+            meta: Meta { start: 53, end: 87 },
+            missing: vec!["Line".to_string()],
+        }],
+    );
+
+    assert_warnings!(
+        "enum Shape { Square Circle Line }
+        fn go(x) { case x { Square -> 1 Circle -> 2 Line -> 3 } }",
+        vec![],
+    );
+
+    assert_warnings!(
+        "enum Shape { Square Circle Line }
+        fn go(x) { case x { Square -> 1 _ -> 2 } }",
+        vec![],
+    );
+
+    assert_warnings!(
+        "enum Shape { Square Circle Line }
+        fn go(x) { case x { Square -> 1 other -> 2 } }",
+        vec![],
+    );
+
+    assert_warnings!(
+        "fn go(x) { case x { True -> 1 } }",
+        vec![Warning::NonExhaustiveCase {
+            meta: Meta { start: 11, end: 31 },
+            missing: vec!["False".to_string()],
+        }],
+    );
+
+    assert_warnings!(
+        "fn go(x, y) { case x { Ok(a) if y -> a Error(_) -> 0 } }",
+        vec![Warning::NonExhaustiveCase {
+            meta: Meta { start: 14, end: 54 },
+            missing: vec!["Ok".to_string()],
+        }],
+    );
+
+    assert_warnings!(
+        "fn go(x) { case x { Ok(1) -> 1 Error(_) -> 0 } }",
+        vec![Warning::NonExhaustiveCase {
+            meta: Meta { start: 11, end: 46 },
+            missing: vec!["Ok".to_string()],
+        }],
+    );
+
+    // Types without constructors cannot be checked
+    assert_warnings!("fn go(x) { case x { 1 -> 1 } }", vec![]);
+
+    assert_warnings!(
+        "fn go(x) { x / 0 }",
+        vec![Warning::DivisionByZero {
+            meta: Meta { start: 15, end: 16 },
+        }],
+    );
+    assert_warnings!(
+        "fn go(x) { x % 0x00 }",
+        vec![Warning::DivisionByZero {
+            meta: Meta { start: 15, end: 19 },
+        }],
+    );
+    assert_warnings!(
+        "fn go(x) { x /. 0.0 }",
+        vec![Warning::DivisionByZero {
+            meta: Meta { start: 16, end: 19 },
+        }],
+    );
+    assert_warnings!("fn go(x) { x / 10 }", vec![]);
+
+    assert_warnings!(
+        "fn go(x) { case x { 1 -> todo _ -> panic } }",
+        vec![Warning::Todo {
+            meta: Meta { start: 25, end: 29 },
+        }],
+    );
+    assert_warnings!("fn go() { panic as \"never\" }", vec![]);
+
+    assert_warnings!(
+        "fn go(x) { let x = x + 1 x }",
+        vec![Warning::Shadowing {
+            meta: Meta { start: 15, end: 16 },
+            name: "x".to_string(),
+            shadowed: Meta { start: 6, end: 7 },
+        }],
+    );
+    assert_warnings!(
+        "fn go(x) { fn(x) { x } }",
+        vec![Warning::Shadowing {
+            meta: Meta { start: 14, end: 15 },
+            name: "x".to_string(),
+            shadowed: Meta { start: 6, end: 7 },
+        }],
+    );
+    assert_warnings!(
+        "fn go() { let y = 1 { let y = 2 y } }",
+        vec![Warning::Shadowing {
+            meta: Meta { start: 26, end: 27 },
+            name: "y".to_string(),
+            shadowed: Meta { start: 14, end: 15 },
+        }],
+    );
+    // Rebinding a variable in the same scope is not shadowing
+    assert_warnings!("fn go() { let y = 1 let y = y + 1 y }", vec![]);
+    assert_warnings!("fn go(x) { case x { y -> y } }", vec![]);
+}
+
+#[test]
+fn infer_module_shadowing_import_test() {
+    let modules = module_one("pub fn map(x) { x }");
+
+    let src = "import one.{map}
+fn go(map) { map }
+fn run() { let map = 1 map }";
+    let mut warnings = vec![];
+    infer_src(src, &modules, &mut warnings).expect("should infer");
+    assert_eq!(
+        vec![
+            Warning::Shadowing {
+                meta: Meta { start: 23, end: 26 },
+                name: "map".to_string(),
+                shadowed: Meta { start: 12, end: 15 },
+            },
+            Warning::Shadowing {
+                meta: Meta { start: 51, end: 54 },
+                name: "map".to_string(),
+                shadowed: Meta { start: 12, end: 15 },
+            },
+        ],
+        warnings
+    );
+}
+
+#[test]
+fn infer_module_qualified_type_test() {
+    let modules = module_one(
+        r#"pub external type Thing
+external type Secret
+pub external fn new() -> Thing = "one" "new""#,
+    );
+    let infer = |src| infer_src(src, &modules, &mut vec![]);
+
+    // Qualified types can be used in any annotation
+    let two = infer(
+        "import one
+pub fn go(x: one.Thing, f: fn(one.Thing) -> Int) -> List(one.Thing) {
+  let y: List(one.Thing) = [x]
+  f(x)
+  y
+}",
+    )
+    .expect("should infer");
+    let go = two
+        .type_info
+        .value_constructors
+        .get("go")
+        .expect("go is public");
+    assert_eq!(
+        "fn(Thing, fn(Thing) -> Int) -> List(Thing)",
+        go.typ.to_gleam_string()
+    );
+
+    // Types that are not public cannot be referenced from other modules
+    match infer("import one\npub fn go(x: one.Secret) { x }") {
+        Err(Error::UnknownModuleType {
+            meta,
+            name,
+            module_name,
+            ..
+        }) => {
+            assert_eq!(Meta { start: 24, end: 34 }, meta);
+            assert_eq!("Secret", name);
+            assert_eq!(vec!["one".to_string()], module_name);
+        }
+        other => panic!("expected an unknown module type error, got {:?}", other),
+    }
+}
+
+#[test]
+fn infer_module_type_alias_test() {
+    let infer =
+        |src, modules: &HashMap<String, ModuleTypeInfo>| infer_src(src, modules, &mut vec![]);
+    let fn_type = |module: &TypedModule, name| {
+        module
+            .type_info
+            .value_constructors
+            .get(name)
+            .expect("should be public")
+            .typ
+            .to_gleam_string()
+    };
+
+    // A simple alias is the same as the type it stands for
+    let one = infer(
+        "pub type UserId = Int
+pub fn next(id: UserId) -> UserId { id + 1 }",
+        &HashMap::new(),
+    )
+    .expect("should infer");
+    assert_eq!("fn(Int) -> Int", fn_type(&one, "next"));
+
+    // The arguments of a parameterised alias are substituted into it
+    let one = infer(
+        "pub type Pair(a) = #(a, a)
+pub type Callback(a, b) = fn(Pair(a)) -> List(b)
+pub fn swap(pair: Pair(x)) -> Pair(x) { let #(a, b) = pair #(b, a) }
+pub fn call(f: Callback(Int, String)) { f(#(1, 2)) }",
+        &HashMap::new(),
+    )
+    .expect("should infer");
+    assert_eq!("fn(struct(a, a)) -> struct(a, a)", fn_type(&one, "swap"));
+    assert_eq!(
+        "fn(fn(struct(Int, Int)) -> List(String)) -> List(String)",
+        fn_type(&one, "call")
+    );
+
+    // The expanded type is checked like any other
+    match infer(
+        "type UserId = Int fn go(id: UserId) { id +. 1.0 }",
+        &HashMap::new(),
+    ) {
+        Err(Error::CouldNotUnify {
+            expected, given, ..
+        }) => assert_eq!((float(), int()), (expected, given)),
+        other => panic!("expected a type mismatch, got {:?}", other),
+    }
+
+    // Public aliases can be used by other modules
+    let modules = module_one("pub type Pair(a) = #(a, a) type Hidden = Int");
+    let two = infer(
+        "import one pub fn dup(x) -> one.Pair(Int) { #(x, x) }",
+        &modules,
+    )
+    .expect("should infer");
+    assert_eq!("fn(Int) -> struct(Int, Int)", fn_type(&two, "dup"));
+    match infer("import one pub fn go(x: one.Hidden) { x }", &modules) {
+        Err(Error::UnknownModuleType { name, .. }) => assert_eq!("Hidden", name),
+        other => panic!("expected an unknown module type error, got {:?}", other),
+    }
+
+    // Aliases that refer to themselves cannot be expanded
+    assert_eq!(
+        Err(Error::RecursiveTypeAlias {
+            meta: Meta { start: 0, end: 9 },
+            name: "Tree".to_string(),
+        }),
+        infer("type Tree = List(Tree)", &HashMap::new()).map(|_| ())
+    );
+    assert_eq!(
+        Err(Error::RecursiveTypeAlias {
+            meta: Meta { start: 0, end: 12 },
+            name: "Ping".to_string(),
+        }),
+        infer(
+            "type Ping(a) = fn(Pong) -> a type Pong = #(Int, Ping(Int))",
+            &HashMap::new()
+        )
+        .map(|_| ())
+    );
+
+    // A public alias may not expose a private type
+    match infer(
+        "enum Hidden { A } pub type Leak = List(Hidden)",
+        &HashMap::new(),
+    ) {
+        Err(Error::PrivateTypeLeak { name, .. }) => assert_eq!("Leak", name),
+        other => panic!("expected a private type leak, got {:?}", other),
+    }
+}
+
+#[test]
+fn infer_module_export_test() {
+    let infer = |src| infer_src(src, &HashMap::new(), &mut vec![]);
+
+    // Only the listed public functions are in the module's interface
+    let module = infer(
+        "@export([go, run])
+pub fn go() { 1 }
+pub fn run() { 2 }
+pub fn helper() { 3 }",
+    )
+    .expect("should infer");
+    let mut names: Vec<_> = module.type_info.value_constructors.keys().collect();
+    names.sort();
+    assert_eq!(vec!["go", "run"], names);
+
+    // Each listed name must be a public function of the module
+    assert_eq!(
+        Err(Error::UnknownExport {
+            meta: Meta { start: 0, end: 19 },
+            name: "stop".to_string(),
+        }),
+        infer("@export([go, stop])\npub fn go() { 1 }\nfn stop() { 2 }").map(|_| ())
+    );
+}
+
+#[test]
+fn infer_module_target_test() {
+    let infer = |src| infer_src(src, &HashMap::new(), &mut vec![]);
+
+    // Each target may have its own definition of a function with the same type
+    let module = infer(
+        "@target(erlang)
+pub external fn now() -> Int = \"erlang\" \"monotonic_time\"
+@target(core_erlang)
+pub external fn now() -> Int = \"os\" \"system_time\"
+pub fn later() { now() + 1 }",
+    )
+    .expect("should infer");
+    assert_eq!(
+        "fn() -> Int",
+        module
+            .type_info
+            .value_constructors
+            .get("now")
+            .expect("should be public")
+            .typ
+            .to_gleam_string()
+    );
+
+    // The definitions for each target must have the same type
+    match infer(
+        "@target(erlang) pub fn id(x) { x }
+@target(core_erlang) pub fn id(x) { x + 1 }",
+    ) {
+        Err(Error::TargetTypeMismatch { name, .. }) => assert_eq!("id", name),
+        other => panic!("expected a target type mismatch, got {:?}", other),
+    }
+    assert!(infer(
+        "@target(erlang) pub fn pair(x, y) { #(x, y) }
+@target(core_erlang) pub fn pair(a, b) { #(a, b) }"
+    )
+    .is_ok());
+    match infer(
+        "@target(erlang) pub fn pair(x, y) { #(x, y) }
+@target(core_erlang) pub fn pair(x, y) { #(x, x) }",
+    ) {
+        Err(Error::TargetTypeMismatch { name, .. }) => assert_eq!("pair", name),
+        other => panic!("expected a target type mismatch, got {:?}", other),
+    }
+
+    // Types of the same name from different modules are different types
+    let modules = module_one("pub enum Thing { Thing }");
+    match infer_src(
+        "import one
+pub enum Thing { Other }
+@target(erlang) pub external fn get() -> Thing = \"a\" \"get\"
+@target(core_erlang) pub external fn get() -> one.Thing = \"a\" \"get\"",
+        &modules,
+        &mut vec![],
+    ) {
+        Err(Error::TargetTypeMismatch { name, .. }) => assert_eq!("get", name),
+        other => panic!("expected a target type mismatch, got {:?}", other),
+    }
+
+    // Only functions can be defined for a target
+    let mut ast = crate::grammar::ModuleParser::new()
+        .parse("const x = 1")
+        .expect("syntax error");
+    let statement = ast.statements.remove(0);
+    ast.statements.push(Statement::Target {
+        meta: Meta { start: 0, end: 11 },
+        target: crate::project::CompileTarget::Erlang,
+        statement: Box::new(statement),
+    });
+    assert_eq!(
+        Err(Error::TargetNotFn {
+            meta: Meta { start: 0, end: 11 },
+        }),
+        infer_module(ast, &HashMap::new(), &mut vec![]).map(|_| ())
+    );
+
+    // A target may only define a function once, and a function defined for all targets cannot
+    // also be defined for one
+    for src in &[
+        "@target(erlang) fn go() { 1 } @target(erlang) fn go() { 2 }",
+        "fn go() { 1 } @target(erlang) fn go() { 2 }",
+        "@target(erlang) fn go() { 2 } fn go() { 1 }",
+    ] {
+        match infer(src) {
+            Err(Error::DuplicateName { name, .. }) => assert_eq!("go", name),
+            other => panic!("expected a duplicate name for {}, got {:?}", src, other),
+        }
+    }
 }